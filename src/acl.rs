@@ -0,0 +1,253 @@
+// access controls a copy can carry beyond its permission bits: SELinux labels
+// (security.*) and POSIX ACLs (system.posix_acl_access), both stored as
+// extended attributes on linux. copies of one file on a server often differ
+// here, and deleting a copy drops whatever grants only it carried. the report
+// lists the differences; --merge-acls union|strict rewrites the keeper's ACL
+// to grant what any removed copy granted, or only what every one granted
+use crate::{DuplicateSet, xattr};
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::Path;
+
+const ACL_ACCESS: &str = "system.posix_acl_access";
+const ACL_VERSION: u32 = 2;
+
+// entry tags of the on-disk ACL format
+const USER_OBJ: u16 = 0x01;
+const USER: u16 = 0x02;
+const GROUP_OBJ: u16 = 0x04;
+const GROUP: u16 = 0x08;
+const MASK: u16 = 0x10;
+const OTHER: u16 = 0x20;
+// ids of entries that don't name a user or group
+const UNDEFINED_ID: u32 = u32::MAX;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AclMerge {
+    Union,
+    Strict,
+}
+
+impl AclMerge {
+    pub fn parse(value: &str) -> Option<AclMerge> {
+        match value {
+            "union" => Some(AclMerge::Union),
+            "strict" => Some(AclMerge::Strict),
+            _ => None,
+        }
+    }
+}
+
+// the attributes that control access, by name
+fn access_attributes(path: &Path) -> BTreeMap<String, Vec<u8>> {
+    let mut attributes = BTreeMap::new();
+    for name in xattr::list(path).unwrap_or_default() {
+        if (name.starts_with("security.") || name == ACL_ACCESS)
+            && let Ok(Some(value)) = xattr::get(path, &name)
+        {
+            attributes.insert(name, value);
+        }
+    }
+    attributes
+}
+
+fn describe(name: &str, value: &[u8]) -> String {
+    if name == ACL_ACCESS {
+        return "an ACL".to_string();
+    }
+    format!("'{}'", String::from_utf8_lossy(value).trim_end_matches('\0'))
+}
+
+// how the access controls of `path` differ from the keeper's
+fn differences(path: &Path, keeper: &Path) -> Vec<String> {
+    let (copy, kept) = (access_attributes(path), access_attributes(keeper));
+    let mut differences = Vec::new();
+    for (name, value) in &copy {
+        match kept.get(name) {
+            None => differences.push(format!("{} {} only on this copy", name, describe(name, value))),
+            Some(kept_value) if kept_value != value => differences.push(format!(
+                "{} is {} here, {} on the keeper",
+                name,
+                describe(name, value),
+                describe(name, kept_value)
+            )),
+            Some(_) => {}
+        }
+    }
+    for (name, value) in &kept {
+        if !copy.contains_key(name) {
+            differences.push(format!("{} {} only on the keeper", name, describe(name, value)));
+        }
+    }
+    differences
+}
+
+pub fn write_differences(out: &mut dyn Write, duplicate_sets: &[DuplicateSet]) -> io::Result<()> {
+    // security labels and ACLs live in extended attributes only on linux
+    if cfg!(not(target_os = "linux")) {
+        return Ok(());
+    }
+
+    let mut header = false;
+    for set in duplicate_sets {
+        for file_info in &set.duplicates {
+            let differences = differences(&file_info.path, &set.keeper.path);
+            if differences.is_empty() {
+                continue;
+            }
+            if !header {
+                writeln!(out, "\n--- Access Control Differences ---")?;
+                writeln!(out, "These copies carry security labels or ACLs that differ from their keeper's.")?;
+                writeln!(out, "Pass --merge-acls union or --merge-acls strict to carry ACLs over first.")?;
+                header = true;
+            }
+            writeln!(out, "{}:", file_info.path.display())?;
+            for difference in differences {
+                writeln!(out, "  {}", difference)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// (tag, id) -> permissions
+type Entries = BTreeMap<(u16, u32), u16>;
+
+fn parse_acl(value: &[u8]) -> Option<Entries> {
+    let (header, body) = value.split_at_checked(4)?;
+    if u32::from_le_bytes(header.try_into().ok()?) != ACL_VERSION || body.len() % 8 != 0 {
+        return None;
+    }
+    Some(
+        body.chunks_exact(8)
+            .map(|entry| {
+                let tag = u16::from_le_bytes([entry[0], entry[1]]);
+                let permissions = u16::from_le_bytes([entry[2], entry[3]]);
+                let id = u32::from_le_bytes([entry[4], entry[5], entry[6], entry[7]]);
+                ((tag, id), permissions)
+            })
+            .collect(),
+    )
+}
+
+// entries sorted by tag and id, as the kernel requires
+fn encode_acl(entries: &Entries) -> Vec<u8> {
+    let mut value = ACL_VERSION.to_le_bytes().to_vec();
+    for (&(tag, id), &permissions) in entries {
+        value.extend_from_slice(&tag.to_le_bytes());
+        value.extend_from_slice(&permissions.to_le_bytes());
+        value.extend_from_slice(&id.to_le_bytes());
+    }
+    value
+}
+
+// the ACL a file without one behaves as: just its permission bits
+fn acl_from_mode(path: &Path) -> io::Result<Entries> {
+    #[cfg(unix)]
+    let mode = std::os::unix::fs::PermissionsExt::mode(&std::fs::metadata(path)?.permissions()) as u16;
+    #[cfg(not(unix))]
+    let mode = {
+        let _ = path;
+        0o644
+    };
+
+    Ok(Entries::from([
+        ((USER_OBJ, UNDEFINED_ID), (mode >> 6) & 7),
+        ((GROUP_OBJ, UNDEFINED_ID), (mode >> 3) & 7),
+        ((OTHER, UNDEFINED_ID), mode & 7),
+    ]))
+}
+
+fn read_acl(path: &Path) -> io::Result<Entries> {
+    match xattr::get(path, ACL_ACCESS)? {
+        Some(value) => parse_acl(&value).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "unreadable ACL")),
+        None => acl_from_mode(path),
+    }
+}
+
+fn merge_entries(mut merged: Entries, other: &Entries, mode: AclMerge) -> Entries {
+    match mode {
+        AclMerge::Union => {
+            for (&key, &permissions) in other {
+                *merged.entry(key).or_default() |= permissions;
+            }
+        }
+        AclMerge::Strict => {
+            merged.retain(|key, _| other.contains_key(key));
+            for (key, permissions) in merged.iter_mut() {
+                *permissions &= other[key];
+            }
+        }
+    }
+
+    // named entries need a mask; it then caps every group-class entry
+    let named = merged.keys().any(|&(tag, _)| tag == USER || tag == GROUP);
+    if !named {
+        merged.remove(&(MASK, UNDEFINED_ID));
+    } else if mode == AclMerge::Union || !merged.contains_key(&(MASK, UNDEFINED_ID)) {
+        let class = merged
+            .iter()
+            .filter(|&(&(tag, _), _)| matches!(tag, USER | GROUP | GROUP_OBJ))
+            .fold(0, |class, (_, &permissions)| class | permissions);
+        let mask = merged.entry((MASK, UNDEFINED_ID)).or_default();
+        *mask = if mode == AclMerge::Union { *mask | class } else { class };
+    }
+    merged
+}
+
+// a copy's ACL, read right before the copy is removed, and whether it was
+// one of its own rather than just its permission bits
+pub struct Taken {
+    entries: Entries,
+    explicit: bool,
+}
+
+pub fn take(path: &Path) -> io::Result<Taken> {
+    Ok(Taken {
+        explicit: matches!(xattr::get(path, ACL_ACCESS), Ok(Some(_))),
+        entries: read_acl(path)?,
+    })
+}
+
+// rewrite the keeper's ACL from its own and those of the removed copies; a
+// copy whose ACL couldn't be read leaves the keeper alone, since --merge-acls
+// strict without it would grant too much. returns what was done
+pub fn merge_onto_keeper(keeper: &Path, taken: &[io::Result<Taken>], mode: AclMerge) -> Vec<String> {
+    let merge = || -> io::Result<Option<String>> {
+        let mut copies = Vec::new();
+        for copy in taken {
+            match copy {
+                Ok(copy) => copies.push(copy),
+                Err(e) => return Err(io::Error::new(e.kind(), e.to_string())),
+            }
+        }
+        // differing permission bits alone are --merge-metadata's business
+        let keeper_explicit = matches!(xattr::get(keeper, ACL_ACCESS), Ok(Some(_)));
+        if !keeper_explicit && !copies.iter().any(|copy| copy.explicit) {
+            return Ok(None);
+        }
+
+        let kept = read_acl(keeper)?;
+        let mut merged = kept.clone();
+        for copy in copies {
+            merged = merge_entries(merged, &copy.entries, mode);
+        }
+        if merged == kept {
+            return Ok(None);
+        }
+
+        xattr::set(keeper, ACL_ACCESS, &encode_acl(&merged))?;
+        let how = match mode {
+            AclMerge::Union => "everything any copy granted",
+            AclMerge::Strict => "only what every copy granted",
+        };
+        Ok(Some(format!("ACL rewritten to grant {}", how)))
+    };
+
+    match merge() {
+        Ok(note) => note.into_iter().collect(),
+        Err(e) if e.kind() == io::ErrorKind::Unsupported => Vec::new(),
+        Err(e) => vec![format!("could not merge ACLs: {}", e)],
+    }
+}
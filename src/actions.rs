@@ -0,0 +1,490 @@
+use crate::matching::MatchMode;
+use crate::pacing::Pacer;
+use crate::{DuplicateSet, FileInfo, Options, acl, consolidate, exec, hash, metadata, restat, retry, trash, truncated};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+
+const MAX_DEFAULT_JOBS: usize = 8;
+const PENDING_SUFFIX: &str = ".hydra-pending";
+
+// copies a scan found still renamed aside, by a run that was interrupted
+// between renaming them and removing them
+static LEFT_PENDING: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Delete,
+    Trash,
+    // replace the copy with a hard link to the keeper: every name stays, the
+    // space is freed
+    Link,
+    // hand the copy to the user's --action exec command, which decides
+    Exec,
+}
+
+impl Action {
+    pub fn parse(value: &str) -> Option<Action> {
+        match value {
+            "delete" => Some(Action::Delete),
+            "trash" => Some(Action::Trash),
+            "link" => Some(Action::Link),
+            "exec" => Some(Action::Exec),
+            _ => None,
+        }
+    }
+
+    // finish off a file already renamed to `pending`; trashed files get their
+    // name back first so the trash records where they really came from, and
+    // linked ones get it back as a link to `keeper`, and ones for the --action
+    // exec `command` get it back before the command runs. returns where a
+    // trashed file went, when known
+    pub fn finalize(
+        self,
+        pending: &Path,
+        original: &Path,
+        keeper: &Path,
+        command: &[String],
+    ) -> io::Result<Option<PathBuf>> {
+        match self {
+            Action::Delete => fs::remove_file(pending).map(|_| None),
+            Action::Trash => {
+                fs::rename(pending, original)?;
+                trash::move_to_trash(original)
+            }
+            Action::Link => {
+                fs::hard_link(keeper, original)?;
+                fs::remove_file(pending)
+                    .inspect_err(|_| {
+                        let _ = fs::remove_file(original);
+                    })
+                    .map(|_| None)
+            }
+            Action::Exec => {
+                fs::rename(pending, original)?;
+                exec::run(command, original, keeper).map(|_| None)
+            }
+        }
+    }
+
+    // "delete" / "trash" / "link", for messages like "Would delete: ..."
+    pub fn verb(self) -> &'static str {
+        match self {
+            Action::Delete => "delete",
+            Action::Trash => "trash",
+            Action::Link => "link",
+            Action::Exec => "exec",
+        }
+    }
+
+    pub fn past_tense(self) -> &'static str {
+        match self {
+            Action::Delete => "Deleted",
+            Action::Trash => "Trashed",
+            Action::Link => "Linked",
+            Action::Exec => "Passed on",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct WorkerStats {
+    pub deleted: usize,
+    pub skipped: usize,
+    // copies renamed aside and then put back because their set was rolled back
+    pub rolled_back: usize,
+    // --action exec: copies the command succeeded on but left where they were
+    pub passed_on: usize,
+    // --action link: copies left alone because they sit on another filesystem
+    // than their keeper
+    pub other_device: usize,
+    pub errors: usize,
+    // each deleted path with the keeper it duplicated
+    pub removed: Vec<(PathBuf, PathBuf)>,
+    // where trashed copies went, for --empty-trash-after
+    pub trashed: Vec<PathBuf>,
+    // keepers whose set was abandoned, with the reason
+    pub aborted: Vec<(PathBuf, String)>,
+    // copies another program had open, regrouped under their keeper so they
+    // can be retried
+    pub in_use: Vec<DuplicateSet>,
+    // copies that failed with a transient error, regrouped likewise
+    pub transient: Vec<DuplicateSet>,
+}
+
+// windows refuses to rename or delete a file another program has open
+#[cfg(windows)]
+fn is_in_use(e: &io::Error) -> bool {
+    const ERROR_SHARING_VIOLATION: i32 = 32;
+    const ERROR_LOCK_VIOLATION: i32 = 33;
+
+    matches!(e.raw_os_error(), Some(ERROR_SHARING_VIOLATION | ERROR_LOCK_VIOLATION))
+}
+
+#[cfg(not(windows))]
+fn is_in_use(_e: &io::Error) -> bool {
+    false
+}
+
+pub fn default_jobs() -> usize {
+    thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .min(MAX_DEFAULT_JOBS)
+}
+
+// the keeper's hash if both files are identical
+fn same_content(file_info: &FileInfo, keeper: &FileInfo) -> io::Result<Option<u64>> {
+    if file_info.size != keeper.size {
+        return Ok(None);
+    }
+
+    let keeper_hash = hash::hash_file(&keeper.path)?;
+    Ok((hash::hash_file(&file_info.path)? == keeper_hash).then_some(keeper_hash))
+}
+
+// final check right before a deletion: anything touched since the scan must
+// still hash identically to the keeper. returns the keeper's hash if it had
+// to be computed
+pub fn verify_unchanged(file_info: &mut FileInfo, keeper: &mut FileInfo) -> Result<Option<u64>, String> {
+    let keeper_changed = restat(keeper).map_err(|e| format!("keeper no longer readable: {}", e))?;
+    let changed = restat(file_info).map_err(|e| format!("no longer readable: {}", e))?;
+
+    if !changed && !keeper_changed {
+        return Ok(None);
+    }
+
+    match same_content(file_info, keeper) {
+        Ok(Some(keeper_hash)) => Ok(Some(keeper_hash)),
+        Ok(None) => Err("modified since scan and no longer identical to keeper".to_string()),
+        Err(e) => Err(format!("modified since scan and could not be re-hashed: {}", e)),
+    }
+}
+
+// a 64-bit FNV hash isn't collision-resistant, and one read back with
+// --xattr-cache is only as good as whoever last wrote it, so a copy matched
+// by content is always compared with its keeper byte by byte before it goes.
+// (a truncated copy is shorter than its keeper and was matched byte by byte
+// already)
+fn same_bytes(file_info: &FileInfo, keeper: &FileInfo) -> Result<(), String> {
+    if file_info.size != keeper.size {
+        return Ok(());
+    }
+    let identical = truncated::is_prefix(&file_info.path, &keeper.path)
+        .map_err(|e| format!("could not be compared with '{}': {}", keeper.path.display(), e))?;
+    if !identical {
+        return Err(format!("not identical to '{}' byte for byte", keeper.path.display()));
+    }
+    Ok(())
+}
+
+// --action link: a hard link can't cross filesystems, so only a copy on the
+// keeper's device can become one. unknown devices are left to the link itself
+pub fn linkable(copy: &Path, keeper: &Path) -> bool {
+    let device = |path: &Path| fs::metadata(path).ok().and_then(|metadata| consolidate::file_id(&metadata));
+    match (device(copy), device(keeper)) {
+        (Some((copy, _)), Some((keeper, _))) => copy == keeper,
+        _ => true,
+    }
+}
+
+// claim a free "<name>.hydra-pending" name next to `path`
+fn pending_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+    let mut candidate = path.with_file_name(format!("{}{}", name, PENDING_SUFFIX));
+    let mut counter = 2;
+
+    while candidate.symlink_metadata().is_ok() {
+        candidate = path.with_file_name(format!("{}{}.{}", name, PENDING_SUFFIX, counter));
+        counter += 1;
+    }
+
+    candidate
+}
+
+// the name a copy had before it was renamed aside to "<name>.hydra-pending"
+// or "<name>.hydra-pending.N", if `path` is such a name
+pub fn pending_original(path: &Path) -> Option<PathBuf> {
+    let name = path.file_name()?.to_str()?;
+    let (original, counter) = name.rsplit_once(PENDING_SUFFIX)?;
+    let numbered = |counter: &str| !counter.is_empty() && counter.bytes().all(|byte| byte.is_ascii_digit());
+    let counter_ok = counter.is_empty() || counter.strip_prefix('.').is_some_and(numbered);
+    (!original.is_empty() && counter_ok).then(|| path.with_file_name(original))
+}
+
+// a scan started over may find the same one twice
+pub fn found_pending(path: PathBuf) {
+    let mut left = LEFT_PENDING.lock().unwrap();
+    if !left.contains(&path) {
+        left.push(path);
+    }
+}
+
+// put the copies an interrupted run left renamed aside back under their own
+// names, as rolling their set back would have; in a dry run only say where
+// they are. they take part in the next run, not this one, whose scan is over
+pub fn recover_pending(dry_run: bool) {
+    let mut left = LEFT_PENDING.lock().unwrap();
+    for pending in left.drain(..) {
+        let Some(original) = pending_original(&pending) else {
+            continue;
+        };
+        if dry_run {
+            eprintln!("Note: '{}' was left aside by an interrupted run; it will be restored", pending.display());
+        } else if original.symlink_metadata().is_ok() {
+            eprintln!(
+                "Warning: '{}' was left aside by an interrupted run, but '{}' exists now; leaving both as they are",
+                pending.display(),
+                original.display()
+            );
+        } else {
+            match fs::rename(&pending, &original) {
+                Ok(()) => eprintln!("Note: Restored '{}', left aside by an interrupted run", original.display()),
+                Err(e) => eprintln!("Warning: Could not restore '{}': {}", pending.display(), e),
+            }
+        }
+    }
+}
+
+// the invariant behind every deletion: the keeper can be opened, is still a
+// regular file of the recorded size and, when its hash is known, still has it
+pub fn verify_keeper(keeper: &FileInfo, expected_hash: Option<u64>) -> Result<(), String> {
+    let file = fs::File::open(&keeper.path).map_err(|e| format!("could not be opened: {}", e))?;
+    let metadata = file.metadata().map_err(|e| format!("could not be read: {}", e))?;
+
+    if !metadata.is_file() {
+        return Err("is no longer a regular file".to_string());
+    }
+    if metadata.len() != keeper.size {
+        return Err(format!("changed size ({} bytes, expected {})", metadata.len(), keeper.size));
+    }
+    if let Some(expected) = expected_hash {
+        match hash::hash_file(&keeper.path) {
+            Ok(hash) if hash == expected => {}
+            Ok(_) => return Err("contents changed".to_string()),
+            Err(e) => return Err(format!("could not be hashed: {}", e)),
+        }
+    }
+
+    Ok(())
+}
+
+fn abort_set(set: &DuplicateSet, reason: String, stats: &mut WorkerStats) {
+    eprintln!(
+        "Aborted set '{}': keeper '{}' {}",
+        set.normalized_filename,
+        set.keeper.path.display(),
+        reason
+    );
+    stats.aborted.push((set.keeper.path.clone(), reason));
+}
+
+// put pending files back under their original names, returning the copies
+// that are back
+fn put_back(renamed: &[(PathBuf, usize)], set: &DuplicateSet, stats: &mut WorkerStats) -> Vec<FileInfo> {
+    let mut restored = Vec::new();
+    for (pending, index) in renamed {
+        let original = &set.duplicates[*index].path;
+        match fs::rename(pending, original) {
+            Ok(_) => restored.push(set.duplicates[*index].clone()),
+            Err(e) => {
+                eprintln!(
+                    "Error restoring '{}' (left as '{}'): {}",
+                    original.display(),
+                    pending.display(),
+                    e
+                );
+                stats.errors += 1;
+            }
+        }
+    }
+    restored
+}
+
+fn roll_back(renamed: &[(PathBuf, usize)], set: &DuplicateSet, stats: &mut WorkerStats) {
+    stats.rolled_back += put_back(renamed, set, stats).len();
+}
+
+// the copies `duplicates` of a set under its keeper, to be tried again
+fn regroup(set: &DuplicateSet, duplicates: Vec<FileInfo>) -> DuplicateSet {
+    DuplicateSet {
+        normalized_filename: set.normalized_filename.clone(),
+        size: set.size,
+        hash: set.hash,
+        keeper: set.keeper.clone(),
+        duplicates,
+    }
+}
+
+// two phases per set: every doomed copy is first renamed aside, then the keeper
+// is checked and only then are the renamed copies removed. if anything fails
+// before that point the renames are undone, so a set is never left without a
+// surviving copy
+fn delete_set(set: &mut DuplicateSet, options: &Options, pacer: &Pacer, stats: &mut WorkerStats) {
+    if let Err(reason) = verify_keeper(&set.keeper, None) {
+        stats.skipped += set.duplicates.len();
+        abort_set(set, reason, stats);
+        return;
+    }
+
+    // phase 1: rename
+    let mut renamed: Vec<(PathBuf, usize)> = Vec::new();
+    let mut keeper_hash = None;
+    let mut in_use = Vec::new();
+    let mut transient = Vec::new();
+    for (index, file_info) in set.duplicates.iter_mut().enumerate() {
+        let verified = verify_unchanged(file_info, &mut set.keeper).and_then(|hash| {
+            if options.match_mode == MatchMode::Content {
+                same_bytes(file_info, &set.keeper)?;
+            }
+            Ok(hash)
+        });
+        match verified {
+            Ok(hash) => keeper_hash = hash.or(keeper_hash),
+            Err(reason) => {
+                eprintln!("Skipped '{}': {}", file_info.path.display(), reason);
+                stats.skipped += 1;
+                continue;
+            }
+        }
+        if options.action == Action::Link && !linkable(&file_info.path, &set.keeper.path) {
+            eprintln!(
+                "Skipped '{}': on another filesystem than '{}', so it can't be hard-linked",
+                file_info.path.display(),
+                set.keeper.path.display()
+            );
+            stats.other_device += 1;
+            continue;
+        }
+
+        let pending = pending_path(&file_info.path);
+        if let Err(e) = fs::rename(&file_info.path, &pending) {
+            if is_in_use(&e) {
+                eprintln!("Skipped '{}': in use by another program", file_info.path.display());
+                in_use.push(file_info.clone());
+                continue;
+            }
+            if retry::is_transient(&e) {
+                eprintln!("Skipped '{}' for now: {}", file_info.path.display(), e);
+                transient.push(file_info.clone());
+                continue;
+            }
+            eprintln!("Error trying to {} '{}': {}", options.action.verb(), file_info.path.display(), e);
+            eprintln!("Rolling back set '{}'", set.normalized_filename);
+            stats.errors += 1;
+            roll_back(&renamed, set, stats);
+            return;
+        }
+        renamed.push((pending, index));
+    }
+
+    if !in_use.is_empty() {
+        stats.in_use.push(regroup(set, in_use));
+    }
+    if !transient.is_empty() {
+        stats.transient.push(regroup(set, transient));
+    }
+
+    if renamed.is_empty() {
+        return;
+    }
+
+    if let Err(reason) = verify_keeper(&set.keeper, keeper_hash) {
+        roll_back(&renamed, set, stats);
+        abort_set(set, reason, stats);
+        return;
+    }
+
+    // phase 2: finalize. what --merge-metadata and --merge-acls need of a copy
+    // is read just before it goes, and merged onto the keeper only from the
+    // copies that did
+    let mut taken_metadata = Vec::new();
+    let mut taken_acls = Vec::new();
+    for (position, (pending, index)) in renamed.iter().enumerate() {
+        let original = &set.duplicates[*index].path;
+        let metadata = options.merge_metadata.then(|| metadata::take(&set.duplicates[*index], pending));
+        let acl = options.merge_acls.map(|_| acl::take(pending));
+        match options.action.finalize(pending, original, &set.keeper.path, &options.exec) {
+            // a command may archive a copy somewhere and leave it in place:
+            // it still exists, so links and playlists keep pointing at it and
+            // nothing of it is merged onto the keeper
+            Ok(_) if options.action == Action::Exec && original.symlink_metadata().is_ok() => {
+                println!("{}: {} (still in place)", options.action.past_tense(), original.display());
+                stats.passed_on += 1;
+            }
+            Ok(location) => {
+                taken_metadata.extend(metadata);
+                taken_acls.extend(acl);
+                stats.trashed.extend(location);
+                println!("{}: {}", options.action.past_tense(), original.display());
+                stats.deleted += 1;
+                stats.removed.push((original.clone(), set.keeper.path.clone()));
+                pacer.removed(original);
+            }
+            Err(e) => {
+                eprintln!("Error trying to {} '{}': {}", options.action.verb(), original.display(), e);
+                // the failed file goes back too if it is still pending
+                let restored = pending.symlink_metadata().is_err() || fs::rename(pending, original).is_ok();
+                if !restored {
+                    eprintln!("Could not restore '{}' (left as '{}')", original.display(), pending.display());
+                }
+                // a transient failure, as on a flaky share, is likely to hit
+                // the copies after it too: they go back with it and the lot is
+                // tried again later. any other failure is this copy's alone
+                if restored && retry::is_transient(&e) {
+                    let mut rest = vec![set.duplicates[*index].clone()];
+                    rest.extend(put_back(&renamed[position + 1..], set, stats));
+                    stats.transient.push(regroup(set, rest));
+                    break;
+                }
+                stats.errors += 1;
+            }
+        }
+    }
+
+    if !taken_metadata.is_empty() {
+        for note in metadata::merge_onto_keeper(&set.keeper, &taken_metadata) {
+            println!("Keeper '{}': {}", set.keeper.path.display(), note);
+        }
+    }
+    if let Some(mode) = options.merge_acls
+        && !taken_acls.is_empty()
+    {
+        for note in acl::merge_onto_keeper(&set.keeper.path, &taken_acls, mode) {
+            println!("Keeper '{}': {}", set.keeper.path.display(), note);
+        }
+    }
+}
+
+// hand out whole sets to a bounded pool of workers; a set never spans workers
+// so its keeper is only ever re-checked by one thread
+pub fn delete_duplicates(duplicate_sets: &mut [DuplicateSet], options: &Options) -> Vec<WorkerStats> {
+    let workers = options.jobs.clamp(1, duplicate_sets.len().max(1));
+    let queue = Mutex::new(duplicate_sets.iter_mut());
+    let pacer = Pacer::new(options.delete_batch, options.delete_interval);
+
+    let stats = thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut stats = WorkerStats::default();
+                    loop {
+                        let set = match queue.lock().unwrap().next() {
+                            Some(set) => set,
+                            None => break,
+                        };
+                        delete_set(set, options, &pacer, &mut stats);
+                    }
+                    stats
+                })
+            })
+            .collect();
+
+        handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+    });
+
+    pacer.finish();
+    stats
+}
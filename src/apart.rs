@@ -0,0 +1,94 @@
+// --verify-apart: with --match name+size or name, "notes.txt" in two unrelated
+// projects is only the same file by coincidence. a group with copies far
+// apart, more than N directory steps from one another or in different
+// projects, is hashed like --match content before it is reported; copies
+// close together are still trusted on name and size alone
+use crate::FileInfo;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+// files or directories that mark the root of a project
+const PROJECT_MARKERS: [&str; 12] = [
+    ".git",
+    ".hg",
+    ".svn",
+    "Cargo.toml",
+    "package.json",
+    "pyproject.toml",
+    "setup.py",
+    "go.mod",
+    "pom.xml",
+    "build.gradle",
+    "CMakeLists.txt",
+    "Gemfile",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Apart {
+    // directory steps between the two copies' folders
+    Steps(usize),
+    // in different projects, or one in a project and one not
+    Project,
+}
+
+impl Apart {
+    pub fn parse(value: &str) -> Option<Apart> {
+        match value {
+            "project" => Some(Apart::Project),
+            _ => value.parse().ok().map(Apart::Steps),
+        }
+    }
+}
+
+// up from `a`'s folder to the deepest folder both share, then down to `b`'s
+fn steps(a: &Path, b: &Path) -> usize {
+    let a: Vec<_> = a.parent().map(|parent| parent.components().collect()).unwrap_or_default();
+    let b: Vec<_> = b.parent().map(|parent| parent.components().collect()).unwrap_or_default();
+    let shared = a.iter().zip(&b).take_while(|(a, b)| a == b).count();
+    a.len() - shared + b.len() - shared
+}
+
+// the nearest folder above `path` holding a project marker; folders already
+// looked at are remembered, as copies tend to sit side by side
+fn project_root(path: &Path, roots: &mut HashMap<PathBuf, Option<PathBuf>>) -> Option<PathBuf> {
+    let directory = path.parent()?;
+    if let Some(root) = roots.get(directory) {
+        return root.clone();
+    }
+    let root = if PROJECT_MARKERS.iter().any(|marker| directory.join(marker).exists()) {
+        Some(directory.to_path_buf())
+    } else {
+        project_root(directory, roots)
+    };
+    roots.insert(directory.to_path_buf(), root.clone());
+    root
+}
+
+pub struct Distances {
+    apart: Apart,
+    project_roots: HashMap<PathBuf, Option<PathBuf>>,
+}
+
+impl Distances {
+    pub fn new(apart: Apart) -> Distances {
+        Distances {
+            apart,
+            project_roots: HashMap::new(),
+        }
+    }
+
+    // whether any two copies of the group are far apart
+    pub fn spread(&mut self, group: &[FileInfo]) -> bool {
+        match self.apart {
+            Apart::Steps(limit) => group
+                .iter()
+                .enumerate()
+                .any(|(index, a)| group[index + 1..].iter().any(|b| steps(&a.path, &b.path) > limit)),
+            Apart::Project => {
+                let mut projects = group.iter().map(|file_info| project_root(&file_info.path, &mut self.project_roots));
+                let first = projects.next();
+                projects.any(|project| Some(project) != first)
+            }
+        }
+    }
+}
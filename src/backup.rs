@@ -0,0 +1,229 @@
+// --backup: pack every file about to be removed into a tar archive, with a
+// manifest of where each one came from, before anything is deleted. the tar is
+// written here and piped through the system compressor picked by extension
+use crate::{DuplicateSet, FileInfo, hash};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::UNIX_EPOCH;
+
+const BLOCK: usize = 512;
+const MANIFEST_NAME: &str = "MANIFEST.tsv";
+
+// external compressor for an archive path, or None for a plain .tar
+fn compressor(path: &Path) -> Result<Option<&'static [&'static str]>, String> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy().to_lowercase();
+
+    let command: &[&str] = if name.ends_with(".tar.zst") || name.ends_with(".tzst") {
+        &["zstd", "-q", "-c"]
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        &["gzip", "-c"]
+    } else if name.ends_with(".tar.xz") || name.ends_with(".txz") {
+        &["xz", "-c"]
+    } else if name.ends_with(".tar") {
+        return Ok(None);
+    } else {
+        return Err(format!(
+            "unsupported --backup archive '{}' (expected .tar, .tar.zst, .tar.gz or .tar.xz)",
+            path.display()
+        ));
+    };
+
+    Ok(Some(command))
+}
+
+// check the archive name up front so a typo is caught before scanning
+pub fn validate(path: &Path) -> Result<(), String> {
+    compressor(path).map(|_| ())
+}
+
+// write `value` as a nul-terminated octal number filling `field`
+fn octal(field: &mut [u8], value: u64) {
+    let text = format!("{:0width$o}", value, width = field.len() - 1);
+    field[..text.len()].copy_from_slice(text.as_bytes());
+}
+
+// pax records are "<length> key=value\n" where length counts itself
+fn pax_record(key: &str, value: &str) -> String {
+    let body = format!(" {}={}\n", key, value);
+    let mut length = body.len() + 1;
+    while (length.to_string().len() + body.len()) != length {
+        length += 1;
+    }
+    format!("{}{}", length, body)
+}
+
+fn header(name: &str, size: u64, mode: u32, mtime: u64, kind: u8) -> [u8; BLOCK] {
+    let mut block = [0u8; BLOCK];
+    let name = name.as_bytes();
+    let length = name.len().min(100);
+
+    block[..length].copy_from_slice(&name[..length]);
+    octal(&mut block[100..108], mode as u64);
+    octal(&mut block[108..116], 0);
+    octal(&mut block[116..124], 0);
+    octal(&mut block[124..136], size.min(0o77777777777));
+    octal(&mut block[136..148], mtime);
+    block[156] = kind;
+    block[257..263].copy_from_slice(b"ustar\0");
+    block[263..265].copy_from_slice(b"00");
+
+    // the checksum is computed with its own field set to spaces
+    block[148..156].fill(b' ');
+    let checksum: u64 = block.iter().map(|byte| *byte as u64).sum();
+    octal(&mut block[148..155], checksum);
+
+    block
+}
+
+fn pad(out: &mut dyn Write, written: u64) -> io::Result<()> {
+    let remainder = (written % BLOCK as u64) as usize;
+    if remainder > 0 {
+        out.write_all(&[0u8; BLOCK][remainder..])?;
+    }
+    Ok(())
+}
+
+// one archive member; names or sizes ustar can't hold go in a pax header
+fn write_entry(
+    out: &mut dyn Write,
+    name: &str,
+    size: u64,
+    mode: u32,
+    mtime: u64,
+    contents: &mut dyn Read,
+) -> io::Result<()> {
+    let mut extended = String::new();
+    if name.len() > 100 {
+        extended.push_str(&pax_record("path", name));
+    }
+    if size > 0o77777777777 {
+        extended.push_str(&pax_record("size", &size.to_string()));
+    }
+    if !extended.is_empty() {
+        out.write_all(&header("././@PaxHeader", extended.len() as u64, 0o644, mtime, b'x'))?;
+        out.write_all(extended.as_bytes())?;
+        pad(out, extended.len() as u64)?;
+    }
+
+    out.write_all(&header(name, size, mode, mtime, b'0'))?;
+    let copied = io::copy(&mut contents.take(size), out)?;
+    if copied != size {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "file shrank while being archived"));
+    }
+    pad(out, size)
+}
+
+#[cfg(unix)]
+fn file_mode(metadata: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+
+    metadata.permissions().mode() & 0o7777
+}
+
+#[cfg(not(unix))]
+fn file_mode(_metadata: &fs::Metadata) -> u32 {
+    0o644
+}
+
+fn member_name(index: usize, file_info: &FileInfo) -> String {
+    let name = file_info.path.file_name().unwrap_or_default().to_string_lossy();
+    format!("files/{:06}/{}", index + 1, name)
+}
+
+fn write_tar(out: &mut dyn Write, duplicate_sets: &[DuplicateSet]) -> Result<(), String> {
+    let files: Vec<(&FileInfo, &FileInfo)> = duplicate_sets
+        .iter()
+        .flat_map(|set| set.duplicates.iter().map(move |file_info| (file_info, &set.keeper)))
+        .collect();
+
+    // the manifest goes first so a partially-read archive still explains itself
+    let mut manifest = String::from("# archived\toriginal path\tkept copy\n");
+    for (index, (file_info, keeper)) in files.iter().enumerate() {
+        manifest.push_str(&format!(
+            "{}\t{}\t{}\n",
+            member_name(index, file_info),
+            file_info.path.display(),
+            keeper.path.display()
+        ));
+    }
+
+    let now = std::time::SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    write_entry(out, MANIFEST_NAME, manifest.len() as u64, 0o644, now, &mut manifest.as_bytes())
+        .map_err(|e| format!("could not write manifest: {}", e))?;
+
+    for (index, (file_info, _)) in files.iter().enumerate() {
+        let error = |e: io::Error| format!("could not archive '{}': {}", file_info.path.display(), e);
+
+        let file = File::open(&file_info.path).map_err(error)?;
+        let metadata = file.metadata().map_err(error)?;
+        let mtime = file_info.modified.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+
+        write_entry(out, &member_name(index, file_info), metadata.len(), file_mode(&metadata), mtime, &mut hash::Throttled(file))
+            .map_err(error)?;
+    }
+
+    // end of archive: two zero blocks
+    out.write_all(&[0u8; BLOCK * 2]).map_err(|e| e.to_string())
+}
+
+fn compress(command: &[&str], output: File, duplicate_sets: &[DuplicateSet]) -> Result<(), String> {
+    let mut child = Command::new(command[0])
+        .args(&command[1..])
+        .stdin(Stdio::piped())
+        .stdout(output)
+        .spawn()
+        .map_err(|e| format!("could not run '{}': {}", command[0], e))?;
+
+    // the writer is dropped at the end of this block, closing the pipe
+    let written = match child.stdin.take() {
+        Some(stdin) => {
+            let mut writer = io::BufWriter::new(stdin);
+            write_tar(&mut writer, duplicate_sets).and_then(|_| writer.flush().map_err(|e| e.to_string()))
+        }
+        None => Err("could not write to compressor".to_string()),
+    };
+    let status = child.wait().map_err(|e| e.to_string())?;
+
+    written?;
+    if !status.success() {
+        return Err(format!("'{}' exited with {}", command[0], status));
+    }
+
+    Ok(())
+}
+
+// write the archive, returning how many files went into it. an existing file
+// is never overwritten, since it may be an earlier run's only record of what it
+// removed; any failure leaves no archive behind and must stop the deletion,
+// and the archive is on disk before this returns
+pub fn write_archive(path: &Path, duplicate_sets: &[DuplicateSet]) -> Result<usize, String> {
+    let count = duplicate_sets.iter().map(|set| set.duplicates.len()).sum();
+    let compressor = compressor(path)?;
+    let output = match File::options().write(true).create_new(true).open(path) {
+        Ok(output) => output,
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+            return Err(format!("'{}' already exists; choose a new name for this run's archive", path.display()));
+        }
+        Err(e) => return Err(format!("could not create '{}': {}", path.display(), e)),
+    };
+    // from here on the file is this run's own, so it can be removed on failure
+    let result = output.try_clone().map_err(|e| e.to_string()).and_then(|synced| {
+        match compressor {
+            None => {
+                let mut writer = io::BufWriter::new(output);
+                write_tar(&mut writer, duplicate_sets).and_then(|_| writer.flush().map_err(|e| e.to_string()))
+            }
+            Some(command) => compress(command, output, duplicate_sets),
+        }?;
+        synced.sync_all().map_err(|e| format!("could not sync '{}': {}", path.display(), e))
+    });
+
+    if let Err(e) = result {
+        let _ = fs::remove_file(path);
+        return Err(e);
+    }
+
+    Ok(count)
+}
@@ -0,0 +1,179 @@
+// `hydra bench [DIR]`: measure how fast the storage under DIR can be walked,
+// stat'ed and hashed, how hashing scales with parallel readers and read sizes,
+// and suggest [hashing] settings for the config file. every configuration
+// reads different files, so one run doesn't warm the cache for the next
+use crate::{config, format_bytes, hash, scheduler};
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// at most this many files are stat'ed and this much data hashed
+const STAT_SAMPLE: usize = 100_000;
+const HASH_SAMPLE: u64 = 512 * 1024 * 1024;
+// in-memory buffer for timing the hash function alone
+const MEMORY_SAMPLE: usize = 64 * 1024 * 1024;
+const BUFFER_SIZES_KB: [usize; 4] = [16, 64, 256, 1024];
+// a setting has to beat the simpler one by this much to be recommended
+const MARGIN: f64 = 1.05;
+
+fn rate(bytes: u64, elapsed: Duration) -> f64 {
+    bytes as f64 / elapsed.as_secs_f64().max(1e-9)
+}
+
+fn format_rate(bytes_per_second: f64) -> String {
+    format!("{}/s", format_bytes(bytes_per_second as u64))
+}
+
+// every file under `directory`, by directory entries alone (no stat)
+fn walk(directory: &Path, files: &mut Vec<PathBuf>, entries: &mut u64) {
+    let Ok(read_dir) = fs::read_dir(directory) else {
+        return;
+    };
+    for entry in read_dir.flatten() {
+        *entries += 1;
+        match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => walk(&entry.path(), files, entries),
+            Ok(file_type) if file_type.is_file() => files.push(entry.path()),
+            _ => {}
+        }
+    }
+}
+
+// hash `files` with `threads` readers of `buffer_size` bytes; returns the
+// bytes read per second
+fn hash_rate(files: &[(PathBuf, u64)], threads: usize, buffer_size: usize) -> f64 {
+    let queue = Mutex::new(files.iter());
+    let started = Instant::now();
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                loop {
+                    let Some((path, _)) = queue.lock().unwrap().next() else {
+                        break;
+                    };
+                    if let Err(e) = hash::hash_contents_with(path, buffer_size) {
+                        eprintln!("Warning: Could not read '{}': {}", path.display(), e);
+                    }
+                }
+            });
+        }
+    });
+    rate(files.iter().map(|(_, size)| size).sum(), started.elapsed())
+}
+
+// the first setting (the simplest, or the default) within MARGIN of the fastest
+fn best<T: Copy>(results: &[(T, f64)]) -> Option<T> {
+    let fastest = results.iter().map(|(_, rate)| *rate).fold(0.0, f64::max);
+    results.iter().find(|(_, rate)| rate * MARGIN >= fastest).map(|(setting, _)| *setting)
+}
+
+// `hydra bench [DIR]`
+pub fn run(args: &[String]) -> Result<(), String> {
+    let directory = match args {
+        [] => crate::get_current_directory(),
+        [directory] => directory.clone(),
+        _ => return Err("usage: hydra bench [DIR]".to_string()),
+    };
+    if !Path::new(&directory).is_dir() {
+        return Err(format!("could not read '{}'", directory));
+    }
+    println!("Benchmarking the storage under {} ...", directory);
+
+    let started = Instant::now();
+    let (mut files, mut entries) = (Vec::new(), 0);
+    walk(Path::new(&directory), &mut files, &mut entries);
+    let elapsed = started.elapsed();
+    let seconds = elapsed.as_secs_f64();
+    println!("\nWalk: {} entries in {:.2}s ({:.0} entries/s)", entries, seconds, entries as f64 / seconds.max(1e-9));
+    if files.is_empty() {
+        return Err(format!("no files to measure under '{}'", directory));
+    }
+
+    // spread the samples over the tree rather than taking its first corner
+    files.sort_by_cached_key(|path| {
+        let mut hasher = DefaultHasher::new();
+        path.hash(&mut hasher);
+        hasher.finish()
+    });
+
+    let started = Instant::now();
+    let mut sizes = Vec::new();
+    for path in files.into_iter().take(STAT_SAMPLE) {
+        if let Ok(metadata) = fs::symlink_metadata(&path) {
+            sizes.push((path, metadata.len()));
+        }
+    }
+    let elapsed = started.elapsed();
+    let seconds = elapsed.as_secs_f64();
+    println!("Stat: {} files in {:.2}s ({:.0} files/s)", sizes.len(), seconds, sizes.len() as f64 / seconds.max(1e-9));
+
+    let memory: Vec<u8> = (0..MEMORY_SAMPLE).map(|index| (index * 31 % 251) as u8).collect();
+    let started = Instant::now();
+    std::hint::black_box(hash::hash_bytes(&memory));
+    let algorithm = rate(MEMORY_SAMPLE as u64, started.elapsed());
+    println!("Hash (FNV-1a, in memory): {}", format_rate(algorithm));
+
+    // one slice of the sample per configuration measured
+    let mut thread_counts = vec![1];
+    while thread_counts.last().unwrap() * 2 <= scheduler::parallel_workers() {
+        thread_counts.push(thread_counts.last().unwrap() * 2);
+    }
+    let runs = thread_counts.len() + BUFFER_SIZES_KB.len();
+    let mut slices = vec![Vec::new(); runs];
+    let mut sampled = 0;
+    for (index, (path, size)) in sizes.into_iter().filter(|(_, size)| *size > 0).enumerate() {
+        if sampled >= HASH_SAMPLE {
+            break;
+        }
+        sampled += size;
+        slices[index % runs].push((path, size));
+    }
+    if slices.iter().any(Vec::is_empty) {
+        return Err(format!("too few files under '{}' to compare settings", directory));
+    }
+    if sampled < HASH_SAMPLE / 8 {
+        eprintln!(
+            "Note: only {} to read; cached files make the results look faster than the storage is",
+            format_bytes(sampled)
+        );
+    }
+
+    println!("\n--- Hash throughput by parallel readers ({} KiB reads) ---", hash::DEFAULT_BUFFER_SIZE / 1024);
+    let mut slices = slices.into_iter();
+    let mut by_threads = Vec::new();
+    for &threads in &thread_counts {
+        let rate = hash_rate(&slices.next().unwrap(), threads, hash::DEFAULT_BUFFER_SIZE);
+        println!("{:>4} thread(s): {}", threads, format_rate(rate));
+        by_threads.push((threads, rate));
+    }
+    let threads = best(&by_threads).unwrap_or(1);
+
+    println!("\n--- Hash throughput by read size ({} thread(s)) ---", threads);
+    let mut by_buffer = Vec::new();
+    for kb in BUFFER_SIZES_KB {
+        let rate = hash_rate(&slices.next().unwrap(), threads, kb * 1024);
+        println!("{:>5} KiB: {}", kb, format_rate(rate));
+        by_buffer.push((kb, rate));
+    }
+    // the default read size wins any near tie
+    let default_kb = hash::DEFAULT_BUFFER_SIZE / 1024;
+    by_buffer.sort_by_key(|(kb, _)| *kb != default_kb);
+    let buffer_kb = best(&by_buffer).unwrap_or(default_kb);
+
+    let fastest = by_threads.iter().chain(&by_buffer).map(|(_, rate)| *rate).fold(0.0, f64::max);
+    println!("\n--- Recommendation ---");
+    // within reach of what the hash function manages on that many cores
+    if fastest >= 0.8 * algorithm * threads as f64 {
+        println!("Hashing is limited by the CPU here, not by the storage.");
+    }
+    if threads == 1 && thread_counts.len() > 1 {
+        println!("Parallel reads don't help on this storage; --io-profile hdd reads one file at a time.");
+    }
+    let path = config::default_path().map_or("the config file".to_string(), |path| path.display().to_string());
+    println!("Add to {}:\n\n[hashing]\nthreads = {}\nbuffer-kb = {}", path, threads, buffer_kb);
+    Ok(())
+}
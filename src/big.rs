@@ -0,0 +1,75 @@
+// `hydra big [--top N] [DIR]`: the largest files and directories under a
+// tree, for a quick look at where the space goes before (or instead of) a
+// duplicate scan. like du, a file with several hard links is counted once
+use crate::consolidate::file_id;
+use crate::{format_bytes, scan};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_TOP: usize = 20;
+
+fn print_largest(title: &str, mut entries: Vec<(PathBuf, u64)>, top: usize) {
+    entries.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    entries.truncate(top);
+
+    println!("\n--- {} ---", title);
+    for (path, size) in entries {
+        println!("{:>10}  {}", format_bytes(size), path.display());
+    }
+}
+
+// `hydra big [--top N] [DIR]`
+pub fn run(args: &[String]) -> Result<(), String> {
+    let usage = "usage: hydra big [--top N] [DIR]";
+    let mut top = DEFAULT_TOP;
+    let mut directory = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--top" => {
+                let value = args.next().ok_or(usage)?;
+                top = value.parse().map_err(|_| format!("invalid --top '{}'", value))?;
+            }
+            _ if directory.is_none() && !arg.starts_with('-') => directory = Some(arg),
+            _ => return Err(usage.to_string()),
+        }
+    }
+
+    let root = match directory {
+        Some(directory) => fs::canonicalize(directory).map_err(|e| format!("could not read '{}': {}", directory, e))?,
+        None => std::env::current_dir().map_err(|e| e.to_string())?,
+    };
+
+    let mut files = Vec::new();
+    let mut directories: HashMap<PathBuf, u64> = HashMap::new();
+    let mut seen_ids = HashSet::new();
+    let readable = scan::walk_files(&root, true, |path, metadata| {
+        if let Some(id) = file_id(&metadata)
+            && !seen_ids.insert(id)
+        {
+            return;
+        }
+        let size = metadata.len();
+        for ancestor in path.ancestors().skip(1) {
+            *directories.entry(ancestor.to_path_buf()).or_default() += size;
+            if ancestor == root {
+                break;
+            }
+        }
+        files.push((path, size));
+    });
+    if !readable {
+        return Err(format!("could not read '{}'", root.display()));
+    }
+
+    let total = directories.get(&root).copied().unwrap_or(0);
+    println!("{}: {} file(s), {}", root.display(), files.len(), format_bytes(total));
+
+    // the root itself would always top the list
+    directories.remove(Path::new(&root));
+    print_largest("Largest files", files, top);
+    print_largest("Largest directories", directories.into_iter().collect(), top);
+    Ok(())
+}
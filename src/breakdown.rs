@@ -0,0 +1,107 @@
+// `hydra stats [DIR] [flags]`: how many files and bytes of each extension and
+// file class a tree holds, and how much of each a normal run would remove, to
+// decide which media types are worth a focused run with --type
+use crate::filetype::{self, FileClass};
+use crate::{format_bytes, scan};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+
+#[derive(Default)]
+struct Tally {
+    files: usize,
+    bytes: u64,
+    duplicate_files: usize,
+    duplicate_bytes: u64,
+}
+
+fn extension(path: &Path) -> String {
+    path.extension().map_or(String::new(), |ext| ext.to_string_lossy().to_lowercase())
+}
+
+fn class_name(extension: &str) -> &'static str {
+    filetype::class_from_extension(extension).map_or("other", FileClass::name)
+}
+
+fn print_tallies(title: &str, tallies: HashMap<String, Tally>) {
+    let mut tallies: Vec<_> = tallies.into_iter().collect();
+    tallies.sort_by(|a, b| b.1.bytes.cmp(&a.1.bytes).then_with(|| a.0.cmp(&b.0)));
+
+    println!("\n--- {} ---", title);
+    println!("{:<12} {:>8} {:>11} {:>10} {:>11}", "", "Files", "Size", "Duplicates", "Wasted");
+    for (name, tally) in tallies {
+        let share = (tally.duplicate_bytes * 100).checked_div(tally.bytes).unwrap_or(0);
+        println!(
+            "{:<12} {:>8} {:>11} {:>10} {:>11} {:>5}%",
+            name,
+            tally.files,
+            format_bytes(tally.bytes),
+            tally.duplicate_files,
+            format_bytes(tally.duplicate_bytes),
+            share
+        );
+    }
+}
+
+// `hydra stats [DIR] [flags]`; the flags of a normal run decide what is scanned
+// and what counts as a duplicate (-r, --match, --min-size, ...)
+pub fn run(args: &[String]) -> Result<(), String> {
+    let (directory, flags) = match args.split_first() {
+        Some((directory, flags)) if !directory.starts_with('-') => (Some(directory), flags),
+        _ => (None, args),
+    };
+    let directory = match directory {
+        Some(directory) => fs::canonicalize(directory).map_err(|e| format!("could not read '{}': {}", directory, e))?,
+        None => std::env::current_dir().map_err(|e| e.to_string())?,
+    };
+    let directory = directory.to_string_lossy().to_string();
+
+    let mut flag_args = vec!["hydra".to_string()];
+    flag_args.extend(flags.iter().map(|flag| flag.to_string()));
+    let options = crate::parse_args(&flag_args)?;
+
+    let mut by_extension: HashMap<String, Tally> = HashMap::new();
+    let readable = scan::walk_files(Path::new(&directory), options.recursive, |path, metadata| {
+        let tally = by_extension.entry(extension(&path)).or_default();
+        tally.files += 1;
+        tally.bytes += metadata.len();
+    });
+    if !readable {
+        return Err(format!("could not read '{}'", directory));
+    }
+    if by_extension.is_empty() {
+        println!("No files found (pass -r to include subdirectories).");
+        return Ok(());
+    }
+
+    let mut started = Instant::now();
+    let findings = crate::find_duplicates(&directory, &options, &mut started)
+        .ok_or(format!("could not read '{}'", directory))?;
+    for file_info in findings.duplicate_sets.iter().flat_map(|set| &set.duplicates) {
+        let tally = by_extension.entry(extension(&file_info.path)).or_default();
+        tally.duplicate_files += 1;
+        tally.duplicate_bytes += file_info.size;
+    }
+
+    let mut by_class: HashMap<String, Tally> = HashMap::new();
+    for (extension, tally) in &by_extension {
+        let class = by_class.entry(class_name(extension).to_string()).or_default();
+        class.files += tally.files;
+        class.bytes += tally.bytes;
+        class.duplicate_files += tally.duplicate_files;
+        class.duplicate_bytes += tally.duplicate_bytes;
+    }
+
+    let by_extension = by_extension
+        .into_iter()
+        .map(|(extension, tally)| {
+            let name = if extension.is_empty() { "(none)".to_string() } else { format!(".{}", extension) };
+            (name, tally)
+        })
+        .collect();
+
+    print_tallies("By class", by_class);
+    print_tallies("By extension", by_extension);
+    Ok(())
+}
@@ -0,0 +1,223 @@
+// --bursts SECONDS: photos taken by the same camera within a few seconds of
+// each other (continuous shooting) are near-duplicates that no hash will
+// match. they are only listed, for the photographer to thin by hand; nothing
+// in a burst is ever removed. the capture time and camera come from the exif
+// data of jpeg and tiff-based raw files
+use crate::{FileInfo, hash};
+use crate::filetype::{self, FileClass};
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+
+// the exif block sits near the start of the file
+const HEAD_BYTES: u64 = 128 * 1024;
+
+const TAG_MAKE: u16 = 0x010f;
+const TAG_MODEL: u16 = 0x0110;
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_GPS_IFD: u16 = 0x8825;
+const TAG_DATE_TIME_ORIGINAL: u16 = 0x9003;
+
+pub struct Burst {
+    pub camera: String,
+    pub started: String,
+    pub seconds: i64,
+    pub paths: Vec<PathBuf>,
+}
+
+struct Tiff<'a> {
+    data: &'a [u8],
+    little_endian: bool,
+}
+
+impl Tiff<'_> {
+    fn u16_at(&self, offset: usize) -> Option<u16> {
+        let bytes: [u8; 2] = self.data.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if self.little_endian { u16::from_le_bytes(bytes) } else { u16::from_be_bytes(bytes) })
+    }
+
+    fn u32_at(&self, offset: usize) -> Option<u32> {
+        let bytes: [u8; 4] = self.data.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if self.little_endian { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) })
+    }
+
+    // (tag, entry offset) of each entry of the IFD at `offset`
+    fn entries(&self, offset: usize) -> Vec<(u16, usize)> {
+        let count = self.u16_at(offset).unwrap_or(0) as usize;
+        (0..count)
+            .map(|index| offset + 2 + index * 12)
+            .filter_map(|entry| Some((self.u16_at(entry)?, entry)))
+            .collect()
+    }
+
+    // an ASCII value; up to 4 bytes are stored in the entry itself
+    fn ascii(&self, entry: usize) -> Option<String> {
+        let count = self.u32_at(entry + 4)? as usize;
+        let start = if count <= 4 { entry + 8 } else { self.u32_at(entry + 8)? as usize };
+        let bytes = self.data.get(start..start + count)?;
+        let text = String::from_utf8_lossy(bytes);
+        Some(text.trim_end_matches('\0').trim().to_string())
+    }
+}
+
+// (camera, "YYYY:MM:DD HH:MM:SS") from a tiff structure
+fn parse_tiff(data: &[u8]) -> Option<(String, String)> {
+    let little_endian = match data.get(..4)? {
+        b"II*\x00" => true,
+        b"MM\x00*" => false,
+        _ => return None,
+    };
+    let tiff = Tiff { data, little_endian };
+    let ifd0 = tiff.u32_at(4)? as usize;
+
+    let (mut make, mut model, mut exif_ifd) = (String::new(), String::new(), None);
+    for (tag, entry) in tiff.entries(ifd0) {
+        match tag {
+            TAG_MAKE => make = tiff.ascii(entry).unwrap_or_default(),
+            TAG_MODEL => model = tiff.ascii(entry).unwrap_or_default(),
+            TAG_EXIF_IFD => exif_ifd = tiff.u32_at(entry + 8),
+            _ => {}
+        }
+    }
+
+    let exif_ifd = exif_ifd? as usize;
+    let (_, entry) = tiff.entries(exif_ifd).into_iter().find(|(tag, _)| *tag == TAG_DATE_TIME_ORIGINAL)?;
+    let taken = tiff.ascii(entry)?;
+
+    // models usually repeat the make ("Canon" / "Canon EOS 5D")
+    let camera = if model.starts_with(&make) { model } else { format!("{} {}", make, model).trim().to_string() };
+    Some((camera, taken))
+}
+
+// how many fields a tiff structure's first IFD and its exif and gps IFDs hold
+pub fn exif_fields(data: &[u8]) -> usize {
+    let little_endian = match data.get(..4) {
+        Some(b"II*\x00") => true,
+        Some(b"MM\x00*") => false,
+        _ => return 0,
+    };
+    let tiff = Tiff { data, little_endian };
+    let Some(ifd0) = tiff.u32_at(4) else {
+        return 0;
+    };
+    let entries = tiff.entries(ifd0 as usize);
+    let nested: usize = entries
+        .iter()
+        .filter(|(tag, _)| *tag == TAG_EXIF_IFD || *tag == TAG_GPS_IFD)
+        .filter_map(|(_, entry)| tiff.u32_at(entry + 8))
+        .map(|offset| tiff.entries(offset as usize).len())
+        .sum();
+    entries.len() + nested
+}
+
+// the tiff structure inside a jpeg's APP1 "Exif" segment
+fn jpeg_exif(data: &[u8]) -> Option<&[u8]> {
+    if !data.starts_with(b"\xff\xd8") {
+        return None;
+    }
+    let mut offset = 2;
+    while data.get(offset) == Some(&0xff) {
+        let marker = *data.get(offset + 1)?;
+        // start of scan: the image data follows, no more metadata
+        if marker == 0xda {
+            return None;
+        }
+        let length = u16::from_be_bytes(data.get(offset + 2..offset + 4)?.try_into().ok()?) as usize;
+        let segment = data.get(offset + 4..offset + 2 + length)?;
+        if marker == 0xe1 && segment.starts_with(b"Exif\x00\x00") {
+            return Some(&segment[6..]);
+        }
+        offset += 2 + length;
+    }
+    None
+}
+
+fn read_exif(file_info: &FileInfo) -> io::Result<Option<(String, String)>> {
+    let mut head = Vec::new();
+    hash::open(&file_info.path)?.take(HEAD_BYTES).read_to_end(&mut head)?;
+    Ok(jpeg_exif(&head).map_or_else(|| parse_tiff(&head), parse_tiff))
+}
+
+// "YYYY:MM:DD HH:MM:SS" as seconds since 1970, for measuring gaps
+fn parse_taken(taken: &str) -> Option<i64> {
+    let number = |range: std::ops::Range<usize>| taken.get(range)?.parse::<i64>().ok();
+    let (year, month, day) = (number(0..4)?, number(5..7)?, number(8..10)?);
+    let (hour, minute, second) = (number(11..13)?, number(14..16)?, number(17..19)?);
+
+    // days from the civil date (Howard Hinnant's algorithm)
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+
+    Some(days * 86400 + hour * 3600 + minute * 60 + second)
+}
+
+// images among `file_infos` shot by one camera with at most `window` seconds
+// between consecutive frames
+pub fn find_bursts<'a>(file_infos: impl Iterator<Item = &'a FileInfo>, window: i64) -> Vec<Burst> {
+    let mut by_camera: HashMap<String, Vec<(i64, String, PathBuf)>> = HashMap::new();
+
+    for file_info in file_infos {
+        let extension = file_info.path.extension().map(|ext| ext.to_string_lossy().to_lowercase());
+        if extension.as_deref().and_then(filetype::class_from_extension) != Some(FileClass::Image) {
+            continue;
+        }
+        match read_exif(file_info) {
+            Ok(Some((camera, taken))) => {
+                if let Some(seconds) = parse_taken(&taken) {
+                    by_camera.entry(camera).or_default().push((seconds, taken, file_info.path.clone()));
+                }
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("Warning: Could not read '{}' for --bursts: {}", file_info.path.display(), e),
+        }
+    }
+
+    let mut bursts = Vec::new();
+    for (camera, mut shots) in by_camera {
+        shots.sort();
+        let mut start = 0;
+        for index in 1..=shots.len() {
+            if index < shots.len() && shots[index].0 - shots[index - 1].0 <= window {
+                continue;
+            }
+            if index - start > 1 {
+                bursts.push(Burst {
+                    camera: camera.clone(),
+                    started: shots[start].1.clone(),
+                    seconds: shots[index - 1].0 - shots[start].0,
+                    paths: shots[start..index].iter().map(|(_, _, path)| path.clone()).collect(),
+                });
+            }
+            start = index;
+        }
+    }
+    bursts.sort_by(|a, b| a.started.cmp(&b.started).then_with(|| a.camera.cmp(&b.camera)));
+    bursts
+}
+
+pub fn write_bursts(out: &mut dyn Write, bursts: &[Burst]) -> io::Result<()> {
+    if bursts.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(out, "\n--- Burst Sets (near-duplicates, review by hand; never removed) ---")?;
+    for burst in bursts {
+        let camera = if burst.camera.is_empty() { "unknown camera" } else { &burst.camera };
+        writeln!(
+            out,
+            "\n{}, {}: {} photo(s) over {} s",
+            camera,
+            burst.started,
+            burst.paths.len(),
+            burst.seconds
+        )?;
+        for path in &burst.paths {
+            writeln!(out, "  {}", path.display())?;
+        }
+    }
+    Ok(())
+}
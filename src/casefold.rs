@@ -0,0 +1,96 @@
+// names that differ only in case ("Report.pdf" / "report.pdf") can end up in
+// one directory of a case-insensitive filesystem, e.g. after restoring files
+// copied from linux. a path then no longer reliably names one file, so such
+// files are pulled out of duplicate grouping and reported instead
+use crate::FileInfo;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub struct CaseCollision {
+    pub paths: Vec<PathBuf>,
+}
+
+fn swap_case(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_uppercase() {
+                c.to_lowercase().next().unwrap_or(c)
+            } else {
+                c.to_uppercase().next().unwrap_or(c)
+            }
+        })
+        .collect()
+}
+
+// probe the directory with a case-swapped spelling that isn't one of the real
+// names: if it still resolves, lookups in this directory ignore case
+fn resolves_case_insensitively(directory: &Path, names: &HashSet<String>) -> bool {
+    names.iter().any(|name| {
+        let swapped = swap_case(name);
+        swapped != *name && !names.contains(&swapped) && directory.join(&swapped).symlink_metadata().is_ok()
+    })
+}
+
+// (directory, lowercased name, name) of a file
+fn folded(file_info: &FileInfo) -> Option<(&Path, String, String)> {
+    let (directory, name) = (file_info.path.parent()?, file_info.path.file_name()?);
+    let name = name.to_string_lossy().to_string();
+    Some((directory, name.to_lowercase(), name))
+}
+
+fn folded_hash(directory: &Path, folded_name: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    (directory, folded_name).hash(&mut hasher);
+    hasher.finish()
+}
+
+// remove every file involved in a case collision on a case-insensitive
+// directory from the name groups, returning the collisions found
+pub fn extract_collisions(hashmap_name: &mut HashMap<String, Vec<FileInfo>>) -> Vec<CaseCollision> {
+    // count folded names by hash first, so only the few that repeat are kept
+    // in full; a directory of a million files must not be copied here
+    let mut counts: HashMap<u64, u32> = HashMap::new();
+    for file_info in hashmap_name.values().flatten() {
+        if let Some((directory, folded_name, _)) = folded(file_info) {
+            *counts.entry(folded_hash(directory, &folded_name)).or_default() += 1;
+        }
+    }
+    counts.retain(|_, count| *count > 1);
+
+    let mut by_folded: HashMap<(PathBuf, String), HashSet<String>> = HashMap::new();
+    if !counts.is_empty() {
+        for file_info in hashmap_name.values().flatten() {
+            let Some((directory, folded_name, name)) = folded(file_info) else {
+                continue;
+            };
+            if counts.contains_key(&folded_hash(directory, &folded_name)) {
+                by_folded.entry((directory.to_path_buf(), folded_name)).or_default().insert(name);
+            }
+        }
+    }
+
+    let mut collisions = Vec::new();
+    let mut colliding: HashSet<PathBuf> = HashSet::new();
+    for ((directory, _), names) in by_folded {
+        if names.len() < 2 || !resolves_case_insensitively(&directory, &names) {
+            continue;
+        }
+
+        let mut paths: Vec<PathBuf> = names.iter().map(|name| directory.join(name)).collect();
+        paths.sort();
+        colliding.extend(paths.iter().cloned());
+        collisions.push(CaseCollision { paths });
+    }
+
+    if !colliding.is_empty() {
+        for file_infos in hashmap_name.values_mut() {
+            file_infos.retain(|file_info| !colliding.contains(&file_info.path));
+        }
+    }
+
+    collisions.sort_by(|a, b| a.paths.cmp(&b.paths));
+    collisions
+}
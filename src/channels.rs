@@ -0,0 +1,278 @@
+// keeping stdout for machine-readable output. with a structured --format,
+// everything else hydra prints (the report, notes, prompts) is sent to stderr
+// by pointing the process's stdout there, so no module has to know, and the
+// real stdout is set aside for the structured output alone. --log-format json
+// then turns each line on stderr into a JSON object for log collectors
+use crate::json;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::sync::Mutex;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime};
+
+// a prompt ends without a newline and then waits for an answer, so text left
+// without one for this long is logged as a line of its own
+const PARTIAL_LINE_WAIT: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl LogFormat {
+    pub fn parse(value: &str) -> Option<LogFormat> {
+        match value {
+            "text" => Some(LogFormat::Text),
+            "json" => Some(LogFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Stream {
+    Output,
+    Error,
+}
+
+// the real stdout, once separate() has set it aside
+static STRUCTURED: Mutex<Option<File>> = Mutex::new(None);
+
+struct Relay {
+    thread: JoinHandle<()>,
+    stderr: File,
+    separated: bool,
+}
+
+static RELAY: Mutex<Option<Relay>> = Mutex::new(None);
+
+// the leading words hydra's messages start with, and their log levels
+const LEVELS: &[(&str, &str)] = &[
+    ("Error: ", "error"),
+    ("Warning: ", "warning"),
+    ("Note: ", "note"),
+    ("Heartbeat: ", "heartbeat"),
+];
+
+#[cfg(unix)]
+mod sys {
+    use super::Stream;
+    use std::fs::File;
+    use std::io;
+    use std::os::fd::{AsRawFd, FromRawFd};
+    use std::os::raw::c_int;
+
+    unsafe extern "C" {
+        fn dup(fd: c_int) -> c_int;
+        fn dup2(fd: c_int, to: c_int) -> c_int;
+        #[link_name = "pipe"]
+        fn create_pipe(fds: *mut c_int) -> c_int;
+    }
+
+    fn fd(stream: Stream) -> c_int {
+        match stream {
+            Stream::Output => 1,
+            Stream::Error => 2,
+        }
+    }
+
+    // a file of our own for what `stream` currently points at
+    pub fn duplicate(stream: Stream) -> io::Result<File> {
+        match unsafe { dup(fd(stream)) } {
+            -1 => Err(io::Error::last_os_error()),
+            new => Ok(unsafe { File::from_raw_fd(new) }),
+        }
+    }
+
+    pub fn redirect(stream: Stream, to: &File) -> io::Result<()> {
+        match unsafe { dup2(to.as_raw_fd(), fd(stream)) } {
+            -1 => Err(io::Error::last_os_error()),
+            _ => Ok(()),
+        }
+    }
+
+    // (read end, write end)
+    pub fn pipe() -> io::Result<(File, File)> {
+        let mut fds = [0; 2];
+        if unsafe { create_pipe(fds.as_mut_ptr()) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) })
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use super::Stream;
+    use std::ffi::c_void;
+    use std::fs::File;
+    use std::io;
+    use std::os::windows::io::{AsRawHandle, FromRawHandle};
+
+    const STD_OUTPUT_HANDLE: u32 = -11i32 as u32;
+    const STD_ERROR_HANDLE: u32 = -12i32 as u32;
+    const DUPLICATE_SAME_ACCESS: u32 = 2;
+
+    unsafe extern "system" {
+        fn GetStdHandle(which: u32) -> *mut c_void;
+        fn SetStdHandle(which: u32, handle: *mut c_void) -> i32;
+        fn GetCurrentProcess() -> *mut c_void;
+        fn DuplicateHandle(
+            source_process: *mut c_void,
+            source: *mut c_void,
+            target_process: *mut c_void,
+            target: *mut *mut c_void,
+            access: u32,
+            inherit: i32,
+            options: u32,
+        ) -> i32;
+        fn CreatePipe(read: *mut *mut c_void, write: *mut *mut c_void, attributes: *mut c_void, size: u32) -> i32;
+        fn CloseHandle(handle: *mut c_void) -> i32;
+    }
+
+    fn which(stream: Stream) -> u32 {
+        match stream {
+            Stream::Output => STD_OUTPUT_HANDLE,
+            Stream::Error => STD_ERROR_HANDLE,
+        }
+    }
+
+    fn duplicate_handle(handle: *mut c_void) -> io::Result<*mut c_void> {
+        let mut new = std::ptr::null_mut();
+        let process = unsafe { GetCurrentProcess() };
+        if unsafe { DuplicateHandle(process, handle, process, &mut new, 0, 1, DUPLICATE_SAME_ACCESS) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(new)
+    }
+
+    pub fn duplicate(stream: Stream) -> io::Result<File> {
+        let handle = duplicate_handle(unsafe { GetStdHandle(which(stream)) })?;
+        Ok(unsafe { File::from_raw_handle(handle) })
+    }
+
+    // like dup2: the stream gets a handle of its own, and the one it had is closed
+    pub fn redirect(stream: Stream, to: &File) -> io::Result<()> {
+        let handle = duplicate_handle(to.as_raw_handle())?;
+        let previous = unsafe { GetStdHandle(which(stream)) };
+        if unsafe { SetStdHandle(which(stream), handle) } == 0 {
+            let error = io::Error::last_os_error();
+            unsafe { CloseHandle(handle) };
+            return Err(error);
+        }
+        unsafe { CloseHandle(previous) };
+        Ok(())
+    }
+
+    pub fn pipe() -> io::Result<(File, File)> {
+        let (mut read, mut write) = (std::ptr::null_mut(), std::ptr::null_mut());
+        if unsafe { CreatePipe(&mut read, &mut write, std::ptr::null_mut(), 0) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(unsafe { (File::from_raw_handle(read), File::from_raw_handle(write)) })
+    }
+}
+
+// set the real stdout aside for structured output and send everything else
+// written to stdout to stderr
+pub fn separate() -> io::Result<()> {
+    io::stdout().flush()?;
+    let stdout = sys::duplicate(Stream::Output)?;
+    sys::redirect(Stream::Output, &sys::duplicate(Stream::Error)?)?;
+    *STRUCTURED.lock().unwrap() = Some(stdout);
+    Ok(())
+}
+
+// where structured output goes: the real stdout
+pub fn structured() -> Box<dyn Write> {
+    match STRUCTURED.lock().unwrap().as_ref().map(File::try_clone) {
+        Some(Ok(file)) => Box::new(io::BufWriter::new(file)),
+        _ => Box::new(io::stdout()),
+    }
+}
+
+fn record(line: &str) -> String {
+    let (level, message) = LEVELS
+        .iter()
+        .find_map(|(prefix, level)| line.strip_prefix(prefix).map(|message| (*level, message)))
+        .unwrap_or(("info", line));
+    format!(
+        "{{\"time\": {}, \"level\": {}, \"message\": {}}}",
+        json::string(&crate::format_timestamp(SystemTime::now())),
+        json::string(level),
+        json::string(message.trim())
+    )
+}
+
+// --log-format json: stderr (and stdout, when separate() sent it there) goes
+// into a pipe, and a thread writes each line read from it to the real stderr
+// as a JSON object. one thread reads the pipe and another writes the lines,
+// so a partial line such as a prompt can be logged while the pipe is quiet
+pub fn log_json() -> io::Result<()> {
+    let stderr = sys::duplicate(Stream::Error)?;
+    let mut out = stderr.try_clone()?;
+    let (mut read, write) = sys::pipe()?;
+    let (chunks, received) = mpsc::channel::<Vec<u8>>();
+    thread::spawn(move || {
+        let mut buffer = [0u8; 8192];
+        while let Ok(count @ 1..) = read.read(&mut buffer) {
+            if chunks.send(buffer[..count].to_vec()).is_err() {
+                break;
+            }
+        }
+    });
+    let thread = thread::spawn(move || {
+        let mut log = |bytes: &[u8]| {
+            let line = String::from_utf8_lossy(bytes);
+            if !line.trim().is_empty() {
+                let _ = writeln!(out, "{}", record(&line));
+            }
+        };
+        let mut pending = Vec::new();
+        loop {
+            let chunk = match received.recv_timeout(PARTIAL_LINE_WAIT) {
+                Ok(chunk) => chunk,
+                Err(RecvTimeoutError::Timeout) => {
+                    log(&pending);
+                    pending.clear();
+                    continue;
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+            pending.extend(chunk);
+            while let Some(end) = pending.iter().position(|byte| *byte == b'\n') {
+                log(&pending[..end]);
+                pending.drain(..=end);
+            }
+        }
+        log(&pending);
+    });
+
+    io::stdout().flush()?;
+    let separated = STRUCTURED.lock().unwrap().is_some();
+    sys::redirect(Stream::Error, &write)?;
+    if separated {
+        sys::redirect(Stream::Output, &write)?;
+    }
+    *RELAY.lock().unwrap() = Some(Relay {
+        thread,
+        stderr,
+        separated,
+    });
+    Ok(())
+}
+
+// before exiting: put stderr back so the relay sees the end of its pipe and
+// has written every line
+pub fn finish() {
+    let _ = io::stdout().flush();
+    if let Some(relay) = RELAY.lock().unwrap().take() {
+        let _ = sys::redirect(Stream::Error, &relay.stderr);
+        if relay.separated {
+            let _ = sys::redirect(Stream::Output, &relay.stderr);
+        }
+        let _ = relay.thread.join();
+    }
+}
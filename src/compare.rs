@@ -0,0 +1,111 @@
+// `hydra compare OLD NEW`: diff two reports saved with --output so a recurring
+// cleanup only has to look at what changed. sets are matched by normalized
+// name and size, since the reports don't carry content hashes
+use crate::DuplicateSet;
+use crate::plan;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+fn members(set: &DuplicateSet) -> Vec<&PathBuf> {
+    std::iter::once(&set.keeper).chain(&set.duplicates).map(|file_info| &file_info.path).collect()
+}
+
+fn index(duplicate_sets: &[DuplicateSet]) -> BTreeMap<(&str, u64), &DuplicateSet> {
+    duplicate_sets
+        .iter()
+        .map(|set| ((set.normalized_filename.as_str(), set.size), set))
+        .collect()
+}
+
+fn print_set(set: &DuplicateSet) {
+    println!("  {} ({} bytes, {} copies)", set.normalized_filename, set.size, set.duplicates.len() + 1);
+    for path in members(set) {
+        println!("    {}", path.display());
+    }
+}
+
+pub fn run(args: &[String]) -> Result<(), String> {
+    let [old_path, new_path] = args else {
+        return Err("usage: hydra compare OLD_REPORT NEW_REPORT".to_string());
+    };
+
+    let old = plan::read(Path::new(old_path))?;
+    let new = plan::read(Path::new(new_path))?;
+    let old_sets = index(&old.duplicate_sets);
+    let new_sets = index(&new.duplicate_sets);
+
+    let mut added = Vec::new();
+    let mut grown = Vec::new();
+    for (key, set) in &new_sets {
+        match old_sets.get(key) {
+            None => added.push(*set),
+            Some(previous) if set.duplicates.len() > previous.duplicates.len() => grown.push((*previous, *set)),
+            Some(_) => {}
+        }
+    }
+    let resolved: Vec<&DuplicateSet> = old_sets
+        .iter()
+        .filter(|(key, _)| !new_sets.contains_key(*key))
+        .map(|(_, set)| *set)
+        .collect();
+
+    println!(
+        "Comparing {} ({} set(s)) with {} ({} set(s))",
+        old_path,
+        old.duplicate_sets.len(),
+        new_path,
+        new.duplicate_sets.len()
+    );
+
+    if !added.is_empty() {
+        println!("\n--- New ({}) ---", added.len());
+        for set in &added {
+            print_set(set);
+        }
+    }
+
+    if !grown.is_empty() {
+        println!("\n--- Grown ({}) ---", grown.len());
+        for (previous, set) in &grown {
+            let known: HashSet<&PathBuf> = members(previous).into_iter().collect();
+            println!(
+                "  {} ({} bytes, {} -> {} copies)",
+                set.normalized_filename,
+                set.size,
+                previous.duplicates.len() + 1,
+                set.duplicates.len() + 1
+            );
+            for path in members(set) {
+                let marker = if known.contains(path) { " " } else { "+" };
+                println!("  {} {}", marker, path.display());
+            }
+        }
+    }
+
+    if !resolved.is_empty() {
+        println!("\n--- Resolved ({}) ---", resolved.len());
+        for set in &resolved {
+            println!("  {} ({} bytes)", set.normalized_filename, set.size);
+        }
+    }
+
+    let wasted = |sets: &[&DuplicateSet]| -> u64 {
+        sets.iter().map(|set| set.size * set.duplicates.len() as u64).sum()
+    };
+    let grown_bytes: u64 = grown
+        .iter()
+        .map(|(previous, set)| set.size * (set.duplicates.len() - previous.duplicates.len()) as u64)
+        .sum();
+
+    println!("\n================================");
+    println!(
+        "Summary: {} new, {} grown, {} resolved, {} unchanged",
+        added.len(),
+        grown.len(),
+        resolved.len(),
+        new_sets.len() - added.len() - grown.len()
+    );
+    println!("New duplicate data: {} bytes", wasted(&added) + grown_bytes);
+    println!("Resolved duplicate data: {} bytes", wasted(&resolved));
+    Ok(())
+}
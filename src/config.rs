@@ -0,0 +1,363 @@
+// a small subset of TOML: [section] / [section."quoted key"] headers and
+// `key = value` pairs where value is a string, integer, boolean or an array
+// of those. enough for hydra's config file without pulling in a parser
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Bool(bool),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_integer(&self) -> Option<i64> {
+        match self {
+            Value::Integer(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_string_array(&self) -> Option<Vec<String>> {
+        match self {
+            Value::Array(values) => values.iter().map(|v| v.as_str().map(str::to_string)).collect(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Section {
+    // header split into its dotted parts, e.g. ["normalize", "*.log"]
+    pub path: Vec<String>,
+    pub entries: Vec<(String, Value)>,
+}
+
+impl Section {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.entries.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Config {
+    pub sections: Vec<Section>,
+}
+
+impl Config {
+    // sections whose header starts with `name`, in file order
+    pub fn sections_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Section> {
+        self.sections
+            .iter()
+            .filter(move |section| section.path.first().is_some_and(|first| first == name))
+    }
+}
+
+pub fn default_path() -> Option<PathBuf> {
+    if cfg!(windows) {
+        return env::var_os("APPDATA").map(|dir| PathBuf::from(dir).join("hydra").join("config.toml"));
+    }
+
+    env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|dir| dir.join("hydra").join("config.toml"))
+}
+
+// where hydra keeps what it remembers between runs (the whitelist, the log of
+// trashed files)
+pub fn state_dir() -> Option<PathBuf> {
+    if cfg!(windows) {
+        return env::var_os("LOCALAPPDATA").map(|dir| PathBuf::from(dir).join("hydra"));
+    }
+
+    env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("state")))
+        .map(|dir| dir.join("hydra"))
+}
+
+// a TOML basic string
+pub fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// the name inside a [header] line, e.g. `keeper` for "[keeper]  # learned"
+fn header_name(line: &str) -> Option<&str> {
+    let inner = line.trim().strip_prefix('[')?;
+    Some(inner.split(']').next()?.trim())
+}
+
+// how many more arrays a value line opens than it closes, outside strings
+fn open_arrays(line: &str) -> i32 {
+    let mut depth = 0;
+    let mut quote = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some('"'), '\\') => {
+                chars.next();
+            }
+            (Some(open), c) if c == open => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '#') => break,
+            (None, '[') => depth += 1,
+            (None, ']') => depth -= 1,
+            _ => {}
+        }
+    }
+    depth
+}
+
+// `existing` with its [`name`] section replaced by `section`
+fn replace_section(existing: &str, name: &str, section: &str) -> String {
+    let mut kept = Vec::new();
+    let mut in_section = false;
+    for line in existing.lines() {
+        if let Some(header) = header_name(line) {
+            in_section = header == name;
+        }
+        if !in_section {
+            kept.push(line);
+        }
+    }
+
+    let mut text = kept.join("\n").trim_end().to_string();
+    if !text.is_empty() {
+        text.push_str("\n\n");
+    }
+    text.push_str(section);
+    text
+}
+
+// `existing` with `key` in its [`name`] section set by `assignment`, a whole
+// `key = value` line; the section's other keys and comments stay as they are
+fn replace_key(existing: &str, name: &str, key: &str, assignment: &str) -> String {
+    let mut lines: Vec<&str> = Vec::new();
+    let mut in_section = false;
+    let mut found_section = false;
+    let mut replaced = false;
+    let mut continued = 0;
+    for line in existing.lines() {
+        // the rest of a multi-line array being replaced
+        if continued > 0 {
+            continued += open_arrays(line);
+            continue;
+        }
+        if let Some(header) = header_name(line) {
+            // a section without the key gets it after its last line
+            if in_section && !replaced {
+                let blank = lines.iter().rev().take_while(|line| line.trim().is_empty()).count();
+                lines.insert(lines.len() - blank, assignment);
+                replaced = true;
+            }
+            in_section = header == name;
+            found_section |= in_section;
+            lines.push(line);
+            continue;
+        }
+        let is_key = line.trim_start().strip_prefix(key).is_some_and(|rest| rest.trim_start().starts_with('='));
+        if in_section && is_key {
+            if !replaced {
+                lines.push(assignment);
+                replaced = true;
+            }
+            let value = line.split_once('=').map_or("", |(_, value)| value);
+            continued = open_arrays(value).max(0);
+            continue;
+        }
+        lines.push(line);
+    }
+    if in_section && !replaced {
+        lines.push(assignment);
+    }
+
+    let mut text = lines.join("\n").trim_end().to_string();
+    if !found_section {
+        if !text.is_empty() {
+            text.push_str("\n\n");
+        }
+        text.push_str(&format!("[{}]\n{}", name, assignment));
+    }
+    text.push('\n');
+    text
+}
+
+// write `text` to the config at `path`, if hydra can read it back
+fn save(path: &Path, text: &str) -> Result<(), String> {
+    // refuse to save anything hydra couldn't read back
+    parse(text).map_err(|e| format!("the new config would not parse: {}", e))?;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("could not create '{}': {}", parent.display(), e))?;
+    }
+    fs::write(path, text).map_err(|e| format!("could not write '{}': {}", path.display(), e))
+}
+
+fn read_existing(path: &Path) -> Result<String, String> {
+    match fs::read_to_string(path) {
+        Ok(text) => Ok(text),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(String::new()),
+        Err(e) => Err(format!("could not read '{}': {}", path.display(), e)),
+    }
+}
+
+// write `section` to the config at `path` in place of its [`name`] section,
+// keeping every other section
+pub fn save_section(path: &Path, name: &str, section: &str) -> Result<(), String> {
+    let existing = read_existing(path)?;
+    save(path, &replace_section(&existing, name, section))
+}
+
+// set one key of the [`name`] section in the config at `path` to the
+// `key = value` line `assignment`, keeping everything else
+pub fn save_key(path: &Path, name: &str, key: &str, assignment: &str) -> Result<(), String> {
+    let existing = read_existing(path)?;
+    save(path, &replace_key(&existing, name, key, assignment))
+}
+
+// split a header like normalize."*.log" on dots outside quotes
+fn parse_header(header: &str) -> Result<Vec<String>, String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+
+    for c in header.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            '.' if !quoted => parts.push(std::mem::take(&mut current).trim().to_string()),
+            c => current.push(c),
+        }
+    }
+    if quoted {
+        return Err(format!("unterminated quote in [{}]", header));
+    }
+    parts.push(current.trim().to_string());
+
+    Ok(parts)
+}
+
+fn parse_string(input: &str) -> Result<(String, &str), String> {
+    let mut value = String::new();
+    let mut chars = input[1..].char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((value, &input[i + 2..])),
+            '\\' => match chars.next() {
+                Some((_, 'n')) => value.push('\n'),
+                Some((_, 't')) => value.push('\t'),
+                Some((_, c)) => value.push(c),
+                None => break,
+            },
+            c => value.push(c),
+        }
+    }
+
+    Err("unterminated string".to_string())
+}
+
+// parse one value from the start of `input`, returning it and the rest
+fn parse_value(input: &str) -> Result<(Value, &str), String> {
+    let input = input.trim_start();
+
+    if input.starts_with('"') {
+        let (value, rest) = parse_string(input)?;
+        return Ok((Value::String(value), rest));
+    }
+
+    // single-quoted literal strings have no escapes
+    if let Some(literal) = input.strip_prefix('\'') {
+        let end = literal.find('\'').ok_or("unterminated string")?;
+        return Ok((Value::String(literal[..end].to_string()), &literal[end + 1..]));
+    }
+
+    if let Some(mut rest) = input.strip_prefix('[') {
+        let mut values = Vec::new();
+        loop {
+            rest = rest.trim_start();
+            if let Some(after) = rest.strip_prefix(']') {
+                return Ok((Value::Array(values), after));
+            }
+            let (value, after) = parse_value(rest)?;
+            values.push(value);
+            rest = after.trim_start();
+            rest = rest.strip_prefix(',').unwrap_or(rest);
+        }
+    }
+
+    let end = input.find([',', ']', '#']).unwrap_or(input.len());
+    let token = input[..end].trim();
+    let value = match token {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        _ => Value::Integer(
+            token
+                .replace('_', "")
+                .parse()
+                .map_err(|_| format!("unsupported value '{}'", token))?,
+        ),
+    };
+
+    Ok((value, &input[end..]))
+}
+
+pub fn parse(text: &str) -> Result<Config, String> {
+    let mut config = Config::default();
+    let mut current = Section::default();
+
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        let error = |e: String| format!("line {}: {}", number + 1, e);
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix('[') {
+            let header = header.split('#').next().unwrap_or("").trim_end();
+            let header = header.strip_suffix(']').ok_or_else(|| error("missing ']'".to_string()))?;
+            config.sections.push(std::mem::take(&mut current));
+            current.path = parse_header(header).map_err(error)?;
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| error(format!("expected key = value, found '{}'", line)))?;
+        let key = key.trim().trim_matches('"').to_string();
+        let (value, rest) = parse_value(value).map_err(error)?;
+        let rest = rest.trim();
+        if !rest.is_empty() && !rest.starts_with('#') {
+            return Err(error(format!("unexpected '{}' after value", rest)));
+        }
+        current.entries.push((key, value));
+    }
+    config.sections.push(current);
+
+    Ok(config)
+}
+
+pub fn load(path: &Path) -> Result<Config, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("could not read '{}': {}", path.display(), e))?;
+    parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
@@ -0,0 +1,352 @@
+// `hydra consolidate --store DIR`: turn an ad-hoc tree into a deduplicated
+// layout like a backup tool's. one copy of each duplicated content moves into a
+// content-addressed store (DIR/ab/abcdef...-SIZE) and every occurrence becomes
+// a hard link to it, so each content takes up space once
+use crate::actions::{verify_keeper, verify_unchanged};
+use crate::scheduler::{self, IoProfile};
+use crate::{FileInfo, format_bytes, restat, scan, terminal, verify};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+struct Content {
+    object: PathBuf,
+    hash: u64,
+    size: u64,
+    // `object` as it was when planned, if it exists from an earlier run
+    stored: Option<FileInfo>,
+    // the files as they were when hashed
+    paths: Vec<FileInfo>,
+}
+
+impl Content {
+    // the files to turn into links; a new content's first file becomes the object
+    fn to_link(&self) -> &[FileInfo] {
+        if self.stored.is_some() { &self.paths } else { &self.paths[1..] }
+    }
+}
+
+// a walked file as it is now, to be re-checked before it is replaced
+pub fn file_info(path: PathBuf, metadata: &fs::Metadata) -> Option<FileInfo> {
+    Some(FileInfo {
+        path,
+        size: metadata.len(),
+        created: metadata.created().ok(),
+        modified: metadata.modified().ok()?,
+    })
+}
+
+fn object_path(store: &Path, hash: u64, size: u64) -> PathBuf {
+    let name = format!("{:016x}-{}", hash, size);
+    store.join(&name[..2]).join(name)
+}
+
+// (device, inode): paths sharing one are already hard links of each other
+#[cfg(unix)]
+pub fn file_id(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+pub fn file_id(_metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+fn plan(tree: &Path, store: &Path, all: bool) -> Option<Vec<Content>> {
+    let existing = store.ancestors().find(|ancestor| ancestor.exists()).unwrap_or(store);
+    let store_device = scheduler::device_id(existing);
+    let mut by_size: HashMap<u64, Vec<FileInfo>> = HashMap::new();
+    let mut seen_ids = HashSet::new();
+    let mut other_device = 0;
+
+    let readable = scan::walk_files(tree, true, |path, metadata| {
+        if path.starts_with(store) || metadata.len() == 0 {
+            return;
+        }
+        if let Some(id) = file_id(&metadata) {
+            if id.0 != store_device {
+                other_device += 1;
+                return;
+            }
+            // a second name for a file already seen adds nothing
+            if !seen_ids.insert(id) {
+                return;
+            }
+        }
+        match file_info(path, &metadata) {
+            Some(file_info) => by_size.entry(metadata.len()).or_default().push(file_info),
+            None => eprintln!("Warning: Could not get the modified time of a file; skipping it"),
+        }
+    });
+    if !readable {
+        return None;
+    }
+    if other_device > 0 {
+        eprintln!("Warning: Skipping {} file(s) on a different filesystem than the store", other_device);
+    }
+
+    let stored = stored_sizes(store);
+    let mut candidates: HashMap<PathBuf, FileInfo> = by_size
+        .into_iter()
+        .filter(|(size, paths)| all || paths.len() > 1 || stored.contains(size))
+        .flat_map(|(_, paths)| paths)
+        .map(|file_info| (file_info.path.clone(), file_info))
+        .collect();
+    let hashes = scheduler::hash_files(candidates.keys().cloned().collect(), IoProfile::Auto);
+
+    let mut by_content: HashMap<(u64, u64), Vec<FileInfo>> = HashMap::new();
+    for (path, hash) in hashes {
+        let Some(file_info) = candidates.remove(&path) else {
+            continue;
+        };
+        match hash {
+            Ok(hash) => by_content.entry((hash, file_info.size)).or_default().push(file_info),
+            Err(e) => eprintln!("Warning: Could not hash '{}': {}", path.display(), e),
+        }
+    }
+
+    let mut contents: Vec<Content> = by_content
+        .into_iter()
+        .map(|((hash, size), mut paths)| {
+            let object = object_path(store, hash, size);
+            let object_metadata = fs::metadata(&object).ok().filter(|metadata| metadata.is_file());
+            let stored = object_metadata.as_ref().and_then(|metadata| file_info(object.clone(), metadata));
+            // names already linked to the object by an earlier run are done
+            if let Some(object_id) = object_metadata.as_ref().and_then(file_id) {
+                paths.retain(|f| fs::metadata(&f.path).ok().as_ref().and_then(file_id) != Some(object_id));
+            }
+            paths.sort_by(|a, b| a.path.cmp(&b.path));
+            Content {
+                object,
+                hash,
+                size,
+                stored,
+                paths,
+            }
+        })
+        .filter(|content| {
+            !content.paths.is_empty() && (all || content.stored.is_some() || content.paths.len() > 1)
+        })
+        .collect();
+    contents.sort_by(|a, b| a.paths[0].path.cmp(&b.paths[0].path));
+
+    Some(contents)
+}
+
+// sizes of the objects already in the store, read from their names, so a file
+// without a duplicate in the tree is only hashed if it could match one
+fn stored_sizes(store: &Path) -> HashSet<u64> {
+    let prefixes = fs::read_dir(store).into_iter().flatten().flatten();
+    prefixes
+        .flat_map(|prefix| fs::read_dir(prefix.path()).into_iter().flatten().flatten())
+        .filter_map(|object| object.file_name().to_string_lossy().rsplit_once('-')?.1.parse().ok())
+        .collect()
+}
+
+// replace `path` with a hard link to `object` without a moment where `path`
+// is missing: link under a temporary name, then rename over the original
+pub fn link_in_place(object: &Path, path: &Path) -> io::Result<()> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temporary = path.with_file_name(format!(".{}.hydra-link", name));
+
+    fs::hard_link(object, &temporary)?;
+    fs::rename(&temporary, path).inspect_err(|_| {
+        let _ = fs::remove_file(&temporary);
+    })
+}
+
+// the first file of a new content moves into the store and is linked back,
+// unless it changed since it was hashed; returns the object as stored
+fn store_object(content: &Content) -> Result<FileInfo, String> {
+    let mut original = content.paths[0].clone();
+    let changed = restat(&mut original).map_err(|e| format!("no longer readable: {}", e))?;
+    if changed && (original.size != content.size || verify_keeper(&original, Some(content.hash)).is_err()) {
+        return Err("modified since scan".to_string());
+    }
+    if let Some(parent) = content.object.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+
+    fs::rename(&original.path, &content.object).map_err(|e| e.to_string())?;
+    fs::hard_link(&content.object, &original.path)
+        .inspect_err(|_| {
+            let _ = fs::rename(&content.object, &original.path);
+        })
+        .map_err(|e| e.to_string())?;
+    Ok(FileInfo {
+        path: content.object.clone(),
+        ..original
+    })
+}
+
+// every file is checked against the object right before it is replaced, as a
+// copy is before it is removed: one changed since it was hashed must still
+// hash like the object, and the object must still be whole
+fn consolidate(content: &Content) -> (usize, usize, usize) {
+    let (mut linked, mut skipped, mut errors) = (0, 0, 0);
+
+    let stored = match &content.stored {
+        Some(stored) => Ok(stored.clone()),
+        None => store_object(content),
+    };
+    let mut object = match stored {
+        Ok(object) => object,
+        Err(e) => {
+            eprintln!("Error storing '{}': {}", content.paths[0].path.display(), e);
+            return (0, 0, 1);
+        }
+    };
+
+    for file_info in content.to_link() {
+        let mut file_info = file_info.clone();
+        // a re-hash, made when either changed, must still give the object's name
+        let checked = verify_unchanged(&mut file_info, &mut object).and_then(|object_hash| match object_hash {
+            Some(hash) if hash != content.hash => Err("the stored object's contents changed".to_string()),
+            _ => verify_keeper(&object, None).map_err(|reason| format!("the stored object {}", reason)),
+        });
+        if let Err(reason) = checked {
+            eprintln!("Skipped '{}': {}", file_info.path.display(), reason);
+            skipped += 1;
+            continue;
+        }
+        match link_in_place(&content.object, &file_info.path) {
+            Ok(()) => linked += 1,
+            Err(e) => {
+                eprintln!("Error linking '{}': {}", file_info.path.display(), e);
+                errors += 1;
+            }
+        }
+    }
+
+    (linked, skipped, errors)
+}
+
+fn print_plan(contents: &[Content], dry_run: bool) {
+    let (store_label, link_label) = if dry_run { ("Would store", "Would link") } else { ("Store", "Link") };
+
+    for content in contents {
+        println!("\n{} ({} bytes)", content.object.display(), content.size);
+        if content.stored.is_none() {
+            println!("{}: {}", store_label, content.paths[0].path.display());
+        }
+        for file_info in content.to_link() {
+            println!("{}: {}", link_label, file_info.path.display());
+        }
+    }
+}
+
+// the store's absolute path; it needn't exist yet (a dry run won't create it)
+fn absolute_store(store: &Path) -> io::Result<PathBuf> {
+    if store.exists() {
+        return fs::canonicalize(store);
+    }
+    let parent = match store.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => fs::canonicalize(parent)?,
+        _ => std::env::current_dir()?,
+    };
+    Ok(parent.join(store.file_name().unwrap_or_default()))
+}
+
+// `hydra consolidate [--dry-run] [--yes] [--all] [--verify-keepers] --store DIR [TREE]`
+pub fn run(args: &[String]) -> Result<(), String> {
+    let usage = "usage: hydra consolidate [--dry-run] [--yes] [--all] [--verify-keepers] --store DIR [TREE]";
+    let (mut dry_run, mut yes, mut all, mut verify_keepers) = (false, false, false, false);
+    let mut store = None;
+    let mut tree = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dry-run" => dry_run = true,
+            "-y" | "--yes" => yes = true,
+            "--all" => all = true,
+            "--verify-keepers" => verify_keepers = true,
+            "--store" => store = Some(args.next().ok_or(usage)?),
+            _ if tree.is_none() && !arg.starts_with('-') => tree = Some(arg),
+            _ => return Err(usage.to_string()),
+        }
+    }
+    let store = store.ok_or(usage)?;
+
+    let tree = match tree {
+        Some(tree) => fs::canonicalize(tree).map_err(|e| format!("could not read '{}': {}", tree, e))?,
+        None => std::env::current_dir().map_err(|e| e.to_string())?,
+    };
+    let store = absolute_store(Path::new(store)).map_err(|e| format!("could not read '{}': {}", store, e))?;
+    if tree.starts_with(&store) {
+        return Err("the tree must not be inside the store".to_string());
+    }
+
+    if !dry_run && !yes && !terminal::can_prompt() {
+        eprintln!("Note: stdin is not a terminal, so this run only reports. Pass --yes to consolidate without asking.");
+        dry_run = true;
+    }
+
+    println!("Consolidating {} into {} ...", tree.display(), store.display());
+
+    let contents = plan(&tree, &store, all).ok_or(format!("could not read '{}'", tree.display()))?;
+    if contents.is_empty() {
+        println!("\nNothing to consolidate.");
+        return Ok(());
+    }
+
+    print_plan(&contents, dry_run);
+
+    let links: usize = contents.iter().map(|content| content.to_link().len()).sum();
+    let reclaimed: u64 = contents.iter().map(|content| content.size * content.to_link().len() as u64).sum();
+    let new = contents.iter().filter(|content| content.stored.is_none()).count();
+
+    println!("\n================================");
+    println!("Contents: {} ({} new in the store)", contents.len(), new);
+    println!("Files to replace with links: {} ({})", links, format_bytes(reclaimed));
+    println!("Linked files share one inode: editing any of them in place changes them all.");
+
+    if dry_run {
+        println!("\n[DRY RUN MODE] Nothing was moved or linked.");
+        return Ok(());
+    }
+
+    if !yes {
+        print!("\nProceed with consolidation? (y/N): ");
+        io::stdout().flush().unwrap();
+
+        let mut input = String::new();
+        io::stdin().read_line(&mut input).unwrap();
+        let input = input.trim().to_lowercase();
+        if input != "y" && input != "yes" {
+            println!("Consolidation cancelled.");
+            return Ok(());
+        }
+    }
+
+    let (mut linked, mut skipped, mut errors) = (0, 0, 0);
+    for content in &contents {
+        let (content_linked, content_skipped, content_errors) = consolidate(content);
+        linked += content_linked;
+        skipped += content_skipped;
+        errors += content_errors;
+    }
+
+    println!("\nConsolidation complete!");
+    println!("Files replaced with links: {}", linked);
+    if skipped > 0 {
+        println!("Files skipped (modified since scan): {}", skipped);
+    }
+    if errors > 0 {
+        println!("Errors encountered: {}", errors);
+    }
+
+    // the objects are the keepers here, and their names record their hashes
+    if verify_keepers {
+        let objects = contents
+            .iter()
+            .filter(|content| content.object.exists())
+            .map(|content| (content.object.clone(), content.hash))
+            .collect();
+        verify::verify_keepers(&objects, IoProfile::Auto);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,278 @@
+// `hydra shell-integration`: run hydra from the file manager. on windows, a
+// "Find duplicates here" entry in the Explorer right-click menu of folders and
+// of a folder's background opens a console in that folder running
+// `hydra --interactive` on it, so the user reviews every set before anything
+// is removed. the entries live under the user's own HKCU\Software\Classes and
+// are written with reg.exe, like the scheduled task `hydra init` adds with
+// schtasks. on macOS, a Finder Quick Action in ~/Library/Services moves the
+// copies in the selected folders to the Trash and posts a notification; with
+// no terminal to review in, the trash is what keeps it safe
+use crate::config;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+const KEYS: &[&str] = &[
+    r"HKCU\Software\Classes\Directory\shell\hydra",
+    r"HKCU\Software\Classes\Directory\Background\shell\hydra",
+];
+const LABEL: &str = "Find duplicates here";
+const QUICK_ACTION: &str = "Move duplicates to Trash with hydra.workflow";
+
+fn reg(args: &[&str]) -> Result<(), String> {
+    let status = Command::new("reg")
+        .args(args)
+        .stdout(Stdio::null())
+        .status()
+        .map_err(|e| format!("could not run reg.exe: {}", e))?;
+    if !status.success() {
+        return Err(format!("reg {} exited with {}", args[0], status));
+    }
+    Ok(())
+}
+
+fn install_windows() -> Result<(), String> {
+    let exe = env::current_exe().map_err(|e| format!("could not find the hydra executable: {}", e))?;
+    let exe = exe.display().to_string();
+    // --root . scans the folder clicked on rather than any [defaults] folders
+    let command = format!("cmd.exe /k cd /d \"%V\" && \"{}\" --root . --interactive", exe);
+    for key in KEYS {
+        reg(&["add", key, "/ve", "/d", LABEL, "/f"])?;
+        reg(&["add", key, "/v", "Icon", "/d", &exe, "/f"])?;
+        reg(&["add", &format!(r"{}\command", key), "/ve", "/d", &command, "/f"])?;
+    }
+    println!("Added \"{}\" to the right-click menu of folders.", LABEL);
+    println!("It runs {}; install it again if hydra moves.", exe);
+    Ok(())
+}
+
+fn uninstall_windows() -> Result<(), String> {
+    for key in KEYS {
+        // a key that isn't there has nothing to remove
+        let exists = Command::new("reg")
+            .args(["query", key])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success());
+        if exists {
+            reg(&["delete", key, "/f"])?;
+        }
+    }
+    println!("Removed \"{}\" from the right-click menu.", LABEL);
+    Ok(())
+}
+
+// quoted for a POSIX shell
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+fn xml_escape(value: &str) -> String {
+    value.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+fn quick_action_dir() -> Result<PathBuf, String> {
+    let home = env::var_os("HOME").ok_or("HOME is not set")?;
+    Ok(PathBuf::from(home).join("Library").join("Services").join(QUICK_ACTION))
+}
+
+// the Info.plist that offers the workflow for folders selected in Finder
+fn info_plist() -> String {
+    r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>NSServices</key>
+	<array>
+		<dict>
+			<key>NSMenuItem</key>
+			<dict>
+				<key>default</key>
+				<string>Move duplicates to Trash with hydra</string>
+			</dict>
+			<key>NSMessage</key>
+			<string>runWorkflowAsService</string>
+			<key>NSRequiredContext</key>
+			<dict>
+				<key>NSApplicationIdentifier</key>
+				<string>com.apple.finder</string>
+			</dict>
+			<key>NSSendFileTypes</key>
+			<array>
+				<string>public.folder</string>
+			</array>
+		</dict>
+	</array>
+</dict>
+</plist>
+"#
+    .to_string()
+}
+
+// a workflow with a single Run Shell Script action, given the selected
+// folders as arguments
+fn document_wflow(script: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>AMApplicationBuild</key>
+	<string>523</string>
+	<key>AMApplicationVersion</key>
+	<string>2.10</string>
+	<key>AMDocumentVersion</key>
+	<string>2</string>
+	<key>actions</key>
+	<array>
+		<dict>
+			<key>action</key>
+			<dict>
+				<key>AMAccepts</key>
+				<dict>
+					<key>Container</key>
+					<string>List</string>
+					<key>Optional</key>
+					<true/>
+					<key>Types</key>
+					<array>
+						<string>com.apple.cocoa.string</string>
+					</array>
+				</dict>
+				<key>AMActionVersion</key>
+				<string>2.0.3</string>
+				<key>AMApplication</key>
+				<array>
+					<string>Automator</string>
+				</array>
+				<key>AMProvides</key>
+				<dict>
+					<key>Container</key>
+					<string>List</string>
+					<key>Types</key>
+					<array>
+						<string>com.apple.cocoa.string</string>
+					</array>
+				</dict>
+				<key>ActionBundlePath</key>
+				<string>/System/Library/Automator/Run Shell Script.action</string>
+				<key>ActionName</key>
+				<string>Run Shell Script</string>
+				<key>ActionParameters</key>
+				<dict>
+					<key>COMMAND_STRING</key>
+					<string>{}</string>
+					<key>CheckedForUserDefaultShell</key>
+					<true/>
+					<key>inputMethod</key>
+					<integer>1</integer>
+					<key>shell</key>
+					<string>/bin/sh</string>
+					<key>source</key>
+					<string></string>
+				</dict>
+				<key>BundleIdentifier</key>
+				<string>com.apple.RunShellScript</string>
+				<key>CFBundleVersion</key>
+				<string>2.0.3</string>
+				<key>CanShowSelectedItemsWhenRun</key>
+				<false/>
+				<key>CanShowWhenRun</key>
+				<true/>
+				<key>Category</key>
+				<array>
+					<string>AMCategoryUtilities</string>
+				</array>
+				<key>Class Name</key>
+				<string>RunShellScriptAction</string>
+				<key>InputUUID</key>
+				<string>5A0E3C2B-6F5B-4C51-9B1E-2D9E1A7C3F01</string>
+				<key>OutputUUID</key>
+				<string>5A0E3C2B-6F5B-4C51-9B1E-2D9E1A7C3F02</string>
+				<key>UUID</key>
+				<string>5A0E3C2B-6F5B-4C51-9B1E-2D9E1A7C3F03</string>
+				<key>isViewVisible</key>
+				<integer>1</integer>
+			</dict>
+		</dict>
+	</array>
+	<key>connectors</key>
+	<dict/>
+	<key>workflowMetaData</key>
+	<dict>
+		<key>serviceInputTypeIdentifier</key>
+		<string>com.apple.Automator.fileSystemObject.folder</string>
+		<key>serviceOutputTypeIdentifier</key>
+		<string>com.apple.Automator.nothing</string>
+		<key>serviceProcessesInput</key>
+		<integer>0</integer>
+		<key>workflowTypeIdentifier</key>
+		<string>com.apple.Automator.servicesMenu</string>
+	</dict>
+</dict>
+</plist>
+"#,
+        xml_escape(script)
+    )
+}
+
+fn install_macos() -> Result<(), String> {
+    let exe = env::current_exe().map_err(|e| format!("could not find the hydra executable: {}", e))?;
+    let log = config::state_dir()
+        .ok_or("could not find where hydra keeps its state")?
+        .join("quick-action.log");
+    if let Some(dir) = log.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("could not create '{}': {}", dir.display(), e))?;
+    }
+    // --root . scans the folder selected rather than any [defaults] folders
+    let script = format!(
+        "for folder in \"$@\"; do\n\
+         \tcd \"$folder\" && {} --root . --action trash --yes >> {} 2>&1\n\
+         done\n\
+         osascript -e 'display notification \"Done; copies found were moved to the Trash\" with title \"hydra\"'\n",
+        shell_quote(&exe.to_string_lossy()),
+        shell_quote(&log.to_string_lossy())
+    );
+
+    let dir = quick_action_dir()?.join("Contents");
+    fs::create_dir_all(&dir).map_err(|e| format!("could not create '{}': {}", dir.display(), e))?;
+    for (name, text) in [("Info.plist", info_plist()), ("document.wflow", document_wflow(&script))] {
+        let path = dir.join(name);
+        fs::write(&path, text).map_err(|e| format!("could not write '{}': {}", path.display(), e))?;
+    }
+    // have Finder pick up the new service without logging out
+    let _ = Command::new("/System/Library/CoreServices/pbs").arg("-flush").status();
+
+    println!("Added \"Move duplicates to Trash with hydra\" to Finder's Quick Actions for folders.");
+    println!("It runs {} and logs to {}; install it again if hydra moves.", exe.display(), log.display());
+    Ok(())
+}
+
+fn uninstall_macos() -> Result<(), String> {
+    let dir = quick_action_dir()?;
+    if dir.exists() {
+        fs::remove_dir_all(&dir).map_err(|e| format!("could not remove '{}': {}", dir.display(), e))?;
+        let _ = Command::new("/System/Library/CoreServices/pbs").arg("-flush").status();
+    }
+    println!("Removed hydra's Quick Action from Finder.");
+    Ok(())
+}
+
+// `hydra shell-integration install|uninstall`
+pub fn run(args: &[String]) -> Result<(), String> {
+    let usage = "usage: hydra shell-integration install|uninstall";
+    let install = match args {
+        [command] if command == "install" => true,
+        [command] if command == "uninstall" => false,
+        _ => return Err(usage.to_string()),
+    };
+    match (cfg!(windows), cfg!(target_os = "macos"), install) {
+        (true, _, true) => install_windows(),
+        (true, _, false) => uninstall_windows(),
+        (_, true, true) => install_macos(),
+        (_, true, false) => uninstall_macos(),
+        _ => Err("shell integration is available on Windows (Explorer) and macOS (Finder)".to_string()),
+    }
+}
@@ -0,0 +1,170 @@
+// --dir-cache: on archive trees that rarely change, most of a rescan goes on
+// listing directories and reading the metadata of files that are just as they
+// were, which on cold network storage is slow. a directory's modification
+// time changes whenever an entry is added to, removed from or renamed in it,
+// so the listing of each directory is kept with that time, and a directory
+// whose time hasn't moved is taken from the cache without being read: one
+// stat instead of one per file. a file rewritten in place leaves its
+// directory's time alone, so its cached size and time can be out of date;
+// every set is re-checked against the files themselves before anything is
+// acted on, as for files that change during a scan
+use crate::config;
+use crate::scan::Stat;
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// a directory changed within this long of being listed may change again
+// within the same tick of a coarse filesystem clock, so it isn't cached
+const SETTLE: Duration = Duration::from_secs(2);
+
+// an entry of a cached listing; `stat` is None for a subdirectory
+#[derive(Debug, Clone)]
+pub struct Listed {
+    pub name: OsString,
+    pub hidden: bool,
+    pub stat: Option<Stat>,
+}
+
+pub struct DirCache {
+    listings: HashMap<PathBuf, (u128, Vec<Listed>)>,
+    changed: bool,
+}
+
+fn state_path() -> Option<PathBuf> {
+    config::state_dir().map(|dir| dir.join("dircache.tsv"))
+}
+
+fn nanos(time: SystemTime) -> Option<u128> {
+    time.duration_since(UNIX_EPOCH).ok().map(|since| since.as_nanos())
+}
+
+fn from_nanos(nanos: u128) -> SystemTime {
+    UNIX_EPOCH + Duration::new((nanos / 1_000_000_000) as u64, (nanos % 1_000_000_000) as u32)
+}
+
+// "F\tname\thidden\tsize\tmodified\tcreated" for a file, "S\tname\thidden" for
+// a subdirectory
+fn parse_entry(line: &str) -> Option<Listed> {
+    let fields: Vec<&str> = line.split('\t').collect();
+    let hidden = fields.get(2)? == &"1";
+    let stat = match fields[0] {
+        "S" => None,
+        "F" => Some(Stat {
+            size: fields.get(3)?.parse().ok()?,
+            modified: from_nanos(fields.get(4)?.parse().ok()?),
+            created: fields.get(5)?.parse().ok().map(from_nanos),
+        }),
+        _ => return None,
+    };
+    Some(Listed {
+        name: OsString::from(fields[1]),
+        hidden,
+        stat,
+    })
+}
+
+impl DirCache {
+    // the listings saved by earlier runs; a damaged cache is started afresh
+    pub fn load() -> DirCache {
+        let mut listings = HashMap::new();
+        let text = state_path().and_then(|path| fs::read_to_string(path).ok()).unwrap_or_default();
+        let mut current: Option<(PathBuf, u128, Vec<Listed>)> = None;
+        for line in text.lines() {
+            if let Some(rest) = line.strip_prefix("D\t") {
+                if let Some((directory, modified, entries)) = current.take() {
+                    listings.insert(directory, (modified, entries));
+                }
+                let Some((directory, modified)) = rest.rsplit_once('\t') else {
+                    return DirCache::empty();
+                };
+                let Ok(modified) = modified.parse() else {
+                    return DirCache::empty();
+                };
+                current = Some((PathBuf::from(directory), modified, Vec::new()));
+                continue;
+            }
+            match (current.as_mut(), parse_entry(line)) {
+                (Some((_, _, entries)), Some(entry)) => entries.push(entry),
+                _ => return DirCache::empty(),
+            }
+        }
+        if let Some((directory, modified, entries)) = current {
+            listings.insert(directory, (modified, entries));
+        }
+        DirCache {
+            listings,
+            changed: false,
+        }
+    }
+
+    fn empty() -> DirCache {
+        DirCache {
+            listings: HashMap::new(),
+            changed: true,
+        }
+    }
+
+    // the cached listing of `directory`, if it was made at this modification time
+    pub fn get(&self, directory: &Path, modified: SystemTime) -> Option<&[Listed]> {
+        let (cached, entries) = self.listings.get(directory)?;
+        (Some(*cached) == nanos(modified)).then_some(entries.as_slice())
+    }
+
+    // remember a complete listing of `directory`, unless it changed too
+    // recently to trust or holds a name the cache can't store
+    pub fn insert(&mut self, directory: &Path, modified: SystemTime, entries: &[Listed]) {
+        let settled = SystemTime::now().duration_since(modified).is_ok_and(|age| age >= SETTLE);
+        let storable = |name: &OsStr| name.to_str().is_some_and(|name| !name.contains(['\t', '\n', '\r']));
+        let Some(modified) = nanos(modified) else {
+            return;
+        };
+        if !settled || !storable(directory.as_os_str()) || entries.iter().any(|entry| !storable(&entry.name)) {
+            self.listings.remove(directory);
+            return;
+        }
+        self.listings.insert(directory.to_path_buf(), (modified, entries.to_vec()));
+        self.changed = true;
+    }
+
+    // write the cache back, through a temporary file so a run killed
+    // partway leaves the old cache whole
+    pub fn save(&self) {
+        let Some(path) = state_path().filter(|_| self.changed) else {
+            return;
+        };
+        let write = || -> io::Result<()> {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let temporary = path.with_extension("tsv.tmp");
+            let mut out = io::BufWriter::new(fs::File::create(&temporary)?);
+            for (directory, (modified, entries)) in &self.listings {
+                writeln!(out, "D\t{}\t{}", directory.display(), modified)?;
+                for entry in entries {
+                    let hidden = if entry.hidden { 1 } else { 0 };
+                    match &entry.stat {
+                        None => writeln!(out, "S\t{}\t{}", entry.name.to_string_lossy(), hidden)?,
+                        Some(stat) => writeln!(
+                            out,
+                            "F\t{}\t{}\t{}\t{}\t{}",
+                            entry.name.to_string_lossy(),
+                            hidden,
+                            stat.size,
+                            nanos(stat.modified).unwrap_or(0),
+                            stat.created.and_then(nanos).map_or("-".to_string(), |created| created.to_string())
+                        )?,
+                    }
+                }
+            }
+            out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+            fs::rename(&temporary, &path)
+        };
+        if let Err(e) = write() {
+            eprintln!("Warning: Could not save the directory cache '{}': {}", path.display(), e);
+        }
+    }
+}
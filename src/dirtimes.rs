@@ -0,0 +1,61 @@
+// --preserve-dir-times: removing a file from a directory changes the
+// directory's modification time, which backup and sync tools take to mean
+// the directory's contents need looking at again. the times of every
+// directory a copy is removed from are read before the action phase and put
+// back once it is over, retries included
+use crate::DuplicateSet;
+use std::collections::BTreeSet;
+use std::fs::{self, File, FileTimes};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+// a directory with its access and modification times
+pub struct Saved(Vec<(PathBuf, SystemTime, SystemTime)>);
+
+// the times of the directories holding the copies of `duplicate_sets`
+pub fn capture(duplicate_sets: &[DuplicateSet]) -> Saved {
+    let directories: BTreeSet<&Path> = duplicate_sets
+        .iter()
+        .flat_map(|set| &set.duplicates)
+        .filter_map(|file_info| file_info.path.parent())
+        .collect();
+
+    let mut saved = Vec::new();
+    for directory in directories {
+        match fs::metadata(directory).and_then(|metadata| Ok((metadata.accessed()?, metadata.modified()?))) {
+            Ok((accessed, modified)) => saved.push((directory.to_path_buf(), accessed, modified)),
+            Err(e) => eprintln!("Warning: Could not read the times of '{}': {}", directory.display(), e),
+        }
+    }
+    Saved(saved)
+}
+
+#[cfg(windows)]
+fn open_directory(path: &Path) -> io::Result<File> {
+    use std::os::windows::fs::OpenOptionsExt;
+    const FILE_WRITE_ATTRIBUTES: u32 = 0x100;
+    const FILE_FLAG_BACKUP_SEMANTICS: u32 = 0x0200_0000;
+
+    File::options()
+        .access_mode(FILE_WRITE_ATTRIBUTES)
+        .custom_flags(FILE_FLAG_BACKUP_SEMANTICS)
+        .open(path)
+}
+
+#[cfg(not(windows))]
+fn open_directory(path: &Path) -> io::Result<File> {
+    File::open(path)
+}
+
+// put the saved times back; a directory that is gone since is passed over
+pub fn restore(saved: Saved) {
+    for (directory, accessed, modified) in saved.0 {
+        let times = FileTimes::new().set_accessed(accessed).set_modified(modified);
+        match open_directory(&directory).and_then(|file| file.set_times(times)) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => eprintln!("Warning: Could not restore the times of '{}': {}", directory.display(), e),
+        }
+    }
+}
@@ -0,0 +1,224 @@
+// --disk-images: the files inside ISO 9660 images (.iso, and .img, .cdr and
+// .toast images that hold an ISO 9660 filesystem) are read in place, without
+// mounting anything, and every scanned file whose contents are also inside an
+// image is listed, so a copy already archived on a disc image is known before
+// the image is burned or the loose file deleted. Joliet names are used when
+// the image has them. nothing inside an image is ever changed, and the
+// listing is for review only
+use crate::scheduler::{self, IoProfile};
+use crate::{FileInfo, hash};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+const EXTENSIONS: [&str; 4] = ["iso", "img", "cdr", "toast"];
+// volume descriptors start at sector 16, whatever the logical block size
+const DESCRIPTORS: u64 = 16 * 2048;
+// directory flags
+const DIRECTORY: u8 = 0x02;
+const MULTI_EXTENT: u8 = 0x80;
+// guards against images whose directories loop, never end or claim to be huge
+const MAX_ENTRIES: usize = 1_000_000;
+const MAX_DIRECTORY: u64 = 64 * 1024 * 1024;
+
+// a file inside an image: its path there, and where its contents lie
+struct Entry {
+    path: String,
+    offset: u64,
+    size: u64,
+}
+
+// a scanned file whose contents are also inside an image
+pub struct ImageCopy {
+    pub path: PathBuf,
+    pub image: PathBuf,
+    pub inner: String,
+}
+
+fn read_at(file: &mut File, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+    let mut buffer = vec![0; len];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut buffer)?;
+    Ok(buffer)
+}
+
+fn u32_le(data: &[u8], offset: usize) -> u64 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap()) as u64
+}
+
+// a record's name, without the ";1" version and, in the primary volume, the
+// "." of names without an extension
+fn record_name(raw: &[u8], joliet: bool) -> String {
+    let name = if joliet {
+        let units: Vec<u16> = raw.chunks_exact(2).map(|unit| u16::from_be_bytes([unit[0], unit[1]])).collect();
+        String::from_utf16_lossy(&units)
+    } else {
+        String::from_utf8_lossy(raw).into_owned()
+    };
+    let name = name.split(';').next().unwrap_or_default();
+    if joliet { name.to_string() } else { name.strip_suffix('.').unwrap_or(name).to_string() }
+}
+
+// the files of an ISO 9660 image, or None if `path` doesn't hold one
+fn entries(path: &Path) -> io::Result<Option<Vec<Entry>>> {
+    let mut file = File::open(path)?;
+    if file.metadata()?.len() < DESCRIPTORS + 2048 {
+        return Ok(None);
+    }
+
+    // the primary volume's root, replaced by a Joliet volume's when there is one
+    let mut root = None;
+    for index in 0..32 {
+        let descriptor = read_at(&mut file, DESCRIPTORS + index * 2048, 2048)?;
+        if &descriptor[1..6] != b"CD001" {
+            return Ok(None);
+        }
+        let block_size = u16::from_le_bytes([descriptor[128], descriptor[129]]) as u64;
+        let record = &descriptor[156..190];
+        let directory = (u32_le(record, 2) * block_size, u32_le(record, 10));
+        match descriptor[0] {
+            1 if root.is_none() => root = Some((directory, block_size, false)),
+            2 if [b"%/@", b"%/C", b"%/E"].iter().any(|escape| &descriptor[88..91] == *escape) => {
+                root = Some((directory, block_size, true))
+            }
+            255 => break,
+            _ => {}
+        }
+    }
+    let Some((root, block_size, joliet)) = root.filter(|(_, block_size, _)| *block_size > 0) else {
+        return Ok(None);
+    };
+
+    let mut files = Vec::new();
+    let mut pending = vec![(String::new(), root)];
+    let mut seen = HashSet::new();
+    while let Some((prefix, (offset, len))) = pending.pop() {
+        if !seen.insert(offset) || files.len() + pending.len() > MAX_ENTRIES || len > MAX_DIRECTORY {
+            continue;
+        }
+        let directory = read_at(&mut file, offset, len as usize)?;
+        let mut position = 0;
+        while position < directory.len() {
+            let record_len = directory[position] as usize;
+            // records never cross a block; the rest of one is padding
+            if record_len == 0 {
+                position = (position as u64 / block_size + 1) as usize * block_size as usize;
+                continue;
+            }
+            let Some(record) = directory.get(position..position + record_len).filter(|record| record.len() >= 34)
+            else {
+                break;
+            };
+            position += record_len;
+
+            let name_len = record[32] as usize;
+            let Some(raw_name) = record.get(33..33 + name_len) else {
+                continue;
+            };
+            // "." and ".." are the single bytes 0 and 1
+            if raw_name == [0] || raw_name == [1] {
+                continue;
+            }
+            let path = format!("{}/{}", prefix, record_name(raw_name, joliet));
+            let (extent, size, flags) = (u32_le(record, 2) * block_size, u32_le(record, 10), record[25]);
+            if flags & DIRECTORY != 0 {
+                pending.push((path, (extent, size)));
+            } else if flags & MULTI_EXTENT == 0 {
+                // files over 4 GiB span several extents and are left out
+                files.push(Entry {
+                    path,
+                    offset: extent,
+                    size,
+                });
+            }
+        }
+    }
+    Ok(Some(files))
+}
+
+fn is_disk_image(path: &Path) -> bool {
+    path.extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .is_some_and(|extension| EXTENSIONS.contains(&extension.as_str()))
+}
+
+// every scanned file that is also inside one of the scanned disk images
+pub fn find_copies<'a>(files: impl Iterator<Item = &'a FileInfo>, io_profile: IoProfile) -> Vec<ImageCopy> {
+    let (images, files): (Vec<&FileInfo>, Vec<&FileInfo>) = files.partition(|f| is_disk_image(&f.path));
+    if images.is_empty() {
+        return Vec::new();
+    }
+    let mut by_size: HashMap<u64, Vec<&Path>> = HashMap::new();
+    for file_info in files.iter().filter(|file_info| file_info.size > 0) {
+        by_size.entry(file_info.size).or_default().push(&file_info.path);
+    }
+
+    // (image, entry) for every entry the size of some scanned file
+    let mut candidates = Vec::new();
+    for image in images {
+        match entries(&image.path) {
+            Ok(Some(entries)) => candidates.extend(
+                entries
+                    .into_iter()
+                    .filter(|entry| by_size.contains_key(&entry.size))
+                    .map(|entry| (&image.path, entry)),
+            ),
+            Ok(None) => {}
+            Err(e) => eprintln!("Warning: Could not read disk image '{}': {}", image.path.display(), e),
+        }
+    }
+    if candidates.is_empty() {
+        return Vec::new();
+    }
+
+    let paths: Vec<PathBuf> = candidates
+        .iter()
+        .flat_map(|(_, entry)| &by_size[&entry.size])
+        .map(|path| path.to_path_buf())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    let hashes = scheduler::hash_files(paths, io_profile);
+
+    let mut copies = Vec::new();
+    for (image, entry) in candidates {
+        let inner = match hash::hash_region(image, entry.offset, entry.size) {
+            Ok(hash) => hash,
+            Err(e) => {
+                eprintln!("Warning: Could not read '{}' in '{}': {}", entry.path, image.display(), e);
+                continue;
+            }
+        };
+        for path in &by_size[&entry.size] {
+            if let Some(Ok(hash)) = hashes.get(*path)
+                && *hash == inner
+            {
+                copies.push(ImageCopy {
+                    path: path.to_path_buf(),
+                    image: image.clone(),
+                    inner: entry.path.clone(),
+                });
+            }
+        }
+    }
+    copies.sort_by(|a, b| a.path.cmp(&b.path).then_with(|| a.image.cmp(&b.image)));
+    copies
+}
+
+pub fn write_copies(out: &mut dyn Write, copies: &[ImageCopy]) -> io::Result<()> {
+    if copies.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(out, "\n--- Also Inside Disk Images (for review; never removed) ---")?;
+    let mut previous: Option<&Path> = None;
+    for copy in copies {
+        if previous != Some(&copy.path) {
+            writeln!(out, "\n{}", copy.path.display())?;
+            previous = Some(&copy.path);
+        }
+        writeln!(out, "  in {}: {}", copy.image.display(), copy.inner)?;
+    }
+    Ok(())
+}
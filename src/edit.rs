@@ -0,0 +1,132 @@
+// --edit: write the plan to a file, one "keep PATH" / "delete PATH" line per
+// file, let the user change it in $EDITOR (like `git rebase -i`) and carry out
+// whatever they saved
+use crate::{DuplicateSet, FileInfo, pager};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+const INSTRUCTIONS: &str = "\
+#
+# Commands:
+# k, keep <path>   = leave this file alone
+# d, delete <path> = remove this file
+#
+# Every set needs at least one kept file; a set where every line says delete
+# is skipped. Removing a line leaves that file alone. If you remove everything,
+# nothing is removed.
+";
+
+fn editor() -> String {
+    env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| if cfg!(windows) { "notepad" } else { "vi" }.to_string())
+}
+
+fn write_plan(duplicate_sets: &[DuplicateSet]) -> String {
+    let mut text = String::new();
+
+    for (index, set) in duplicate_sets.iter().enumerate() {
+        text.push_str(&format!("# set {}: {} ({} bytes)\n", index + 1, set.normalized_filename, set.size));
+        text.push_str(&format!("keep {}\n", set.keeper.path.display()));
+        for file_info in &set.duplicates {
+            text.push_str(&format!("delete {}\n", file_info.path.display()));
+        }
+        text.push('\n');
+    }
+
+    text.push_str(INSTRUCTIONS);
+    text
+}
+
+// parse the edited file into (path -> delete?) decisions
+fn parse_plan(text: &str) -> Result<HashMap<PathBuf, bool>, String> {
+    let mut decisions = HashMap::new();
+
+    for (number, line) in text.lines().enumerate() {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        let (command, path) = trimmed.split_once(' ').unwrap_or((trimmed, ""));
+        let delete = match command {
+            "k" | "keep" => false,
+            "d" | "delete" => true,
+            _ => return Err(format!("line {}: unknown command '{}'", number + 1, command)),
+        };
+        if path.is_empty() {
+            return Err(format!("line {}: missing path", number + 1));
+        }
+        decisions.insert(PathBuf::from(path), delete);
+    }
+
+    Ok(decisions)
+}
+
+// rebuild one set from the decisions; None if nothing in it is to be removed
+fn apply_decisions(set: DuplicateSet, decisions: &HashMap<PathBuf, bool>) -> Option<DuplicateSet> {
+    let mut kept: Vec<FileInfo> = Vec::new();
+    let mut deleted: Vec<FileInfo> = Vec::new();
+
+    for file_info in std::iter::once(set.keeper).chain(set.duplicates) {
+        match decisions.get(&file_info.path) {
+            Some(false) => kept.push(file_info),
+            Some(true) => deleted.push(file_info),
+            None => {}
+        }
+    }
+
+    if deleted.is_empty() {
+        return None;
+    }
+    if kept.is_empty() {
+        eprintln!("Warning: Skipping set '{}': every copy was marked delete", set.normalized_filename);
+        return None;
+    }
+
+    Some(DuplicateSet {
+        normalized_filename: set.normalized_filename,
+        size: set.size,
+        hash: set.hash,
+        keeper: kept.swap_remove(0),
+        duplicates: deleted,
+    })
+}
+
+fn run_editor(path: &Path) -> Result<String, String> {
+    let editor = editor();
+    // the path is quoted for the shell since the editor may carry its own flags
+    let command = format!("{} \"{}\"", editor, path.display());
+    let status = pager::shell(&command)
+        .status()
+        .map_err(|e| format!("could not run editor '{}': {}", editor, e))?;
+
+    if !status.success() {
+        return Err(format!("editor '{}' exited with {}", editor, status));
+    }
+
+    fs::read_to_string(path).map_err(|e| format!("could not read '{}': {}", path.display(), e))
+}
+
+// let the user edit the plan; returns false if they emptied the file
+pub fn edit_sets(duplicate_sets: &mut Vec<DuplicateSet>) -> Result<bool, String> {
+    let path = env::temp_dir().join(format!("hydra-plan-{}.txt", process::id()));
+    fs::write(&path, write_plan(duplicate_sets)).map_err(|e| format!("could not write '{}': {}", path.display(), e))?;
+
+    let edited = run_editor(&path);
+    let _ = fs::remove_file(&path);
+    let decisions = parse_plan(&edited?)?;
+
+    if decisions.is_empty() {
+        return Ok(false);
+    }
+
+    *duplicate_sets = std::mem::take(duplicate_sets)
+        .into_iter()
+        .filter_map(|set| apply_decisions(set, &decisions))
+        .collect();
+    Ok(true)
+}
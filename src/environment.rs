@@ -0,0 +1,57 @@
+// the environment a JSON report was written in: which hydra, run with which
+// arguments and filename normalization, on which host and when. plans and
+// snapshots embed it so a file reviewed weeks later explains itself
+use crate::{Options, format_timestamp, json};
+use std::env;
+use std::sync::OnceLock;
+use std::time::SystemTime;
+
+static STARTED: OnceLock<SystemTime> = OnceLock::new();
+
+// called first thing, so reports can say when the run began
+pub fn mark_started() {
+    STARTED.get_or_init(SystemTime::now);
+}
+
+#[cfg(unix)]
+pub fn host_name() -> String {
+    use std::os::raw::{c_char, c_int};
+
+    unsafe extern "C" {
+        fn gethostname(name: *mut c_char, length: usize) -> c_int;
+    }
+
+    let mut buffer = [0u8; 256];
+    if unsafe { gethostname(buffer.as_mut_ptr() as *mut c_char, buffer.len()) } != 0 {
+        return String::new();
+    }
+    let length = buffer.iter().position(|byte| *byte == 0).unwrap_or(buffer.len());
+    String::from_utf8_lossy(&buffer[..length]).into_owned()
+}
+
+#[cfg(not(unix))]
+pub fn host_name() -> String {
+    std::env::var("COMPUTERNAME").unwrap_or_default()
+}
+
+// the "environment" object, indented to sit at the top level of a report
+pub fn to_json(options: &Options) -> String {
+    let arguments: Vec<String> = env::args().skip(1).map(|arg| json::string(&arg)).collect();
+    let started = *STARTED.get_or_init(SystemTime::now);
+    let fields = [
+        ("tool", json::string("hydra")),
+        ("version", json::string(env!("CARGO_PKG_VERSION"))),
+        ("arguments", format!("[{}]", arguments.join(", "))),
+        ("normalization", options.normalizer.to_json()),
+        ("host", json::string(&host_name())),
+        ("os", json::string(env::consts::OS)),
+        ("working_directory", json::string(&env::current_dir().unwrap_or_default().to_string_lossy())),
+        ("started", json::string(&format_timestamp(started))),
+        ("written", json::string(&format_timestamp(SystemTime::now()))),
+    ];
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|(name, value)| format!("    {}: {}", json::string(name), value))
+        .collect();
+    format!("{{\n{}\n  }}", fields.join(",\n"))
+}
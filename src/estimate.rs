@@ -0,0 +1,143 @@
+// `hydra estimate`: content-defined chunking over a whole tree to estimate how
+// much data is duplicated at the block level, even between files that differ
+use crate::{format_bytes, hash, scan};
+use std::collections::HashSet;
+use std::io::{self, Read};
+use std::path::Path;
+
+const MIN_CHUNK: usize = 2 * 1024;
+const MAX_CHUNK: usize = 64 * 1024;
+// 13 bits gives an average chunk size of roughly 8 KiB
+const BOUNDARY_MASK: u64 = (1 << 13) - 1;
+const BUFFER_SIZE: usize = 1024 * 1024;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+#[derive(Debug, Default)]
+struct Estimate {
+    files: u64,
+    total_bytes: u64,
+    unique_bytes: u64,
+    chunks: u64,
+    unique_chunks: u64,
+}
+
+// gear table for the rolling hash, generated deterministically (splitmix64)
+fn gear_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut state: u64 = 0;
+
+    for entry in table.iter_mut() {
+        state = state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        *entry = z ^ (z >> 31);
+    }
+
+    table
+}
+
+// split a file into content-defined chunks, calling `on_chunk` with each
+// chunk's fingerprint and length
+fn chunk_file(path: &Path, gear: &[u64; 256], mut on_chunk: impl FnMut(u64, usize)) -> io::Result<()> {
+    let mut file = hash::open(path)?;
+    let mut buffer = vec![0u8; BUFFER_SIZE];
+
+    let mut rolling: u64 = 0;
+    let mut fingerprint = FNV_OFFSET_BASIS;
+    let mut length = 0;
+
+    loop {
+        let bytes_read = file.read(&mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+
+        for byte in &buffer[..bytes_read] {
+            rolling = (rolling << 1).wrapping_add(gear[*byte as usize]);
+            fingerprint ^= *byte as u64;
+            fingerprint = fingerprint.wrapping_mul(FNV_PRIME);
+            length += 1;
+
+            let at_boundary = length >= MIN_CHUNK && rolling & BOUNDARY_MASK == 0;
+            if at_boundary || length >= MAX_CHUNK {
+                on_chunk(fingerprint ^ length as u64, length);
+                rolling = 0;
+                fingerprint = FNV_OFFSET_BASIS;
+                length = 0;
+            }
+        }
+    }
+
+    if length > 0 {
+        on_chunk(fingerprint ^ length as u64, length);
+    }
+
+    Ok(())
+}
+
+fn estimate_tree(directory: &Path) -> Option<Estimate> {
+    let gear = gear_table();
+    let mut seen_chunks: HashSet<u64> = HashSet::new();
+    let mut estimate = Estimate::default();
+
+    let readable = scan::walk_files(directory, true, |path, _| {
+        let mut file_estimate = Estimate::default();
+
+        let result = chunk_file(&path, &gear, |fingerprint, length| {
+            file_estimate.chunks += 1;
+            file_estimate.total_bytes += length as u64;
+            if seen_chunks.insert(fingerprint) {
+                file_estimate.unique_chunks += 1;
+                file_estimate.unique_bytes += length as u64;
+            }
+        });
+
+        if let Err(e) = result {
+            eprintln!("Error reading '{}': {}", path.display(), e);
+        }
+
+        estimate.files += 1;
+        estimate.total_bytes += file_estimate.total_bytes;
+        estimate.unique_bytes += file_estimate.unique_bytes;
+        estimate.chunks += file_estimate.chunks;
+        estimate.unique_chunks += file_estimate.unique_chunks;
+    });
+
+    readable.then_some(estimate)
+}
+
+pub fn run(args: &[String]) -> Result<(), String> {
+    let directory = match args {
+        [] => crate::get_current_directory(),
+        [directory] => directory.clone(),
+        _ => return Err("usage: hydra estimate [DIR]".to_string()),
+    };
+
+    println!("Estimating block-level duplication in {} ...", directory);
+
+    let estimate = match estimate_tree(Path::new(&directory)) {
+        Some(estimate) => estimate,
+        None => return Err(format!("could not read '{}'", directory)),
+    };
+
+    let duplicate_bytes = estimate.total_bytes - estimate.unique_bytes;
+    let percentage = if estimate.total_bytes > 0 {
+        duplicate_bytes as f64 * 100.0 / estimate.total_bytes as f64
+    } else {
+        0.0
+    };
+
+    println!("\n================================");
+    println!("Files scanned: {}", estimate.files);
+    println!("Total data: {}", format_bytes(estimate.total_bytes));
+    println!("Chunks: {} ({} unique)", estimate.chunks, estimate.unique_chunks);
+    println!("Unique data: {}", format_bytes(estimate.unique_bytes));
+    println!("Duplicate data: {} ({:.1}%)", format_bytes(duplicate_bytes), percentage);
+    println!("\nThis is an estimate of savings from block-level (filesystem) dedup;");
+    println!("whole-file duplicates found by a normal run are a subset of it.");
+
+    Ok(())
+}
@@ -0,0 +1,81 @@
+// --action exec:'CMD {path} {keeper}': hand each copy to a command of the
+// user's instead of removing it, e.g. to queue it for an archiving system.
+// the command is split into words once, like a shell would, and run without
+// one, so a file name can never be taken for part of the command
+use std::ffi::OsString;
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+// words separated by whitespace; quotes group words and backslashes escape
+// outside single quotes
+pub fn split(command: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (None | Some('"'), '\\') => {
+                let escaped = chars.next().ok_or("the command ends in a lone backslash")?;
+                word.get_or_insert_default().push(escaped);
+            }
+            (Some(_), c) => word.get_or_insert_default().push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                word.get_or_insert_default();
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_default().push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err("the command has an unclosed quote".to_string());
+    }
+    words.extend(word);
+    if words.is_empty() {
+        return Err("the command is empty".to_string());
+    }
+    Ok(words)
+}
+
+// a word with {path} and {keeper} filled in, in one pass so a path that
+// itself contains "{keeper}" is passed on as it is. paths are kept as they
+// are on disk, not made into valid unicode
+fn fill(word: &str, path: &Path, keeper: &Path) -> OsString {
+    let mut filled = OsString::new();
+    let mut rest = word;
+    while let Some(start) = rest.find('{') {
+        filled.push(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(after) = rest.strip_prefix("{path}") {
+            filled.push(path);
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("{keeper}") {
+            filled.push(keeper);
+            rest = after;
+        } else {
+            filled.push("{");
+            rest = &rest[1..];
+        }
+    }
+    filled.push(rest);
+    filled
+}
+
+// run the command with {path} and {keeper} filled in; it succeeded if it
+// exited with status 0
+pub fn run(words: &[String], path: &Path, keeper: &Path) -> io::Result<()> {
+    let (program, args) = words
+        .split_first()
+        .ok_or_else(|| io::Error::other("--action exec has no command"))?;
+    let status = Command::new(fill(program, path, keeper))
+        .args(args.iter().map(|word| fill(word, path, keeper)))
+        .stdin(Stdio::null())
+        .status()?;
+    if !status.success() {
+        return Err(io::Error::other(format!("the command exited with {}", status)));
+    }
+    Ok(())
+}
@@ -0,0 +1,130 @@
+// content sniffing by magic numbers, falling back to the extension for
+// formats without a signature (plain text, csv, ...)
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+const SNIFF_BYTES: u64 = 512;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileClass {
+    Image,
+    Video,
+    Audio,
+    Document,
+    Archive,
+}
+
+impl FileClass {
+    pub fn parse(value: &str) -> Option<FileClass> {
+        match value {
+            "image" => Some(FileClass::Image),
+            "video" => Some(FileClass::Video),
+            "audio" => Some(FileClass::Audio),
+            "document" => Some(FileClass::Document),
+            "archive" => Some(FileClass::Archive),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FileClass::Image => "image",
+            FileClass::Video => "video",
+            FileClass::Audio => "audio",
+            FileClass::Document => "document",
+            FileClass::Archive => "archive",
+        }
+    }
+}
+
+// zip containers that are really office documents or ebooks
+const ZIP_DOCUMENT_EXTENSIONS: [&str; 8] = ["docx", "xlsx", "pptx", "odt", "ods", "odp", "epub", "pages"];
+
+fn class_from_magic(head: &[u8], extension: Option<&str>) -> Option<FileClass> {
+    let starts = |magic: &[u8]| head.starts_with(magic);
+    let at = |offset: usize, magic: &[u8]| head.get(offset..offset + magic.len()) == Some(magic);
+
+    // iso base media (mp4, mov, heic, m4a) share the ftyp box; the brand decides
+    if at(4, b"ftyp") {
+        return match head.get(8..12) {
+            Some(b"heic" | b"heix" | b"mif1" | b"msf1" | b"avif") => Some(FileClass::Image),
+            Some(b"M4A " | b"M4B " | b"M4P ") => Some(FileClass::Audio),
+            _ => Some(FileClass::Video),
+        };
+    }
+
+    if starts(b"RIFF") {
+        return match head.get(8..12) {
+            Some(b"WEBP") => Some(FileClass::Image),
+            Some(b"AVI ") => Some(FileClass::Video),
+            Some(b"WAVE") => Some(FileClass::Audio),
+            _ => None,
+        };
+    }
+
+    if starts(b"PK\x03\x04") {
+        return match extension {
+            Some(ext) if ZIP_DOCUMENT_EXTENSIONS.contains(&ext) => Some(FileClass::Document),
+            _ => Some(FileClass::Archive),
+        };
+    }
+
+    let images: [&[u8]; 7] = [
+        b"\xff\xd8\xff",
+        b"\x89PNG\r\n\x1a\n",
+        b"GIF87a",
+        b"GIF89a",
+        b"II*\x00",
+        b"MM\x00*",
+        b"\x00\x00\x01\x00",
+    ];
+    let videos: [&[u8]; 4] = [b"\x1a\x45\xdf\xa3", b"FLV\x01", b"\x00\x00\x01\xba", b"\x00\x00\x01\xb3"];
+    let audio: [&[u8]; 7] = [b"ID3", b"fLaC", b"OggS", b"MThd", b"\xff\xfb", b"\xff\xf3", b"\xff\xf2"];
+    let documents: [&[u8]; 3] = [b"%PDF-", b"\xd0\xcf\x11\xe0\xa1\xb1\x1a\xe1", b"{\\rtf"];
+    let archives: [&[u8]; 6] = [
+        b"\x1f\x8b",
+        b"BZh",
+        b"\xfd7zXZ\x00",
+        b"7z\xbc\xaf\x27\x1c",
+        b"Rar!\x1a\x07",
+        b"\x28\xb5\x2f\xfd",
+    ];
+
+    if images.iter().any(|magic| starts(magic)) || (starts(b"BM") && extension == Some("bmp")) {
+        Some(FileClass::Image)
+    } else if videos.iter().any(|magic| starts(magic)) {
+        Some(FileClass::Video)
+    } else if audio.iter().any(|magic| starts(magic)) || (starts(b"FORM") && at(8, b"AIFF")) {
+        Some(FileClass::Audio)
+    } else if documents.iter().any(|magic| starts(magic)) {
+        Some(FileClass::Document)
+    } else if archives.iter().any(|magic| starts(magic)) || at(257, b"ustar") {
+        Some(FileClass::Archive)
+    } else {
+        None
+    }
+}
+
+pub fn class_from_extension(extension: &str) -> Option<FileClass> {
+    match extension {
+        "jpg" | "jpeg" | "png" | "gif" | "webp" | "bmp" | "tif" | "tiff" | "heic" | "svg" | "raw" | "cr2" | "nef"
+        | "arw" | "dng" => Some(FileClass::Image),
+        "mp4" | "m4v" | "mov" | "mkv" | "webm" | "avi" | "wmv" | "flv" | "mpg" | "mpeg" => Some(FileClass::Video),
+        "mp3" | "m4a" | "flac" | "ogg" | "opus" | "wav" | "aiff" | "aac" | "wma" | "mid" => Some(FileClass::Audio),
+        "pdf" | "txt" | "md" | "rtf" | "csv" | "doc" | "docx" | "xls" | "xlsx" | "ppt" | "pptx" | "odt" | "ods"
+        | "odp" | "epub" | "pages" | "numbers" | "key" => Some(FileClass::Document),
+        "zip" | "gz" | "tgz" | "bz2" | "xz" | "7z" | "rar" | "tar" | "zst" | "dmg" | "iso" => Some(FileClass::Archive),
+        _ => None,
+    }
+}
+
+pub fn detect(path: &Path) -> io::Result<Option<FileClass>> {
+    let mut head = Vec::with_capacity(SNIFF_BYTES as usize);
+    File::open(path)?.take(SNIFF_BYTES).read_to_end(&mut head)?;
+
+    let extension = path.extension().map(|ext| ext.to_string_lossy().to_lowercase());
+    let extension = extension.as_deref();
+
+    Ok(class_from_magic(&head, extension).or_else(|| extension.and_then(class_from_extension)))
+}
@@ -0,0 +1,190 @@
+// `hydra find-copies FILE [DIR...]`: the one-off question "where else do I
+// have this file?" without scanning a whole tree for every duplicate. only
+// files of FILE's size are hashed, and the copies found can be removed or
+// turned into hard links to FILE, through the same checked two-phase removal
+// as a normal run, with FILE as the keeper
+use crate::actions::{self, Action};
+use crate::consolidate::{file_id, file_info};
+use crate::scheduler::{self, IoProfile};
+use crate::{DuplicateSet, FileInfo, format_bytes, scan, terminal};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Treatment {
+    List,
+    Act(Action),
+}
+
+// files in `directories` with the same content as `file`, other than `file`
+// itself and names already hard-linked to it, as they were when walked, with
+// the hash of `file`
+fn find_copies(file: &Path, directories: &[PathBuf]) -> Result<(Vec<FileInfo>, u64), String> {
+    let metadata = fs::metadata(file).map_err(|e| format!("could not read '{}': {}", file.display(), e))?;
+    let (size, id) = (metadata.len(), file_id(&metadata));
+
+    let mut candidates = HashMap::new();
+    for directory in directories {
+        let readable = scan::walk_files(directory, true, |path, metadata| {
+            if metadata.len() == size
+                && path != file
+                && (id.is_none() || file_id(&metadata) != id)
+                && let Some(file_info) = file_info(path.clone(), &metadata)
+            {
+                candidates.insert(path, file_info);
+            }
+        });
+        if !readable {
+            return Err(format!("could not read '{}'", directory.display()));
+        }
+    }
+    if candidates.is_empty() {
+        return Ok((Vec::new(), 0));
+    }
+
+    let mut paths: Vec<PathBuf> = candidates.keys().cloned().collect();
+    paths.push(file.to_path_buf());
+    let hashes = scheduler::hash_files(paths, IoProfile::Auto);
+    let target = match &hashes[file] {
+        Ok(hash) => *hash,
+        Err(e) => return Err(format!("could not hash '{}': {}", file.display(), e)),
+    };
+
+    let mut copies: Vec<FileInfo> = hashes
+        .into_iter()
+        .filter_map(|(path, hash)| match hash {
+            Ok(hash) if hash == target && path != file => candidates.remove(&path),
+            Ok(_) => None,
+            Err(e) => {
+                eprintln!("Warning: Could not hash '{}': {}", path.display(), e);
+                None
+            }
+        })
+        .collect();
+    copies.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok((copies, target))
+}
+
+// `hydra find-copies FILE [DIR...] [--delete | --trash | --link] [--dry-run] [--yes]`
+pub fn run(args: &[String]) -> Result<(), String> {
+    let usage = "usage: hydra find-copies FILE [DIR...] [--delete | --trash | --link] [--dry-run] [--yes]";
+    let mut treatment = Treatment::List;
+    let (mut dry_run, mut yes) = (false, false);
+    let mut paths = Vec::new();
+
+    for arg in args {
+        let chosen = match arg.as_str() {
+            "--delete" => Treatment::Act(Action::Delete),
+            "--trash" => Treatment::Act(Action::Trash),
+            "--link" => Treatment::Act(Action::Link),
+            "--dry-run" => {
+                dry_run = true;
+                continue;
+            }
+            "-y" | "--yes" => {
+                yes = true;
+                continue;
+            }
+            _ if !arg.starts_with('-') => {
+                paths.push(arg);
+                continue;
+            }
+            _ => return Err(usage.to_string()),
+        };
+        if treatment != Treatment::List && treatment != chosen {
+            return Err("--delete, --trash and --link can't be combined".to_string());
+        }
+        treatment = chosen;
+    }
+
+    let (file, directories) = paths.split_first().ok_or(usage)?;
+    let file = fs::canonicalize(file).map_err(|e| format!("could not read '{}': {}", file, e))?;
+    let directories = if directories.is_empty() {
+        vec![std::env::current_dir().map_err(|e| e.to_string())?]
+    } else {
+        let canonical = directories.iter().map(|directory| {
+            fs::canonicalize(directory).map_err(|e| format!("could not read '{}': {}", directory, e))
+        });
+        canonical.collect::<Result<Vec<_>, _>>()?
+    };
+
+    if treatment != Treatment::List && !dry_run && !yes && !terminal::can_prompt() {
+        eprintln!("Note: stdin is not a terminal, so this run only reports. Pass --yes to act without confirmation.");
+        dry_run = true;
+    }
+
+    println!("Looking for copies of {} ...", file.display());
+    let (copies, hash) = find_copies(&file, &directories)?;
+    if copies.is_empty() {
+        println!("\nNo copies found.");
+        return Ok(());
+    }
+
+    let label = match (treatment, dry_run) {
+        (Treatment::List, _) => "Copy".to_string(),
+        (Treatment::Act(action), true) => format!("Would {}", action.verb()),
+        (Treatment::Act(action), false) => format!("Will {}", action.verb()),
+    };
+    println!();
+    for copy in &copies {
+        println!("{}: {}", label, copy.path.display());
+    }
+
+    let size = fs::metadata(&file).map(|m| m.len()).unwrap_or(0);
+    println!("\n================================");
+    println!("Copies found: {} ({})", copies.len(), format_bytes(size * copies.len() as u64));
+
+    let Treatment::Act(action) = treatment else {
+        return Ok(());
+    };
+    if dry_run {
+        println!("\n[DRY RUN MODE] No files were changed.");
+        return Ok(());
+    }
+
+    if !yes {
+        print!("\nProceed? (y/N): ");
+        io::stdout().flush().unwrap();
+
+        let mut input = String::new();
+        io::stdin().read_line(&mut input).unwrap();
+        let input = input.trim().to_lowercase();
+        if input != "y" && input != "yes" {
+            println!("Cancelled. No files were changed.");
+            return Ok(());
+        }
+    }
+
+    let keeper_metadata = fs::metadata(&file).map_err(|e| format!("could not read '{}': {}", file.display(), e))?;
+    let keeper = file_info(file.clone(), &keeper_metadata).ok_or("could not read the file's modified time")?;
+    let mut sets = vec![DuplicateSet {
+        normalized_filename: file.file_name().unwrap_or_default().to_string_lossy().to_string(),
+        size: keeper.size,
+        hash: Some(hash),
+        keeper,
+        duplicates: copies,
+    }];
+    let mut options = crate::parse_args(&["hydra".to_string()])?;
+    options.action = action;
+    options.jobs = 1;
+
+    let worker_stats = actions::delete_duplicates(&mut sets, &options);
+    let count = |field: fn(&actions::WorkerStats) -> usize| worker_stats.iter().map(field).sum::<usize>();
+    let unfinished = |stats: &actions::WorkerStats| {
+        stats.in_use.iter().chain(&stats.transient).map(|set| set.duplicates.len()).sum()
+    };
+    let changed = count(|stats| stats.deleted + stats.passed_on);
+    let skipped = count(|stats| stats.skipped + stats.rolled_back + stats.other_device) + count(unfinished);
+    let errors = count(|stats| stats.errors);
+
+    println!("\nDone! Files changed: {}", changed);
+    if skipped > 0 {
+        println!("Files skipped: {}", skipped);
+    }
+    if errors > 0 {
+        println!("Errors encountered: {}", errors);
+    }
+    Ok(())
+}
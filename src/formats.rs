@@ -0,0 +1,101 @@
+// --format: the duplicate sets of a run as machine-readable output on stdout.
+// json is the document --output saves (so it can be fed to `hydra apply`),
+// ndjson one set per line, csv one file per row, and print0 the paths that
+// would be removed, NUL-terminated for `xargs -0`
+use crate::{DuplicateSet, Options, json, plan, report};
+use std::io::{self, Write};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Text,
+    Json,
+    Ndjson,
+    Csv,
+    Print0,
+}
+
+impl Format {
+    pub fn parse(value: &str) -> Option<Format> {
+        match value {
+            "text" => Some(Format::Text),
+            "json" => Some(Format::Json),
+            "ndjson" => Some(Format::Ndjson),
+            "csv" => Some(Format::Csv),
+            "print0" => Some(Format::Print0),
+            _ => None,
+        }
+    }
+
+    pub fn is_structured(self) -> bool {
+        self != Format::Text
+    }
+}
+
+fn path_json(path: &Path) -> String {
+    json::string(&path.to_string_lossy())
+}
+
+fn set_line(set: &DuplicateSet) -> String {
+    let duplicates: Vec<String> = set.duplicates.iter().map(|file_info| path_json(&file_info.path)).collect();
+    format!(
+        "{{\"id\": {}, \"name\": {}, \"size\": {}, \"reclaimable_files\": {}, \"reclaimable_bytes\": {}, \
+         \"keeper\": {}, \"duplicates\": [{}]}}",
+        set.id().map_or("null".to_string(), |id| json::string(&id)),
+        json::string(&set.normalized_filename),
+        set.size,
+        set.duplicates.len(),
+        report::reclaimable_bytes(set),
+        path_json(&set.keeper.path),
+        duplicates.join(", ")
+    )
+}
+
+// quoted only when it has to be, as RFC 4180 asks
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub fn write(
+    out: &mut dyn Write,
+    format: Format,
+    directory: &str,
+    duplicate_sets: &[DuplicateSet],
+    options: &Options,
+) -> io::Result<()> {
+    match format {
+        Format::Text => {}
+        Format::Json => out.write_all(plan::to_json(directory, duplicate_sets, options).as_bytes())?,
+        Format::Ndjson => {
+            for set in duplicate_sets {
+                writeln!(out, "{}", set_line(set))?;
+            }
+        }
+        Format::Csv => {
+            writeln!(out, "set,name,size,role,path,reclaimable_files,reclaimable_bytes")?;
+            for set in duplicate_sets {
+                let id = set.id().unwrap_or_default();
+                let name = csv_field(&set.normalized_filename);
+                let reclaimable = (set.duplicates.len(), report::reclaimable_bytes(set));
+                let files = std::iter::once((&set.keeper, "keeper"))
+                    .chain(set.duplicates.iter().map(|file_info| (file_info, "duplicate")));
+                for (file_info, role) in files {
+                    let path = csv_field(&file_info.path.to_string_lossy());
+                    let (files, bytes) = reclaimable;
+                    writeln!(out, "{},{},{},{},{},{},{}", id, name, set.size, role, path, files, bytes)?;
+                }
+            }
+        }
+        Format::Print0 => {
+            for file_info in duplicate_sets.iter().flat_map(|set| &set.duplicates) {
+                out.write_all(file_info.path.as_os_str().as_encoded_bytes())?;
+                out.write_all(b"\0")?;
+            }
+        }
+    }
+    out.flush()
+}
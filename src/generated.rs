@@ -0,0 +1,102 @@
+// machine-generated files that look like families of copies once their
+// numeric suffixes are stripped: rotated logs ("app.log.1", "app(3).log"),
+// numbered files in caches ("Cache/data_12") and thumbnails (".thumbnails",
+// "@eaDir", "photo-150x150.jpg"). they are skipped by default, as
+// --include-system-files does for OS metadata; --include-generated keeps them.
+// [generated] in the config chooses which heuristics apply and adds globs
+use crate::config::Config;
+use crate::normalize::glob_match;
+use regex::Regex;
+use std::path::Path;
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Heuristic {
+    Logs,
+    Caches,
+    Thumbnails,
+}
+
+const HEURISTICS: [(&str, Heuristic); 3] =
+    [("logs", Heuristic::Logs), ("caches", Heuristic::Caches), ("thumbnails", Heuristic::Thumbnails)];
+
+// directories that hold nothing but thumbnails: freedesktop, synology, qnap
+const THUMBNAIL_DIRECTORIES: [&str; 5] = [".thumbnails", "thumbnails", ".thumbs", "@eadir", ".@__thumb"];
+
+#[derive(Debug)]
+pub struct Generated {
+    heuristics: Vec<Heuristic>,
+    // globs of file names of the user's own
+    patterns: Vec<String>,
+}
+
+impl Default for Generated {
+    fn default() -> Generated {
+        Generated {
+            heuristics: HEURISTICS.iter().map(|(_, heuristic)| *heuristic).collect(),
+            patterns: Vec::new(),
+        }
+    }
+}
+
+// "app.log.1", "app.log.2.gz", "app-3.log", "app(4).log", "app-2024-05-01.log"
+fn rotated_log() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"(?i)(\.log\.\d+(\.(gz|bz2|xz|zst))?|[._ -]\d+\.log|\(\d+\)\.log)$").unwrap())
+}
+
+// "photo_thumb.jpg", "photo.thumb.png", and the sizes wordpress renders,
+// "photo-150x150.jpg"
+fn thumbnail() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"(?i)([._-]thumb(nail)?|-\d+x\d+)\.(jpe?g|png|gif|webp)$").unwrap())
+}
+
+// a file directly in a cache directory ("Cache", ".cache", "Cache_Data")
+// whose name ends in a number
+fn is_numbered_cache_file(path: &Path, name: &str) -> bool {
+    let stem = name.split('.').next().unwrap_or(name);
+    let directory = path.parent().and_then(Path::file_name).map(|name| name.to_string_lossy().to_lowercase());
+    stem.ends_with(|c: char| c.is_ascii_digit()) && directory.is_some_and(|directory| directory.contains("cache"))
+}
+
+impl Generated {
+    // [generated] heuristics = ["logs", "caches", "thumbnails"] chooses the
+    // built-in rules (all by default); patterns = ["*.part"] adds globs
+    pub fn from_config(config: &Config) -> Result<Generated, String> {
+        let mut generated = Generated::default();
+        for section in config.sections_named("generated") {
+            if let Some(value) = section.get("heuristics") {
+                let names = value.as_string_array().ok_or("[generated] `heuristics` needs a list of strings")?;
+                generated.heuristics = Vec::new();
+                for name in names {
+                    let heuristic = HEURISTICS.iter().find(|(known, _)| *known == name).ok_or(format!(
+                        "unknown [generated] heuristic '{}' (expected logs, caches, thumbnails)",
+                        name
+                    ))?;
+                    generated.heuristics.push(heuristic.1);
+                }
+            }
+            if let Some(value) = section.get("patterns") {
+                generated.patterns = value.as_string_array().ok_or("[generated] `patterns` needs a list of globs")?;
+            }
+        }
+        Ok(generated)
+    }
+
+    // whether a file or directory is generated
+    pub fn matches(&self, path: &Path, is_dir: bool) -> bool {
+        let Some(name) = path.file_name().map(|name| name.to_string_lossy()) else {
+            return false;
+        };
+        if is_dir {
+            return self.heuristics.contains(&Heuristic::Thumbnails)
+                && THUMBNAIL_DIRECTORIES.contains(&name.to_lowercase().as_str());
+        }
+        self.heuristics.iter().any(|heuristic| match heuristic {
+            Heuristic::Logs => rotated_log().is_match(&name),
+            Heuristic::Caches => is_numbered_cache_file(path, &name),
+            Heuristic::Thumbnails => thumbnail().is_match(&name),
+        }) || self.patterns.iter().any(|pattern| glob_match(pattern, &name))
+    }
+}
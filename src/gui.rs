@@ -0,0 +1,508 @@
+// `hydra gui`: a point-and-click frontend for people who never open a
+// terminal. hydra serves a page to the browser on 127.0.0.1: pick folders,
+// watch the scan, review each set with image previews, change keepers or
+// leave copies out, and apply. a browser page rather than a native window, so
+// hydra needs no GUI toolkit. every request must carry a random session
+// cookie, so no other page or local user can drive it, and only files of the
+// current scan are ever served. the cookie is handed out once, for a launch
+// code that only reaches the browser through a file only this user can read
+// (or the terminal), never through a command line others could see in `ps`
+use crate::actions::Action;
+use crate::json::{self, Json};
+use crate::{DuplicateSet, FileInfo, Options, interactive};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+const PAGE: &str = include_str!("gui.html");
+// request bodies are small JSON documents
+const MAX_BODY: usize = 1024 * 1024;
+// the request line and headers together; browsers send a few KiB
+const MAX_HEAD: u64 = 64 * 1024;
+const COOKIE: &str = "hydra-session";
+
+enum Phase {
+    Idle,
+    Scanning(Instant),
+    Ready,
+    Applying(Instant),
+    Applied(String),
+    Failed(String),
+}
+
+struct State {
+    phase: Phase,
+    action: Action,
+    duplicate_sets: Vec<DuplicateSet>,
+    // what the last scan ran with, so applying uses the same settings
+    options: Option<Options>,
+}
+
+type Shared = Arc<Mutex<State>>;
+
+struct Request {
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+    cookies: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.query.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+
+    fn cookie(&self, name: &str) -> Option<&str> {
+        self.cookies.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+}
+
+// the session token and the one-time code that gets a browser the cookie
+struct Keys {
+    session: String,
+    launch: Mutex<Option<String>>,
+    // the launch page handed to the browser, removed once it has been used
+    launch_file: Option<PathBuf>,
+}
+
+// 128 bits from the OS-seeded hasher keys; enough to be unguessable
+fn new_token() -> String {
+    (0..2)
+        .map(|index| {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_usize(index);
+            format!("{:016x}", hasher.finish())
+        })
+        .collect()
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escaped = bytes.get(index + 1..index + 3).and_then(|hex| std::str::from_utf8(hex).ok());
+        match (bytes[index], escaped.and_then(|hex| u8::from_str_radix(hex, 16).ok())) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                index += 3;
+                continue;
+            }
+            (b'+', _) => decoded.push(b' '),
+            (byte, _) => decoded.push(byte),
+        }
+        index += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+fn read_request(stream: &mut TcpStream) -> io::Result<Request> {
+    let too_large = |what| io::Error::new(io::ErrorKind::InvalidData, format!("{} too large", what));
+    let mut reader = BufReader::new(stream);
+    let mut head = (&mut reader).take(MAX_HEAD);
+    let mut line = String::new();
+    head.read_line(&mut line)?;
+    if !line.ends_with('\n') {
+        return Err(too_large("request line"));
+    }
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let target = parts.next().unwrap_or_default().to_string();
+
+    let mut length = 0;
+    let mut cookies = Vec::new();
+    loop {
+        let mut header = String::new();
+        if head.read_line(&mut header)? == 0 {
+            return Err(too_large("request header"));
+        }
+        if header.trim().is_empty() {
+            break;
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            continue;
+        };
+        if name.trim().eq_ignore_ascii_case("content-length") {
+            length = value.trim().parse().unwrap_or(0);
+        } else if name.trim().eq_ignore_ascii_case("cookie") {
+            let pairs = value.split(';').filter_map(|pair| pair.split_once('='));
+            cookies.extend(pairs.map(|(key, value)| (key.trim().to_string(), value.trim().to_string())));
+        }
+    }
+    if length > MAX_BODY {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "request too large"));
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+
+    let (path, query) = target.split_once('?').unwrap_or((&target, ""));
+    let query = query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| (percent_decode(key), percent_decode(value)))
+        .collect();
+    Ok(Request {
+        method,
+        path: path.to_string(),
+        query,
+        cookies,
+        body,
+    })
+}
+
+fn respond(stream: &mut TcpStream, status: &str, content_type: &str, body: &[u8]) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    stream.write_all(body)
+}
+
+fn respond_json(stream: &mut TcpStream, status: &str, body: &str) -> io::Result<()> {
+    respond(stream, status, "application/json", body.as_bytes())
+}
+
+fn error_json(message: &str) -> String {
+    format!("{{\"error\": {}}}", json::string(message))
+}
+
+fn image_type(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_string_lossy().to_lowercase();
+    match extension.as_str() {
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "png" => Some("image/png"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        "bmp" => Some("image/bmp"),
+        _ => None,
+    }
+}
+
+fn file_json(file_info: &FileInfo) -> String {
+    format!(
+        "{{\"path\": {}, \"image\": {}}}",
+        json::string(&file_info.path.to_string_lossy()),
+        image_type(&file_info.path).is_some()
+    )
+}
+
+fn status_json(state: &State) -> String {
+    let (phase, detail) = match &state.phase {
+        Phase::Idle => ("idle", String::new()),
+        Phase::Scanning(started) => ("scanning", format!("{}s", started.elapsed().as_secs())),
+        Phase::Ready => ("ready", String::new()),
+        Phase::Applying(started) => ("applying", format!("{}s", started.elapsed().as_secs())),
+        Phase::Applied(summary) => ("applied", summary.clone()),
+        Phase::Failed(message) => ("failed", message.clone()),
+    };
+    let sets: Vec<String> = match state.phase {
+        Phase::Ready => state
+            .duplicate_sets
+            .iter()
+            .map(|set| {
+                let files: Vec<String> =
+                    std::iter::once(&set.keeper).chain(&set.duplicates).map(file_json).collect();
+                format!(
+                    "{{\"name\": {}, \"size\": {}, \"files\": [{}]}}",
+                    json::string(&set.normalized_filename),
+                    set.size,
+                    files.join(", ")
+                )
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    format!(
+        "{{\"phase\": {}, \"detail\": {}, \"action\": {}, \"sets\": [{}]}}",
+        json::string(phase),
+        json::string(&detail),
+        json::string(state.action.verb()),
+        sets.join(", ")
+    )
+}
+
+fn string_list(value: Option<&Json>) -> Vec<String> {
+    value
+        .and_then(Json::as_array)
+        .map(|values| values.iter().filter_map(Json::as_str).map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+// POST /scan {"folders": [...], "recursive": bool, "action": "trash"}
+fn start_scan(shared: &Shared, body: &Json) -> Result<(), String> {
+    let mut folders = Vec::new();
+    for folder in string_list(body.get("folders")) {
+        let folder = folder.trim();
+        if folder.is_empty() {
+            continue;
+        }
+        let path = std::fs::canonicalize(folder).map_err(|e| format!("could not read '{}': {}", folder, e))?;
+        if !path.is_dir() {
+            return Err(format!("'{}' is not a folder", folder));
+        }
+        folders.push(path);
+    }
+    let Some((first, rest)) = folders.split_first() else {
+        return Err("choose at least one folder".to_string());
+    };
+
+    let mut args = vec!["hydra".to_string(), "--yes".to_string()];
+    if matches!(body.get("recursive"), Some(Json::Bool(true))) {
+        args.push("--recursive".to_string());
+    }
+    // the config's process-wide settings were set up once when the gui started
+    let mut options = crate::parse_args(&args)?;
+    // the page offers delete, trash and link; it never runs commands
+    let action = body.get("action").and_then(Json::as_str).and_then(Action::parse);
+    if let Some(action) = action.filter(|action| *action != Action::Exec) {
+        options.action = action;
+    }
+    options.directory = Some(first.to_string_lossy().to_string());
+    options.roots = rest.to_vec();
+
+    let mut state = shared.lock().unwrap();
+    if matches!(state.phase, Phase::Scanning(_) | Phase::Applying(_)) {
+        return Err("hydra is busy; wait for it to finish".to_string());
+    }
+    state.phase = Phase::Scanning(Instant::now());
+    state.action = options.action;
+    state.duplicate_sets.clear();
+    state.options = None;
+    drop(state);
+
+    let shared = Arc::clone(shared);
+    thread::spawn(move || {
+        let directory = options.directory.clone().unwrap_or_default();
+        let findings = crate::find_duplicates(&directory, &options, &mut Instant::now());
+        let mut state = shared.lock().unwrap();
+        match findings {
+            Some(findings) => {
+                state.duplicate_sets = findings.duplicate_sets;
+                state.options = Some(options);
+                state.phase = Phase::Ready;
+            }
+            None => state.phase = Phase::Failed(format!("could not read '{}'", directory)),
+        }
+    });
+    Ok(())
+}
+
+// the sets as the user left them: {"sets": [{"index": 0, "keeper": path,
+// "remove": [paths]}]}. a set keeps its files; only the keeper and what is
+// removed may change
+fn chosen_sets(state: &State, body: &Json) -> Result<Vec<DuplicateSet>, String> {
+    let mut chosen = Vec::new();
+    for entry in body.get("sets").and_then(Json::as_array).unwrap_or_default() {
+        let set = entry
+            .get("index")
+            .and_then(Json::as_u64)
+            .and_then(|index| state.duplicate_sets.get(index as usize))
+            .ok_or("the page is out of date; scan again")?;
+        let files: Vec<&FileInfo> = std::iter::once(&set.keeper).chain(&set.duplicates).collect();
+        let find = |path: &str| files.iter().find(|file_info| file_info.path == Path::new(path)).copied();
+
+        let keeper = entry.get("keeper").and_then(Json::as_str).and_then(find).ok_or("unknown keeper")?;
+        let mut duplicates = Vec::new();
+        for path in string_list(entry.get("remove")) {
+            let file_info = find(&path).ok_or("unknown file")?;
+            if file_info.path != keeper.path {
+                duplicates.push(file_info.clone());
+            }
+        }
+        if !duplicates.is_empty() {
+            chosen.push(DuplicateSet {
+                normalized_filename: set.normalized_filename.clone(),
+                size: set.size,
+                hash: set.hash,
+                keeper: keeper.clone(),
+                duplicates,
+            });
+        }
+    }
+    Ok(chosen)
+}
+
+// POST /apply
+fn start_apply(shared: &Shared, body: &Json) -> Result<(), String> {
+    let mut state = shared.lock().unwrap();
+    if !matches!(state.phase, Phase::Ready) {
+        return Err("there is nothing to apply; scan first".to_string());
+    }
+    let duplicate_sets = chosen_sets(&state, body)?;
+    let options = state.options.take().ok_or("there is nothing to apply; scan first")?;
+    state.phase = Phase::Applying(Instant::now());
+    drop(state);
+
+    // the same path as the terminal: backup, hooks, retries, reference fixing
+    // and keeper verification all apply, and their output goes to this window
+    let shared = Arc::clone(shared);
+    thread::spawn(move || {
+        let directory = options.directory.clone().unwrap_or_default();
+        let summary = match crate::apply_sets(&directory, duplicate_sets, &options) {
+            Some(applied) => format!(
+                "{} {} file(s); {} skipped because they changed, {} error(s), {} left alone",
+                options.action.past_tense(),
+                applied.done,
+                applied.skipped,
+                applied.errors,
+                applied.left_alone
+            ),
+            None => "Nothing was changed; see the hydra window for why".to_string(),
+        };
+        let mut state = shared.lock().unwrap();
+        state.duplicate_sets.clear();
+        state.phase = Phase::Applied(summary);
+    });
+    Ok(())
+}
+
+// GET /file?path=...: only files of the current scan
+fn serve_file(stream: &mut TcpStream, shared: &Shared, path: &str) -> io::Result<()> {
+    let known = {
+        let state = shared.lock().unwrap();
+        state
+            .duplicate_sets
+            .iter()
+            .flat_map(|set| std::iter::once(&set.keeper).chain(&set.duplicates))
+            .any(|file_info| file_info.path == Path::new(path))
+    };
+    let path = PathBuf::from(path);
+    match (known, image_type(&path)) {
+        (true, Some(content_type)) => match std::fs::read(&path) {
+            Ok(bytes) => respond(stream, "200 OK", content_type, &bytes),
+            Err(e) => respond_json(stream, "404 Not Found", &error_json(&e.to_string())),
+        },
+        _ => respond_json(stream, "404 Not Found", &error_json("not a file of this scan")),
+    }
+}
+
+// GET /launch?code=...: trade the one-time code for the session cookie
+fn launch(stream: &mut TcpStream, keys: &Keys, code: Option<&str>) -> io::Result<()> {
+    let mut launch = keys.launch.lock().unwrap();
+    if code.is_none() || launch.as_deref() != code {
+        return respond_json(stream, "403 Forbidden", &error_json("this launch address was used already"));
+    }
+    *launch = None;
+    if let Some(file) = &keys.launch_file {
+        let _ = std::fs::remove_file(file);
+    }
+    write!(
+        stream,
+        "HTTP/1.1 303 See Other\r\nLocation: /\r\nSet-Cookie: {}={}; Path=/; HttpOnly; SameSite=Strict\r\n\
+         Cache-Control: no-store\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        COOKIE, keys.session
+    )
+}
+
+fn handle(mut stream: TcpStream, shared: &Shared, keys: &Keys) -> io::Result<()> {
+    let request = read_request(&mut stream)?;
+    if (request.method.as_str(), request.path.as_str()) == ("GET", "/launch") {
+        return launch(&mut stream, keys, request.param("code"));
+    }
+    if request.cookie(COOKIE) != Some(keys.session.as_str()) {
+        return respond_json(&mut stream, "403 Forbidden", &error_json("open the address hydra printed"));
+    }
+
+    let body = || json::parse(&String::from_utf8_lossy(&request.body));
+    let started = match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/") => return respond(&mut stream, "200 OK", "text/html; charset=utf-8", PAGE.as_bytes()),
+        ("GET", "/status") => {
+            let status = status_json(&shared.lock().unwrap());
+            return respond_json(&mut stream, "200 OK", &status);
+        }
+        ("GET", "/file") => return serve_file(&mut stream, shared, request.param("path").unwrap_or_default()),
+        ("POST", "/scan") => body().and_then(|body| start_scan(shared, &body)),
+        ("POST", "/apply") => body().and_then(|body| start_apply(shared, &body)),
+        _ => return respond_json(&mut stream, "404 Not Found", &error_json("no such page")),
+    };
+    match started {
+        Ok(()) => respond_json(&mut stream, "202 Accepted", "{}"),
+        Err(e) => respond_json(&mut stream, "400 Bad Request", &error_json(&e)),
+    }
+}
+
+// the browser is handed a page that forwards it to the launch address, in a
+// file only this user can read: the browser's command line shows just the
+// file's name
+fn open_browser(url: &str) -> io::Result<PathBuf> {
+    let directory = std::env::temp_dir().join(format!("hydra-gui-{}", &new_token()[..16]));
+    let mut builder = std::fs::DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(&directory)?;
+    let file = directory.join("launch.html");
+    let page = format!(
+        "<!doctype html><meta http-equiv=\"refresh\" content=\"0; url={0}\"><a href=\"{0}\">Open hydra</a>\n",
+        url
+    );
+    std::fs::write(&file, page)?;
+    interactive::open_with_default_app(&file)?;
+    Ok(file)
+}
+
+// `hydra gui [--port N] [--no-browser]`
+pub fn run(args: &[String]) -> Result<(), String> {
+    let usage = "usage: hydra gui [--port N] [--no-browser]";
+    let (mut port, mut browser) = (0, true);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--port" => port = args.next().and_then(|value| value.parse().ok()).ok_or(usage)?,
+            "--no-browser" => browser = false,
+            _ => return Err(usage.to_string()),
+        }
+    }
+
+    // what the config asks of the whole process (throttling, priority,
+    // language) holds for every scan started from the page
+    crate::parse_args(&["hydra".to_string(), "--yes".to_string()]).and_then(crate::set_up)?;
+
+    let listener = TcpListener::bind(("127.0.0.1", port)).map_err(|e| format!("could not listen: {}", e))?;
+    let address = listener.local_addr().map_err(|e| e.to_string())?;
+    let code = new_token();
+    let url = format!("http://{}/launch?code={}", address, code);
+    println!("hydra is running at http://{}/", address);
+    println!("Open {} in your browser to start (it works once).", url);
+    println!("Keep this window open while you use it; press Ctrl-C to stop.");
+    let launch_file = if browser {
+        match open_browser(&url) {
+            Ok(file) => Some(file),
+            Err(e) => {
+                eprintln!("Warning: Could not open a browser ({}); open the address above yourself", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let keys = Arc::new(Keys {
+        session: new_token(),
+        launch: Mutex::new(Some(code)),
+        launch_file,
+    });
+
+    let shared: Shared = Arc::new(Mutex::new(State {
+        phase: Phase::Idle,
+        action: Action::Trash,
+        duplicate_sets: Vec::new(),
+        options: None,
+    }));
+    for stream in listener.incoming().flatten() {
+        let (shared, keys) = (Arc::clone(&shared), Arc::clone(&keys));
+        thread::spawn(move || {
+            if let Err(e) = handle(stream, &shared, &keys) {
+                eprintln!("Warning: Could not answer the browser: {}", e);
+            }
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,226 @@
+use crate::{heartbeat, ownership, power, stats, volumes, xattr};
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+pub const DEFAULT_BUFFER_SIZE: usize = 64 * 1024;
+pub const XATTR_NAME: &str = "user.hydra.hash";
+// v1 stamps carried no filesystem identity
+const XATTR_VERSION: &str = "v2";
+
+static XATTR_CACHE: AtomicBool = AtomicBool::new(false);
+static BUFFER_SIZE: AtomicUsize = AtomicUsize::new(DEFAULT_BUFFER_SIZE);
+
+pub fn enable_xattr_cache() {
+    XATTR_CACHE.store(true, Ordering::Relaxed);
+}
+
+// how much of a file is read at a time ([hashing] buffer-kb in the config)
+pub fn set_buffer_size(bytes: usize) {
+    BUFFER_SIZE.store(bytes, Ordering::Relaxed);
+}
+
+// shared across every reader so the limit holds no matter how many files are
+// being hashed in parallel
+struct Throttle {
+    bytes_per_second: u64,
+    state: Mutex<(Instant, u64)>,
+}
+
+static THROTTLE: OnceLock<Throttle> = OnceLock::new();
+
+pub fn set_bandwidth_limit(bytes_per_second: u64) {
+    let _ = THROTTLE.set(Throttle {
+        bytes_per_second,
+        state: Mutex::new((Instant::now(), 0)),
+    });
+}
+
+// account for bytes read and sleep long enough to stay under the limit
+fn throttle(bytes: usize) {
+    let throttle = match THROTTLE.get() {
+        Some(throttle) => throttle,
+        None => return,
+    };
+
+    let delay = {
+        let mut state = throttle.state.lock().unwrap();
+        state.1 += bytes as u64;
+        let allowed_at = Duration::from_secs_f64(state.1 as f64 / throttle.bytes_per_second as f64);
+        allowed_at.saturating_sub(state.0.elapsed())
+    };
+
+    if !delay.is_zero() {
+        thread::sleep(delay);
+    }
+}
+
+// a reader whose reads count toward --bwlimit like hashing's; every other bulk
+// read of file contents (byte compares, --backup, decoding, copies) goes
+// through one, so the limit holds for the whole run
+pub struct Throttled<R>(pub R);
+
+impl<R: Read> Read for Throttled<R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let read = self.0.read(buffer)?;
+        throttle(read);
+        Ok(read)
+    }
+}
+
+pub fn open(path: &Path) -> io::Result<Throttled<File>> {
+    File::open(path).map(Throttled)
+}
+
+// fs::read under --bwlimit
+pub fn read(path: &Path) -> io::Result<Vec<u8>> {
+    let mut file = open(path)?;
+    let mut data = Vec::with_capacity(file.0.metadata().map_or(0, |metadata| metadata.len() as usize));
+    file.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+// filesystem tags by device, looked up once per device
+static FILESYSTEMS: OnceLock<Mutex<HashMap<u64, String>>> = OnceLock::new();
+
+// a short hash of the filesystem's identity (uuid and mount point); the
+// stamp's fields are colon-separated, and mount points may contain colons
+fn filesystem_tag(path: &Path, metadata: &fs::Metadata) -> String {
+    #[cfg(unix)]
+    let device = std::os::unix::fs::MetadataExt::dev(metadata);
+    #[cfg(not(unix))]
+    let device = {
+        let _ = metadata;
+        0
+    };
+
+    let filesystems = FILESYSTEMS.get_or_init(|| Mutex::new(HashMap::new()));
+    if let Some(tag) = filesystems.lock().unwrap().get(&device) {
+        return tag.clone();
+    }
+    let mut hasher = DefaultHasher::new();
+    volumes::filesystem_identity(path, device).hash(&mut hasher);
+    let tag = format!("{:016x}", hasher.finish());
+    filesystems.lock().unwrap().insert(device, tag.clone());
+    tag
+}
+
+// the cached hash is only trusted while size and mtime still match what was
+// recorded next to it, on the same filesystem it was recorded on
+fn cache_stamp(path: &Path, metadata: &fs::Metadata) -> io::Result<String> {
+    let modified = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    Ok(format!(
+        "{}:{}:{}.{:09}",
+        filesystem_tag(path, metadata),
+        metadata.len(),
+        modified.as_secs(),
+        modified.subsec_nanos()
+    ))
+}
+
+fn cached_hash(path: &Path, stamp: &str) -> Option<u64> {
+    let value = xattr::get(path, XATTR_NAME).ok()??;
+    let value = String::from_utf8(value).ok()?;
+    let mut parts = value.splitn(3, ':');
+
+    if parts.next()? != XATTR_VERSION {
+        return None;
+    }
+    let hash = u64::from_str_radix(parts.next()?, 16).ok()?;
+
+    (parts.next()? == stamp).then_some(hash)
+}
+
+// hash a file, consulting and refreshing the xattr cache when enabled. the
+// attribute on someone else's file is whatever they wrote there, so only the
+// running user's own files use the cache
+pub fn hash_file(path: &Path) -> io::Result<u64> {
+    if !XATTR_CACHE.load(Ordering::Relaxed) {
+        return hash_contents(path);
+    }
+
+    let metadata = fs::metadata(path)?;
+    if !ownership::owned_by_me(&metadata) {
+        return hash_contents(path);
+    }
+    let stamp = cache_stamp(path, &metadata)?;
+    if let Some(hash) = cached_hash(path, &stamp) {
+        stats::add(&stats::CACHE_HITS, 1);
+        return Ok(hash);
+    }
+    stats::add(&stats::CACHE_MISSES, 1);
+
+    let hash = hash_contents(path)?;
+
+    // only store the hash if the file didn't change while we were reading it;
+    // read-only files and filesystems without xattrs are simply not cached
+    if cache_stamp(path, &fs::metadata(path)?)? == stamp {
+        let value = format!("{}:{:016x}:{}", XATTR_VERSION, hash, stamp);
+        let _ = xattr::set(path, XATTR_NAME, value.as_bytes());
+    }
+
+    Ok(hash)
+}
+
+// hash the full contents of a file (64-bit FNV-1a), never from the cache
+pub fn hash_contents(path: &Path) -> io::Result<u64> {
+    hash_contents_with(path, BUFFER_SIZE.load(Ordering::Relaxed))
+}
+
+pub fn hash_contents_with(path: &Path, buffer_size: usize) -> io::Result<u64> {
+    hash_stream(File::open(path)?, path, buffer_size)
+}
+
+// hash `len` bytes of a file from `offset`, such as a file inside a disk image
+pub fn hash_region(path: &Path, offset: u64, len: u64) -> io::Result<u64> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    hash_stream(file.take(len), path, BUFFER_SIZE.load(Ordering::Relaxed))
+}
+
+fn hash_stream(mut reader: impl Read, path: &Path, buffer_size: usize) -> io::Result<u64> {
+    let mut buffer = vec![0u8; buffer_size];
+    let mut hash = FNV_OFFSET_BASIS;
+    power::wait();
+    let hashing = heartbeat::begin("hashing", path);
+
+    loop {
+        let bytes_read = reader.read(&mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+        hashing.read(bytes_read);
+        throttle(bytes_read);
+        stats::add(&stats::BYTES_HASHED, bytes_read as u64);
+        hash = fnv1a(hash, &buffer[..bytes_read]);
+    }
+    stats::add(&stats::FILES_HASHED, 1);
+
+    Ok(hash)
+}
+
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+// the hash of an in-memory buffer, as hash_file would compute it
+pub fn hash_bytes(bytes: &[u8]) -> u64 {
+    fnv1a(FNV_OFFSET_BASIS, bytes)
+}
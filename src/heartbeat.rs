@@ -0,0 +1,129 @@
+// heartbeats: hashing one huge file or listing a slow network directory can
+// hold a run up for minutes with nothing printed, which looks like a hang.
+// whatever has been going on for longer than --heartbeat (30s by default) is
+// named on stderr every interval until it finishes; with --log-format json
+// each is a record of level "heartbeat"
+use crate::format_bytes;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(30);
+
+// in milliseconds; 0 turns heartbeats off
+static INTERVAL: AtomicU64 = AtomicU64::new(DEFAULT_INTERVAL.as_millis() as u64);
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+static ACTIVE: Mutex<Vec<(u64, Arc<State>)>> = Mutex::new(Vec::new());
+static WATCHER: OnceLock<()> = OnceLock::new();
+static EPOCH: OnceLock<Instant> = OnceLock::new();
+
+struct State {
+    what: &'static str,
+    path: PathBuf,
+    // when the current wait began, in milliseconds since EPOCH plus one; 0
+    // while paused
+    since: AtomicU64,
+    bytes: AtomicU64,
+}
+
+pub fn set_interval(interval: Duration) {
+    INTERVAL.store(interval.as_millis() as u64, Ordering::Relaxed);
+}
+
+fn now() -> u64 {
+    EPOCH.get_or_init(Instant::now).elapsed().as_millis() as u64 + 1
+}
+
+// something under way that may stall; it stops being watched when dropped
+pub struct Activity(Option<(u64, Arc<State>)>);
+
+// start watching `what` ("hashing", "listing") being done to `path`
+pub fn begin(what: &'static str, path: &Path) -> Activity {
+    if INTERVAL.load(Ordering::Relaxed) == 0 {
+        return Activity(None);
+    }
+    WATCHER.get_or_init(|| {
+        thread::spawn(watch);
+    });
+    let state = Arc::new(State {
+        what,
+        path: path.to_path_buf(),
+        since: AtomicU64::new(now()),
+        bytes: AtomicU64::new(0),
+    });
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    ACTIVE.lock().unwrap().push((id, state.clone()));
+    Activity(Some((id, state)))
+}
+
+impl Activity {
+    // count bytes read, for the heartbeat to show
+    pub fn read(&self, bytes: usize) {
+        if let Some((_, state)) = &self.0 {
+            state.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+        }
+    }
+
+    // between waits, e.g. while a directory's entries are being handled
+    pub fn pause(&self) {
+        if let Some((_, state)) = &self.0 {
+            state.since.store(0, Ordering::Relaxed);
+        }
+    }
+
+    pub fn resume(&self) {
+        if let Some((_, state)) = &self.0 {
+            state.since.store(now(), Ordering::Relaxed);
+        }
+    }
+}
+
+impl Drop for Activity {
+    fn drop(&mut self) {
+        if let Some((id, _)) = &self.0 {
+            ACTIVE.lock().unwrap().retain(|(active, _)| active != id);
+        }
+    }
+}
+
+// once a second, name every activity whose current wait has passed another
+// interval
+fn watch() {
+    // (the wait last reported on, heartbeats given for it) per activity
+    let mut reported: HashMap<u64, (u64, u64)> = HashMap::new();
+    loop {
+        thread::sleep(Duration::from_secs(1));
+        let interval = INTERVAL.load(Ordering::Relaxed);
+        let active = ACTIVE.lock().unwrap().clone();
+        reported.retain(|id, _| active.iter().any(|(active, _)| active == id));
+        for (id, state) in active {
+            let since = state.since.load(Ordering::Relaxed);
+            if since == 0 {
+                continue;
+            }
+            let waited = now().saturating_sub(since);
+            let beats = reported.entry(id).or_insert((since, 0));
+            if beats.0 != since {
+                *beats = (since, 0);
+            }
+            if waited < interval * (beats.1 + 1) {
+                continue;
+            }
+            beats.1 += 1;
+            let bytes = match state.bytes.load(Ordering::Relaxed) {
+                0 => String::new(),
+                bytes => format!(" ({} read)", format_bytes(bytes)),
+            };
+            eprintln!(
+                "Heartbeat: still {} '{}' after {}s{}",
+                state.what,
+                state.path.display(),
+                waited / 1000,
+                bytes
+            );
+        }
+    }
+}
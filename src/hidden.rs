@@ -0,0 +1,32 @@
+// hidden files and the metadata files operating systems drop into folders;
+// copies of .DS_Store or Thumbs.db are never interesting duplicates
+use std::fs;
+
+// matched case-insensitively, as windows and macos would
+const SYSTEM_FILES: [&str; 6] = [".DS_Store", ".localized", "Icon\r", "Thumbs.db", "ehthumbs.db", "desktop.ini"];
+
+pub fn is_system_file(name: &str) -> bool {
+    // "._name" files hold resource forks copied onto non-apple filesystems
+    name.starts_with("._") || SYSTEM_FILES.iter().any(|system| system.eq_ignore_ascii_case(name))
+}
+
+#[cfg(windows)]
+fn has_hidden_attribute(entry: &fs::DirEntry) -> bool {
+    use std::os::windows::fs::MetadataExt;
+
+    const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+
+    entry
+        .metadata()
+        .is_ok_and(|metadata| metadata.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0)
+}
+
+#[cfg(not(windows))]
+fn has_hidden_attribute(_entry: &fs::DirEntry) -> bool {
+    false
+}
+
+// dotfiles everywhere, plus anything with the windows hidden attribute
+pub fn is_hidden(entry: &fs::DirEntry) -> bool {
+    entry.file_name().to_string_lossy().starts_with('.') || has_hidden_attribute(entry)
+}
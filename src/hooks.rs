@@ -0,0 +1,149 @@
+// [hooks] in the config file: commands run around a run, each given a JSON
+// summary on stdin and HYDRA_HOOK naming the hook. pre-scan runs before the
+// scan, and pre-delete right before anything is removed, after the prompt and
+// any --backup, e.g. to take a btrfs or zfs snapshot; if either fails the run
+// stops there. post-run runs last, whatever happened, e.g. to notify someone;
+// its failure is only reported
+use crate::config::Config;
+use crate::{DuplicateSet, Options, format_timestamp, json, report};
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+const NAMES: [&str; 3] = ["pre-scan", "pre-delete", "post-run"];
+
+#[derive(Debug, Default)]
+pub struct Hooks {
+    pre_scan: Option<String>,
+    pre_delete: Option<String>,
+    post_run: Option<String>,
+}
+
+impl Hooks {
+    pub fn from_config(config: &Config) -> Result<Hooks, String> {
+        let mut hooks = Hooks::default();
+        for section in config.sections_named("hooks") {
+            for (key, value) in &section.entries {
+                let command = value.as_str().ok_or(format!("[hooks] `{}` needs a command string", key))?;
+                let hook = match key.as_str() {
+                    "pre-scan" => &mut hooks.pre_scan,
+                    "pre-delete" => &mut hooks.pre_delete,
+                    "post-run" => &mut hooks.post_run,
+                    _ => return Err(format!("unknown hook '{}' (expected {})", key, NAMES.join(", "))),
+                };
+                *hook = Some(command.to_string());
+            }
+        }
+        Ok(hooks)
+    }
+}
+
+// what the run found and did, for post-run
+struct Outcome {
+    found: Option<(usize, usize, u64)>,
+    removed: usize,
+    skipped: usize,
+    errors: usize,
+}
+
+static OUTCOME: Mutex<Outcome> = Mutex::new(Outcome {
+    found: None,
+    removed: 0,
+    skipped: 0,
+    errors: 0,
+});
+
+fn totals(duplicate_sets: &[DuplicateSet]) -> (usize, usize, u64) {
+    let files = duplicate_sets.iter().map(|set| set.duplicates.len()).sum();
+    let bytes = duplicate_sets.iter().map(report::reclaimable_bytes).sum();
+    (duplicate_sets.len(), files, bytes)
+}
+
+// the sets the run settled on, once reviewed
+pub fn found(duplicate_sets: &[DuplicateSet]) {
+    OUTCOME.lock().unwrap().found = Some(totals(duplicate_sets));
+}
+
+pub fn acted(removed: usize, skipped: usize, errors: usize) {
+    let mut outcome = OUTCOME.lock().unwrap();
+    outcome.removed += removed;
+    outcome.skipped += skipped;
+    outcome.errors += errors;
+}
+
+fn shell(command: &str) -> Command {
+    let mut shell = if cfg!(windows) { Command::new("cmd") } else { Command::new("sh") };
+    shell.arg(if cfg!(windows) { "/C" } else { "-c" }).arg(command);
+    shell
+}
+
+fn summary(name: &str, directory: &str, options: &Options, extra: &[(&str, String)]) -> String {
+    let mut fields = vec![
+        ("hook", json::string(name)),
+        ("time", json::string(&format_timestamp(SystemTime::now()))),
+        ("directory", json::string(directory)),
+        ("action", json::string(options.action.verb())),
+        ("dry_run", options.dry_run.to_string()),
+    ];
+    fields.extend(extra.iter().map(|(key, value)| (*key, value.clone())));
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|(key, value)| format!("{}: {}", json::string(key), value))
+        .collect();
+    format!("{{{}}}\n", fields.join(", "))
+}
+
+fn run(name: &str, command: &str, summary: &str) -> Result<(), String> {
+    let failed = |e: String| format!("the {} hook `{}` failed: {}", name, command, e);
+    let mut child = shell(command)
+        .env("HYDRA_HOOK", name)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| failed(e.to_string()))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // a hook that doesn't read its input is fine
+        let _ = stdin.write_all(summary.as_bytes());
+    }
+    match child.wait() {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(failed(format!("exited with {}", status))),
+        Err(e) => Err(failed(e.to_string())),
+    }
+}
+
+pub fn pre_scan(directory: &str, options: &Options) -> Result<(), String> {
+    match &options.hooks.pre_scan {
+        Some(command) => run("pre-scan", command, &summary("pre-scan", directory, options, &[])),
+        None => Ok(()),
+    }
+}
+
+pub fn pre_delete(directory: &str, duplicate_sets: &[DuplicateSet], options: &Options) -> Result<(), String> {
+    let Some(command) = &options.hooks.pre_delete else {
+        return Ok(());
+    };
+    let (sets, files, bytes) = totals(duplicate_sets);
+    let extra = [("sets", sets.to_string()), ("files", files.to_string()), ("bytes", bytes.to_string())];
+    run("pre-delete", command, &summary("pre-delete", directory, options, &extra))
+}
+
+pub fn post_run(directory: &str, options: &Options, status: i32) {
+    let Some(command) = &options.hooks.post_run else {
+        return;
+    };
+    let outcome = OUTCOME.lock().unwrap();
+    let (sets, files, bytes) = outcome.found.unwrap_or_default();
+    let extra = [
+        ("sets", sets.to_string()),
+        ("files", files.to_string()),
+        ("bytes", bytes.to_string()),
+        ("removed", outcome.removed.to_string()),
+        ("skipped", outcome.skipped.to_string()),
+        ("errors", outcome.errors.to_string()),
+        ("status", status.to_string()),
+    ];
+    if let Err(e) = run("post-run", command, &summary("post-run", directory, options, &extra)) {
+        eprintln!("Warning: {}", e);
+    }
+}
@@ -0,0 +1,440 @@
+// translations of the messages a normal run shows: the report, the dry-run
+// notes, the confirmation prompt and the outcome. the language comes from
+// --lang, else from LC_ALL, LC_MESSAGES or LANG, gettext-style. messages are
+// looked up by their English text, so anything without a translation (and
+// every "Error:"/"Warning:" prefix) stays in English
+use std::env;
+use std::fmt::Display;
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Language {
+    English,
+    German,
+    Spanish,
+}
+
+impl Language {
+    // "de", "de_DE", "de_DE.UTF-8", "es-MX", ...
+    pub fn parse(value: &str) -> Option<Language> {
+        let code = value.split(['_', '-', '.', '@']).next().unwrap_or_default().to_lowercase();
+        match code.as_str() {
+            "en" | "c" | "posix" => Some(Language::English),
+            "de" => Some(Language::German),
+            "es" => Some(Language::Spanish),
+            _ => None,
+        }
+    }
+
+    fn catalog(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Language::English => &[],
+            Language::German => GERMAN,
+            Language::Spanish => SPANISH,
+        }
+    }
+
+    // answers to a (y/N) prompt that mean yes
+    fn yes(self) -> &'static [&'static str] {
+        match self {
+            Language::English => &["y", "yes"],
+            Language::German => &["y", "yes", "j", "ja"],
+            Language::Spanish => &["y", "yes", "s", "si", "sí"],
+        }
+    }
+}
+
+static LANGUAGE: OnceLock<Language> = OnceLock::new();
+
+// --lang, before anything is printed
+pub fn select(language: Language) {
+    let _ = LANGUAGE.set(language);
+}
+
+fn current() -> Language {
+    *LANGUAGE.get_or_init(|| {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|name| env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .and_then(|value| Language::parse(&value))
+            .unwrap_or(Language::English)
+    })
+}
+
+pub fn translate(message: &'static str) -> &'static str {
+    current()
+        .catalog()
+        .iter()
+        .find(|(english, _)| *english == message)
+        .map_or(message, |(_, translated)| translated)
+}
+
+// `{}` placeholders filled in order, like format!
+pub fn fill(template: &str, args: &[&dyn Display]) -> String {
+    let mut filled = String::with_capacity(template.len());
+    let mut args = args.iter();
+    let mut pieces = template.split("{}");
+    filled.push_str(pieces.next().unwrap_or_default());
+    for piece in pieces {
+        if let Some(arg) = args.next() {
+            filled.push_str(&arg.to_string());
+        }
+        filled.push_str(piece);
+    }
+    filled
+}
+
+pub fn is_yes(answer: &str) -> bool {
+    current().yes().contains(&answer)
+}
+
+// tr!("Files deleted: {}", count): the translated message, filled in
+macro_rules! tr {
+    ($message:literal) => {
+        $crate::i18n::translate($message)
+    };
+    ($message:literal, $($arg:expr),+ $(,)?) => {
+        $crate::i18n::fill($crate::i18n::translate($message), &[$(&$arg as &dyn std::fmt::Display),+])
+    };
+}
+pub(crate) use tr;
+
+const GERMAN: &[(&str, &str)] = &[
+    ("--- Duplicate Set ---", "--- Duplikatgruppe ---"),
+    ("Normalized filename: {}", "Normalisierter Dateiname: {}"),
+    ("Size: {} bytes", "Größe: {} Bytes"),
+    ("Keeping: {}", "Behalten: {}"),
+    ("--- Reclaimable space by folder ---", "--- Freizugebender Platz nach Ordner ---"),
+    ("Affected: {} file(s), {} in total", "Betroffen: {} Datei(en), insgesamt {}"),
+    ("and {} more folder(s): {} file(s), {}", "und {} weitere Ordner: {} Datei(en), {}"),
+    ("{} file(s)", "{} Datei(en)"),
+    ("because {}", "weil {}"),
+    ("the other copies match --delete-matching", "die anderen Kopien auf --delete-matching passen"),
+    ("the other copies are mail attachments", "die anderen Kopien Mail-Anhänge sind"),
+    ("the other copies are damaged", "die anderen Kopien beschädigt sind"),
+    ("it matches --keep-matching", "sie auf --keep-matching passt"),
+    ("it is in a preferred directory ([keeper] prefer)", "sie in einem bevorzugten Ordner liegt ([keeper] prefer)"),
+    ("it has the most embedded metadata", "sie die meisten eingebetteten Metadaten hat"),
+    (
+        "it has the most embedded metadata; the other copies differ only in metadata",
+        "sie die meisten eingebetteten Metadaten hat; die anderen Kopien unterscheiden sich nur darin",
+    ),
+    ("the plugin ranks it first", "das Plugin sie am höchsten einstuft"),
+    ("it is complete; the other copies are truncated", "sie vollständig ist; die anderen Kopien sind abgeschnitten"),
+    ("chosen by hand", "von Hand gewählt"),
+    ("it has the earliest timestamp", "sie den frühesten Zeitstempel hat"),
+    (
+        "it has the plain name; the other copies carry a copy suffix",
+        "sie den einfachen Namen trägt; die anderen Kopien haben einen Kopie-Zusatz",
+    ),
+    ("a timestamp tie, broken by the earliest timestamp", "Zeitstempel-Gleichstand, entschieden nach dem frühesten"),
+    ("a timestamp tie, broken by the shortest path", "Zeitstempel-Gleichstand, entschieden nach dem kürzesten Pfad"),
+    (
+        "a timestamp tie, broken by the first path alphabetically",
+        "Zeitstempel-Gleichstand, entschieden nach dem alphabetisch ersten Pfad",
+    ),
+    ("Would delete", "Würde löschen"),
+    ("Will delete", "Wird gelöscht"),
+    ("Would trash", "Würde in den Papierkorb verschieben"),
+    ("Will trash", "Wird in den Papierkorb verschoben"),
+    ("Would link", "Würde durch harten Link ersetzen"),
+    ("Will link", "Wird durch harten Link ersetzt"),
+    ("Would pass on", "Würde an den Befehl übergeben"),
+    ("Will pass on", "Wird an den Befehl übergeben"),
+    ("{} file(s) to delete, {}", "{} Datei(en) zu löschen, {}"),
+    (" - every file in this directory is a copy", " - jede Datei in diesem Ordner ist eine Kopie"),
+    (" of {} file(s)", " von {} Datei(en)"),
+    ("  (keeping {})", "  (behalten: {})"),
+    ("  (keeping {}, on {})", "  (behalten: {}, auf {})"),
+    ("=== Volume {} ===", "=== Laufwerk {} ==="),
+    ("{} file(s) to delete, {} reclaimable", "{} Datei(en) zu löschen, {} freizugeben"),
+    ("--- Volatile Files ---", "--- Während des Scans geänderte Dateien ---"),
+    ("--- Case Collisions ---", "--- Namen, die sich nur in Groß-/Kleinschreibung unterscheiden ---"),
+    (
+        "These names differ only in case on a case-insensitive filesystem, so a path",
+        "Diese Namen unterscheiden sich nur in Groß-/Kleinschreibung, die das Dateisystem",
+    ),
+    (
+        "may open the wrong file. They were left untouched; rename one of each group.",
+        "nicht unterscheidet. Sie wurden nicht angetastet; benennen Sie je Gruppe einen um.",
+    ),
+    ("No duplicates found!", "Keine Duplikate gefunden!"),
+    ("Summary: Found {} duplicate set(s)", "Zusammenfassung: {} Duplikatgruppe(n) gefunden"),
+    ("Total files to delete: {}", "Zu löschende Dateien insgesamt: {}"),
+    (
+        "Space freed: {}, when this run's items are emptied from the trash at the end",
+        "Freigegebener Platz: {}, wenn die Einträge dieses Laufs am Ende aus dem Papierkorb entfernt werden",
+    ),
+    (
+        "Space freed now: none; {} once the trash is emptied",
+        "Jetzt freigegebener Platz: keiner; {} sobald der Papierkorb geleert wird",
+    ),
+    (
+        "Emptied {} file(s) moved to the trash by this run, freeing {}",
+        "{} von diesem Lauf in den Papierkorb verschobene Datei(en) endgültig entfernt, {} freigegeben",
+    ),
+    ("Total files to trash: {}", "In den Papierkorb zu verschiebende Dateien insgesamt: {}"),
+    ("Total files to link: {}", "Durch harte Links zu ersetzende Dateien insgesamt: {}"),
+    ("Total files to pass to the command: {}", "An den Befehl zu übergebende Dateien insgesamt: {}"),
+    ("  on {}: {} file(s), {}", "  auf {}: {} Datei(en), {}"),
+    (
+        "Reclaimable: {} ({} more is already shared by hard links or reflinks)",
+        "Freizugeben: {} (weitere {} sind bereits über harte Links oder Reflinks geteilt)",
+    ),
+    ("Volatile files: {}", "Während des Scans geänderte Dateien: {}"),
+    (
+        "--- Oversized Sets (skipped, review by hand) ---",
+        "--- Übergroße Gruppen (übersprungen, bitte selbst prüfen) ---",
+    ),
+    (
+        "Sets this large usually come from generated files or a normalization rule that",
+        "So große Gruppen entstehen meist durch generierte Dateien oder eine Normalisierungsregel,",
+    ),
+    (
+        "matches too much. Raise --max-set-size (or pass 0) to act on them anyway.",
+        "die zu viel erfasst. --max-set-size erhöhen (oder 0 angeben), um sie trotzdem zu bearbeiten.",
+    ),
+    ("{}: {} files of {} bytes", "{}: {} Dateien zu je {} Bytes"),
+    ("  e.g. {}", "  z. B. {}"),
+    ("{} file(s), {} bytes reclaimable", "{} Datei(en), {} Bytes freizugeben"),
+    (
+        "Files skipped (transient errors, retried {} times): {}",
+        "Übersprungene Dateien (vorübergehende Fehler, {}-mal wiederholt): {}",
+    ),
+    (
+        "The run was cut short by {}, so nothing was changed.",
+        "Der Lauf wurde durch {} vorzeitig beendet, daher wurde nichts geändert.",
+    ),
+    ("Case collisions (left untouched): {}", "Namenskonflikte (nicht angetastet): {}"),
+    ("Running in DRY RUN mode - no files will be deleted", "Testlauf (DRY RUN) - es werden keine Dateien gelöscht"),
+    ("[DRY RUN MODE] No files were deleted.", "[TESTLAUF] Es wurden keine Dateien gelöscht."),
+    (
+        "Run without --dry-run to actually delete files.",
+        "Ohne --dry-run ausführen, um die Dateien wirklich zu löschen.",
+    ),
+    (
+        "Run without --dry-run to actually trash files.",
+        "Ohne --dry-run ausführen, um die Dateien wirklich in den Papierkorb zu verschieben.",
+    ),
+    (
+        "Run without --dry-run to actually link files.",
+        "Ohne --dry-run ausführen, um die Dateien wirklich durch harte Links zu ersetzen.",
+    ),
+    (
+        "Run without --dry-run to actually run the command.",
+        "Ohne --dry-run ausführen, um den Befehl wirklich auszuführen.",
+    ),
+    (
+        "stdin is not a terminal, so this run only reports. Pass --yes to act without confirmation.",
+        "stdin ist kein Terminal, daher wird nur berichtet. Mit --yes wird ohne Rückfrage gehandelt.",
+    ),
+    ("Proceed with deletion? (y/N): ", "Mit dem Löschen fortfahren? (j/N): "),
+    ("Proceed with moving files to the trash? (y/N): ", "Dateien in den Papierkorb verschieben? (j/N): "),
+    ("Proceed with replacing copies with hard links? (y/N): ", "Kopien durch harte Links ersetzen? (j/N): "),
+    (
+        "Proceed with running the command on each copy? (y/N): ",
+        "Den Befehl für jede Kopie ausführen? (j/N): ",
+    ),
+    ("Deletion cancelled.", "Löschen abgebrochen."),
+    ("Deleting files...", "Dateien werden gelöscht..."),
+    ("Moving files to the trash...", "Dateien werden in den Papierkorb verschoben..."),
+    ("Replacing copies with hard links...", "Kopien werden durch harte Links ersetzt..."),
+    ("Running the command on each copy...", "Der Befehl wird für jede Kopie ausgeführt..."),
+    ("Deletion complete!", "Löschen abgeschlossen!"),
+    ("Files deleted: {}", "Gelöschte Dateien: {}"),
+    ("Done!", "Fertig!"),
+    ("Files moved to the trash: {}", "In den Papierkorb verschobene Dateien: {}"),
+    ("Files replaced with hard links: {}", "Durch harte Links ersetzte Dateien: {}"),
+    ("Files passed to the command and gone: {}", "An den Befehl übergebene und entfernte Dateien: {}"),
+    (
+        "Files passed to the command and still in place: {}",
+        "An den Befehl übergebene, noch vorhandene Dateien: {}",
+    ),
+    ("Files skipped (modified since scan): {}", "Übersprungene Dateien (seit dem Scan geändert): {}"),
+    (
+        "Files left in place (their set was rolled back): {}",
+        "An Ort und Stelle belassene Dateien (ihr Satz wurde zurückgesetzt): {}",
+    ),
+    (
+        "Files not linked (on another filesystem than their keeper): {}",
+        "Nicht verknüpfte Dateien (auf einem anderen Dateisystem als die behaltene Datei): {}",
+    ),
+    ("Errors encountered: {}", "Aufgetretene Fehler: {}"),
+    (
+        "Files skipped (in use by another program): {}",
+        "Übersprungene Dateien (von einem anderen Programm verwendet): {}",
+    ),
+    (
+        "Sets aborted (keeper failed verification): {}",
+        "Abgebrochene Gruppen (behaltene Datei nicht bestätigt): {}",
+    ),
+];
+
+const SPANISH: &[(&str, &str)] = &[
+    ("--- Duplicate Set ---", "--- Conjunto de duplicados ---"),
+    ("Normalized filename: {}", "Nombre normalizado: {}"),
+    ("Size: {} bytes", "Tamaño: {} bytes"),
+    ("Keeping: {}", "Se conserva: {}"),
+    ("--- Reclaimable space by folder ---", "--- Espacio recuperable por carpeta ---"),
+    ("Affected: {} file(s), {} in total", "Afectados: {} archivo(s), {} en total"),
+    ("and {} more folder(s): {} file(s), {}", "y {} carpeta(s) más: {} archivo(s), {}"),
+    ("{} file(s)", "{} archivo(s)"),
+    ("because {}", "porque {}"),
+    ("the other copies match --delete-matching", "las otras copias coinciden con --delete-matching"),
+    ("the other copies are mail attachments", "las otras copias son adjuntos de correo"),
+    ("the other copies are damaged", "las otras copias están dañadas"),
+    ("it matches --keep-matching", "coincide con --keep-matching"),
+    ("it is in a preferred directory ([keeper] prefer)", "está en una carpeta preferida ([keeper] prefer)"),
+    ("it has the most embedded metadata", "tiene más metadatos incrustados"),
+    (
+        "it has the most embedded metadata; the other copies differ only in metadata",
+        "tiene más metadatos incrustados; las otras copias solo difieren en ellos",
+    ),
+    ("the plugin ranks it first", "el plugin la clasifica primero"),
+    ("it is complete; the other copies are truncated", "está completo; las otras copias están truncadas"),
+    ("chosen by hand", "elegido a mano"),
+    ("it has the earliest timestamp", "tiene la marca de tiempo más antigua"),
+    (
+        "it has the plain name; the other copies carry a copy suffix",
+        "tiene el nombre sin sufijo; las otras copias llevan un sufijo de copia",
+    ),
+    ("a timestamp tie, broken by the earliest timestamp", "empate de marcas de tiempo, resuelto por la más antigua"),
+    ("a timestamp tie, broken by the shortest path", "empate de marcas de tiempo, resuelto por la ruta más corta"),
+    (
+        "a timestamp tie, broken by the first path alphabetically",
+        "empate de marcas de tiempo, resuelto por la primera ruta alfabéticamente",
+    ),
+    ("Would delete", "Se eliminaría"),
+    ("Will delete", "Se eliminará"),
+    ("Would trash", "Se movería a la papelera"),
+    ("Will trash", "Se moverá a la papelera"),
+    ("Would link", "Se reemplazaría por un enlace duro"),
+    ("Will link", "Se reemplazará por un enlace duro"),
+    ("Would pass on", "Se pasaría al comando"),
+    ("Will pass on", "Se pasará al comando"),
+    ("{} file(s) to delete, {}", "{} archivo(s) para eliminar, {}"),
+    (" - every file in this directory is a copy", " - todos los archivos de esta carpeta son copias"),
+    (" of {} file(s)", " de {} archivo(s)"),
+    ("  (keeping {})", "  (se conserva {})"),
+    ("  (keeping {}, on {})", "  (se conserva {}, en {})"),
+    ("=== Volume {} ===", "=== Volumen {} ==="),
+    ("{} file(s) to delete, {} reclaimable", "{} archivo(s) para eliminar, {} recuperables"),
+    ("--- Volatile Files ---", "--- Archivos modificados durante el análisis ---"),
+    ("--- Case Collisions ---", "--- Nombres que solo difieren en mayúsculas ---"),
+    (
+        "These names differ only in case on a case-insensitive filesystem, so a path",
+        "Estos nombres solo difieren en mayúsculas y el sistema de archivos no las distingue,",
+    ),
+    (
+        "may open the wrong file. They were left untouched; rename one of each group.",
+        "así que una ruta puede abrir otro archivo. No se tocaron; renombre uno de cada grupo.",
+    ),
+    ("No duplicates found!", "¡No se encontraron duplicados!"),
+    ("Summary: Found {} duplicate set(s)", "Resumen: se encontraron {} conjunto(s) de duplicados"),
+    ("Total files to delete: {}", "Total de archivos para eliminar: {}"),
+    (
+        "Space freed: {}, when this run's items are emptied from the trash at the end",
+        "Espacio liberado: {}, cuando los elementos de esta ejecución se vacíen de la papelera al final",
+    ),
+    (
+        "Space freed now: none; {} once the trash is emptied",
+        "Espacio liberado ahora: ninguno; {} cuando se vacíe la papelera",
+    ),
+    (
+        "Emptied {} file(s) moved to the trash by this run, freeing {}",
+        "Se eliminaron definitivamente {} archivo(s) que esta ejecución movió a la papelera, liberando {}",
+    ),
+    ("Total files to trash: {}", "Total de archivos para mover a la papelera: {}"),
+    ("Total files to link: {}", "Total de archivos para reemplazar por enlaces duros: {}"),
+    ("Total files to pass to the command: {}", "Total de archivos para pasar al comando: {}"),
+    ("  on {}: {} file(s), {}", "  en {}: {} archivo(s), {}"),
+    (
+        "Reclaimable: {} ({} more is already shared by hard links or reflinks)",
+        "Recuperable: {} (otros {} ya se comparten mediante enlaces duros o reflinks)",
+    ),
+    ("Volatile files: {}", "Archivos modificados durante el análisis: {}"),
+    (
+        "--- Oversized Sets (skipped, review by hand) ---",
+        "--- Conjuntos demasiado grandes (omitidos, revíselos a mano) ---",
+    ),
+    (
+        "Sets this large usually come from generated files or a normalization rule that",
+        "Conjuntos tan grandes suelen deberse a archivos generados o a una regla de normalización",
+    ),
+    (
+        "matches too much. Raise --max-set-size (or pass 0) to act on them anyway.",
+        "demasiado amplia. Aumente --max-set-size (o pase 0) para procesarlos igualmente.",
+    ),
+    ("{}: {} files of {} bytes", "{}: {} archivos de {} bytes"),
+    ("  e.g. {}", "  p. ej. {}"),
+    ("{} file(s), {} bytes reclaimable", "{} archivo(s), {} bytes recuperables"),
+    (
+        "Files skipped (transient errors, retried {} times): {}",
+        "Archivos omitidos (errores transitorios, reintentados {} veces): {}",
+    ),
+    (
+        "The run was cut short by {}, so nothing was changed.",
+        "La ejecución se interrumpió por {}, así que no se cambió nada.",
+    ),
+    ("Case collisions (left untouched): {}", "Nombres en conflicto (sin tocar): {}"),
+    (
+        "Running in DRY RUN mode - no files will be deleted",
+        "Modo de prueba (DRY RUN): no se eliminará ningún archivo",
+    ),
+    ("[DRY RUN MODE] No files were deleted.", "[MODO DE PRUEBA] No se eliminó ningún archivo."),
+    (
+        "Run without --dry-run to actually delete files.",
+        "Ejecute sin --dry-run para eliminar los archivos de verdad.",
+    ),
+    (
+        "Run without --dry-run to actually trash files.",
+        "Ejecute sin --dry-run para mover los archivos a la papelera de verdad.",
+    ),
+    (
+        "Run without --dry-run to actually link files.",
+        "Ejecute sin --dry-run para reemplazar los archivos por enlaces duros de verdad.",
+    ),
+    (
+        "Run without --dry-run to actually run the command.",
+        "Ejecute sin --dry-run para ejecutar el comando de verdad.",
+    ),
+    (
+        "stdin is not a terminal, so this run only reports. Pass --yes to act without confirmation.",
+        "stdin no es una terminal, así que esta ejecución solo informa. Use --yes para actuar sin confirmar.",
+    ),
+    ("Proceed with deletion? (y/N): ", "¿Continuar con la eliminación? (s/N): "),
+    ("Proceed with moving files to the trash? (y/N): ", "¿Mover los archivos a la papelera? (s/N): "),
+    ("Proceed with replacing copies with hard links? (y/N): ", "¿Reemplazar las copias por enlaces duros? (s/N): "),
+    ("Proceed with running the command on each copy? (y/N): ", "¿Ejecutar el comando para cada copia? (s/N): "),
+    ("Deletion cancelled.", "Eliminación cancelada."),
+    ("Deleting files...", "Eliminando archivos..."),
+    ("Moving files to the trash...", "Moviendo archivos a la papelera..."),
+    ("Replacing copies with hard links...", "Reemplazando las copias por enlaces duros..."),
+    ("Running the command on each copy...", "Ejecutando el comando para cada copia..."),
+    ("Deletion complete!", "¡Eliminación completada!"),
+    ("Files deleted: {}", "Archivos eliminados: {}"),
+    ("Done!", "¡Listo!"),
+    ("Files moved to the trash: {}", "Archivos movidos a la papelera: {}"),
+    ("Files replaced with hard links: {}", "Archivos reemplazados por enlaces duros: {}"),
+    ("Files passed to the command and gone: {}", "Archivos pasados al comando y eliminados: {}"),
+    (
+        "Files passed to the command and still in place: {}",
+        "Archivos pasados al comando que siguen en su sitio: {}",
+    ),
+    ("Files skipped (modified since scan): {}", "Archivos omitidos (modificados desde el análisis): {}"),
+    (
+        "Files left in place (their set was rolled back): {}",
+        "Archivos dejados en su sitio (su conjunto se revirtió): {}",
+    ),
+    (
+        "Files not linked (on another filesystem than their keeper): {}",
+        "Archivos no enlazados (en otro sistema de archivos que el archivo conservado): {}",
+    ),
+    ("Errors encountered: {}", "Errores encontrados: {}"),
+    ("Files skipped (in use by another program): {}", "Archivos omitidos (en uso por otro programa): {}"),
+    (
+        "Sets aborted (keeper failed verification): {}",
+        "Conjuntos cancelados (no se pudo verificar el archivo conservado): {}",
+    ),
+];
@@ -0,0 +1,221 @@
+// a plain DEFLATE decoder (RFC 1951) for reading PNG image data. it decodes
+// huffman codes a bit at a time, after Mark Adler's puff.c: slow next to zlib,
+// but small, and only ever run on the few files that need it
+const MAX_BITS: usize = 15;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145,
+    8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13,
+];
+// the order code length code lengths are stored in
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+struct Bits<'a> {
+    data: &'a [u8],
+    position: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl Bits<'_> {
+    fn bits(&mut self, n: u32) -> Result<u32, String> {
+        while self.count < n {
+            let byte = *self.data.get(self.position).ok_or("compressed data is truncated")?;
+            self.position += 1;
+            self.buffer |= (byte as u32) << self.count;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1 << n) - 1);
+        self.buffer >>= n;
+        self.count -= n;
+        Ok(value)
+    }
+}
+
+// a canonical huffman code: how many codes have each length, and the
+// symbols ordered by code
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for length in 1..=MAX_BITS {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0; offsets[MAX_BITS + 1] as usize];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+
+        Huffman { counts, symbols }
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Result<u16, String> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for length in 1..=MAX_BITS {
+            code |= bits.bits(1)? as i32;
+            let count = self.counts[length] as i32;
+            if code - count < first {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("invalid huffman code".to_string())
+    }
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+fn dynamic_codes(bits: &mut Bits) -> Result<(Huffman, Huffman), String> {
+    let literals = bits.bits(5)? as usize + 257;
+    let distances = bits.bits(5)? as usize + 1;
+    let code_lengths = bits.bits(4)? as usize + 4;
+
+    let mut lengths = [0u8; 19];
+    for &index in &CODE_LENGTH_ORDER[..code_lengths] {
+        lengths[index] = bits.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&lengths);
+
+    let mut lengths = vec![0u8; literals + distances];
+    let mut index = 0;
+    while index < lengths.len() {
+        let symbol = code_length_code.decode(bits)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths[..index].last().ok_or("length repeat with no previous length")?;
+                (previous, 3 + bits.bits(2)? as usize)
+            }
+            17 => (0, 3 + bits.bits(3)? as usize),
+            _ => (0, 11 + bits.bits(7)? as usize),
+        };
+        let end = index + repeat;
+        lengths.get_mut(index..end).ok_or("too many code lengths")?.fill(value);
+        index = end;
+    }
+
+    Ok((Huffman::new(&lengths[..literals]), Huffman::new(&lengths[literals..])))
+}
+
+// what comes out may not exceed `limit` bytes: a few bytes of crafted input
+// can otherwise ask for gigabytes
+fn too_long(limit: usize) -> String {
+    format!("decompresses to more than the expected {} bytes", limit)
+}
+
+fn inflate_block(
+    bits: &mut Bits,
+    out: &mut Vec<u8>,
+    literal: &Huffman,
+    distance: &Huffman,
+    limit: usize,
+) -> Result<(), String> {
+    loop {
+        let symbol = literal.decode(bits)? as usize;
+        if out.len() >= limit && symbol != 256 {
+            return Err(too_long(limit));
+        }
+        if symbol < 256 {
+            out.push(symbol as u8);
+            continue;
+        }
+        if symbol == 256 {
+            return Ok(());
+        }
+
+        let index = symbol - 257;
+        let length = *LENGTH_BASE.get(index).ok_or("invalid length code")? as usize
+            + bits.bits(LENGTH_EXTRA[index] as u32)? as usize;
+        let index = distance.decode(bits)? as usize;
+        let back = *DISTANCE_BASE.get(index).ok_or("invalid distance code")? as usize
+            + bits.bits(DISTANCE_EXTRA[index] as u32)? as usize;
+        if back > out.len() {
+            return Err("distance reaches before the start of the data".to_string());
+        }
+        if length > limit - out.len() {
+            return Err(too_long(limit));
+        }
+        // byte by byte: a copy may overlap what it is producing
+        let start = out.len() - back;
+        for offset in 0..length {
+            out.push(out[start + offset]);
+        }
+    }
+}
+
+// decompress a zlib stream (the 2-byte header, deflate data, a checksum) of
+// at most `limit` bytes
+pub fn zlib_decompress(data: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    inflate(data.get(2..).ok_or("compressed data is truncated")?, limit)
+}
+
+// decompress raw deflate data, as zip entries hold it, of at most `limit` bytes
+pub fn inflate(data: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    let mut bits = Bits {
+        data,
+        position: 0,
+        buffer: 0,
+        count: 0,
+    };
+    let mut out = Vec::new();
+
+    loop {
+        let last = bits.bits(1)? == 1;
+        match bits.bits(2)? {
+            0 => {
+                // stored: byte-aligned, with its length and its complement
+                bits.buffer = 0;
+                bits.count = 0;
+                let header = bits.data.get(bits.position..bits.position + 4).ok_or("stored block is truncated")?;
+                let length = u16::from_le_bytes([header[0], header[1]]) as usize;
+                let start = bits.position + 4;
+                if length > limit - out.len() {
+                    return Err(too_long(limit));
+                }
+                out.extend_from_slice(bits.data.get(start..start + length).ok_or("stored block is truncated")?);
+                bits.position = start + length;
+            }
+            1 => {
+                let (literal, distance) = fixed_codes();
+                inflate_block(&mut bits, &mut out, &literal, &distance, limit)?;
+            }
+            2 => {
+                let (literal, distance) = dynamic_codes(&mut bits)?;
+                inflate_block(&mut bits, &mut out, &literal, &distance, limit)?;
+            }
+            _ => return Err("invalid block type".to_string()),
+        }
+        if last {
+            return Ok(out);
+        }
+    }
+}
@@ -0,0 +1,266 @@
+// `hydra init`: a first-run wizard for people who'd rather not learn the
+// flags. it asks which folders to look after, what to do with copies, which
+// presets to apply and how often to run, and writes the answers to the config
+// file's [defaults] section, keeping any other section. a schedule runs
+// `hydra --yes --scheduled` from cron, or from the task scheduler on windows
+use crate::actions::Action;
+use crate::{config, presets, terminal};
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+// marks the crontab line `hydra init` manages, so running it again replaces it
+const CRON_MARKER: &str = "# added by hydra init";
+const TASK_NAME: &str = "hydra";
+
+#[derive(Debug, Clone, Copy)]
+enum Schedule {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+impl Schedule {
+    // at 03:00, on sundays, on the first of the month
+    fn cron_time(self) -> &'static str {
+        match self {
+            Schedule::Daily => "0 3 * * *",
+            Schedule::Weekly => "0 3 * * 0",
+            Schedule::Monthly => "0 3 1 * *",
+        }
+    }
+
+    fn task_schedule(self) -> &'static str {
+        match self {
+            Schedule::Daily => "DAILY",
+            Schedule::Weekly => "WEEKLY",
+            Schedule::Monthly => "MONTHLY",
+        }
+    }
+}
+
+fn ask(question: &str) -> Result<String, String> {
+    print!("{}", question);
+    io::stdout().flush().map_err(|e| e.to_string())?;
+    let mut answer = String::new();
+    if io::stdin().read_line(&mut answer).map_err(|e| e.to_string())? == 0 {
+        return Err("setup cancelled; nothing was written".to_string());
+    }
+    Ok(answer.trim().to_string())
+}
+
+fn ask_yes(question: &str, default: bool) -> Result<bool, String> {
+    let hint = if default { "(Y/n)" } else { "(y/N)" };
+    loop {
+        match ask(&format!("{} {}: ", question, hint))?.to_lowercase().as_str() {
+            "" => return Ok(default),
+            "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            _ => println!("Please answer y or n."),
+        }
+    }
+}
+
+// a numbered menu; the index of the choice made
+fn choose(question: &str, choices: &[&str], default: usize) -> Result<usize, String> {
+    println!("\n{}", question);
+    for (index, choice) in choices.iter().enumerate() {
+        println!("  {}) {}", index + 1, choice);
+    }
+    loop {
+        let answer = ask(&format!("Choice [{}]: ", default + 1))?;
+        if answer.is_empty() {
+            return Ok(default);
+        }
+        match answer.parse::<usize>() {
+            Ok(number) if (1..=choices.len()).contains(&number) => return Ok(number - 1),
+            _ => println!("Please enter a number from 1 to {}.", choices.len()),
+        }
+    }
+}
+
+fn home() -> Option<PathBuf> {
+    env::var_os(if cfg!(windows) { "USERPROFILE" } else { "HOME" }).map(PathBuf::from)
+}
+
+fn expand_home(answer: &str) -> PathBuf {
+    match (answer, home()) {
+        ("~", Some(home)) => home,
+        (answer, Some(home)) if answer.starts_with("~/") => home.join(&answer[2..]),
+        (answer, _) => PathBuf::from(answer),
+    }
+}
+
+fn ask_folders() -> Result<Vec<PathBuf>, String> {
+    // where copies usually pile up
+    let suggested: Vec<PathBuf> = home()
+        .map(|home| ["Downloads", "Desktop", "Pictures", "Music", "Documents"].map(|name| home.join(name)))
+        .into_iter()
+        .flatten()
+        .filter(|path| path.is_dir())
+        .collect();
+
+    println!("\nWhich folders should hydra look after?");
+    if suggested.is_empty() {
+        println!("Enter them one per line.");
+    } else {
+        println!("Press Enter to use these, or enter your own one per line:");
+        for path in &suggested {
+            println!("  {}", path.display());
+        }
+    }
+
+    let mut folders = Vec::new();
+    loop {
+        let answer = ask(if folders.is_empty() { "Folder: " } else { "Another folder (Enter when done): " })?;
+        if answer.is_empty() {
+            match (folders.is_empty(), suggested.is_empty()) {
+                (false, _) => return Ok(folders),
+                (true, false) => return Ok(suggested),
+                (true, true) => println!("Please enter at least one folder."),
+            }
+            continue;
+        }
+        match fs::canonicalize(expand_home(&answer)) {
+            Ok(path) if path.is_dir() => folders.push(path),
+            Ok(_) => println!("'{}' is not a folder.", answer),
+            Err(e) => println!("Could not read '{}': {}", answer, e),
+        }
+    }
+}
+
+fn defaults_section(folders: &[PathBuf], recursive: bool, action: Action, presets: &[&str]) -> String {
+    let folders: Vec<String> = folders.iter().map(|path| config::quote(&path.to_string_lossy())).collect();
+    let presets: Vec<String> = presets.iter().map(|name| config::quote(name)).collect();
+    format!(
+        "[defaults]\nfolders = [{}]\nrecursive = {}\naction = {}\npresets = [{}]\n",
+        folders.join(", "),
+        recursive,
+        config::quote(action.verb()),
+        presets.join(", ")
+    )
+}
+
+fn run_with_input(command: &mut Command, input: &str) -> io::Result<()> {
+    let mut child = command.stdin(Stdio::piped()).spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input.as_bytes())?;
+    }
+    match child.wait()? {
+        status if status.success() => Ok(()),
+        status => Err(io::Error::other(format!("exited with {}", status))),
+    }
+}
+
+// replace the line hydra manages in the user's crontab
+fn install_cron(line: &str) -> io::Result<()> {
+    // `crontab -l` fails when there is no crontab yet
+    let current = Command::new("crontab").arg("-l").stderr(Stdio::null()).output()?;
+    let current = if current.status.success() {
+        String::from_utf8_lossy(&current.stdout).to_string()
+    } else {
+        String::new()
+    };
+
+    let mut lines: Vec<&str> = current.lines().filter(|existing| !existing.ends_with(CRON_MARKER)).collect();
+    lines.push(line);
+    run_with_input(Command::new("crontab").arg("-"), &format!("{}\n", lines.join("\n")))
+}
+
+fn install_task(schedule: Schedule, command: &str) -> io::Result<()> {
+    let status = Command::new("schtasks")
+        .args(["/Create", "/F", "/SC", schedule.task_schedule(), "/ST", "03:00", "/TN", TASK_NAME, "/TR", command])
+        .status()?;
+    if !status.success() {
+        return Err(io::Error::other(format!("schtasks exited with {}", status)));
+    }
+    Ok(())
+}
+
+fn set_up_schedule(schedule: Schedule, action: Action) -> Result<(), String> {
+    let exe = env::current_exe().map_err(|e| format!("could not find the hydra executable: {}", e))?;
+    if action == Action::Delete {
+        println!("Note: scheduled runs don't ask first, and deleted files can't be recovered.");
+    }
+
+    if cfg!(windows) {
+        let command = format!("\"{}\" --yes --scheduled", exe.display());
+        if !ask_yes(&format!("Add a {} task running `{}`?", schedule.task_schedule().to_lowercase(), command), true)? {
+            return Ok(());
+        }
+        install_task(schedule, &command).map_err(|e| format!("could not add the scheduled task: {}", e))?;
+        println!("Added the scheduled task '{}'.", TASK_NAME);
+        return Ok(());
+    }
+
+    let log = config::state_dir().map(|dir| dir.join("scheduled.log"));
+    if let Some(dir) = log.as_ref().and_then(|log| log.parent()) {
+        let _ = fs::create_dir_all(dir);
+    }
+    let output = log.map_or(">/dev/null 2>&1".to_string(), |log| format!(">> '{}' 2>&1", log.display()));
+    let line = format!("{} '{}' --yes --scheduled {} {}", schedule.cron_time(), exe.display(), output, CRON_MARKER);
+    println!("\n{}", line);
+    if !ask_yes("Add this line to your crontab?", true)? {
+        println!("Add it yourself with `crontab -e` to run hydra on schedule.");
+        return Ok(());
+    }
+    install_cron(&line).map_err(|e| format!("could not update the crontab: {}", e))?;
+    println!("Added it to your crontab.");
+    Ok(())
+}
+
+// `hydra init [--config PATH]`
+pub fn run(args: &[String]) -> Result<(), String> {
+    let path = match args {
+        [] => config::default_path().ok_or("could not find where the config file belongs; pass --config PATH")?,
+        [flag, path] if flag == "--config" => PathBuf::from(path),
+        _ => return Err("usage: hydra init [--config PATH]".to_string()),
+    };
+    if !terminal::can_prompt() {
+        return Err("hydra init asks questions and needs a terminal on stdin".to_string());
+    }
+
+    println!("This sets up hydra to look after your folders. Press Enter to take the suggestion.");
+    if path.is_file() {
+        println!("Your settings go to {}; its other sections are kept.", path.display());
+    }
+
+    let folders = ask_folders()?;
+    let recursive = ask_yes("\nLook inside their subfolders too?", true)?;
+
+    let actions = [
+        "Move them to the trash (you can still get them back)",
+        "Delete them for good",
+        "Replace them with hard links (every name keeps working; needs one disk)",
+    ];
+    let action = [Action::Trash, Action::Delete, Action::Link][choose("What should happen to copies?", &actions, 0)?];
+
+    let mut chosen_presets = Vec::new();
+    for (name, directories) in presets::all() {
+        let examples = directories[..directories.len().min(3)].join(", ");
+        let question = format!("\nSkip '{}' folders ({}, ...)?", name, examples);
+        if ask_yes(&question, true)? {
+            chosen_presets.push(name);
+        }
+    }
+
+    let schedules = ["No, I'll run hydra myself", "Every day", "Every week", "Every month"];
+    let schedule = [None, Some(Schedule::Daily), Some(Schedule::Weekly), Some(Schedule::Monthly)]
+        [choose("Should hydra run on its own?", &schedules, 0)?];
+
+    let section = defaults_section(&folders, recursive, action, &chosen_presets);
+    println!("\n{}", section);
+    if !ask_yes(&format!("Save this to {}?", path.display()), true)? {
+        println!("Nothing was written.");
+        return Ok(());
+    }
+    config::save_section(&path, "defaults", &section)?;
+    println!("Saved. Run `hydra --dry-run` to see what it would do, then `hydra` to clean up.");
+
+    if let Some(schedule) = schedule {
+        set_up_schedule(schedule, action)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,209 @@
+// --check-integrity: a copy can be damaged by a bad sector or an interrupted
+// write and keep its name and size. jpegs, pngs, zip archives (with the
+// office documents, jars and epubs built on them) and pdfs are checked for
+// their structure and checksums, and a damaged copy is never kept over a
+// sound one, however old it is. copies matched by content are identical, so
+// this matters with --match name+size, size or name
+use crate::{hash, inflate};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+// larger files are taken as sound rather than read whole
+const MAX_CHECKED: u64 = 512 * 1024 * 1024;
+// where a pdf's trailer is looked for
+const PDF_TAIL: u64 = 1024;
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut crc = n as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { 0xedb8_8320 ^ (crc >> 1) } else { crc >> 1 };
+            bit += 1;
+        }
+        table[n] = crc;
+        n += 1;
+    }
+    table
+}
+
+// the crc-32 zip and png use
+fn crc32(data: &[u8]) -> u32 {
+    !data
+        .iter()
+        .fold(!0, |crc, byte| CRC_TABLE[((crc ^ *byte as u32) & 0xff) as usize] ^ (crc >> 8))
+}
+
+fn u16_le(data: &[u8], offset: usize) -> Option<usize> {
+    Some(u16::from_le_bytes(data.get(offset..offset + 2)?.try_into().ok()?) as usize)
+}
+
+fn u32_le(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+fn check_jpeg(data: &[u8]) -> Result<(), String> {
+    let mut offset = 2;
+    loop {
+        if data.get(offset) != Some(&0xff) {
+            return Err("its headers are cut short or garbled".to_string());
+        }
+        match data.get(offset + 1) {
+            // fill bytes before a marker
+            Some(0xff) => offset += 1,
+            // start of scan: the image data follows
+            Some(0xda) => break,
+            Some(_) => {
+                let length = data
+                    .get(offset + 2..offset + 4)
+                    .ok_or("its headers are cut short or garbled")?;
+                offset += 2 + u16::from_be_bytes([length[0], length[1]]) as usize;
+            }
+            None => return Err("its headers are cut short or garbled".to_string()),
+        }
+    }
+    // some writers pad the file after its end marker
+    let end = data.iter().rposition(|byte| *byte != 0).map_or(0, |last| last + 1);
+    if !data[..end].ends_with(b"\xff\xd9") {
+        return Err("its image data is cut short".to_string());
+    }
+    Ok(())
+}
+
+fn check_png(data: &[u8]) -> Result<(), String> {
+    let mut offset = 8;
+    while offset < data.len() {
+        let cut_short = || "it is cut short".to_string();
+        let length = u32::from_be_bytes(data.get(offset..offset + 4).ok_or_else(cut_short)?.try_into().unwrap());
+        let end = offset + 8 + length as usize;
+        let chunk = data.get(offset + 4..end).ok_or_else(cut_short)?;
+        let crc = u32::from_be_bytes(data.get(end..end + 4).ok_or_else(cut_short)?.try_into().unwrap());
+        if crc32(chunk) != crc {
+            return Err(format!("its {} chunk fails its checksum", String::from_utf8_lossy(&chunk[..4])));
+        }
+        if &chunk[..4] == b"IEND" {
+            return Ok(());
+        }
+        offset = end + 4;
+    }
+    Err("it is cut short".to_string())
+}
+
+// every entry's crc; stored and deflated entries are checked, encrypted ones
+// and other methods taken as sound
+fn check_zip(data: &[u8]) -> Result<(), String> {
+    // the end of central directory record, followed by at most a 64 KiB comment
+    let earliest = data.len().saturating_sub(22 + 65535);
+    let record = (earliest..data.len().saturating_sub(21))
+        .rev()
+        .find(|offset| data[*offset..].starts_with(b"PK\x05\x06"))
+        .ok_or("its central directory is missing")?;
+    let garbled = || "its central directory is garbled".to_string();
+    let entries = u16_le(data, record + 10).ok_or_else(garbled)?;
+    let directory = u32_le(data, record + 16).ok_or_else(garbled)?;
+    // zip64 archives keep the real numbers elsewhere
+    if entries == 0xffff || directory == u32::MAX {
+        return Ok(());
+    }
+
+    let mut offset = directory as usize;
+    for _ in 0..entries {
+        if !data.get(offset..).is_some_and(|rest| rest.starts_with(b"PK\x01\x02")) {
+            return Err(garbled());
+        }
+        let field = |at: usize| u16_le(data, offset + at).ok_or_else(garbled);
+        let (flags, method) = (field(8)?, field(10)?);
+        let crc = u32_le(data, offset + 16).ok_or_else(garbled)?;
+        let compressed = u32_le(data, offset + 20).ok_or_else(garbled)? as usize;
+        let size = u32_le(data, offset + 24).ok_or_else(garbled)? as usize;
+        let (name_len, extra_len, comment_len) = (field(28)?, field(30)?, field(32)?);
+        let local = u32_le(data, offset + 42).ok_or_else(garbled)? as usize;
+        let name = data.get(offset + 46..offset + 46 + name_len).ok_or_else(garbled)?;
+        let name = String::from_utf8_lossy(name);
+        offset += 46 + name_len + extra_len + comment_len;
+
+        // zip64 entries keep their real sizes in an extra field
+        if flags & 1 != 0 || !(method == 0 || method == 8) || size == u32::MAX as usize {
+            continue;
+        }
+        if !data.get(local..).is_some_and(|rest| rest.starts_with(b"PK\x03\x04")) {
+            return Err(format!("its entry '{}' is missing", name));
+        }
+        let start = local + 30 + u16_le(data, local + 26).unwrap_or(0) + u16_le(data, local + 28).unwrap_or(0);
+        let stored = data
+            .get(start..start + compressed)
+            .ok_or_else(|| format!("its entry '{}' is cut short", name))?;
+        // inflating stops at the size the directory declares; an entry that
+        // would grow past it is damaged, however much more it holds
+        let contents = if method == 8 {
+            inflate::inflate(stored, size).map_err(|e| format!("its entry '{}' is damaged: it {}", name, e))?
+        } else {
+            stored.to_vec()
+        };
+        if contents.len() != size || crc32(&contents) != crc {
+            return Err(format!("its entry '{}' fails its checksum", name));
+        }
+    }
+    Ok(())
+}
+
+fn check_pdf(mut file: File, size: u64) -> io::Result<Result<(), String>> {
+    file.seek(SeekFrom::Start(size.saturating_sub(PDF_TAIL)))?;
+    let mut tail = Vec::new();
+    hash::Throttled(file).read_to_end(&mut tail)?;
+    let has = |needle: &[u8]| tail.windows(needle.len()).any(|window| window == needle);
+    Ok(if has(b"startxref") && has(b"%%EOF") {
+        Ok(())
+    } else {
+        Err("its trailer is missing, so it is cut short".to_string())
+    })
+}
+
+fn check(path: &Path) -> io::Result<Option<String>> {
+    let mut head = [0u8; 8];
+    let mut file = File::open(path)?;
+    let read = file.read(&mut head)?;
+    let head = &head[..read];
+    let size = file.metadata()?.len();
+    if head.starts_with(b"%PDF-") {
+        return Ok(check_pdf(file, size)?.err());
+    }
+    let known = [b"\xff\xd8".as_slice(), b"\x89PNG\r\n\x1a\n", b"PK\x03\x04"];
+    if !known.iter().any(|magic| head.starts_with(magic)) || size > MAX_CHECKED {
+        return Ok(None);
+    }
+    let data = hash::read(path)?;
+    let checked = if data.starts_with(b"\xff\xd8") {
+        check_jpeg(&data)
+    } else if data.starts_with(b"PK") {
+        check_zip(&data)
+    } else {
+        check_png(&data)
+    };
+    Ok(checked.err())
+}
+
+// files already checked this run, as a set's keeper is chosen more than once
+static CHECKED: OnceLock<Mutex<HashMap<PathBuf, Option<String>>>> = OnceLock::new();
+
+// what is wrong with a file, if it is of a format hydra checks and damaged;
+// a file that can't be read is reported and taken as sound
+pub fn damage(path: &Path) -> Option<String> {
+    let checked = CHECKED.get_or_init(|| Mutex::new(HashMap::new()));
+    if let Some(damage) = checked.lock().unwrap().get(path) {
+        return damage.clone();
+    }
+    let damage = check(path).unwrap_or_else(|e| {
+        eprintln!("Warning: Could not check '{}': {}", path.display(), e);
+        None
+    });
+    checked.lock().unwrap().insert(path.to_path_buf(), damage.clone());
+    damage
+}
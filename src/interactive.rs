@@ -0,0 +1,314 @@
+// line-based review of each duplicate set before anything is acted on, with
+// helpers to open, preview and diff candidates without leaving the terminal
+use crate::actions::Action;
+use crate::keeper::Override;
+use crate::{DuplicateSet, FileInfo, hash, thumbnails};
+use std::fs::File;
+use std::io::{self, BufRead, Read, Write};
+use std::path::Path;
+use std::process::Command;
+
+const PREVIEW_BYTES: usize = 4096;
+const PREVIEW_LINES: usize = 20;
+const HEX_BYTES: usize = 256;
+const DIFF_MAX_LINES: usize = 2000;
+const DIFF_MAX_OUTPUT: usize = 40;
+
+// --confirm: one prompt for the whole run, or one per set
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Confirm {
+    Once,
+    Each,
+}
+
+impl Confirm {
+    pub fn parse(value: &str) -> Option<Confirm> {
+        match value {
+            "once" => Some(Confirm::Once),
+            "each" => Some(Confirm::Each),
+            _ => None,
+        }
+    }
+}
+
+enum Decision {
+    Accept,
+    Skip,
+    Remember,
+    Quit,
+}
+
+fn members(set: &DuplicateSet) -> Vec<&FileInfo> {
+    std::iter::once(&set.keeper).chain(set.duplicates.iter()).collect()
+}
+
+fn print_set(set: &DuplicateSet, index: usize, total: usize, show_thumbnails: bool) {
+    println!("\n--- Duplicate Set {}/{} ---", index + 1, total);
+    println!("Normalized filename: {}", set.normalized_filename);
+    if let Some(id) = set.id() {
+        println!("ID: {}", id);
+    }
+    println!("Size: {} bytes", set.size);
+    if show_thumbnails {
+        thumbnails::show(&set.keeper.path);
+    }
+    for (number, file_info) in members(set).iter().enumerate() {
+        let role = if number == 0 { "keep  " } else { "delete" };
+        println!("  [{}] {} {}", number + 1, role, file_info.path.display());
+    }
+}
+
+fn print_help() {
+    println!("  y        accept this set as shown");
+    println!("  s        skip this set and remember it (not reported again)");
+    println!("  n        skip this set for this run only");
+    println!("  k N      keep file N instead");
+    println!("  o N      open file N with the default application");
+    println!("  p N      preview file N (text head or hex dump)");
+    println!("  d N M    diff files N and M");
+    println!("  q        stop reviewing and cancel");
+}
+
+fn read_head(path: &Path, limit: usize) -> io::Result<Vec<u8>> {
+    let mut head = Vec::with_capacity(limit);
+    File::open(path)?.take(limit as u64).read_to_end(&mut head)?;
+    Ok(head)
+}
+
+fn looks_like_text(bytes: &[u8]) -> bool {
+    !bytes.contains(&0) && std::str::from_utf8(bytes).is_ok()
+}
+
+pub fn open_with_default_app(path: &Path) -> io::Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+
+    command.arg(path).spawn().map(|_| ())
+}
+
+pub fn preview(path: &Path) -> io::Result<()> {
+    let head = read_head(path, PREVIEW_BYTES)?;
+
+    if looks_like_text(&head) {
+        let text = String::from_utf8_lossy(&head);
+        for line in text.lines().take(PREVIEW_LINES) {
+            println!("  | {}", line);
+        }
+        return Ok(());
+    }
+
+    for (row, chunk) in head[..head.len().min(HEX_BYTES)].chunks(16).enumerate() {
+        let hex: Vec<String> = chunk.iter().map(|byte| format!("{:02x}", byte)).collect();
+        let ascii: String = chunk
+            .iter()
+            .map(|byte| if byte.is_ascii_graphic() || *byte == b' ' { *byte as char } else { '.' })
+            .collect();
+        println!("  {:08x}  {:<48} {}", row * 16, hex.join(" "), ascii);
+    }
+
+    Ok(())
+}
+
+// line diff via longest common subsequence; fine for the small text files
+// people typically compare by eye
+fn diff_lines(left: &[&str], right: &[&str]) -> Vec<String> {
+    let mut lengths = vec![vec![0usize; right.len() + 1]; left.len() + 1];
+    for i in (0..left.len()).rev() {
+        for j in (0..right.len()).rev() {
+            lengths[i][j] = if left[i] == right[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut output = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < left.len() || j < right.len() {
+        if i < left.len() && j < right.len() && left[i] == right[j] {
+            i += 1;
+            j += 1;
+        } else if i < left.len() && (j == right.len() || lengths[i + 1][j] >= lengths[i][j + 1]) {
+            output.push(format!("- {}", left[i]));
+            i += 1;
+        } else {
+            output.push(format!("+ {}", right[j]));
+            j += 1;
+        }
+    }
+
+    output
+}
+
+pub fn diff(left: &Path, right: &Path) -> io::Result<()> {
+    let left_bytes = hash::read(left)?;
+    let right_bytes = hash::read(right)?;
+
+    if left_bytes == right_bytes {
+        println!("  files are byte-for-byte identical");
+        return Ok(());
+    }
+
+    if looks_like_text(&left_bytes) && looks_like_text(&right_bytes) {
+        let left_text = String::from_utf8_lossy(&left_bytes);
+        let right_text = String::from_utf8_lossy(&right_bytes);
+        let left_lines: Vec<&str> = left_text.lines().take(DIFF_MAX_LINES).collect();
+        let right_lines: Vec<&str> = right_text.lines().take(DIFF_MAX_LINES).collect();
+
+        let output = diff_lines(&left_lines, &right_lines);
+        for line in output.iter().take(DIFF_MAX_OUTPUT) {
+            println!("  {}", line);
+        }
+        if output.len() > DIFF_MAX_OUTPUT {
+            println!("  ... {} more changed line(s)", output.len() - DIFF_MAX_OUTPUT);
+        }
+        return Ok(());
+    }
+
+    let first_difference = left_bytes
+        .iter()
+        .zip(&right_bytes)
+        .position(|(a, b)| a != b)
+        .unwrap_or(left_bytes.len().min(right_bytes.len()));
+    println!(
+        "  binary files differ: first difference at byte {} ({} vs {} bytes)",
+        first_difference,
+        left_bytes.len(),
+        right_bytes.len()
+    );
+
+    Ok(())
+}
+
+fn parse_member(value: Option<&str>, count: usize) -> Option<usize> {
+    let number: usize = value?.parse().ok()?;
+    (1..=count).contains(&number).then(|| number - 1)
+}
+
+fn review_set(
+    set: &mut DuplicateSet,
+    index: usize,
+    total: usize,
+    show_thumbnails: bool,
+    input: &mut impl BufRead,
+) -> Decision {
+    print_set(set, index, total, show_thumbnails);
+
+    loop {
+        print!("Action [y/s/n/k N/o N/p N/d N M/q, ? for help]: ");
+        io::stdout().flush().unwrap();
+
+        let mut line = String::new();
+        if input.read_line(&mut line).unwrap_or(0) == 0 {
+            return Decision::Quit;
+        }
+
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or("");
+        let count = set.duplicates.len() + 1;
+        let first = parse_member(words.next(), count);
+        let second = parse_member(words.next(), count);
+
+        let result = match (command, first, second) {
+            ("y", _, _) => return Decision::Accept,
+            ("s", _, _) => return Decision::Remember,
+            ("n", _, _) => return Decision::Skip,
+            ("q", _, _) => return Decision::Quit,
+            ("k", Some(0), _) => Ok(()),
+            ("k", Some(n), _) => {
+                std::mem::swap(&mut set.keeper, &mut set.duplicates[n - 1]);
+                print_set(set, index, total, false);
+                Ok(())
+            }
+            ("o", Some(n), _) => open_with_default_app(&members(set)[n].path),
+            ("p", Some(n), _) => preview(&members(set)[n].path),
+            ("d", Some(n), Some(m)) => diff(&members(set)[n].path, &members(set)[m].path),
+            _ => {
+                print_help();
+                Ok(())
+            }
+        };
+
+        if let Err(e) = result {
+            eprintln!("Error: {}", e);
+        }
+    }
+}
+
+// walk the user through every set; returns false if they quit. sets skipped
+// with 's' are collected into `remembered`, and sets accepted with another
+// keeper than hydra chose into `overrides`, even when they quit
+pub fn review_sets(
+    duplicate_sets: &mut Vec<DuplicateSet>,
+    remembered: &mut Vec<DuplicateSet>,
+    overrides: &mut Vec<Override>,
+    show_thumbnails: bool,
+) -> bool {
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let total = duplicate_sets.len();
+    let mut reviewed = Vec::with_capacity(total);
+
+    for (index, mut set) in std::mem::take(duplicate_sets).into_iter().enumerate() {
+        let chosen = set.keeper.path.clone();
+        match review_set(&mut set, index, total, show_thumbnails, &mut input) {
+            Decision::Accept => {
+                if set.keeper.path != chosen {
+                    overrides.push(Override {
+                        kept: set.keeper.path.clone(),
+                        instead_of: chosen,
+                    });
+                }
+                reviewed.push(set)
+            }
+            Decision::Skip => {}
+            Decision::Remember => remembered.push(set),
+            Decision::Quit => return false,
+        }
+    }
+
+    *duplicate_sets = reviewed;
+    true
+}
+
+// --confirm each: a y/N/a/q prompt per set, keeping only the confirmed sets.
+// 'a' confirms this and every remaining set, 'q' skips this and the rest
+pub fn confirm_each(duplicate_sets: &mut Vec<DuplicateSet>, action: Action) {
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let total = duplicate_sets.len();
+    let mut confirmed = Vec::with_capacity(total);
+    let mut remaining = std::mem::take(duplicate_sets).into_iter().enumerate();
+
+    while let Some((index, set)) = remaining.next() {
+        println!("\n--- Duplicate Set {}/{} ---", index + 1, total);
+        println!("Keeping: {}", set.keeper.path.display());
+        for file_info in &set.duplicates {
+            println!("Will {}: {}", action.verb(), file_info.path.display());
+        }
+        print!("Proceed with this set? [y/N/a(ll)/q(uit)]: ");
+        io::stdout().flush().unwrap();
+
+        let mut line = String::new();
+        input.read_line(&mut line).unwrap_or(0);
+        match line.trim().to_lowercase().as_str() {
+            "y" | "yes" => confirmed.push(set),
+            "a" | "all" => {
+                confirmed.push(set);
+                confirmed.extend(remaining.by_ref().map(|(_, set)| set));
+            }
+            "q" | "quit" => break,
+            _ => {}
+        }
+    }
+
+    *duplicate_sets = confirmed;
+}
@@ -0,0 +1,336 @@
+// just enough JPEG decoding to compare photos by eye: baseline huffman-coded
+// images, reduced to the average brightness of each 8x8 block. that is the
+// DC coefficient of the luma component, so no inverse DCT is needed; the AC
+// coefficients are only decoded to be skipped. progressive and arithmetic
+// coded files are not read
+use crate::hash;
+use crate::png::GrayImage;
+use std::path::Path;
+
+// one byte per 8x8 block; a header claiming more blocks than this is damaged
+// or hostile (it would be a photo of two gigapixels)
+const MAX_BLOCKS: usize = 1 << 25;
+
+#[derive(Clone, Default)]
+struct Huffman {
+    // per code length: the largest code of that length (or -1), and where
+    // its symbols start in `symbols` relative to the smallest code
+    max_code: [i32; 17],
+    offset: [i32; 17],
+    symbols: Vec<u8>,
+}
+
+impl Huffman {
+    fn new(counts: &[u8], symbols: &[u8]) -> Huffman {
+        let mut table = Huffman {
+            max_code: [-1; 17],
+            offset: [0; 17],
+            symbols: symbols.to_vec(),
+        };
+        let (mut code, mut index) = (0i32, 0i32);
+        for length in 1..=16 {
+            let count = counts[length - 1] as i32;
+            if count > 0 {
+                table.offset[length] = index - code;
+                code += count;
+                index += count;
+                table.max_code[length] = code - 1;
+            }
+            code <<= 1;
+        }
+        table
+    }
+}
+
+struct Component {
+    id: u8,
+    horizontal: usize,
+    vertical: usize,
+    quant_table: usize,
+}
+
+// the entropy-coded data of a scan, with byte stuffing and restart markers
+struct Bits<'a> {
+    data: &'a [u8],
+    position: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl Bits<'_> {
+    fn bit(&mut self) -> Result<u32, String> {
+        if self.count == 0 {
+            let byte = *self.data.get(self.position).ok_or("the image data is cut short")?;
+            if byte == 0xff {
+                match self.data.get(self.position + 1) {
+                    Some(0) => self.position += 2,
+                    // a marker: the data ends here, and the decoder reads zeros
+                    _ => {
+                        self.buffer = 0;
+                        self.count = 8;
+                        return self.bit();
+                    }
+                }
+            } else {
+                self.position += 1;
+            }
+            self.buffer = byte as u32;
+            self.count = 8;
+        }
+        self.count -= 1;
+        Ok((self.buffer >> self.count) & 1)
+    }
+
+    fn bits(&mut self, count: u8) -> Result<u32, String> {
+        let mut value = 0;
+        for _ in 0..count {
+            value = (value << 1) | self.bit()?;
+        }
+        Ok(value)
+    }
+
+    fn decode(&mut self, table: &Huffman) -> Result<u8, String> {
+        let mut code = 0i32;
+        for length in 1..=16 {
+            code = (code << 1) | self.bit()? as i32;
+            if code <= table.max_code[length] {
+                let index = (code + table.offset[length]) as usize;
+                return table.symbols.get(index).copied().ok_or_else(|| "a huffman code is invalid".to_string());
+            }
+        }
+        Err("a huffman code is invalid".to_string())
+    }
+
+    // a coefficient of `size` bits, negative ones stored as one less
+    fn signed(&mut self, size: u8) -> Result<i32, String> {
+        if size == 0 {
+            return Ok(0);
+        }
+        if size > 16 {
+            return Err("a coefficient is out of range".to_string());
+        }
+        let value = self.bits(size)? as i32;
+        Ok(if value < 1 << (size - 1) { value - (1 << size) + 1 } else { value })
+    }
+
+    // skip to just past the next RSTn marker
+    fn restart(&mut self) -> Result<(), String> {
+        self.count = 0;
+        while self.position + 1 < self.data.len() {
+            if self.data[self.position] == 0xff && (0xd0..=0xd7).contains(&self.data[self.position + 1]) {
+                self.position += 2;
+                return Ok(());
+            }
+            self.position += 1;
+        }
+        Err("a restart marker is missing".to_string())
+    }
+}
+
+fn be_u16(data: &[u8], offset: usize) -> Result<usize, String> {
+    let bytes = data.get(offset..offset + 2).ok_or("the file is cut short")?;
+    Ok(u16::from_be_bytes([bytes[0], bytes[1]]) as usize)
+}
+
+pub fn decode_gray(path: &Path) -> Result<GrayImage, String> {
+    let data = hash::read(path).map_err(|e| e.to_string())?;
+    if !data.starts_with(b"\xff\xd8") {
+        return Err("not a JPEG file".to_string());
+    }
+
+    let mut quant = [1i32; 4];
+    let (mut dc_tables, mut ac_tables) = (vec![Huffman::default(); 4], vec![Huffman::default(); 4]);
+    let mut frame: Option<(usize, usize, Vec<Component>)> = None;
+    let mut restart_interval = 0;
+    let mut offset = 2;
+    loop {
+        // markers may be padded with any number of 0xff bytes
+        if data.get(offset) != Some(&0xff) {
+            return Err("a marker is missing".to_string());
+        }
+        while data.get(offset + 1) == Some(&0xff) {
+            offset += 1;
+        }
+        let marker = *data.get(offset + 1).ok_or("the file ends before the image data")?;
+        let length = be_u16(&data, offset + 2)?;
+        let segment = data.get(offset + 4..offset + 2 + length).ok_or("a segment is cut short")?;
+        match marker {
+            0xdb => {
+                let mut rest = segment;
+                while let Some(&spec) = rest.first() {
+                    let wide = spec >> 4 != 0;
+                    let table_length = if wide { 129 } else { 65 };
+                    let table = rest.get(..table_length).ok_or("a quantization table is cut short")?;
+                    let value = if wide { be_u16(table, 1)? as i32 } else { table[1] as i32 };
+                    quant[(spec & 3) as usize] = value;
+                    rest = &rest[table_length..];
+                }
+            }
+            0xc4 => {
+                let mut rest = segment;
+                while let Some(&spec) = rest.first() {
+                    let counts = rest.get(1..17).ok_or("a huffman table is cut short")?;
+                    let total: usize = counts.iter().map(|&count| count as usize).sum();
+                    let symbols = rest.get(17..17 + total).ok_or("a huffman table is cut short")?;
+                    let table = Huffman::new(counts, symbols);
+                    if spec >> 4 == 0 {
+                        dc_tables[(spec & 3) as usize] = table;
+                    } else {
+                        ac_tables[(spec & 3) as usize] = table;
+                    }
+                    rest = &rest[17 + total..];
+                }
+            }
+            0xdd => restart_interval = be_u16(segment, 0)?,
+            0xc0 | 0xc1 => {
+                let height = be_u16(segment, 1)?;
+                let width = be_u16(segment, 3)?;
+                let count = *segment.get(5).ok_or("the frame header is cut short")? as usize;
+                let mut components = Vec::new();
+                for index in 0..count {
+                    let spec = segment.get(6 + index * 3..9 + index * 3).ok_or("the frame header is cut short")?;
+                    components.push(Component {
+                        id: spec[0],
+                        horizontal: (spec[1] >> 4).max(1) as usize,
+                        vertical: (spec[1] & 15).max(1) as usize,
+                        quant_table: (spec[2] & 3) as usize,
+                    });
+                }
+                if width == 0 || height == 0 || components.is_empty() {
+                    return Err("the image has no size".to_string());
+                }
+                if width.div_ceil(8) * height.div_ceil(8) > MAX_BLOCKS {
+                    return Err(format!("the image is larger than {} blocks", MAX_BLOCKS));
+                }
+                frame = Some((width, height, components));
+            }
+            0xc2 | 0xc6 | 0xca | 0xce => return Err("progressive JPEGs aren't supported".to_string()),
+            0xc3 | 0xc5 | 0xc7 | 0xc9 | 0xcb | 0xcd | 0xcf => {
+                return Err("lossless and arithmetic-coded JPEGs aren't supported".to_string());
+            }
+            0xd9 => return Err("the file has no luma data".to_string()),
+            0xda => {
+                let (width, height, components) = frame.as_ref().ok_or("the image data comes before its header")?;
+                let scan = data.get(offset + 2 + length..).unwrap_or_default();
+                let tables = Tables {
+                    dc: &dc_tables,
+                    ac: &ac_tables,
+                    quant: &quant,
+                    restart_interval,
+                };
+                if let Some(image) = decode_scan(segment, scan, *width, *height, components, &tables)? {
+                    return Ok(image);
+                }
+                // the luma is in a later scan; step over this one's data to
+                // the next marker that isn't a restart
+                let is_marker = |at: usize| {
+                    data[at] == 0xff && data[at + 1] != 0 && !(0xd0..=0xd7).contains(&data[at + 1])
+                };
+                offset += 2 + length;
+                while offset + 1 < data.len() && !is_marker(offset) {
+                    offset += 1;
+                }
+                continue;
+            }
+            _ => {}
+        }
+        offset += 2 + length;
+    }
+}
+
+// what the segments before a scan set up for it
+struct Tables<'a> {
+    dc: &'a [Huffman],
+    ac: &'a [Huffman],
+    quant: &'a [i32; 4],
+    restart_interval: usize,
+}
+
+// the DC image of the frame's first component if this scan holds it
+fn decode_scan(
+    header: &[u8],
+    scan: &[u8],
+    width: usize,
+    height: usize,
+    components: &[Component],
+    tables: &Tables,
+) -> Result<Option<GrayImage>, String> {
+    let count = *header.first().ok_or("the scan header is cut short")? as usize;
+    let mut members = Vec::new();
+    for index in 0..count {
+        let spec = header.get(1 + index * 2..3 + index * 2).ok_or("the scan header is cut short")?;
+        let component = components
+            .iter()
+            .position(|component| component.id == spec[0])
+            .ok_or("the scan names an unknown component")?;
+        members.push((component, (spec[1] >> 4) as usize & 3, (spec[1] & 3) as usize));
+    }
+    if !members.iter().any(|(component, _, _)| *component == 0) {
+        return Ok(None);
+    }
+
+    let max_horizontal = components.iter().map(|component| component.horizontal).max().unwrap_or(1);
+    let max_vertical = components.iter().map(|component| component.vertical).max().unwrap_or(1);
+    let luma = &components[0];
+    // the luma's own size, in blocks
+    let blocks_wide = (width * luma.horizontal).div_ceil(max_horizontal).div_ceil(8);
+    let blocks_high = (height * luma.vertical).div_ceil(max_vertical).div_ceil(8);
+    // a scan of one component codes its blocks in plain raster order
+    let (mcus_wide, mcus_high) = if members.len() == 1 {
+        (blocks_wide, blocks_high)
+    } else {
+        (width.div_ceil(8 * max_horizontal), height.div_ceil(8 * max_vertical))
+    };
+
+    let mut pixels = vec![0u8; blocks_wide * blocks_high];
+    let mut predictions = vec![0i32; members.len()];
+    let mut bits = Bits { data: scan, position: 0, buffer: 0, count: 0 };
+    for mcu in 0..mcus_wide * mcus_high {
+        if tables.restart_interval > 0 && mcu > 0 && mcu % tables.restart_interval == 0 {
+            bits.restart()?;
+            predictions.iter_mut().for_each(|prediction| *prediction = 0);
+        }
+        let (mcu_x, mcu_y) = (mcu % mcus_wide, mcu / mcus_wide);
+        for (member, &(component, dc_table, ac_table)) in members.iter().enumerate() {
+            let (horizontal, vertical) = if members.len() == 1 {
+                (1, 1)
+            } else {
+                (components[component].horizontal, components[component].vertical)
+            };
+            for block in 0..horizontal * vertical {
+                let size = bits.decode(&tables.dc[dc_table])?;
+                predictions[member] += bits.signed(size)?;
+                let mut index = 1;
+                while index < 64 {
+                    let symbol = bits.decode(&tables.ac[ac_table])?;
+                    let (run, size) = (symbol >> 4, symbol & 15);
+                    if size == 0 {
+                        if run != 15 {
+                            break;
+                        }
+                        index += 16;
+                        continue;
+                    }
+                    bits.bits(size)?;
+                    index += run as usize + 1;
+                }
+                if component != 0 {
+                    continue;
+                }
+
+                let x = mcu_x * horizontal + block % horizontal;
+                let y = mcu_y * vertical + block / horizontal;
+                // blocks past the edge pad the last MCU and aren't shown
+                if x < blocks_wide && y < blocks_high {
+                    let average = predictions[member] * tables.quant[luma.quant_table] / 8 + 128;
+                    pixels[y * blocks_wide + x] = average.clamp(0, 255) as u8;
+                }
+            }
+        }
+    }
+    Ok(Some(GrayImage {
+        width: blocks_wide,
+        height: blocks_high,
+        pixels,
+    }))
+}
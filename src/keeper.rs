@@ -0,0 +1,114 @@
+// standing keeper preferences: [keeper] prefer = ["/photos/originals", ...]
+// lists directories whose copies are kept over copies anywhere else, earlier
+// entries first. --interactive learns them: a set where the user kept another
+// copy than hydra chose says they prefer the directory of the copy they kept,
+// and after the review hydra offers to add it to the config so later runs
+// choose the same way
+use crate::config::{self, Config};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Default)]
+pub struct Preferences {
+    directories: Vec<PathBuf>,
+}
+
+impl Preferences {
+    pub fn from_config(config: &Config) -> Result<Preferences, String> {
+        let mut preferences = Preferences::default();
+        for section in config.sections_named("keeper") {
+            if let Some(value) = section.get("prefer") {
+                let paths = value.as_string_array().ok_or("[keeper] `prefer` needs a list of directories")?;
+                preferences.directories = paths.into_iter().map(PathBuf::from).collect();
+            }
+        }
+        Ok(preferences)
+    }
+
+    // the position of the first preferred directory holding `path`
+    pub fn rank(&self, path: &Path) -> Option<usize> {
+        self.directories.iter().position(|directory| path.starts_with(directory))
+    }
+}
+
+// a set accepted in review with another keeper than hydra chose
+pub struct Override {
+    pub kept: PathBuf,
+    pub instead_of: PathBuf,
+}
+
+fn parent(path: &Path) -> PathBuf {
+    path.parent().map(Path::to_path_buf).unwrap_or_default()
+}
+
+// the directories the overrides kept copies in, with how often and which
+// directories they were kept over; overrides within one directory say nothing
+// about directories and are left out, as are directories already preferred
+fn rules(overrides: &[Override], preferences: &Preferences) -> BTreeMap<PathBuf, (usize, BTreeSet<PathBuf>)> {
+    let mut rules: BTreeMap<PathBuf, (usize, BTreeSet<PathBuf>)> = BTreeMap::new();
+    for choice in overrides {
+        let (kept, instead_of) = (parent(&choice.kept), parent(&choice.instead_of));
+        if kept == instead_of || preferences.rank(&choice.kept).is_some() {
+            continue;
+        }
+        let rule = rules.entry(kept).or_default();
+        rule.0 += 1;
+        rule.1.insert(instead_of);
+    }
+    rules
+}
+
+// offer each directory the user kept copies in as a standing preference and
+// save those accepted to the config's [keeper] section; the path saved to and
+// how many were added, if any
+pub fn learn(
+    overrides: &[Override],
+    preferences: &Preferences,
+    config_path: Option<&Path>,
+) -> Result<Option<(PathBuf, usize)>, String> {
+    let rules = rules(overrides, preferences);
+    if rules.is_empty() {
+        return Ok(None);
+    }
+
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let mut learned = Vec::new();
+    for (directory, (count, others)) in rules {
+        let others: Vec<String> = others.iter().map(|other| format!("'{}'", other.display())).collect();
+        println!(
+            "\nYou kept the copy in '{}' over {} in {} set(s).",
+            directory.display(),
+            others.join(", "),
+            count
+        );
+        print!("Always keep copies in '{}' from now on? [y/N]: ", directory.display());
+        io::stdout().flush().unwrap();
+
+        let mut line = String::new();
+        if input.read_line(&mut line).unwrap_or(0) == 0 {
+            break;
+        }
+        if matches!(line.trim().to_lowercase().as_str(), "y" | "yes") {
+            learned.push(directory);
+        }
+    }
+    if learned.is_empty() {
+        return Ok(None);
+    }
+
+    let path = config_path
+        .map(Path::to_path_buf)
+        .or_else(config::default_path)
+        .ok_or("could not find where the config file belongs; pass --config PATH")?;
+    let directories: Vec<String> = preferences
+        .directories
+        .iter()
+        .chain(&learned)
+        .map(|directory| config::quote(&directory.to_string_lossy()))
+        .collect();
+    let assignment = format!("prefer = [{}]", directories.join(", "));
+    config::save_key(&path, "keeper", "prefer", &assignment)?;
+    Ok(Some((path, learned.len())))
+}
@@ -0,0 +1,18 @@
+// managed photo libraries (Apple Photos and Aperture bundles, Lightroom
+// catalogs and their preview caches) track every file they hold in a
+// database; removing one behind the application's back corrupts the catalog.
+// they are skipped unless --allow-libraries is passed
+use std::path::Path;
+
+const BUNDLE_EXTENSIONS: &[&str] = &["photoslibrary", "migratedphotolibrary", "photolibrary", "aplibrary", "lrlibrary"];
+const CATALOG_EXTENSIONS: &[&str] = &["lrcat", "lrcat-data", "lrdata", "lrcat-wal", "lrcat-shm", "lrcat-journal"];
+
+// whether `path` (a directory or file met while walking) is a library bundle
+// or part of a Lightroom catalog
+pub fn is_library(path: &Path) -> bool {
+    let Some(extension) = path.extension() else {
+        return false;
+    };
+    let extension = extension.to_string_lossy().to_lowercase();
+    BUNDLE_EXTENSIONS.contains(&extension.as_str()) || CATALOG_EXTENSIONS.contains(&extension.as_str())
+}
@@ -0,0 +1,68 @@
+// --timeout / --max-files: bounds for scheduled runs on trees of unknown
+// size. once either is reached the scan stops where it is, hashing stops
+// between passes, and what was found so far is reported. a run cut short
+// acts on nothing, since a set's rightful keeper may be among the files it
+// never saw
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+// (deadline, most files scanned)
+static LIMITS: OnceLock<(Option<Instant>, Option<u64>)> = OnceLock::new();
+static FILES: AtomicU64 = AtomicU64::new(0);
+// the limit that stopped the run, once one has
+static REACHED: Mutex<Option<String>> = Mutex::new(None);
+
+// start the clock; called as the scan begins
+pub fn start(timeout: Option<Duration>, max_files: Option<u64>) {
+    LIMITS.get_or_init(|| (timeout.map(|timeout| Instant::now() + timeout), max_files));
+}
+
+fn reach(limit: String) {
+    let mut reached = REACHED.lock().unwrap();
+    if reached.is_none() {
+        eprintln!("Note: {} reached; the run stops here and the report covers only what was found so far", limit);
+        *reached = Some(limit);
+    }
+}
+
+// whether --timeout has passed
+pub fn timed_out() -> bool {
+    let Some((Some(deadline), _)) = LIMITS.get() else {
+        return false;
+    };
+    if Instant::now() < *deadline {
+        return false;
+    }
+    reach("--timeout".to_string());
+    true
+}
+
+// whether the scan has been stopped by either limit
+pub fn stopped() -> bool {
+    REACHED.lock().unwrap().is_some() || timed_out()
+}
+
+// count a file the scan is about to take in; false if a limit stops it
+pub fn admit_file() -> bool {
+    if stopped() {
+        return false;
+    }
+    if let Some((_, Some(max_files))) = LIMITS.get()
+        && FILES.fetch_add(1, Ordering::Relaxed) >= *max_files
+    {
+        reach(format!("--max-files {}", max_files));
+        return false;
+    }
+    true
+}
+
+// a scan started over counts its files again toward --max-files
+pub fn restart_scan() {
+    FILES.store(0, Ordering::Relaxed);
+}
+
+// the limit that cut the run short, if any
+pub fn reached() -> Option<String> {
+    REACHED.lock().unwrap().clone()
+}
@@ -0,0 +1,48 @@
+// --profile mail: mail clients save a copy of every attachment opened or
+// previewed into their own directories, so most files there are duplicates of
+// the copy the user saved somewhere on purpose. the copy in the attachment
+// directory is the one to remove. mail stores themselves (Maildir folders,
+// Thunderbird mbox files) hold messages, not attachments, and are left alone
+use regex::Regex;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+fn attachment_directories() -> &'static Regex {
+    static DIRECTORIES: OnceLock<Regex> = OnceLock::new();
+    DIRECTORIES.get_or_init(|| {
+        Regex::new(concat!(
+            r"(?i)[/\\](",
+            r"Library[/\\]Mail[/\\]V\d+[/\\].*[/\\]Attachments",     // apple mail
+            r"|Library[/\\]Containers[/\\]com\.apple\.mail[/\\].*", // apple mail (sandboxed)
+            r"|Mail Downloads",                                     // apple mail
+            r"|Content\.Outlook|Outlook Temp|OLK[0-9A-F]*",         // outlook
+            r"|\.cache[/\\]evolution[/\\]mail",                     // evolution
+            r"|\.claws-mail[/\\]mimetmp",                           // claws mail
+            r")[/\\]"
+        ))
+        .unwrap()
+    })
+}
+
+// whether `path` lies in a directory a mail client saves attachments to
+pub fn is_attachment(path: &Path) -> bool {
+    attachment_directories().is_match(&path.to_string_lossy())
+}
+
+// a Maildir folder (cur, new and tmp side by side) or a Thunderbird mbox file
+// (with its .msf index next to it)
+pub fn is_mail_store(path: &Path, is_dir: bool) -> bool {
+    let Some(parent) = path.parent() else {
+        return false;
+    };
+    if is_dir {
+        let name = path.file_name().unwrap_or_default();
+        ["cur", "new", "tmp"].iter().any(|folder| name == *folder)
+            && ["cur", "new", "tmp"].iter().all(|folder| parent.join(folder).is_dir())
+    } else {
+        let mut index = path.as_os_str().to_owned();
+        index.push(".msf");
+        fs::metadata(index).is_ok_and(|metadata| metadata.is_file())
+    }
+}
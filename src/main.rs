@@ -1,16 +1,290 @@
+mod acl;
+mod actions;
+mod apart;
+mod backup;
+mod bench;
+mod big;
+mod breakdown;
+mod bursts;
+mod casefold;
+mod channels;
+mod compare;
+mod config;
+mod consolidate;
+mod contextmenu;
+mod dirtimes;
+mod dircache;
+mod diskimage;
+mod edit;
+mod environment;
+mod estimate;
+mod exec;
+mod filetype;
+mod findcopies;
+mod formats;
+mod generated;
+mod keeper;
+mod hash;
+mod heartbeat;
+mod gui;
+mod hidden;
+mod hooks;
+mod i18n;
+mod inflate;
+mod init;
+mod integrity;
+mod interactive;
+mod jpeg;
+mod json;
+mod libraries;
+mod limits;
+mod mail;
+mod matching;
+mod merge;
+mod metadata;
+mod normalize;
+mod ownership;
+mod pacing;
+mod pager;
+mod png;
+mod plan;
+mod plugin;
+mod playlists;
+mod power;
+mod prefilter;
+mod presets;
+mod priority;
+mod purge;
+mod remote;
+mod report;
+mod results;
+mod retry;
+mod richness;
+mod rollup;
+mod scan;
+mod scanlist;
+mod scheduler;
+mod screenshots;
+mod setids;
+mod similar;
+mod sharing;
+mod snapshot;
+mod snapshots;
+mod stats;
+mod streams;
+mod symlinks;
+mod terminal;
+mod thumbnails;
+mod timestamps;
+mod trash;
+mod truncated;
+mod verify;
+mod volumes;
+mod wasm;
+mod whitelist;
+mod xattr;
+
 use regex::Regex;
-use std::collections::HashMap;
+use actions::Action;
+use acl::AclMerge;
+use bursts::Burst;
+use apart::Apart;
+use casefold::CaseCollision;
+use diskimage::ImageCopy;
+use channels::LogFormat;
+use interactive::Confirm;
+use filetype::FileClass;
+use hooks::Hooks;
+use i18n::{Language, tr};
+use formats::Format;
+use generated::Generated;
+use keeper::Preferences;
+use scanlist::ScanList;
+use power::Pause;
+use matching::MatchMode;
+use normalize::Normalizer;
+use ownership::OwnerFilter;
+use plugin::Plugin;
+use report::{GroupBy, SortKey};
+use scheduler::IoProfile;
+use timestamps::TimeSource;
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::fs;
 use std::io::{self, Write};
-use std::path::PathBuf;
-use std::time::SystemTime;
+use std::path::{Component, Path, PathBuf};
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const IN_USE_RETRY_DELAY: Duration = Duration::from_secs(2);
+// sets with more files than this are held back for review (--max-set-size)
+const DEFAULT_MAX_SET_SIZE: usize = 100;
 
 #[derive(Debug)]
+struct Options {
+    dry_run: bool,
+    io_profile: IoProfile,
+    bwlimit: Option<u64>,
+    hash_threads: Option<usize>,
+    hash_buffer: Option<usize>,
+    idle_priority: bool,
+    jobs: usize,
+    delete_batch: Option<usize>,
+    delete_interval: Option<Duration>,
+    two_pass: bool,
+    xattr_cache: bool,
+    // --dir-cache
+    dir_cache: bool,
+    merge_metadata: bool,
+    merge_acls: Option<AclMerge>,
+    fix_symlinks: bool,
+    fix_playlists: bool,
+    // --preserve-dir-times
+    preserve_dir_times: bool,
+    recursive: bool,
+    skip_hidden: bool,
+    include_system_files: bool,
+    // rotated logs, numbered cache files and thumbnails are skipped unless
+    // --include-generated; [generated] in the config tunes what counts
+    include_generated: bool,
+    generated: Generated,
+    allow_libraries: bool,
+    include_snapshots: bool,
+    excluded_directories: Vec<&'static str>,
+    keep_per_dir: bool,
+    // scanned instead of the current directory: the first of the config's
+    // [defaults] folders when no --root is given
+    directory: Option<String>,
+    roots: Vec<PathBuf>,
+    within_roots_only: bool,
+    keep_matching: Option<Regex>,
+    mail: bool,
+    delete_matching: Option<Regex>,
+    // --explain: say in the report why each keeper was chosen
+    explain: bool,
+    group_by: GroupBy,
+    interactive: bool,
+    edit: bool,
+    thumbnails: bool,
+    pager: bool,
+    sort: Option<SortKey>,
+    reverse: bool,
+    limit: Option<usize>,
+    // --max-set-size: sets with more files than this are a bad normalization
+    // rule or generated files far more often than real copies; None for no cap
+    max_set_size: Option<usize>,
+    types: Vec<FileClass>,
+    match_mode: MatchMode,
+    // --rollup / --rollup-depth: reclaimable space per folder this many levels
+    // below each root
+    rollup: Option<usize>,
+    // --verify-apart: hash groups whose copies are far apart even when
+    // match_mode wouldn't
+    verify_apart: Option<Apart>,
+    truncated: bool,
+    // --check-integrity
+    check_integrity: bool,
+    // --ignore-image-metadata, --ignore-audio-tags and --prefer-metadata
+    ignore_image_metadata: bool,
+    ignore_audio_tags: bool,
+    // copies differing only in metadata are listed rather than removed, as a
+    // profile turns the matching on; passing either flag lets them go
+    metadata_copies_for_review: bool,
+    prefer_metadata: bool,
+    ignore_extension: bool,
+    bursts: Option<i64>,
+    screenshots: bool,
+    // --similar-images: list images that look alike for review
+    similar_images: bool,
+    // --disk-images: list scanned files also found inside iso images
+    disk_images: bool,
+    owner_filter: OwnerFilter,
+    normalizer: Normalizer,
+    backup: Option<PathBuf>,
+    retry_in_use: bool,
+    verify_keepers: bool,
+    purge_older_than: Option<Duration>,
+    // --empty-trash-after: remove what this run trashed once it is done
+    empty_trash_after: bool,
+    stats: bool,
+    summary: bool,
+    fail_if_wasted: Option<u64>,
+    fail_if_files: Option<usize>,
+    // --timeout / --max-files: stop the scan and report what was found so far
+    timeout: Option<Duration>,
+    max_files: Option<u64>,
+    // --settle: leave out files modified this recently, which may be mid-write
+    settle: Option<Duration>,
+    // --heartbeat: how long a file may take to hash, or a directory to list,
+    // before it is named on stderr; zero turns heartbeats off
+    heartbeat: Duration,
+    output: Option<PathBuf>,
+    // the results file kept up to date during the run (--results), by default
+    // last-run.json in the state directory
+    results: Option<PathBuf>,
+    lang: Option<Language>,
+    format: Format,
+    log_format: LogFormat,
+    only_sets: Vec<String>,
+    skip_sets: Vec<String>,
+    snapshot: Option<PathBuf>,
+    // --scan-only FILE: save the scanned files there and stop
+    scan_only: Option<PathBuf>,
+    // --hash-only FILE: start from the files a --scan-only run saved
+    hash_only: Option<ScanList>,
+    yes: bool,
+    confirm: Confirm,
+    time: TimeSource,
+    time_tolerance: Duration,
+    action: Action,
+    // [hooks] from the config file
+    hooks: Hooks,
+    // [keeper] prefer from the config file
+    keeper: Preferences,
+    // [pause] from the config file, for --scheduled runs
+    pause: Pause,
+    // --scheduled: a run `hydra init` set up, which nobody is waiting on
+    scheduled: bool,
+    // the config file in use, where --interactive saves keeper preferences
+    config_path: Option<PathBuf>,
+    // --action exec:CMD, split into words
+    exec: Vec<String>,
+    // --plugin FILE.wasm
+    plugin: Option<Plugin>,
+}
+
+#[derive(Debug, Clone)]
 struct FileInfo {
     path: PathBuf,
     size: u64,
-    created: SystemTime
+    created: Option<SystemTime>,
+    modified: SystemTime,
+}
+
+#[derive(Debug)]
+struct DuplicateSet {
+    normalized_filename: String,
+    size: u64,
+    // the keeper's content hash, once known; the set's ID is derived from it
+    hash: Option<u64>,
+    keeper: FileInfo,
+    duplicates: Vec<FileInfo>,
+}
+
+impl DuplicateSet {
+    // the same content gets the same ID in every run, so --only-set and
+    // --skip-set can refer to sets from an earlier report
+    fn id(&self) -> Option<String> {
+        self.hash.map(|hash| format!("{:016x}", hash)[..12].to_string())
+    }
+}
+
+// a file whose size or mtime changed between being indexed and being acted on
+#[derive(Debug)]
+struct VolatileFile {
+    path: PathBuf,
+    outcome: String,
 }
 
 fn get_current_directory() -> String {
@@ -22,236 +296,2176 @@ fn get_current_directory() -> String {
         .to_string()
 }
 
-fn normalize_filename(filename: &str) -> String {
-    // separate name and extension
-    let (stem, extension) = match filename.rsplit_once('.') {
-        Some((s, e)) => (s, Some(e)),
-        None => (filename, None),
-    };
-    
-    // patterns to strip (order matters - check longer regex patterns first)
-    let patterns = [
-        r" copy \d+$",       // "file copy 2"
-        r" copy$",           // "file copy"
-        r" - Copy \(\d+\)$", // "file - Copy (2)"
-        r" - Copy$",         // "file - Copy"
-        r" \(\d+\)$",        // "file (1)"
-        r"\(\d+\)$",         // "file(1)"
-    ];
-    
-    let mut normalized = stem.to_string();
-    
-    for pattern in patterns {
-        let re = Regex::new(pattern).unwrap();
-        if re.is_match(&normalized) {
-            normalized = re.replace(&normalized, "").to_string();
-            break;
+// human-readable byte count using binary units
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+
+    if bytes < 1024 {
+        return format!("{} bytes", bytes);
+    }
+
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+// resolve `.` and `..` without touching the filesystem, since the path may
+// no longer exist
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
         }
     }
-    
-    // reconstruct with extension
-    match extension {
-        Some(ext) => format!("{}.{}", normalized, ext),
-        None => normalized,
+
+    normalized
+}
+
+// express `path` relative to `base`; both must be absolute and normalized
+fn relative_path(base: &Path, path: &Path) -> PathBuf {
+    let base: Vec<_> = base.components().collect();
+    let target: Vec<_> = path.components().collect();
+    let common = base.iter().zip(&target).take_while(|(a, b)| a == b).count();
+
+    let mut relative = PathBuf::new();
+    for _ in common..base.len() {
+        relative.push("..");
+    }
+    for component in &target[common..] {
+        relative.push(component);
     }
+
+    relative
+}
+
+// percent-encode everything but unreserved characters and path separators
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
 }
 
-fn find_and_delete_duplicate_files(directory: String, dry_run: bool) {
-    // step 1: group files by normalized filename
-    let mut hashmap_name: HashMap<String, Vec<FileInfo>> = HashMap::new();
+// UTC timestamp in ISO 8601 form, e.g. 2024-05-01T10:00:00Z
+fn format_timestamp(time: SystemTime) -> String {
+    let seconds = time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let (days, remainder) = (seconds / 86400, seconds % 86400);
 
-    let entries = match fs::read_dir(&directory) {
-        Ok(entries) => entries,
-        Err(e) => {
-            eprintln!("Error reading directory '{}': {}", directory, e);
-            return;
+    // civil-from-days (Howard Hinnant's algorithm)
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        remainder / 3600,
+        remainder % 3600 / 60,
+        remainder % 60
+    )
+}
+
+// drop files whose sniffed content class isn't one of the requested --type
+// values; only files that could still form a set are sniffed
+fn filter_by_type(hashmap_name: &mut HashMap<String, Vec<FileInfo>>, types: &[FileClass]) {
+    for file_infos in hashmap_name.values_mut() {
+        if file_infos.len() < 2 {
+            continue;
         }
-    };
 
-    for file in entries {
-        let file = match file {
-            Ok(f) => f,
+        let before = file_infos.len();
+        file_infos.retain(|file_info| match filetype::detect(&file_info.path) {
+            Ok(class) => class.is_some_and(|class| types.contains(&class)),
             Err(e) => {
-                eprintln!("Error reading directory entry: {}", e);
-                continue;
+                eprintln!("Warning: Could not read '{}' to detect its type: {}", file_info.path.display(), e);
+                false
             }
-        };
+        });
+        stats::filtered("--type", (before - file_infos.len()) as u64);
+    }
+}
 
-        let path = file.path();
+// --settle: drop files modified within `settle` of now, which may still be
+// downloading or syncing; returns how many were dropped
+fn filter_unsettled(hashmap_name: &mut HashMap<String, Vec<FileInfo>>, settle: Duration) -> usize {
+    let now = SystemTime::now();
+    // a time in the future, from a skewed clock, counts as recent too
+    let settled = |file_info: &FileInfo| now.duration_since(file_info.modified).is_ok_and(|age| age >= settle);
+    let mut dropped = 0;
+    for file_infos in hashmap_name.values_mut() {
+        if file_infos.len() < 2 {
+            continue;
+        }
 
-        // skip directories, only process files
-        let metadata = match fs::metadata(&path) {
-            Ok(m) => m,
-            Err(e) => {
-                eprintln!("Error reading metadata for '{}': {}", path.display(), e);
-                continue;
+        let before = file_infos.len();
+        file_infos.retain(settled);
+        dropped += before - file_infos.len();
+    }
+    stats::filtered("--settle", dropped as u64);
+    dropped
+}
+
+// the copies that may be kept: those allowed by --keep-matching /
+// --delete-matching; with --profile mail a copy in a mail client's attachment
+// directory only when every copy is in one. each comes with the rule, if any,
+// that ruled the others out
+fn keeper_candidates<'a>(group: &'a [FileInfo], options: &Options) -> (Vec<(usize, &'a FileInfo)>, Option<String>) {
+    let path_matches = |re: &Option<Regex>, file_info: &FileInfo| {
+        re.as_ref()
+            .is_some_and(|re| re.is_match(&file_info.path.to_string_lossy()))
+    };
+    let mut rule = None;
+
+    let mut allowed: Vec<(usize, &FileInfo)> = group
+        .iter()
+        .enumerate()
+        .filter(|(_, f)| !path_matches(&options.delete_matching, f))
+        .collect();
+    if allowed.len() < group.len() {
+        rule = Some(tr!("the other copies match --delete-matching").to_string());
+    }
+
+    if options.mail && allowed.iter().any(|(_, f)| !mail::is_attachment(&f.path)) {
+        let before = allowed.len();
+        allowed.retain(|(_, f)| !mail::is_attachment(&f.path));
+        if allowed.len() < before {
+            rule = Some(tr!("the other copies are mail attachments").to_string());
+        }
+    }
+
+    // a damaged copy is only kept when every copy is damaged
+    if options.check_integrity && allowed.len() > 1 {
+        let damaged: Vec<bool> = allowed.iter().map(|(_, f)| integrity::damage(&f.path).is_some()).collect();
+        if damaged.contains(&true) && damaged.contains(&false) {
+            let mut damaged = damaged.iter();
+            allowed.retain(|_| damaged.next() == Some(&false));
+            rule = Some(tr!("the other copies are damaged").to_string());
+        }
+    }
+
+    let preferred: Vec<(usize, &FileInfo)> = allowed
+        .iter()
+        .copied()
+        .filter(|(_, f)| path_matches(&options.keep_matching, f))
+        .collect();
+
+    let mut candidates = if preferred.is_empty() {
+        allowed
+    } else {
+        if preferred.len() < allowed.len() {
+            rule = Some(tr!("it matches --keep-matching").to_string());
+        }
+        preferred
+    };
+
+    // [keeper] prefer: copies in the first listed directory that holds any
+    let ranks: Vec<Option<usize>> = candidates.iter().map(|(_, f)| options.keeper.rank(&f.path)).collect();
+    if let Some(best) = ranks.iter().flatten().min().copied() {
+        let before = candidates.len();
+        let mut ranks = ranks.iter();
+        candidates.retain(|_| ranks.next() == Some(&Some(best)));
+        if candidates.len() < before {
+            rule = Some(tr!("it is in a preferred directory ([keeper] prefer)").to_string());
+        }
+    }
+
+    if options.prefer_metadata && candidates.len() > 1 {
+        let fields: Vec<usize> = candidates.iter().map(|(_, f)| richness::fields_or_none(&f.path)).collect();
+        let most = fields.iter().copied().max().unwrap_or_default();
+        if fields.iter().any(|count| *count < most) {
+            let mut fields = fields.iter();
+            candidates.retain(|_| fields.next() == Some(&most));
+            rule = Some(tr!("it has the most embedded metadata").to_string());
+        }
+    }
+
+    // a plugin's rank goes last; a plugin that fails on a file leaves it unranked
+    if let Some(plugin) = &options.plugin {
+        let ranks: Vec<Option<i32>> = candidates
+            .iter()
+            .map(|(_, f)| {
+                plugin.keep_rank(&f.path).unwrap_or_else(|e| {
+                    eprintln!("Warning: {}", e);
+                    None
+                })
+            })
+            .collect();
+        if let Some(best) = ranks.iter().flatten().min().copied() {
+            let before = candidates.len();
+            let mut ranks = ranks.iter();
+            candidates.retain(|_| ranks.next() == Some(&Some(best)));
+            if candidates.len() < before {
+                rule = Some(tr!("the plugin ranks it first").to_string());
             }
+        }
+    }
+    (candidates, rule)
+}
+
+// timestamps within --time-tolerance of the earliest count as a tie. ties go,
+// in order, to a file already carrying the plain name, the earliest
+// timestamp, the shortest path and the first path alphabetically, so the same
+// files always yield the same keeper
+fn tie_break(file_info: &FileInfo, options: &Options) -> (bool, SystemTime, usize, PathBuf) {
+    let copy_named = file_info.path.file_name().is_none_or(|name| {
+        let name = name.to_string_lossy();
+        options.normalizer.strip_copy_suffix(&name) != name
+    });
+    let timestamp = options.time.timestamp(file_info);
+    (copy_named, timestamp, file_info.path.as_os_str().len(), file_info.path.clone())
+}
+
+fn contenders<'a>(candidates: Vec<(usize, &'a FileInfo)>, options: &Options) -> Vec<(usize, &'a FileInfo)> {
+    let Some(earliest) = candidates.iter().map(|(_, f)| options.time.timestamp(f)).min() else {
+        return Vec::new();
+    };
+    let cutoff = earliest + options.time_tolerance;
+    candidates
+        .into_iter()
+        .filter(|(_, f)| options.time.timestamp(f) <= cutoff)
+        .collect()
+}
+
+// keep the file with the earliest timestamp among the candidates, ties broken
+// by tie_break
+fn choose_keeper(group: &[FileInfo], options: &Options) -> Option<usize> {
+    let (candidates, _) = keeper_candidates(group, options);
+    contenders(candidates, options)
+        .into_iter()
+        .min_by_key(|(_, f)| tie_break(f, options))
+        .map(|(index, _)| index)
+}
+
+// --explain: the rule that picked a set's keeper over its other copies
+fn explain_keeper(set: &DuplicateSet, options: &Options) -> String {
+    let stripped = |file_info: &FileInfo| {
+        file_info.size != set.keeper.size && richness::same_media(&file_info.path, &set.keeper.path)
+    };
+    if richness::ignores_metadata(options) && set.duplicates.iter().any(stripped) {
+        return tr!("it has the most embedded metadata; the other copies differ only in metadata").to_string();
+    }
+    if options.truncated && set.duplicates.iter().any(|file_info| file_info.size < set.keeper.size) {
+        return tr!("it is complete; the other copies are truncated").to_string();
+    }
+    let group: Vec<FileInfo> = std::iter::once(&set.keeper).chain(&set.duplicates).cloned().collect();
+    let (candidates, rule) = keeper_candidates(&group, options);
+    let candidate_count = candidates.len();
+    let contenders = contenders(candidates, options);
+    if contenders.iter().min_by_key(|(_, f)| tie_break(f, options)).map(|(index, _)| *index) != Some(0) {
+        return tr!("chosen by hand").to_string();
+    }
+    if contenders.len() == 1 {
+        let oldest = tr!("it has the earliest timestamp").to_string();
+        return match rule {
+            Some(rule) if candidate_count == 1 => rule,
+            Some(rule) => format!("{}; {}", rule, oldest),
+            None => oldest,
         };
+    }
 
-        if !metadata.is_file() {
+    // the tie_break field needed to set the keeper apart from the closest other
+    let keeper = tie_break(&set.keeper, options);
+    let decided_by = contenders[1..]
+        .iter()
+        .map(|(_, f)| {
+            let other = tie_break(f, options);
+            [keeper.0 != other.0, keeper.1 != other.1, keeper.2 != other.2].iter().position(|differs| *differs)
+        })
+        .max_by_key(|field| field.unwrap_or(3));
+    let tie = match decided_by.flatten() {
+        Some(0) => tr!("it has the plain name; the other copies carry a copy suffix"),
+        Some(1) => tr!("a timestamp tie, broken by the earliest timestamp"),
+        Some(_) => tr!("a timestamp tie, broken by the shortest path"),
+        None => tr!("a timestamp tie, broken by the first path alphabetically"),
+    };
+    match rule {
+        Some(rule) => format!("{}; {}", rule, tie),
+        None => tie.to_string(),
+    }
+}
+
+// a set is named after the normalized name of a member (the keeper, once
+// chosen); with --match size its members may not share one
+fn set_name(file_info: &FileInfo, options: &Options) -> String {
+    let name = file_info.path.file_name().unwrap_or_default().to_string_lossy();
+    options.normalizer.normalize(&name)
+}
+
+fn find_duplicate_sets(
+    hashmap_name: HashMap<String, Vec<FileInfo>>,
+    roots: &[PathBuf],
+    options: &Options,
+) -> Vec<DuplicateSet> {
+    let mut groups = Vec::new();
+
+    for file_infos in hashmap_name.into_values() {
+        // only process if there are multiple files with this normalized name
+        if file_infos.len() < 2 {
             continue;
         }
 
-        // get filename
-        let filename = match path.file_name() {
-            Some(name) => name.to_string_lossy().to_string(),
+        // sub-group by size within this filename group (unless --match name),
+        // and by parent directory (or root) when one copy should survive in
+        // every directory (or root)
+        let mut hashmap_size: HashMap<(Option<u64>, Option<PathBuf>), Vec<FileInfo>> = HashMap::new();
+        for file_info in file_infos {
+            let size = options.match_mode.uses_size().then_some(file_info.size);
+            let directory = if options.keep_per_dir {
+                file_info.path.parent().map(Path::to_path_buf)
+            } else if options.within_roots_only {
+                root_of(&file_info.path, roots).cloned()
+            } else {
+                None
+            };
+            hashmap_size.entry((size, directory)).or_default().push(file_info);
+        }
+
+        groups.extend(hashmap_size.into_values().filter(|size_group| size_group.len() > 1));
+    }
+
+    let groups: Vec<(Option<u64>, Vec<FileInfo>)> = if options.match_mode == MatchMode::Content {
+        matching::split_by_content(groups, options.io_profile)
+            .into_iter()
+            .map(|(hash, group)| (Some(hash), group))
+            .collect()
+    } else if let Some(apart) = options.verify_apart {
+        // only copies far apart have to prove they're the same
+        let mut distances = apart::Distances::new(apart);
+        let (spread, near): (Vec<_>, Vec<_>) = groups.into_iter().partition(|group| distances.spread(group));
+        matching::split_by_content(spread, options.io_profile)
+            .into_iter()
+            .map(|(hash, group)| (Some(hash), group))
+            .chain(near.into_iter().map(|group| (None, group)))
+            .collect()
+    } else {
+        groups.into_iter().map(|group| (None, group)).collect()
+    };
+
+    let mut duplicate_sets = Vec::new();
+    for (hash, mut group) in groups {
+        let keeper_index = match choose_keeper(&group, options) {
+            Some(index) => index,
             None => {
-                eprintln!("Warning: Could not extract filename from path '{}'", path.display());
+                eprintln!(
+                    "Warning: Skipping duplicate set '{}': every copy matches --delete-matching",
+                    set_name(&group[0], options)
+                );
                 continue;
             }
         };
+        let keeper = group.remove(keeper_index);
 
-        let normalized_filename = normalize_filename(&filename);
-        let size = metadata.len();
-
-        // try to get creation time, use modified time as fallback
-        let created = match metadata.created() {
-            Ok(time) => time,
-            Err(_) => {
-                match metadata.modified() {
-                    Ok(time) => time,
-                    Err(e) => {
-                        eprintln!("Warning: Could not get creation or modified time for '{}': {}", path.display(), e);
-                        continue;
-                    }
+        duplicate_sets.push(DuplicateSet {
+            normalized_filename: set_name(&keeper, options),
+            size: keeper.size,
+            hash,
+            keeper,
+            duplicates: group,
+        });
+    }
+
+    duplicate_sets
+}
+
+// re-stat a file, refreshing its recorded size/mtime, and report whether either changed
+fn restat(file_info: &mut FileInfo) -> io::Result<bool> {
+    let metadata = fs::metadata(&file_info.path)?;
+    let modified = metadata.modified()?;
+    let changed = metadata.len() != file_info.size || modified != file_info.modified;
+
+    file_info.size = metadata.len();
+    file_info.modified = modified;
+
+    Ok(changed)
+}
+
+// look up the fresh hashes of a file and its keeper and compare them
+fn hashed_match(
+    hashes: &HashMap<PathBuf, io::Result<u64>>,
+    file_info: &FileInfo,
+    keeper: &FileInfo,
+) -> Result<bool, String> {
+    if file_info.size != keeper.size {
+        return Ok(false);
+    }
+
+    let lookup = |path: &PathBuf| match hashes.get(path) {
+        Some(Ok(hash)) => Ok(*hash),
+        Some(Err(e)) => Err(format!("{}", e)),
+        None => Err("not hashed".to_string()),
+    };
+
+    Ok(lookup(&file_info.path)? == lookup(&keeper.path)?)
+}
+
+// re-stat every member of every set and re-hash anything that changed since it
+// was indexed, dropping files that no longer match their keeper
+fn guard_volatile_files(duplicate_sets: &mut Vec<DuplicateSet>, io_profile: IoProfile) -> Vec<VolatileFile> {
+    let mut volatile_files = Vec::new();
+    let mut changed_paths: HashSet<PathBuf> = HashSet::new();
+    let mut paths_to_hash = Vec::new();
+
+    // pass 1: re-stat, dropping anything that disappeared
+    duplicate_sets.retain_mut(|set| {
+        match restat(&mut set.keeper) {
+            Ok(true) => {
+                changed_paths.insert(set.keeper.path.clone());
+            }
+            Ok(false) => {}
+            Err(e) => {
+                volatile_files.push(VolatileFile {
+                    path: set.keeper.path.clone(),
+                    outcome: format!("keeper no longer readable ({}), set skipped", e),
+                });
+                return false;
+            }
+        }
+
+        set.duplicates.retain_mut(|file_info| match restat(file_info) {
+            Ok(changed) => {
+                if changed {
+                    changed_paths.insert(file_info.path.clone());
                 }
+                true
             }
-        };
+            Err(e) => {
+                volatile_files.push(VolatileFile {
+                    path: file_info.path.clone(),
+                    outcome: format!("no longer readable ({}), skipped", e),
+                });
+                false
+            }
+        });
 
-        let file_info = FileInfo {
-            path: path.clone(),
-            size,
-            created,
-        };
-        hashmap_name.entry(normalized_filename).or_insert(vec![]).push(file_info);
+        let keeper_changed = changed_paths.contains(&set.keeper.path);
+        for file_info in &set.duplicates {
+            if keeper_changed || changed_paths.contains(&file_info.path) {
+                paths_to_hash.push(file_info.path.clone());
+            }
+        }
+        if keeper_changed || set.duplicates.iter().any(|f| changed_paths.contains(&f.path)) {
+            paths_to_hash.push(set.keeper.path.clone());
+        }
+
+        set.size = set.keeper.size;
+        !set.duplicates.is_empty()
+    });
+
+    if changed_paths.is_empty() {
+        return volatile_files;
     }
 
-    // step 2: for each normalized filename group, sub-group by size and find duplicates
-    let mut total_duplicates_found = 0;
-    let mut total_files_to_delete = 0;
+    // pass 2: re-hash changed files together with their keepers
+    let hashes = scheduler::hash_files(paths_to_hash, io_profile);
 
-    for (normalized_filename, file_infos) in &hashmap_name {
-        // only process if there are multiple files with this normalized name
-        if file_infos.len() > 1 {
-            // sub-group by size within this filename group
-            let mut hashmap_size: HashMap<u64, Vec<&FileInfo>> = HashMap::new();
-            for file_info in file_infos {
-                hashmap_size.entry(file_info.size).or_insert(vec![]).push(file_info);
-            }
-
-            // check each size group for duplicates
-            for (size, size_group) in &hashmap_size {
-                if size_group.len() > 1 {
-                    total_duplicates_found += 1;
-                    total_files_to_delete += size_group.len() - 1;
-
-                    // find one specific file to keep (first one with earliest timestamp)
-                    let file_to_keep = match size_group.iter().min_by_key(|f| f.created) {
-                        Some(file) => file,
-                        None => continue,
-                    };
-
-                    println!("\n--- Duplicate Set ---");
-                    println!("Normalized filename: {}", normalized_filename);
-                    println!("Size: {} bytes", size);
-                    println!("Keeping: {}", file_to_keep.path.display());
-
-                    // list files to delete
-                    for file_info in size_group {
-                        if file_info.path != file_to_keep.path {
-                            if dry_run {
-                                println!("Would delete: {}", file_info.path.display());
-                            } else {
-                                println!("Will delete: {}", file_info.path.display());
-                            }
-                        }
+    duplicate_sets.retain_mut(|set| {
+        let keeper_changed = changed_paths.contains(&set.keeper.path);
+        if keeper_changed {
+            volatile_files.push(VolatileFile {
+                path: set.keeper.path.clone(),
+                outcome: "keeper modified during scan, copies re-hashed against it".to_string(),
+            });
+        }
+
+        set.duplicates.retain(|file_info| {
+            let changed = changed_paths.contains(&file_info.path);
+            if !changed && !keeper_changed {
+                return true;
+            }
+
+            match hashed_match(&hashes, file_info, &set.keeper) {
+                Ok(true) => {
+                    if changed {
+                        volatile_files.push(VolatileFile {
+                            path: file_info.path.clone(),
+                            outcome: "modified during scan, re-hashed and still identical to keeper".to_string(),
+                        });
                     }
+                    true
+                }
+                Ok(false) => {
+                    volatile_files.push(VolatileFile {
+                        path: file_info.path.clone(),
+                        outcome: "no longer identical to keeper, kept".to_string(),
+                    });
+                    false
+                }
+                Err(e) => {
+                    volatile_files.push(VolatileFile {
+                        path: file_info.path.clone(),
+                        outcome: format!("could not be re-hashed ({}), kept", e),
+                    });
+                    false
                 }
             }
+        });
+
+        !set.duplicates.is_empty()
+    });
+
+    volatile_files
+}
+
+// what a scan turned up, before any review or action
+struct Findings {
+    duplicate_sets: Vec<DuplicateSet>,
+    volatile_files: Vec<VolatileFile>,
+    case_collisions: Vec<CaseCollision>,
+    bursts: Vec<Burst>,
+    similar_screenshots: Vec<Vec<PathBuf>>,
+    similar_images: Vec<Vec<PathBuf>>,
+    metadata_copies: Vec<Vec<PathBuf>>,
+    image_copies: Vec<ImageCopy>,
+    oversized: Vec<DuplicateSet>,
+}
+
+// the directory and every --root, leaving out any a recursive scan of another
+// already covers so no file is seen twice. roots are compared by their
+// canonical form, since the current directory isn't one on windows
+fn scan_roots(directory: &str, options: &Options) -> Vec<PathBuf> {
+    let mut roots: Vec<(PathBuf, PathBuf)> = Vec::new();
+    for root in std::iter::once(PathBuf::from(directory)).chain(options.roots.iter().cloned()) {
+        let canonical = fs::canonicalize(&root).unwrap_or_else(|_| root.clone());
+        let covered = |outer: &PathBuf, inner: &PathBuf| {
+            outer == inner || (options.recursive && inner.starts_with(outer))
+        };
+        if roots.iter().any(|(other, _)| covered(other, &canonical)) {
+            continue;
         }
+        roots.retain(|(other, _)| !covered(&canonical, other));
+        roots.push((canonical, root));
     }
+    roots.into_iter().map(|(_, root)| root).collect()
+}
 
-    if total_duplicates_found == 0 {
-        println!("\nNo duplicates found!");
-        return;
+// the root `path` was found under (the deepest, when roots nest)
+fn root_of<'a>(path: &Path, roots: &'a [PathBuf]) -> Option<&'a PathBuf> {
+    roots
+        .iter()
+        .filter(|root| path.starts_with(root))
+        .max_by_key(|root| root.components().count())
+}
+
+// --action link: names already hard-linked to their keeper have nothing left
+// to do, and copies on another filesystem than their keeper can't be linked
+fn drop_linked(duplicate_sets: &mut Vec<DuplicateSet>) {
+    let id = |path: &Path| fs::metadata(path).ok().and_then(|metadata| consolidate::file_id(&metadata));
+    let mut other_device = 0;
+    for set in duplicate_sets.iter_mut() {
+        if let Some(keeper) = id(&set.keeper.path) {
+            set.duplicates.retain(|file_info| id(&file_info.path) != Some(keeper));
+        }
+        let before = set.duplicates.len();
+        set.duplicates.retain(|file_info| actions::linkable(&file_info.path, &set.keeper.path));
+        other_device += before - set.duplicates.len();
+    }
+    if other_device > 0 {
+        eprintln!(
+            "Note: {} copy(ies) on another filesystem than their keeper can't be hard-linked; leaving them as is",
+            other_device
+        );
     }
+    stats::filtered("on another filesystem than its keeper (--action link)", other_device as u64);
+    duplicate_sets.retain(|set| !set.duplicates.is_empty());
+}
 
-    println!("\n================================");
-    println!("Summary: Found {} duplicate set(s)", total_duplicates_found);
-    println!("Total files to delete: {}", total_files_to_delete);
+// steps 1-3: scan, filter, group and verify; None if the directory couldn't be read
+fn find_duplicates(directory: &str, options: &Options, started: &mut Instant) -> Option<Findings> {
+    if let Some(warning) = options.match_mode.warning() {
+        eprintln!("Warning: {}", warning);
+    }
+    if options.verify_apart.is_some() && options.match_mode == MatchMode::Content {
+        eprintln!("Note: --verify-apart has no effect with --match content, which hashes every group");
+    }
+    let roots = scan_roots(directory, options);
+    limits::start(options.timeout, options.max_files);
+    results::start(directory, options);
 
-    if dry_run {
-        println!("\n[DRY RUN MODE] No files were deleted.");
-        println!("Run without --dry-run to actually delete files.");
-        return;
+    // two-pass mode: learn which sizes (or names and sizes) repeat before
+    // keeping any metadata
+    let size_filter = if options.two_pass && options.hash_only.is_none() {
+        Some(scan::collect_sizes(&roots, options)?)
+    } else {
+        None
+    };
+
+    // step 1: group files by normalized filename. the order files were
+    // listed in varies between runs, so every choice made from here on sees
+    // each group sorted by path
+    let mut hashmap_name = match &options.hash_only {
+        Some(scan) => {
+            let count: usize = scan.files.values().map(Vec::len).sum();
+            eprintln!("Note: Using the {} file(s) scanned {}", count, snapshot::age(scan.created));
+            scan::regroup(scan.files.values().flatten(), options)
+        }
+        None => scan::scan_roots(&roots, options, size_filter.as_ref())?,
+    };
+    for file_infos in hashmap_name.values_mut() {
+        file_infos.sort_by(|a, b| a.path.cmp(&b.path));
     }
+    actions::recover_pending(options.dry_run);
+    stats::phase("scan", started);
 
-    print!("\nProceed with deletion? (y/N): ");
-    io::stdout().flush().unwrap();
+    if !options.types.is_empty() {
+        filter_by_type(&mut hashmap_name, &options.types);
+    }
 
-    let mut input = String::new();
-    io::stdin().read_line(&mut input).unwrap();
-    let input = input.trim().to_lowercase();
+    if options.owner_filter.is_active() {
+        ownership::filter_by_owner(&mut hashmap_name, &options.owner_filter);
+    }
+
+    if let Some(settle) = options.settle {
+        let dropped = filter_unsettled(&mut hashmap_name, settle);
+        if dropped > 0 {
+            eprintln!("Note: Leaving out {} file(s) modified within the --settle window", dropped);
+        }
+    }
+
+    if options.time != TimeSource::Modified {
+        let fallbacks = hashmap_name
+            .values()
+            .filter(|file_infos| file_infos.len() > 1)
+            .flatten()
+            .filter(|file_info| options.time.falls_back(file_info))
+            .count();
+        if fallbacks > 0 {
+            eprintln!(
+                "Note: {} file(s) have no creation time here; their modification time is used instead (see --time)",
+                fallbacks
+            );
+        }
+    }
+
+    // files whose path is ambiguous are reported but never acted on
+    let case_collisions = casefold::extract_collisions(&mut hashmap_name);
+    let colliding: usize = case_collisions.iter().map(|collision| collision.paths.len()).sum();
+    stats::filtered("case collision", colliding as u64);
+    stats::phase("filter", started);
+
+    // every image scanned, not just those sharing a name, can be in a burst
+    let mut bursts = match options.bursts {
+        Some(window) => bursts::find_bursts(hashmap_name.values().flatten(), window),
+        None => Vec::new(),
+    };
+    // any scanned file, whatever its name, can be inside a disk image
+    let image_copies = if options.disk_images {
+        diskimage::find_copies(hashmap_name.values().flatten(), options.io_profile)
+    } else {
+        Vec::new()
+    };
+    let screenshots: Vec<PathBuf> = match hashmap_name.get(screenshots::GROUP) {
+        Some(file_infos) => file_infos.iter().map(|file_info| file_info.path.clone()).collect(),
+        None => Vec::new(),
+    };
+    // every image can look like any other, whatever its name; screenshots
+    // are compared among themselves
+    let images: Vec<PathBuf> = if options.similar_images {
+        hashmap_name
+            .iter()
+            .filter(|(group, _)| group.as_str() != screenshots::GROUP)
+            .flat_map(|(_, file_infos)| file_infos)
+            .map(|file_info| file_info.path.clone())
+            .filter(|path| similar::is_image(path))
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let metadata_sets = if richness::ignores_metadata(options) {
+        richness::extract_metadata_copies(&mut hashmap_name, options)
+    } else {
+        Vec::new()
+    };
+    let truncated_sets = if options.truncated {
+        truncated::extract_truncated(&mut hashmap_name, options)
+    } else {
+        Vec::new()
+    };
+
+    // step 2: for each normalized filename group, sub-group by size and find duplicates
+    let mut duplicate_sets = find_duplicate_sets(hashmap_name, &roots, options);
+    // copies that aren't the same bytes are only removed when asked for by name
+    let metadata_copies: Vec<Vec<PathBuf>> = if options.metadata_copies_for_review {
+        let paths = |set: DuplicateSet| {
+            let copies = set.duplicates.into_iter().map(|file_info| file_info.path);
+            std::iter::once(set.keeper.path).chain(copies).collect()
+        };
+        metadata_sets.into_iter().map(paths).collect()
+    } else {
+        duplicate_sets.extend(metadata_sets);
+        Vec::new()
+    };
+    duplicate_sets.extend(truncated_sets);
+    let oversized: Vec<DuplicateSet> = match options.max_set_size {
+        Some(max) => {
+            let (oversized, sets) = duplicate_sets.into_iter().partition(|set| set.duplicates.len() + 1 > max);
+            duplicate_sets = sets;
+            oversized
+        }
+        None => Vec::new(),
+    };
+    let held_back: usize = oversized.iter().map(|set| set.duplicates.len() + 1).sum();
+    stats::filtered("oversized set (--max-set-size)", held_back as u64);
+    let whitelisted = whitelist::filter_sets(&mut duplicate_sets, options.io_profile);
+    if whitelisted > 0 && !options.summary {
+        println!("{} whitelisted set(s) not shown (see `hydra whitelist list`)", whitelisted);
+    }
+    setids::assign(&mut duplicate_sets, options.io_profile);
+    if !options.only_sets.is_empty() || !options.skip_sets.is_empty() {
+        setids::filter(&mut duplicate_sets, &options.only_sets, &options.skip_sets);
+    }
+    stats::phase("group", started);
+
+    if options.action == Action::Link {
+        drop_linked(&mut duplicate_sets);
+    }
+
+    // step 3: re-check anything that changed while we were scanning
+    let volatile_files = guard_volatile_files(&mut duplicate_sets, options.io_profile);
+    stats::phase("verify", started);
+
+    report::sort_and_limit(&mut duplicate_sets, options);
+
+    // exact copies are handled as duplicates; what's left of a burst is for review
+    if !bursts.is_empty() {
+        let removed: HashSet<&Path> = duplicate_sets
+            .iter()
+            .flat_map(|set| &set.duplicates)
+            .map(|file_info| file_info.path.as_path())
+            .collect();
+        for burst in &mut bursts {
+            burst.paths.retain(|path| !removed.contains(path.as_path()));
+        }
+        bursts.retain(|burst| burst.paths.len() > 1);
+    }
+    // exact copies are removed anyway, and those differing only in metadata
+    // are listed already; only what would be left is compared
+    let mut removed: HashSet<&PathBuf> =
+        duplicate_sets.iter().flat_map(|set| &set.duplicates).map(|file_info| &file_info.path).collect();
+    removed.extend(metadata_copies.iter().flat_map(|paths| &paths[1..]));
+    let similar_screenshots = similar::find_similar(screenshots.iter().filter(|path| !removed.contains(path)));
+    let similar_images = similar::find_similar(images.iter().filter(|path| !removed.contains(path)));
+
+    results::finish(&duplicate_sets);
+    Some(Findings {
+        duplicate_sets,
+        volatile_files,
+        case_collisions,
+        bursts,
+        similar_screenshots,
+        similar_images,
+        metadata_copies,
+        image_copies,
+        oversized,
+    })
+}
+
+// --fail-if-wasted / --fail-if-files: exit status 1 when what the scan found
+// is over either limit, for storage hygiene checks in CI
+fn threshold_status(duplicate_sets: &[DuplicateSet], options: &Options) -> i32 {
+    let files: usize = duplicate_sets.iter().map(|set| set.duplicates.len()).sum();
+    let bytes = sharing::measure(duplicate_sets).reclaimable;
+    let mut status = 0;
+
+    if let Some(limit) = options.fail_if_wasted
+        && bytes > limit
+    {
+        eprintln!(
+            "Threshold exceeded: {} reclaimable, over --fail-if-wasted {}",
+            format_bytes(bytes),
+            format_bytes(limit)
+        );
+        status = 1;
+    }
+    if let Some(limit) = options.fail_if_files
+        && files > limit
+    {
+        eprintln!("Threshold exceeded: {} file(s) to delete, over --fail-if-files {}", files, limit);
+        status = 1;
+    }
+
+    status
+}
 
-    if input != "y" && input != "yes" {
-        println!("Deletion cancelled.");
+// --format json|ndjson|csv|print0: the sets found, on the stdout channels::separate() kept for them
+fn write_structured(directory: &str, duplicate_sets: &[DuplicateSet], options: &Options) {
+    if !options.format.is_structured() {
         return;
     }
+    let mut out = channels::structured();
+    if let Err(e) = formats::write(&mut out, options.format, directory, duplicate_sets, options) {
+        eprintln!("Error: Could not write the --format output: {}", e);
+    }
+}
 
-    println!("\nDeleting files...");
-    let mut deleted_count = 0;
-    let mut error_count = 0;
-
-    for (_normalized_filename, file_infos) in &hashmap_name {
-        if file_infos.len() > 1 {
-            let mut hashmap_size: HashMap<u64, Vec<&FileInfo>> = HashMap::new();
-            for file_info in file_infos {
-                hashmap_size.entry(file_info.size).or_insert(vec![]).push(file_info);
-            }
-
-            for (_size, size_group) in &hashmap_size {
-                if size_group.len() > 1 {
-                    let file_to_keep = match size_group.iter().min_by_key(|f| f.created) {
-                        Some(file) => file,
-                        None => continue,
-                    };
-
-                    for file_info in size_group {
-                        if file_info.path != file_to_keep.path {
-                            match fs::remove_file(&file_info.path) {
-                                Ok(_) => {
-                                    println!("Deleted: {}", file_info.path.display());
-                                    deleted_count += 1;
-                                }
-                                Err(e) => {
-                                    eprintln!("Error deleting '{}': {}", file_info.path.display(), e);
-                                    error_count += 1;
-                                }
-                            }
-                        }
-                    }
-                }
-            }
+// --scan-only: list the files, group them by name and save them for
+// --hash-only, hashing nothing
+fn scan_only(directory: &str, path: &Path, options: &Options) -> i32 {
+    let roots = scan_roots(directory, options);
+    limits::start(options.timeout, options.max_files);
+    let Some(mut hashmap_name) = scan::scan_roots(&roots, options, None) else {
+        return 2;
+    };
+    for file_infos in hashmap_name.values_mut() {
+        file_infos.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+    if let Some(limit) = limits::reached() {
+        eprintln!("Error: The scan was cut short by {}; a partial list is not saved.", limit);
+        return 2;
+    }
+
+    let count: usize = hashmap_name.values().map(Vec::len).sum();
+    match scanlist::write(path, directory, &roots, &hashmap_name, options) {
+        Ok(()) => {
+            println!(
+                "Saved the {0} file(s) scanned to {1}; find the duplicates among them with `hydra --hash-only {1}`.",
+                count,
+                path.display()
+            );
+            0
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            2
         }
     }
+}
 
-    println!("\n================================");
-    println!("Deletion complete!");
-    println!("Files deleted: {}", deleted_count);
-    if error_count > 0 {
-        println!("Errors encountered: {}", error_count);
+// --summary: only the totals, with the exit status telling whether there was
+// anything to clean up (0 none, 1 some, 2 the scan failed), like diff(1). with
+// a threshold set, 1 means it was exceeded instead
+fn summarize(directory: &str, options: &Options) -> i32 {
+    let mut started = Instant::now();
+    let Some(findings) = find_duplicates(directory, options, &mut started) else {
+        return 2;
+    };
+
+    report::write_summary(&mut io::stdout(), &findings.duplicate_sets).unwrap();
+    hooks::found(&findings.duplicate_sets);
+    if let Some(depth) = options.rollup {
+        let roots = scan_roots(directory, options);
+        rollup::write_rollup(&mut io::stdout(), &findings.duplicate_sets, &roots, depth).unwrap();
+    }
+    write_structured(directory, &findings.duplicate_sets, options);
+    if options.fail_if_wasted.is_some() || options.fail_if_files.is_some() {
+        threshold_status(&findings.duplicate_sets, options)
+    } else if findings.duplicate_sets.is_empty() {
+        0
+    } else {
+        1
     }
 }
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
+// --interactive and --edit: let the user change the plan. false if they
+// cancelled or emptied it, in which case nothing is done
+fn review(duplicate_sets: &mut Vec<DuplicateSet>, options: &Options, started: &mut Instant) -> bool {
+    if options.interactive && !duplicate_sets.is_empty() {
+        let mut remembered = Vec::new();
+        let mut overrides = Vec::new();
+        let finished = interactive::review_sets(duplicate_sets, &mut remembered, &mut overrides, options.thumbnails);
+        match whitelist::remember(&remembered, options.io_profile) {
+            Ok(0) => {}
+            Ok(count) => println!("\nAdded {} set(s) to the whitelist.", count),
+            Err(e) => eprintln!("Warning: Could not save the whitelist: {}", e),
+        }
+        match keeper::learn(&overrides, &options.keeper, options.config_path.as_deref()) {
+            Ok(None) => {}
+            Ok(Some((path, count))) => println!("\nSaved {} keeper preference(s) to {}.", count, path.display()),
+            Err(e) => eprintln!("Warning: Could not save the keeper preferences: {}", e),
+        }
+        if !finished {
+            println!("\nReview cancelled. No files were deleted.");
+            return false;
+        }
+        println!("\nReview complete. Final plan:");
+        stats::phase("review", started);
+    }
+
+    if options.edit && !duplicate_sets.is_empty() {
+        match edit::edit_sets(duplicate_sets) {
+            Ok(true) => println!("\nEdited plan:"),
+            Ok(false) => {
+                println!("\nPlan is empty. No files were deleted.");
+                return false;
+            }
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                println!("No files were deleted.");
+                return false;
+            }
+        }
+        stats::phase("edit", started);
+    }
+
+    true
+}
 
-    // check for --dry-run flag
-    let dry_run = args.iter().any(|arg| arg == "--dry-run");
+fn find_and_delete_duplicate_files(directory: String, options: &Options) -> i32 {
+    let dry_run = options.dry_run;
+    let mut started = Instant::now();
 
-    if dry_run {
-        println!("Running in DRY RUN mode - no files will be deleted\n");
+    // retention for earlier --action trash runs
+    if let Some(older_than) = options.purge_older_than {
+        match purge::purge(older_than, dry_run) {
+            Ok((0, _)) => {}
+            Ok((files, bytes)) => {
+                let verb = if dry_run { "Would purge" } else { "Purged" };
+                println!("{} {} file(s) from the trash, freeing {}.\n", verb, files, format_bytes(bytes));
+            }
+            Err(e) => eprintln!("Warning: Could not purge the trash: {}", e),
+        }
+    }
+
+    let Some(findings) = find_duplicates(&directory, options, &mut started) else {
+        return 2;
+    };
+    let Findings {
+        mut duplicate_sets,
+        volatile_files,
+        case_collisions,
+        bursts,
+        similar_screenshots,
+        similar_images,
+        metadata_copies,
+        image_copies,
+        oversized,
+    } = findings;
+    let status = threshold_status(&duplicate_sets, options);
+
+    if let Some(path) = &options.snapshot {
+        match snapshot::write(path, &directory, &duplicate_sets, options) {
+            Ok(()) => {
+                println!("Saved the scan results to {0}; review them with `hydra import snapshot {0}`.", path.display())
+            }
+            Err(e) => eprintln!("Error: {}", e),
+        }
     }
 
-    find_and_delete_duplicate_files(get_current_directory(), dry_run);
-}
\ No newline at end of file
+    // there is nothing to review in a run that won't act
+    if limits::reached().is_none() && !review(&mut duplicate_sets, options, &mut started) {
+        return status;
+    }
+    hooks::found(&duplicate_sets);
+
+    let mut output = Vec::new();
+    if let Some(depth) = options.rollup {
+        rollup::write_rollup(&mut output, &duplicate_sets, &scan_roots(&directory, options), depth).unwrap();
+    }
+    report::write_report(&mut output, &duplicate_sets, &volatile_files, &case_collisions, options).unwrap();
+    bursts::write_bursts(&mut output, &bursts).unwrap();
+    similar::write_similar(&mut output, "Similar Screenshots", &similar_screenshots).unwrap();
+    similar::write_similar(&mut output, "Similar Images", &similar_images).unwrap();
+    similar::write_similar(&mut output, "Same Content, Different Metadata", &metadata_copies).unwrap();
+    diskimage::write_copies(&mut output, &image_copies).unwrap();
+    report::write_oversized(&mut output, &oversized).unwrap();
+    pager::page(&output, options.pager);
+    write_structured(&directory, &duplicate_sets, options);
+    results::reviewed(&directory, &duplicate_sets, options);
+
+    if let Some(path) = &options.output {
+        match plan::write(path, &directory, &duplicate_sets, options) {
+            Ok(_) => println!("\nSaved this plan to {0}; run `hydra apply {0}` to carry it out.", path.display()),
+            Err(e) => eprintln!("Error: {}", e),
+        }
+    }
+
+    if duplicate_sets.is_empty() {
+        return status;
+    }
+
+    if let Some(limit) = limits::reached() {
+        println!("\n{}", tr!("The run was cut short by {}, so nothing was changed.", limit));
+        return status;
+    }
+
+    if dry_run {
+        if options.fix_symlinks || options.fix_playlists {
+            let planned: HashMap<PathBuf, PathBuf> = duplicate_sets
+                .iter()
+                .flat_map(|set| set.duplicates.iter().map(|f| (f.path.clone(), set.keeper.path.clone())))
+                .collect();
+            fix_references(&directory, &planned, options, true);
+        }
+
+        if let Some(path) = &options.backup {
+            let count: usize = duplicate_sets.iter().map(|set| set.duplicates.len()).sum();
+            println!("\nWould back up {} file(s) to {}", count, path.display());
+        }
+
+        println!("\n{}", tr!("[DRY RUN MODE] No files were deleted."));
+        match options.action {
+            Action::Delete => println!("{}", tr!("Run without --dry-run to actually delete files.")),
+            Action::Trash => println!("{}", tr!("Run without --dry-run to actually trash files.")),
+            Action::Link => println!("{}", tr!("Run without --dry-run to actually link files.")),
+            Action::Exec => println!("{}", tr!("Run without --dry-run to actually run the command.")),
+        }
+        return status;
+    }
+
+    apply_sets(&directory, duplicate_sets, options);
+    status
+}
+
+// with no terminal on stdin nobody can answer the confirmation prompt, so a run
+// that would ask first only reports; acting unattended takes an explicit --yes
+fn report_only_without_terminal(mut options: Options) -> Result<Options, String> {
+    if options.summary || options.scan_only.is_some() || terminal::can_prompt() {
+        return Ok(options);
+    }
+    if options.interactive || options.edit || options.confirm == Confirm::Each {
+        return Err("--interactive, --edit and --confirm each need a terminal on stdin".to_string());
+    }
+    if !options.yes && !options.dry_run {
+        eprintln!(
+            "Note: {}",
+            tr!("stdin is not a terminal, so this run only reports. Pass --yes to act without confirmation.")
+        );
+        options.dry_run = true;
+    }
+    Ok(options)
+}
+
+// ask before anything is removed (unless --yes), once or per set with
+// --confirm each, dropping the sets that weren't confirmed. false if nothing is
+// left to do
+fn confirm(duplicate_sets: &mut Vec<DuplicateSet>, options: &Options) -> bool {
+    if options.yes {
+        return true;
+    }
+
+    if options.confirm == Confirm::Each {
+        interactive::confirm_each(duplicate_sets, options.action);
+        if duplicate_sets.is_empty() {
+            println!("\nNo sets confirmed. No files were deleted.");
+            return false;
+        }
+        return true;
+    }
+
+    rollup::write_affected(&mut io::stdout(), duplicate_sets).unwrap();
+    match options.action {
+        Action::Delete => print!("\n{}", tr!("Proceed with deletion? (y/N): ")),
+        Action::Trash => print!("\n{}", tr!("Proceed with moving files to the trash? (y/N): ")),
+        Action::Link => print!("\n{}", tr!("Proceed with replacing copies with hard links? (y/N): ")),
+        Action::Exec => print!("\n{}", tr!("Proceed with running the command on each copy? (y/N): ")),
+    }
+    io::stdout().flush().unwrap();
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input).unwrap();
+    let input = input.trim().to_lowercase();
+
+    if !i18n::is_yes(&input) {
+        println!("{}", tr!("Deletion cancelled."));
+        return false;
+    }
+    true
+}
+
+// confirm (unless --yes), then back up, remove and tidy up after the planned
+// sets; shared by normal runs and `hydra apply`
+// what apply_sets did, for `hydra gui` to show on its page
+struct Applied {
+    done: usize,
+    skipped: usize,
+    errors: usize,
+    // copies put back or never touched: rolled-back and aborted sets, copies on
+    // another filesystem, and ones still in use or failing after the retries
+    left_alone: usize,
+}
+
+// None if nothing was attempted: not confirmed, or the backup or a pre_delete
+// hook failed
+fn apply_sets(directory: &str, mut duplicate_sets: Vec<DuplicateSet>, options: &Options) -> Option<Applied> {
+    if !confirm(&mut duplicate_sets, options) {
+        return None;
+    }
+    hooks::found(&duplicate_sets);
+    // time spent at the prompt isn't part of any phase
+    let mut started = Instant::now();
+
+    if let Some(path) = &options.backup {
+        println!("\nBacking up files to {}...", path.display());
+        match backup::write_archive(path, &duplicate_sets) {
+            Ok(count) => println!("Backed up {} file(s)", count),
+            Err(e) => {
+                eprintln!("Error: Backup failed: {}", e);
+                println!("No files were deleted.");
+                return None;
+            }
+        }
+        stats::phase("backup", &mut started);
+    }
+
+    if let Err(e) = hooks::pre_delete(directory, &duplicate_sets, options) {
+        eprintln!("Error: {}", e);
+        println!("No files were deleted.");
+        return None;
+    }
+
+    let keeper_hashes = options
+        .verify_keepers
+        .then(|| verify::hash_keepers(&duplicate_sets, options.io_profile));
+
+    match options.action {
+        Action::Delete => println!("\n{}", tr!("Deleting files...")),
+        Action::Trash => println!("\n{}", tr!("Moving files to the trash...")),
+        Action::Link => println!("\n{}", tr!("Replacing copies with hard links...")),
+        Action::Exec => println!("\n{}", tr!("Running the command on each copy...")),
+    }
+    let dir_times = options.preserve_dir_times.then(|| dirtimes::capture(&duplicate_sets));
+    let mut worker_stats = actions::delete_duplicates(&mut duplicate_sets, options);
+
+    // give programs holding files open a moment, then try those files once more
+    let mut in_use: Vec<DuplicateSet> = worker_stats.iter_mut().flat_map(|stats| stats.in_use.drain(..)).collect();
+    if options.retry_in_use && !in_use.is_empty() {
+        let count: usize = in_use.iter().map(|set| set.duplicates.len()).sum();
+        println!("\nRetrying {} file(s) that were in use...", count);
+        thread::sleep(IN_USE_RETRY_DELAY);
+
+        let mut retry_stats = actions::delete_duplicates(&mut in_use, options);
+        in_use = retry_stats.iter_mut().flat_map(|stats| stats.in_use.drain(..)).collect();
+        absorb(&mut worker_stats, retry_stats);
+    }
+
+    // copies that failed transiently, as on a flaky network share, are tried
+    // again in rounds with growing pauses
+    let mut transient: Vec<DuplicateSet> =
+        worker_stats.iter_mut().flat_map(|stats| stats.transient.drain(..)).collect();
+    for delay in retry::DELAYS {
+        if transient.is_empty() {
+            break;
+        }
+        let count: usize = transient.iter().map(|set| set.duplicates.len()).sum();
+        println!("\nRetrying {} file(s) that failed with a transient error in {}s...", count, delay.as_secs());
+        thread::sleep(delay);
+
+        let mut retry_stats = actions::delete_duplicates(&mut transient, options);
+        transient = retry_stats.iter_mut().flat_map(|stats| stats.transient.drain(..)).collect();
+        in_use.extend(retry_stats.iter_mut().flat_map(|stats| stats.in_use.drain(..)));
+        absorb(&mut worker_stats, retry_stats);
+    }
+    if let Some(dir_times) = dir_times {
+        dirtimes::restore(dir_times);
+    }
+    stats::phase(options.action.verb(), &mut started);
+
+    let deleted_count: usize = worker_stats.iter().map(|stats| stats.deleted).sum();
+    let skipped_count: usize = worker_stats.iter().map(|stats| stats.skipped).sum();
+    let rolled_back_count: usize = worker_stats.iter().map(|stats| stats.rolled_back).sum();
+    let other_device_count: usize = worker_stats.iter().map(|stats| stats.other_device).sum();
+    let passed_on_count: usize = worker_stats.iter().map(|stats| stats.passed_on).sum();
+    let error_count: usize = worker_stats.iter().map(|stats| stats.errors).sum();
+    hooks::acted(deleted_count, skipped_count, error_count);
+
+    println!("\n================================");
+    match options.action {
+        Action::Delete => {
+            println!("{}", tr!("Deletion complete!"));
+            println!("{}", tr!("Files deleted: {}", deleted_count));
+        }
+        Action::Trash => {
+            println!("{}", tr!("Done!"));
+            println!("{}", tr!("Files moved to the trash: {}", deleted_count));
+        }
+        Action::Link => {
+            println!("{}", tr!("Done!"));
+            println!("{}", tr!("Files replaced with hard links: {}", deleted_count));
+        }
+        Action::Exec => {
+            println!("{}", tr!("Done!"));
+            println!("{}", tr!("Files passed to the command and gone: {}", deleted_count));
+            println!("{}", tr!("Files passed to the command and still in place: {}", passed_on_count));
+        }
+    }
+    if options.action == Action::Trash && options.empty_trash_after {
+        let trashed: Vec<PathBuf> = worker_stats.iter().flat_map(|stats| &stats.trashed).cloned().collect();
+        match purge::empty(&trashed) {
+            Ok((files, bytes)) => {
+                let freed = format_bytes(bytes);
+                println!("{}", tr!("Emptied {} file(s) moved to the trash by this run, freeing {}", files, freed));
+            }
+            Err(e) => eprintln!("Warning: Could not empty the trash: {}", e),
+        }
+    }
+    if skipped_count > 0 {
+        println!("{}", tr!("Files skipped (modified since scan): {}", skipped_count));
+    }
+    if rolled_back_count > 0 {
+        println!("{}", tr!("Files left in place (their set was rolled back): {}", rolled_back_count));
+    }
+    if other_device_count > 0 {
+        println!("{}", tr!("Files not linked (on another filesystem than their keeper): {}", other_device_count));
+    }
+    if error_count > 0 {
+        println!("{}", tr!("Errors encountered: {}", error_count));
+    }
+
+    let transient_count: usize = transient.iter().map(|set| set.duplicates.len()).sum();
+    if transient_count > 0 {
+        let line = tr!("Files skipped (transient errors, retried {} times): {}", retry::DELAYS.len(), transient_count);
+        println!("{}", line);
+        for file_info in transient.iter().flat_map(|set| &set.duplicates) {
+            println!("  {}", file_info.path.display());
+        }
+    }
+
+    let in_use_count: usize = in_use.iter().map(|set| set.duplicates.len()).sum();
+    if in_use_count > 0 {
+        println!("{}", tr!("Files skipped (in use by another program): {}", in_use_count));
+        for file_info in in_use.iter().flat_map(|set| &set.duplicates) {
+            println!("  {}", file_info.path.display());
+        }
+        if !options.retry_in_use {
+            println!("Close the programs using them and run again, or pass --retry-in-use.");
+        }
+    }
+
+    let aborted: Vec<&(PathBuf, String)> = worker_stats.iter().flat_map(|stats| &stats.aborted).collect();
+    let applied = Applied {
+        done: deleted_count + passed_on_count,
+        skipped: skipped_count,
+        errors: error_count,
+        left_alone: rolled_back_count + other_device_count + transient_count + in_use_count + aborted.len(),
+    };
+    if !aborted.is_empty() {
+        println!("{}", tr!("Sets aborted (keeper failed verification): {}", aborted.len()));
+        for (keeper, reason) in aborted {
+            println!("  {}: {}", keeper.display(), reason);
+        }
+    }
+
+    // per-worker breakdown helps spot a single stuck or failing worker
+    if worker_stats.len() > 1 && (skipped_count > 0 || error_count > 0) {
+        for (index, stats) in worker_stats.iter().enumerate() {
+            println!(
+                "  Worker {}: {} {}, {} skipped, {} error(s)",
+                index + 1,
+                stats.deleted,
+                options.action.past_tense().to_lowercase(),
+                stats.skipped,
+                stats.errors
+            );
+        }
+    }
+
+    if options.fix_symlinks || options.fix_playlists {
+        let removed: HashMap<PathBuf, PathBuf> = worker_stats
+            .into_iter()
+            .flat_map(|stats| stats.removed)
+            .collect();
+        fix_references(directory, &removed, options, false);
+        stats::phase("fix references", &mut started);
+    }
+
+    if let Some(before) = keeper_hashes {
+        println!();
+        verify::verify_keepers(&before, options.io_profile);
+        stats::phase("verify keepers", &mut started);
+    }
+    Some(applied)
+}
+
+// fold what a retry did into the run's stats
+fn absorb(worker_stats: &mut [actions::WorkerStats], retry_stats: Vec<actions::WorkerStats>) {
+    for (stats, retried) in worker_stats.iter_mut().zip(retry_stats) {
+        stats.deleted += retried.deleted;
+        stats.skipped += retried.skipped;
+        stats.rolled_back += retried.rolled_back;
+        stats.other_device += retried.other_device;
+        stats.passed_on += retried.passed_on;
+        stats.errors += retried.errors;
+        stats.removed.extend(retried.removed);
+        stats.trashed.extend(retried.trashed);
+        stats.aborted.extend(retried.aborted);
+        stats.in_use.extend(retried.in_use);
+        stats.transient.extend(retried.transient);
+    }
+}
+
+// repoint symlinks and playlist entries at keepers; `removed` maps each
+// deleted (or to-be-deleted) path to its keeper
+fn fix_references(directory: &str, removed: &HashMap<PathBuf, PathBuf>, options: &Options, dry_run: bool) {
+    if options.fix_symlinks {
+        println!("\nChecking symlinks...");
+        let report = symlinks::fix_symlinks(Path::new(directory), options.recursive, removed, dry_run);
+
+        if report.retargeted == 0 && report.dangling.is_empty() {
+            println!("No symlinks needed updating.");
+        }
+        for (link, target) in &report.dangling {
+            println!("Dangling symlink (could not fix): {} -> {}", link.display(), target.display());
+        }
+    }
+
+    if options.fix_playlists {
+        println!("\nChecking playlists...");
+        if playlists::fix_playlists(Path::new(directory), options.recursive, removed, dry_run) == 0 {
+            println!("No playlists needed updating.");
+        }
+    }
+}
+
+// parse a byte count with an optional K/M/G/T suffix (powers of 1024)
+fn parse_size(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let (digits, multiplier) = match value.char_indices().last()? {
+        (i, 'K' | 'k') => (&value[..i], 1u64 << 10),
+        (i, 'M' | 'm') => (&value[..i], 1 << 20),
+        (i, 'G' | 'g') => (&value[..i], 1 << 30),
+        (i, 'T' | 't') => (&value[..i], 1 << 40),
+        _ => (value, 1),
+    };
+
+    digits.parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+// parse a duration such as 2s, 500ms, 5m or 1h; a bare number is seconds
+fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let amount: u64 = value[..split].parse().ok()?;
+
+    match &value[split..] {
+        "ms" => Some(Duration::from_millis(amount)),
+        "" | "s" => Some(Duration::from_secs(amount)),
+        "m" => Some(Duration::from_secs(amount.checked_mul(60)?)),
+        "h" => Some(Duration::from_secs(amount.checked_mul(3600)?)),
+        "d" => Some(Duration::from_secs(amount.checked_mul(86400)?)),
+        "w" => Some(Duration::from_secs(amount.checked_mul(7 * 86400)?)),
+        _ => None,
+    }
+}
+
+// delete, trash, link, or exec:'CMD {path} {keeper}'
+fn set_action(options: &mut Options, value: &str) -> Result<(), String> {
+    if let Some(command) = value.strip_prefix("exec:") {
+        options.exec = exec::split(command).map_err(|e| format!("invalid --action exec: {}", e))?;
+        options.action = Action::Exec;
+        return Ok(());
+    }
+    match Action::parse(value) {
+        Some(Action::Exec) => Err("--action exec needs a command, as in exec:'CMD {path} {keeper}'".to_string()),
+        Some(action) => {
+            options.action = action;
+            Ok(())
+        }
+        None => Err(format!("invalid --action '{}' (expected delete, trash, link, exec:CMD)", value)),
+    }
+}
+
+// the config's [defaults] section, as `hydra init` writes it; returns the
+// folders to scan when no --root is given
+fn apply_defaults(options: &mut Options, config: &config::Config) -> Result<Vec<PathBuf>, String> {
+    let mut folders = Vec::new();
+    for section in config.sections_named("defaults") {
+        if let Some(value) = section.get("folders") {
+            let paths = value.as_string_array().ok_or("[defaults] `folders` needs a list of directories")?;
+            for path in paths {
+                folders.push(fs::canonicalize(&path).map_err(|e| format!("could not read folder '{}': {}", path, e))?);
+            }
+        }
+        if let Some(value) = section.get("action") {
+            let value = value.as_str().ok_or("[defaults] `action` needs to be a string")?;
+            set_action(options, value).map_err(|e| format!("[defaults] {}", e))?;
+        }
+        if let Some(value) = section.get("presets") {
+            let names = value.as_string_array().ok_or("[defaults] `presets` needs a list of preset names")?;
+            for name in names {
+                let directories = presets::directories(&name)
+                    .ok_or(format!("unknown preset '{}' (expected {})", name, presets::names()))?;
+                options.excluded_directories.extend(directories);
+            }
+        }
+        if let Some(value) = section.get("recursive") {
+            options.recursive = value.as_bool().ok_or("[defaults] `recursive` needs true or false")?;
+        }
+    }
+    Ok(folders)
+}
+
+// named bundles of defaults for common cleanups
+fn apply_profile(options: &mut Options, name: &str) -> Result<(), String> {
+    match name {
+        // copy-suffix matching in one folder, recoverable from the trash
+        "downloads" => {
+            options.action = Action::Trash;
+        }
+        "photos" => {
+            options.recursive = true;
+            options.types = vec![FileClass::Image];
+            options.merge_metadata = true;
+            options.ignore_image_metadata = true;
+            options.metadata_copies_for_review = true;
+            options.similar_images = true;
+            options.prefer_metadata = true;
+            options.action = Action::Trash;
+        }
+        "music" => {
+            options.recursive = true;
+            options.types = vec![FileClass::Audio];
+            options.ignore_audio_tags = true;
+            options.metadata_copies_for_review = true;
+            options.prefer_metadata = true;
+            options.fix_playlists = true;
+            options.action = Action::Trash;
+        }
+        "mail" => {
+            options.recursive = true;
+            options.mail = true;
+            options.action = Action::Trash;
+        }
+        _ => return Err(format!("unknown --profile '{}' (expected downloads, photos, music, mail)", name)),
+    }
+
+    Ok(())
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        dry_run: false,
+        io_profile: IoProfile::Auto,
+        bwlimit: None,
+        hash_threads: None,
+        hash_buffer: None,
+        idle_priority: false,
+        jobs: actions::default_jobs(),
+        delete_batch: None,
+        delete_interval: None,
+        two_pass: false,
+        xattr_cache: false,
+        dir_cache: false,
+        merge_metadata: false,
+        merge_acls: None,
+        fix_symlinks: false,
+        fix_playlists: false,
+        preserve_dir_times: false,
+        recursive: false,
+        skip_hidden: false,
+        include_system_files: false,
+        include_generated: false,
+        generated: Generated::default(),
+        allow_libraries: false,
+        include_snapshots: false,
+        excluded_directories: Vec::new(),
+        keep_per_dir: false,
+        directory: None,
+        roots: Vec::new(),
+        within_roots_only: false,
+        keep_matching: None,
+        mail: false,
+        delete_matching: None,
+        explain: false,
+        group_by: GroupBy::Set,
+        interactive: false,
+        edit: false,
+        thumbnails: true,
+        pager: true,
+        sort: None,
+        reverse: false,
+        limit: None,
+        max_set_size: Some(DEFAULT_MAX_SET_SIZE),
+        types: Vec::new(),
+        match_mode: MatchMode::Content,
+        rollup: None,
+        verify_apart: None,
+        truncated: false,
+        check_integrity: false,
+        ignore_image_metadata: false,
+        ignore_audio_tags: false,
+        metadata_copies_for_review: false,
+        prefer_metadata: false,
+        ignore_extension: false,
+        bursts: None,
+        disk_images: false,
+        screenshots: false,
+        similar_images: false,
+        owner_filter: OwnerFilter::default(),
+        normalizer: Normalizer::new(),
+        backup: None,
+        retry_in_use: false,
+        verify_keepers: false,
+        purge_older_than: None,
+        empty_trash_after: false,
+        stats: false,
+        summary: false,
+        fail_if_wasted: None,
+        fail_if_files: None,
+        timeout: None,
+        max_files: None,
+        settle: None,
+        heartbeat: heartbeat::DEFAULT_INTERVAL,
+        output: None,
+        results: config::state_dir().map(|dir| dir.join("last-run.json")),
+        lang: None,
+        format: Format::Text,
+        log_format: LogFormat::Text,
+        only_sets: Vec::new(),
+        skip_sets: Vec::new(),
+        snapshot: None,
+        scan_only: None,
+        hash_only: None,
+        yes: false,
+        confirm: Confirm::Once,
+        time: TimeSource::Created,
+        time_tolerance: Duration::ZERO,
+        action: Action::Delete,
+        hooks: Hooks::default(),
+        keeper: Preferences::default(),
+        pause: Pause::default(),
+        scheduled: false,
+        config_path: None,
+        exec: Vec::new(),
+        plugin: None,
+    };
+
+    let config_path = match args.iter().position(|arg| arg == "--config") {
+        Some(index) => Some(PathBuf::from(args.get(index + 1).ok_or("--config requires a path")?)),
+        None => config::default_path().filter(|path| path.is_file()),
+    };
+    let config = match &config_path {
+        Some(path) => Some(config::load(path)?),
+        None => None,
+    };
+    // the config's [defaults] come before the profile and any explicit flag
+    let mut folders = Vec::new();
+    if let (Some(path), Some(config)) = (&config_path, &config) {
+        folders = apply_defaults(&mut options, config).map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+
+    // a profile only sets defaults, so apply it before any explicit flag
+    if let Some(index) = args.iter().position(|arg| arg == "--profile") {
+        let name = args.get(index + 1).ok_or("--profile requires a name (downloads, photos, music, mail)")?;
+        apply_profile(&mut options, name)?;
+    }
+
+    let mut args = args.iter().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dry-run" => options.dry_run = true,
+            "--profile" => {
+                args.next();
+            }
+            "--config" => {
+                args.next();
+            }
+            "--action" => {
+                let value = args.next().ok_or("--action requires a value (delete, trash, link, exec:CMD)")?;
+                set_action(&mut options, value)?;
+            }
+            "--backup" => {
+                let value = args.next().ok_or("--backup requires an archive path (e.g. deleted.tar.zst)")?;
+                let path = PathBuf::from(value);
+                backup::validate(&path)?;
+                options.backup = Some(path);
+            }
+            "-r" | "--recursive" => options.recursive = true,
+            "--skip-hidden" => options.skip_hidden = true,
+            "--include-system-files" => options.include_system_files = true,
+            "--include-generated" => options.include_generated = true,
+            "--allow-libraries" => options.allow_libraries = true,
+            "--include-snapshots" => options.include_snapshots = true,
+            "--preset" => {
+                let value = args.next().ok_or(format!("--preset requires a name ({})", presets::names()))?;
+                for name in value.split(',') {
+                    let directories = presets::directories(name.trim())
+                        .ok_or(format!("unknown --preset '{}' (expected {})", name, presets::names()))?;
+                    options.excluded_directories.extend(directories);
+                }
+            }
+            "-i" | "--interactive" => options.interactive = true,
+            "--edit" => options.edit = true,
+            "--no-thumbnails" => options.thumbnails = false,
+            "--no-pager" => options.pager = false,
+            "--keep-per-dir" => options.keep_per_dir = true,
+            "--root" => {
+                let value = args.next().ok_or("--root requires a directory")?;
+                let root = fs::canonicalize(value).map_err(|e| format!("could not read --root '{}': {}", value, e))?;
+                options.roots.push(root);
+            }
+            "--within-roots-only" => options.within_roots_only = true,
+            "--explain" => options.explain = true,
+            "--keep-matching" | "--delete-matching" => {
+                let value = args.next().ok_or(format!("{} requires a regular expression", arg))?;
+                let re = Regex::new(value).map_err(|e| format!("invalid {} pattern: {}", arg, e))?;
+                if arg == "--keep-matching" {
+                    options.keep_matching = Some(re);
+                } else {
+                    options.delete_matching = Some(re);
+                }
+            }
+            "--group-by" => {
+                let value = args.next().ok_or("--group-by requires a value (set, dir, volume)")?;
+                options.group_by = GroupBy::parse(value)
+                    .ok_or(format!("invalid --group-by '{}' (expected set, dir, volume)", value))?;
+            }
+            "--sort" => {
+                let value = args.next().ok_or("--sort requires a value (size, count, name, path)")?;
+                options.sort = Some(
+                    SortKey::parse(value).ok_or(format!("invalid --sort '{}' (expected size, count, name, path)", value))?,
+                );
+            }
+            "--reverse" => options.reverse = true,
+            "--time" => {
+                let value = args.next().ok_or("--time requires a value (created, modified, oldest-of-both)")?;
+                options.time = TimeSource::parse(value).ok_or(format!(
+                    "invalid --time '{}' (expected created, modified, oldest-of-both)",
+                    value
+                ))?;
+            }
+            "--time-tolerance" => {
+                let value = args.next().ok_or("--time-tolerance requires a duration (e.g. 2s)")?;
+                options.time_tolerance = parse_duration(value).ok_or(format!("invalid --time-tolerance '{}'", value))?;
+            }
+            "--match" => {
+                let value = args.next().ok_or("--match requires a value (content, name+size, size, name)")?;
+                options.match_mode = MatchMode::parse(value).ok_or(format!(
+                    "invalid --match '{}' (expected content, name+size, size, name)",
+                    value
+                ))?;
+            }
+            "--verify-apart" => {
+                let value = args.next().ok_or("--verify-apart requires a number of directory steps or `project`")?;
+                options.verify_apart = Some(Apart::parse(value).ok_or(format!(
+                    "invalid --verify-apart '{}' (expected a number of directory steps, project)",
+                    value
+                ))?);
+            }
+            "--rollup" => options.rollup = options.rollup.or(Some(1)),
+            "--rollup-depth" => {
+                let value = args.next().ok_or("--rollup-depth requires a number of levels")?;
+                match value.parse() {
+                    Ok(depth) if depth > 0 => options.rollup = Some(depth),
+                    _ => return Err(format!("invalid --rollup-depth '{}' (expected a positive number)", value)),
+                }
+            }
+            "--truncated" => options.truncated = true,
+            "--check-integrity" => options.check_integrity = true,
+            "--ignore-image-metadata" => {
+                options.ignore_image_metadata = true;
+                options.metadata_copies_for_review = false;
+            }
+            "--ignore-audio-tags" => {
+                options.ignore_audio_tags = true;
+                options.metadata_copies_for_review = false;
+            }
+            "--prefer-metadata" => options.prefer_metadata = true,
+            "--ignore-extension" => options.ignore_extension = true,
+            "--bursts" => {
+                let value = args.next().ok_or("--bursts requires a number of seconds")?;
+                let seconds: u32 = value.parse().map_err(|_| format!("invalid --bursts '{}'", value))?;
+                options.bursts = Some(seconds.into());
+            }
+            "--screenshots" => options.screenshots = true,
+            "--similar-images" => options.similar_images = true,
+            "--disk-images" => options.disk_images = true,
+            "--plugin" => {
+                let value = args.next().ok_or("--plugin requires a WebAssembly file (e.g. rules.wasm)")?;
+                options.plugin = Some(Plugin::load(Path::new(value))?);
+            }
+            "--limit" => {
+                let value = args.next().ok_or("--limit requires a value")?;
+                options.limit = Some(value.parse().map_err(|_| format!("invalid --limit '{}'", value))?);
+            }
+            "--max-set-size" => {
+                let value = args.next().ok_or("--max-set-size requires a number of files (or 0 for no limit)")?;
+                let max: usize = value.parse().map_err(|_| format!("invalid --max-set-size '{}'", value))?;
+                options.max_set_size = (max > 0).then_some(max);
+            }
+            "--type" => {
+                let value = args.next().ok_or("--type requires a value (image, video, audio, document, archive)")?;
+                for name in value.split(',') {
+                    options.types.push(FileClass::parse(name.trim()).ok_or(format!(
+                        "invalid --type '{}' (expected image, video, audio, document, archive)",
+                        name
+                    ))?);
+                }
+            }
+            "--owner" | "--group" | "--writable-only" if !cfg!(unix) => {
+                return Err(format!("{} is only supported on Unix", arg));
+            }
+            "--owner" => {
+                let value = args.next().ok_or("--owner requires a user name or uid")?;
+                options.owner_filter.uid = Some(ownership::lookup_user(value).ok_or(format!("unknown user '{}'", value))?);
+            }
+            "--group" => {
+                let value = args.next().ok_or("--group requires a group name or gid")?;
+                options.owner_filter.gid =
+                    Some(ownership::lookup_group(value).ok_or(format!("unknown group '{}'", value))?);
+            }
+            "--writable-only" => options.owner_filter.writable_only = true,
+            "--io-profile" => {
+                let value = args.next().ok_or("--io-profile requires a value (auto, hdd, ssd)")?;
+                options.io_profile = IoProfile::parse(value)
+                    .ok_or(format!("invalid --io-profile '{}' (expected auto, hdd, ssd)", value))?;
+            }
+            "--bwlimit" => {
+                let value = args.next().ok_or("--bwlimit requires a value (bytes/sec, e.g. 20M)")?;
+                match parse_size(value) {
+                    Some(limit) if limit > 0 => options.bwlimit = Some(limit),
+                    _ => return Err(format!("invalid --bwlimit '{}'", value)),
+                }
+            }
+            "--idle-priority" => options.idle_priority = true,
+            "--scheduled" => options.scheduled = true,
+            "--two-pass" => options.two_pass = true,
+            "--xattr-cache" => options.xattr_cache = true,
+            "--dir-cache" => options.dir_cache = true,
+            "--merge-metadata" => options.merge_metadata = true,
+            "--merge-acls" => {
+                let value = args.next().ok_or("--merge-acls requires a mode (union, strict)")?;
+                options.merge_acls =
+                    Some(AclMerge::parse(value).ok_or(format!("invalid --merge-acls '{}' (expected union, strict)", value))?);
+            }
+            "--fix-symlinks" => options.fix_symlinks = true,
+            "--preserve-dir-times" => options.preserve_dir_times = true,
+            "--fix-playlists" => options.fix_playlists = true,
+            "--retry-in-use" => options.retry_in_use = true,
+            "--verify-keepers" => options.verify_keepers = true,
+            "--empty-trash-after" if cfg!(windows) => {
+                return Err("--empty-trash-after can't find items in the Windows recycle bin".to_string());
+            }
+            "--empty-trash-after" => options.empty_trash_after = true,
+            "--purge-older-than" => {
+                let value = args.next().ok_or("--purge-older-than requires a duration (e.g. 30d)")?;
+                options.purge_older_than =
+                    Some(parse_duration(value).ok_or(format!("invalid --purge-older-than '{}'", value))?);
+            }
+            "--stats" => options.stats = true,
+            "--summary" => options.summary = true,
+            "--fail-if-wasted" => {
+                let value = args.next().ok_or("--fail-if-wasted requires a size (e.g. 10G)")?;
+                options.fail_if_wasted =
+                    Some(parse_size(value).ok_or(format!("invalid --fail-if-wasted '{}'", value))?);
+            }
+            "--fail-if-files" => {
+                let value = args.next().ok_or("--fail-if-files requires a number of files")?;
+                options.fail_if_files =
+                    Some(value.parse().map_err(|_| format!("invalid --fail-if-files '{}'", value))?);
+            }
+            "--timeout" => {
+                let value = args.next().ok_or("--timeout requires a duration (e.g. 30m)")?;
+                options.timeout = Some(parse_duration(value).ok_or(format!("invalid --timeout '{}'", value))?);
+            }
+            "--settle" => {
+                let value = args.next().ok_or("--settle requires a duration (e.g. 10m)")?;
+                options.settle = Some(parse_duration(value).ok_or(format!("invalid --settle '{}'", value))?);
+            }
+            "--max-files" => {
+                let value = args.next().ok_or("--max-files requires a number of files")?;
+                options.max_files = Some(value.parse().map_err(|_| format!("invalid --max-files '{}'", value))?);
+            }
+            "--heartbeat" => {
+                let value = args.next().ok_or("--heartbeat requires a duration (e.g. 30s, or 0 for none)")?;
+                options.heartbeat = parse_duration(value).ok_or(format!("invalid --heartbeat '{}'", value))?;
+            }
+            "--output" => {
+                let value = args.next().ok_or("--output requires a file path (e.g. report.json)")?;
+                options.output = Some(PathBuf::from(value));
+            }
+            "--results" => {
+                let value = args.next().ok_or("--results requires a file path")?;
+                options.results = Some(PathBuf::from(value));
+            }
+            "--no-results" => options.results = None,
+            "--format" => {
+                let value = args.next().ok_or("--format requires a format (text, json, ndjson, csv, print0)")?;
+                options.format = Format::parse(value)
+                    .ok_or(format!("invalid --format '{}' (expected text, json, ndjson, csv, print0)", value))?;
+            }
+            "--lang" => {
+                let value = args.next().ok_or("--lang requires a language (en, de, es)")?;
+                options.lang =
+                    Some(Language::parse(value).ok_or(format!("invalid --lang '{}' (expected en, de, es)", value))?);
+            }
+            "--log-format" => {
+                let value = args.next().ok_or("--log-format requires a format (text, json)")?;
+                options.log_format =
+                    LogFormat::parse(value).ok_or(format!("invalid --log-format '{}' (expected text, json)", value))?;
+            }
+            "--only-set" => {
+                let value = args.next().ok_or("--only-set requires a set ID")?;
+                options.only_sets.extend(setids::parse(value, "--only-set")?);
+            }
+            "--skip-set" => {
+                let value = args.next().ok_or("--skip-set requires a set ID")?;
+                options.skip_sets.extend(setids::parse(value, "--skip-set")?);
+            }
+            "--scan-only" => {
+                let value = args.next().ok_or("--scan-only requires a file path (e.g. files.json)")?;
+                options.scan_only = Some(PathBuf::from(value));
+            }
+            "--hash-only" => {
+                let value = args.next().ok_or("--hash-only requires a file saved by --scan-only")?;
+                options.hash_only = Some(scanlist::read(Path::new(value))?);
+            }
+            "--snapshot" => {
+                let value = args.next().ok_or("--snapshot requires a file path (e.g. scan.json)")?;
+                options.snapshot = Some(PathBuf::from(value));
+            }
+            "-y" | "--yes" => options.yes = true,
+            "--confirm" => {
+                let value = args.next().ok_or("--confirm requires a value (once, each)")?;
+                options.confirm =
+                    Confirm::parse(value).ok_or(format!("invalid --confirm '{}' (expected once, each)", value))?;
+            }
+            "--delete-batch" => {
+                let value = args.next().ok_or("--delete-batch requires a number of files")?;
+                match value.parse::<usize>() {
+                    Ok(batch) if batch > 0 => options.delete_batch = Some(batch),
+                    _ => return Err(format!("invalid --delete-batch '{}'", value)),
+                }
+            }
+            "--delete-interval" => {
+                let value = args.next().ok_or("--delete-interval requires milliseconds")?;
+                let millis: u64 = value.parse().map_err(|_| format!("invalid --delete-interval '{}'", value))?;
+                options.delete_interval = Some(Duration::from_millis(millis));
+            }
+            "--jobs" => {
+                let value = args.next().ok_or("--jobs requires a value")?;
+                match value.parse::<usize>() {
+                    Ok(jobs) if jobs > 0 => options.jobs = jobs,
+                    _ => return Err(format!("invalid --jobs '{}'", value)),
+                }
+            }
+            _ => return Err(format!("unknown argument '{}'", arg)),
+        }
+    }
+
+    if options.scan_only.is_some() && options.hash_only.is_some() {
+        return Err("--scan-only and --hash-only each run one phase and can't be combined".to_string());
+    }
+    // a scan list brings the folders it was made from, and hashing it acts on
+    // nothing
+    if let Some(scan) = &options.hash_only {
+        options.directory = Some(scan.directory.clone());
+        options.roots = scan.roots.clone();
+        options.dry_run = true;
+    }
+
+    if options.two_pass
+        && let Some(flag) = two_pass_conflict(&options)
+    {
+        return Err(format!("--two-pass can't be combined with {}", flag));
+    }
+
+    if options.summary && (options.interactive || options.edit) {
+        return Err("--summary only reports and can't be combined with --interactive or --edit".to_string());
+    }
+
+    if options.truncated && !options.match_mode.uses_name() {
+        return Err("--truncated can't be combined with --match size".to_string());
+    }
+
+    // files differing only in extension are often different formats, so
+    // only their contents can say they are copies
+    if options.ignore_extension && options.match_mode != MatchMode::Content {
+        return Err("--ignore-extension needs --match content".to_string());
+    }
+    // screenshots share a group whatever their names, so contents must decide
+    if options.screenshots && options.match_mode != MatchMode::Content {
+        return Err("--screenshots needs --match content".to_string());
+    }
+
+    if options.roots.is_empty()
+        && let Some((first, rest)) = folders.split_first()
+    {
+        options.directory = Some(first.to_string_lossy().to_string());
+        options.roots = rest.to_vec();
+    }
+
+    options.config_path = config_path.clone();
+    if let (Some(path), Some(config)) = (config_path, config) {
+        options.normalizer = Normalizer::from_config(&config).map_err(|e| format!("{}: {}", path.display(), e))?;
+        options.hooks = Hooks::from_config(&config).map_err(|e| format!("{}: {}", path.display(), e))?;
+        options.generated = Generated::from_config(&config).map_err(|e| format!("{}: {}", path.display(), e))?;
+        options.keeper = Preferences::from_config(&config).map_err(|e| format!("{}: {}", path.display(), e))?;
+        options.pause = Pause::from_config(&config).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        // [hashing] threads / buffer-kb, as `hydra bench` recommends
+        for section in config.sections_named("hashing") {
+            let setting = |key: &str| match section.get(key) {
+                None => Ok(None),
+                Some(value) => match value.as_integer() {
+                    Some(number) if number > 0 => Ok(Some(number as usize)),
+                    _ => Err(format!("{}: [hashing] `{}` needs a positive number", path.display(), key)),
+                },
+            };
+            options.hash_threads = setting("threads")?.or(options.hash_threads);
+            options.hash_buffer = setting("buffer-kb")?.map(|kb| kb * 1024).or(options.hash_buffer);
+        }
+    }
+    if options.ignore_extension {
+        options.normalizer.ignore_extension();
+    }
+    // the report goes to stderr alongside the structured output, not to a pager
+    if options.format.is_structured() {
+        options.pager = false;
+    }
+
+    Ok(options)
+}
+
+// the process-wide settings a run's options ask for; shared by a scan and
+// `hydra apply`, which --act-only runs
+// a flag whose mode needs files the two-pass filter drops, those of a name and
+// size no other file has: same-named files of different sizes, copies whose
+// size differs by their metadata, or images and bursts compared whatever
+// their names
+fn two_pass_conflict(options: &Options) -> Option<&'static str> {
+    let flags = [
+        (!options.match_mode.uses_size(), "--match name"),
+        (options.truncated, "--truncated"),
+        (options.ignore_image_metadata, "--ignore-image-metadata"),
+        (options.ignore_audio_tags, "--ignore-audio-tags"),
+        (options.screenshots, "--screenshots"),
+        (options.similar_images, "--similar-images"),
+        (options.bursts.is_some(), "--bursts"),
+        (options.disk_images, "--disk-images"),
+    ];
+    flags.iter().find(|(set, _)| *set).map(|(_, flag)| *flag)
+}
+
+fn set_up(options: Options) -> Result<Options, String> {
+    // before anything is printed, so all of it lands on the right channel and
+    // in the right language. structured logs stay in English to be matched on
+    if options.log_format == LogFormat::Json {
+        i18n::select(Language::English);
+    } else if let Some(language) = options.lang {
+        i18n::select(language);
+    }
+    if options.format.is_structured()
+        && let Err(e) = channels::separate()
+    {
+        eprintln!("Warning: Could not keep stdout for --format output: {}", e);
+    }
+    if options.log_format == LogFormat::Json
+        && let Err(e) = channels::log_json()
+    {
+        eprintln!("Warning: Could not switch to --log-format json: {}", e);
+    }
+    let options = report_only_without_terminal(options)?;
+
+    if let Some(limit) = options.bwlimit {
+        hash::set_bandwidth_limit(limit);
+    }
+    heartbeat::set_interval(options.heartbeat);
+    if options.scheduled {
+        power::set_pause(options.pause);
+    }
+
+    if options.xattr_cache {
+        hash::enable_xattr_cache();
+    }
+
+    if let Some(threads) = options.hash_threads {
+        scheduler::set_workers(threads);
+    }
+    if let Some(bytes) = options.hash_buffer {
+        hash::set_buffer_size(bytes);
+    }
+
+    if options.idle_priority && !priority::set_idle_priority() {
+        eprintln!("Warning: Could not lower process priority, continuing at normal priority");
+    }
+
+    Ok(options)
+}
+
+fn run_subcommand(name: &str, args: &[String]) -> Option<Result<(), String>> {
+    match name {
+        "estimate" => Some(estimate::run(args)),
+        "merge" => Some(merge::run(args)),
+        "apply" => Some(plan::run(args)),
+        "compare" => Some(compare::run(args)),
+        "whitelist" => Some(whitelist::run(args)),
+        "purge" => Some(purge::run(args)),
+        "consolidate" => Some(consolidate::run(args)),
+        "import" => Some(snapshot::import(args)),
+        "agent" => Some(remote::agent(args)),
+        "remote" => Some(remote::remote(args)),
+        "find-copies" => Some(findcopies::run(args)),
+        "big" => Some(big::run(args)),
+        "bench" => Some(bench::run(args)),
+        "init" => Some(init::run(args)),
+        "gui" => Some(gui::run(args)),
+        "shell-integration" => Some(contextmenu::run(args)),
+        "stats" => Some(breakdown::run(args)),
+        _ => None,
+    }
+}
+
+fn main() {
+    environment::mark_started();
+    let args: Vec<String> = env::args().collect();
+
+    if let Some(result) = args.get(1).and_then(|name| run_subcommand(name, &args[2..])) {
+        if let Err(e) = result {
+            eprintln!("Error: {}", e);
+            channels::finish();
+            process::exit(1);
+        }
+        channels::finish();
+        return;
+    }
+
+    let fail = |e: String| -> ! {
+        eprintln!("Error: {}", e);
+        channels::finish();
+        process::exit(2);
+    };
+    // --act-only PLAN is `hydra apply PLAN`, and fails the same way
+    if let Some(index) = args.iter().position(|arg| arg == "--act-only") {
+        let mut rest = args[1..].to_vec();
+        rest.remove(index - 1);
+        let result = if index >= args.len() - 1 {
+            Err("--act-only requires a plan saved with --output".to_string())
+        } else if rest.iter().any(|arg| arg == "--scan-only" || arg == "--hash-only") {
+            Err("--act-only runs one phase and can't be combined with --scan-only or --hash-only".to_string())
+        } else {
+            let plan = rest.remove(index - 1);
+            rest.insert(0, plan);
+            plan::run(&rest)
+        };
+        if let Err(e) = result {
+            eprintln!("Error: {}", e);
+            channels::finish();
+            process::exit(1);
+        }
+        channels::finish();
+        return;
+    }
+    let options = parse_args(&args).and_then(set_up).unwrap_or_else(|e| fail(e));
+
+    if options.dry_run && !options.summary && options.scan_only.is_none() {
+        println!("{}\n", tr!("Running in DRY RUN mode - no files will be deleted"));
+    }
+
+    let directory = options.directory.clone().unwrap_or_else(get_current_directory);
+    hooks::pre_scan(&directory, &options).unwrap_or_else(|e| fail(e));
+    let started = Instant::now();
+    let status = if let Some(path) = &options.scan_only {
+        scan_only(&directory, path, &options)
+    } else if options.summary {
+        summarize(&directory, &options)
+    } else {
+        find_and_delete_duplicate_files(directory.clone(), &options)
+    };
+    hooks::post_run(&directory, &options, status);
+
+    if options.stats {
+        stats::print(started.elapsed());
+    }
+    channels::finish();
+    process::exit(status);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // options as a bare `hydra ARGS...` would get them, without the user's config
+    fn options(args: &[&str], scratch: &Path) -> Options {
+        let config = scratch.join("hydra.toml");
+        fs::write(&config, "").unwrap();
+        let mut argv = vec!["hydra".to_string(), "--config".to_string(), config.display().to_string()];
+        argv.extend(args.iter().map(|arg| arg.to_string()));
+        parse_args(&argv).unwrap()
+    }
+
+    fn scratch(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("hydra-test-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        path
+    }
+
+    fn file(path: &str) -> FileInfo {
+        FileInfo {
+            path: PathBuf::from(path),
+            size: 10,
+            created: None,
+            modified: SystemTime::UNIX_EPOCH,
+        }
+    }
+
+    fn by_name(files: Vec<FileInfo>) -> HashMap<String, Vec<FileInfo>> {
+        HashMap::from([("a.txt".to_string(), files)])
+    }
+
+    #[test]
+    fn root_of_picks_the_deepest_root() {
+        let roots = [PathBuf::from("/data"), PathBuf::from("/data/photos"), PathBuf::from("/backup")];
+        assert_eq!(root_of(Path::new("/data/photos/a.jpg"), &roots), Some(&roots[1]));
+        assert_eq!(root_of(Path::new("/data/music/a.mp3"), &roots), Some(&roots[0]));
+        assert_eq!(root_of(Path::new("/backup/a.jpg"), &roots), Some(&roots[2]));
+        // a shared prefix is not enough: /database is not under /data
+        assert_eq!(root_of(Path::new("/database/a.jpg"), &roots), None);
+    }
+
+    #[test]
+    fn scan_roots_drops_roots_a_recursive_scan_already_covers() {
+        let dir = scratch("roots");
+        let (outer, inner, other) = (dir.join("outer"), dir.join("outer/inner"), dir.join("other"));
+        for path in [&inner, &other] {
+            fs::create_dir_all(path).unwrap();
+        }
+        let root = |path: &Path| path.display().to_string();
+
+        let recursive = options(&["-r", "--root", &root(&outer), "--root", &root(&other)], &dir);
+        assert_eq!(scan_roots(&root(&inner), &recursive), vec![outer.clone(), other.clone()]);
+
+        // the same root twice, once through a `..`, is scanned once
+        let again = root(&other.join("../other"));
+        let flat = options(&["--root", &root(&inner), "--root", &again], &dir);
+        assert_eq!(scan_roots(&root(&other), &flat), vec![other.clone(), inner.clone()]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn copies_group_across_roots_unless_within_roots_only() {
+        let dir = scratch("grouping");
+        let roots = [PathBuf::from("/one"), PathBuf::from("/two")];
+        let files = || vec![file("/one/a.txt"), file("/one/sub/a.txt"), file("/two/a.txt")];
+
+        let global = find_duplicate_sets(by_name(files()), &roots, &options(&["--match", "name+size"], &dir));
+        assert_eq!(global.len(), 1);
+        assert_eq!(global[0].duplicates.len(), 2);
+
+        let within = options(&["--match", "name+size", "--within-roots-only"], &dir);
+        let sets = find_duplicate_sets(by_name(files()), &roots, &within);
+        assert_eq!(sets.len(), 1);
+        let members: Vec<&Path> =
+            std::iter::once(&sets[0].keeper).chain(&sets[0].duplicates).map(|f| f.path.as_path()).collect();
+        assert!(members.iter().all(|path| path.starts_with("/one")));
+
+        // a lone copy in each root is no duplicate at all
+        let apart = vec![file("/one/a.txt"), file("/two/a.txt")];
+        assert!(find_duplicate_sets(by_name(apart), &roots, &within).is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
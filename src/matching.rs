@@ -0,0 +1,145 @@
+// what makes two files duplicates. the default confirms every name+size group
+// by content hash; the older heuristics stay available as explicit --match
+// modes, each with a warning about what it can get wrong
+use crate::{FileInfo, format_bytes, hash, limits, results, retry};
+use crate::scheduler::{self, IoProfile};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::io::{self, IsTerminal};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MatchMode {
+    Content,
+    NameSize,
+    Size,
+    Name,
+}
+
+impl MatchMode {
+    pub fn parse(value: &str) -> Option<MatchMode> {
+        match value {
+            "content" => Some(MatchMode::Content),
+            "name+size" => Some(MatchMode::NameSize),
+            "size" => Some(MatchMode::Size),
+            "name" => Some(MatchMode::Name),
+            _ => None,
+        }
+    }
+
+    // whether files are grouped by their normalized name at all
+    pub fn uses_name(self) -> bool {
+        self != MatchMode::Size
+    }
+
+    // whether files of different sizes can end up in the same set
+    pub fn uses_size(self) -> bool {
+        self != MatchMode::Name
+    }
+
+    pub fn warning(self) -> Option<&'static str> {
+        match self {
+            MatchMode::Content => None,
+            MatchMode::NameSize => Some(
+                "--match name+size treats files with the same name and size as identical without reading them; \
+                 an edited copy of the same length will be removed",
+            ),
+            MatchMode::Size => Some(
+                "--match size treats any two files of the same size as identical, whatever their names or contents; \
+                 unrelated files are very likely to be removed",
+            ),
+            MatchMode::Name => Some(
+                "--match name treats files with the same name as identical even if their sizes differ; \
+                 different versions of a file will be removed",
+            ),
+        }
+    }
+}
+
+// files hashed per pass; passes run biggest potential savings first, so a run
+// interrupted partway (with --xattr-cache keeping what was hashed) has already
+// confirmed the largest wins
+const PASS_FILES: usize = 256;
+
+// what removing all but one file of a group would free
+fn potential_savings(group: &[FileInfo]) -> u64 {
+    group.first().map_or(0, |file_info| file_info.size * (group.len() as u64 - 1))
+}
+
+// split each group by content hash, keeping the hash of each part; files that
+// can't be hashed are left out
+pub fn split_by_content(mut groups: Vec<Vec<FileInfo>>, io_profile: IoProfile) -> Vec<(u64, Vec<FileInfo>)> {
+    groups.sort_by_key(|group| Reverse(potential_savings(group)));
+    let total: u64 = groups.iter().map(|group| potential_savings(group)).sum();
+    let progress = io::stderr().is_terminal() && !groups.is_empty();
+
+    // (group, hash, files) for each part, and the files to hash again with
+    // the group they came from
+    let mut split: Vec<(usize, u64, Vec<FileInfo>)> = Vec::new();
+    let mut deferred = Vec::new();
+    let (mut checked, mut done) = (0u64, 0);
+    let mut remaining = groups.into_iter().peekable();
+    // groups left unhashed at --timeout are dropped, as they aren't confirmed
+    while remaining.peek().is_some() && !limits::timed_out() {
+        let mut pass = Vec::new();
+        let mut files = 0;
+        while files < PASS_FILES
+            && let Some(group) = remaining.next()
+        {
+            files += group.len();
+            pass.push(group);
+        }
+
+        if progress {
+            let largest = potential_savings(&pass[0]);
+            eprint!(
+                "\rHashing by potential savings: {} of {} checked, next set up to {}   ",
+                format_bytes(checked),
+                format_bytes(total),
+                format_bytes(largest)
+            );
+        }
+
+        let paths = pass.iter().flatten().map(|file_info| file_info.path.clone()).collect();
+        let hashes = scheduler::hash_files(paths, io_profile);
+        for group in pass {
+            checked += potential_savings(&group);
+            done += 1;
+            let mut by_hash: HashMap<u64, Vec<FileInfo>> = HashMap::new();
+            for file_info in group {
+                match hashes.get(&file_info.path) {
+                    Some(Ok(hash)) => by_hash.entry(*hash).or_default().push(file_info),
+                    Some(Err(e)) if retry::is_transient(e) => {
+                        deferred.push(((done, file_info), io::Error::new(e.kind(), e.to_string())))
+                    }
+                    Some(Err(e)) => eprintln!("Warning: Could not hash '{}': {}", file_info.path.display(), e),
+                    None => {}
+                }
+            }
+            split.extend(by_hash.into_iter().map(|(hash, file_infos)| (done, hash, file_infos)));
+        }
+        results::found(split.iter().map(|(_, hash, file_infos)| (*hash, file_infos.as_slice())));
+    }
+
+    // files that failed transiently are tried again once everything else is
+    // hashed, and join the part of their group they match
+    for ((index, file_info), hash) in retry::queue(deferred, |(_, file_info)| hash::hash_file(&file_info.path)) {
+        match hash {
+            Ok(hash) => match split.iter_mut().find(|(group, known, _)| *group == index && *known == hash) {
+                Some((_, _, file_infos)) => file_infos.push(file_info),
+                None => split.push((index, hash, vec![file_info])),
+            },
+            Err(e) => eprintln!(
+                "Warning: Could not hash '{}' (tried {} more times): {}",
+                file_info.path.display(),
+                retry::DELAYS.len(),
+                e
+            ),
+        }
+    }
+    split.retain(|(_, _, file_infos)| file_infos.len() > 1);
+    if progress {
+        eprintln!("\rHashed {} group(s), {} of potential savings checked{}", done, format_bytes(checked), " ".repeat(20));
+    }
+
+    split.into_iter().map(|(_, hash, file_infos)| (hash, file_infos)).collect()
+}
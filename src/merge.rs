@@ -0,0 +1,335 @@
+// `hydra merge SRC DEST`: fold a stray copy of a folder back into the
+// canonical one. files already in DEST (by content, anywhere in DEST) are
+// removed from SRC, everything else is moved across at the same relative path
+use crate::actions::{verify_keeper, verify_unchanged};
+use crate::consolidate::file_info;
+use crate::{FileInfo, format_bytes, hash, restat, scan, terminal};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+enum Step {
+    // `path` in SRC has the same content as `existing` in DEST; both as they
+    // were when compared, to be checked again before `path` is removed
+    Remove { path: FileInfo, existing: FileInfo },
+    // `from` as it was when planned, to be checked again before it moves
+    Move { from: FileInfo, to: PathBuf },
+}
+
+// files in DEST (and files already planned to land there) indexed by size as
+// (the file where it is now, where it will be after the merge); hashes are
+// only computed once two sizes meet
+#[derive(Default)]
+struct ContentIndex {
+    by_size: HashMap<u64, Vec<(FileInfo, PathBuf)>>,
+    hashes: HashMap<PathBuf, u64>,
+}
+
+impl ContentIndex {
+    fn insert(&mut self, file_info: FileInfo, location: PathBuf) {
+        self.by_size.entry(file_info.size).or_default().push((file_info, location));
+    }
+
+    fn hash(&mut self, path: &Path) -> io::Result<u64> {
+        if let Some(hash) = self.hashes.get(path) {
+            return Ok(*hash);
+        }
+        let hash = hash::hash_file(path)?;
+        self.hashes.insert(path.to_path_buf(), hash);
+        Ok(hash)
+    }
+
+    // an indexed file with the same content as `path`, at its final location
+    fn find(&mut self, path: &Path, size: u64) -> io::Result<Option<FileInfo>> {
+        let candidates = match self.by_size.get(&size) {
+            Some(candidates) => candidates.clone(),
+            None => return Ok(None),
+        };
+
+        let hash = self.hash(path)?;
+        for (candidate, location) in candidates {
+            match self.hash(&candidate.path) {
+                Ok(candidate_hash) if candidate_hash == hash => {
+                    return Ok(Some(FileInfo {
+                        path: location,
+                        ..candidate
+                    }));
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("Warning: Could not read '{}': {}", candidate.path.display(), e),
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+// "name.ext" -> "name (merged 2).ext"; deliberately not a suffix hydra strips,
+// so a later run won't mistake the renamed file for a copy
+fn collision_name(path: &Path, attempt: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    let extension = path.extension().map(|ext| format!(".{}", ext.to_string_lossy())).unwrap_or_default();
+
+    path.with_file_name(format!("{} (merged {}){}", stem, attempt, extension))
+}
+
+fn plan(source: &Path, destination: &Path) -> Option<Vec<Step>> {
+    let mut index = ContentIndex::default();
+    let readable = scan::walk_files(destination, true, |path, metadata| {
+        if let Some(file_info) = file_info(path.clone(), &metadata) {
+            index.insert(file_info, path);
+        }
+    });
+    if !readable {
+        return None;
+    }
+
+    let mut source_files = Vec::new();
+    let readable = scan::walk_files(source, true, |path, metadata| match file_info(path, &metadata) {
+        Some(file_info) => source_files.push(file_info),
+        None => eprintln!("Warning: Could not get the modified time of a file in SRC, leaving it in place"),
+    });
+    if !readable {
+        return None;
+    }
+    source_files.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let mut claimed: HashSet<PathBuf> = HashSet::new();
+    let mut steps = Vec::new();
+
+    for file_info in source_files {
+        let path = file_info.path.clone();
+        match index.find(&path, file_info.size) {
+            Ok(Some(existing)) => {
+                steps.push(Step::Remove {
+                    path: file_info,
+                    existing,
+                });
+                continue;
+            }
+            Ok(None) => {}
+            Err(e) => {
+                eprintln!("Warning: Could not read '{}', leaving it in place: {}", path.display(), e);
+                continue;
+            }
+        }
+
+        let relative = path.strip_prefix(source).unwrap_or(&path);
+        let mut target = destination.join(relative);
+        let mut attempt = 2;
+        while target.symlink_metadata().is_ok() || claimed.contains(&target) {
+            target = collision_name(&destination.join(relative), attempt);
+            attempt += 1;
+        }
+
+        // later source files with the same content are removed rather than moved
+        index.insert(file_info.clone(), target.clone());
+        claimed.insert(target.clone());
+        steps.push(Step::Move { from: file_info, to: target });
+    }
+
+    Some(steps)
+}
+
+// move without ever replacing a file: a rename would silently overwrite one
+// that appeared in DEST since the plan was made, so the file is hard-linked
+// into place (which fails if the target exists) and then unlinked. where
+// links can't be made, across filesystems or on FAT, it is copied into a
+// newly created target instead, keeping its permissions and modification time
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    match fs::hard_link(from, to) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => return Err(e),
+        Err(_) => copy_new(from, to)?,
+    }
+    fs::remove_file(from).inspect_err(|_| {
+        let _ = fs::remove_file(to);
+    })
+}
+
+fn copy_new(from: &Path, to: &Path) -> io::Result<()> {
+    let source = fs::File::open(from)?;
+    let metadata = source.metadata()?;
+    let mut target = fs::OpenOptions::new().write(true).create_new(true).open(to)?;
+    let copied = io::copy(&mut hash::Throttled(source), &mut target)
+        .and_then(|_| target.set_permissions(metadata.permissions()))
+        .and_then(|_| target.set_modified(metadata.modified()?))
+        .and_then(|_| target.sync_all());
+    copied.inspect_err(|_| {
+        let _ = fs::remove_file(to);
+    })
+}
+
+// remove directories left empty under `directory`, deepest first; the root
+// itself is removed too if nothing is left in it
+fn remove_empty_directories(directory: &Path) -> bool {
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(_) => return false,
+    };
+
+    let mut empty = true;
+    for entry in entries.flatten() {
+        let is_dir = entry.file_type().is_ok_and(|file_type| file_type.is_dir());
+        if !is_dir || !remove_empty_directories(&entry.path()) {
+            empty = false;
+        }
+    }
+
+    empty && fs::remove_dir(directory).is_ok()
+}
+
+fn print_plan(steps: &[Step], dry_run: bool) {
+    let (remove_label, move_label) = if dry_run { ("Would remove", "Would move") } else { ("Remove", "Move") };
+
+    for step in steps {
+        match step {
+            Step::Remove { path, existing } => {
+                println!("{}: {} (already in {})", remove_label, path.path.display(), existing.path.display())
+            }
+            Step::Move { from, to } => println!("{}: {} -> {}", move_label, from.path.display(), to.display()),
+        }
+    }
+}
+
+pub fn run(args: &[String]) -> Result<(), String> {
+    let (mut dry_run, mut yes) = (false, false);
+    let mut paths = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--dry-run" => dry_run = true,
+            "-y" | "--yes" => yes = true,
+            _ => paths.push(arg),
+        }
+    }
+
+    let (source, destination) = match paths.as_slice() {
+        [source, destination] => (source, destination),
+        _ => return Err("usage: hydra merge [--dry-run] [--yes] SRC DEST".to_string()),
+    };
+
+    let source = fs::canonicalize(source).map_err(|e| format!("could not read '{}': {}", source, e))?;
+    let destination =
+        fs::canonicalize(destination).map_err(|e| format!("could not read '{}': {}", destination, e))?;
+    if source.starts_with(&destination) || destination.starts_with(&source) {
+        return Err("SRC and DEST must not contain one another".to_string());
+    }
+
+    if !dry_run && !yes && !terminal::can_prompt() {
+        eprintln!("Note: stdin is not a terminal, so this merge only reports. Pass --yes to merge without asking.");
+        dry_run = true;
+    }
+
+    println!("Merging {} into {} ...\n", source.display(), destination.display());
+
+    let steps = match plan(&source, &destination) {
+        Some(steps) => steps,
+        None => return Err("could not read both directories".to_string()),
+    };
+
+    if steps.is_empty() {
+        println!("Nothing to merge.");
+        return Ok(());
+    }
+
+    print_plan(&steps, dry_run);
+
+    let removals = steps.iter().filter(|step| matches!(step, Step::Remove { .. })).count();
+    let reclaimed: u64 = steps
+        .iter()
+        .filter_map(|step| match step {
+            Step::Remove { path, .. } => Some(path.size),
+            Step::Move { .. } => None,
+        })
+        .sum();
+
+    println!("\n================================");
+    println!("Files already in destination: {} ({})", removals, format_bytes(reclaimed));
+    println!("Files to move: {}", steps.len() - removals);
+
+    if dry_run {
+        println!("\n[DRY RUN MODE] No files were moved or removed.");
+        return Ok(());
+    }
+
+    if !yes {
+        print!("\nProceed with merge? (y/N): ");
+        io::stdout().flush().unwrap();
+
+        let mut input = String::new();
+        io::stdin().read_line(&mut input).unwrap();
+        let input = input.trim().to_lowercase();
+
+        if input != "y" && input != "yes" {
+            println!("Merge cancelled.");
+            return Ok(());
+        }
+    }
+
+    let (mut skipped, mut errors) = (0, 0);
+    for step in steps {
+        let (path, result) = match step {
+            Step::Remove {
+                mut path,
+                mut existing,
+            } => {
+                // never remove the last copy: the match in DEST must still be
+                // there, and both must still have the content they were
+                // compared with
+                let checked = verify_unchanged(&mut path, &mut existing)
+                    .and_then(|existing_hash| verify_keeper(&existing, existing_hash));
+                if let Err(reason) = checked {
+                    eprintln!("Skipped '{}' (kept: '{}'): {}", path.path.display(), existing.path.display(), reason);
+                    skipped += 1;
+                    continue;
+                }
+                let result = fs::remove_file(&path.path);
+                (path.path, result)
+            }
+            Step::Move { mut from, to } => {
+                // what moves must be what was planned; a file changed since
+                // is left in SRC for another look
+                match restat(&mut from) {
+                    Ok(false) => {}
+                    Ok(true) => {
+                        eprintln!("Skipped '{}': modified since the plan", from.path.display());
+                        skipped += 1;
+                        continue;
+                    }
+                    Err(e) => {
+                        eprintln!("Skipped '{}': no longer readable: {}", from.path.display(), e);
+                        skipped += 1;
+                        continue;
+                    }
+                }
+                let result = move_file(&from.path, &to);
+                (from.path, result)
+            }
+        };
+
+        if let Err(e) = result {
+            eprintln!("Error merging '{}': {}", path.display(), e);
+            errors += 1;
+        }
+    }
+
+    if remove_empty_directories(&source) {
+        println!("\nRemoved now-empty {}", source.display());
+    }
+
+    println!("\nMerge complete!");
+    if skipped > 0 {
+        println!("Files skipped (changed since the plan): {}", skipped);
+    }
+    if errors > 0 {
+        println!("Errors encountered: {}", errors);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,197 @@
+// carry useful metadata from the deleted copies over to the keeper, so the
+// surviving file loses nothing relative to them. the keeper is only changed
+// once its copies are really gone, so a set that is aborted or rolled back
+// leaves it as it was
+use crate::{FileInfo, hash, streams, xattr};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::SystemTime;
+
+// set-id and sticky bits and world-write are never added to the keeper
+#[cfg(unix)]
+const UNSAFE_MODE_BITS: u32 = 0o7002;
+
+// what a copy has to give, read right before the copy is removed, since none
+// of it can be read afterwards; it is only merged once the copy is gone
+#[derive(Debug, Default)]
+pub struct Taken {
+    created: Option<SystemTime>,
+    xattrs: Vec<(String, Vec<u8>)>,
+    streams: Vec<(String, Vec<u8>)>,
+    permissions: Option<fs::Permissions>,
+    // what couldn't be read
+    notes: Vec<String>,
+}
+
+// read what `file_info`, now at `path` (renamed aside), could give the keeper
+pub fn take(file_info: &FileInfo, path: &Path) -> Taken {
+    let mut taken = Taken {
+        created: file_info.created,
+        ..Taken::default()
+    };
+
+    let xattrs = || -> io::Result<Vec<(String, Vec<u8>)>> {
+        let mut xattrs = Vec::new();
+        for name in xattr::list(path)? {
+            if name == hash::XATTR_NAME || name.starts_with("security.") {
+                continue;
+            }
+            if let Some(value) = xattr::get(path, &name)? {
+                xattrs.push((name, value));
+            }
+        }
+        Ok(xattrs)
+    };
+    match xattrs() {
+        Ok(xattrs) => taken.xattrs = xattrs,
+        Err(e) if e.kind() == io::ErrorKind::Unsupported => {}
+        Err(e) => taken.notes.push(format!("could not read the extended attributes of a copy: {}", e)),
+    }
+
+    let streams = || -> io::Result<Vec<(String, Vec<u8>)>> {
+        let mut streams = Vec::new();
+        for stream in streams::list(path)? {
+            let contents = fs::read(streams::stream_path(path, &stream))?;
+            streams.push((stream, contents));
+        }
+        Ok(streams)
+    };
+    match streams() {
+        Ok(streams) => taken.streams = streams,
+        Err(e) if e.kind() == io::ErrorKind::Unsupported => {}
+        Err(e) => taken.notes.push(format!("could not read the alternate data streams of a copy: {}", e)),
+    }
+
+    match fs::metadata(path) {
+        Ok(metadata) => taken.permissions = Some(metadata.permissions()),
+        Err(e) => taken.notes.push(format!("could not read the permissions of a copy: {}", e)),
+    }
+
+    taken
+}
+
+// merge what the removed copies gave onto the keeper; returns a description
+// of each change made (or attempted and failed)
+pub fn merge_onto_keeper(keeper: &FileInfo, taken: &[Taken]) -> Vec<String> {
+    let mut notes: Vec<String> = taken.iter().flat_map(|taken| taken.notes.iter().cloned()).collect();
+
+    if let Err(e) = merge_creation_time(keeper, taken, &mut notes) {
+        notes.push(format!("could not set creation time: {}", e));
+    }
+    if let Err(e) = merge_xattrs(&keeper.path, taken, &mut notes)
+        && e.kind() != io::ErrorKind::Unsupported
+    {
+        notes.push(format!("could not merge extended attributes: {}", e));
+    }
+    if let Err(e) = merge_streams(&keeper.path, taken, &mut notes)
+        && e.kind() != io::ErrorKind::Unsupported
+    {
+        notes.push(format!("could not merge alternate data streams: {}", e));
+    }
+    if let Err(e) = merge_permissions(&keeper.path, taken, &mut notes) {
+        notes.push(format!("could not merge permissions: {}", e));
+    }
+
+    notes
+}
+
+fn merge_creation_time(keeper: &FileInfo, taken: &[Taken], notes: &mut Vec<String>) -> io::Result<()> {
+    let keeper_created = match keeper.created {
+        Some(created) => created,
+        None => return Ok(()),
+    };
+    let earliest = match taken.iter().filter_map(|taken| taken.created).min() {
+        Some(earliest) if earliest < keeper_created => earliest,
+        _ => return Ok(()),
+    };
+
+    set_created(&keeper.path, earliest)?;
+    notes.push("took the earliest creation time from a deleted copy".to_string());
+    Ok(())
+}
+
+#[cfg(any(target_os = "macos", windows))]
+fn set_created(path: &Path, created: std::time::SystemTime) -> io::Result<()> {
+    #[cfg(target_os = "macos")]
+    use std::os::macos::fs::FileTimesExt;
+    #[cfg(windows)]
+    use std::os::windows::fs::FileTimesExt;
+
+    let file = fs::File::options().write(true).open(path)?;
+    file.set_times(fs::FileTimes::new().set_created(created))
+}
+
+// linux has no API for changing a file's birth time
+#[cfg(not(any(target_os = "macos", windows)))]
+fn set_created(_path: &Path, _created: std::time::SystemTime) -> io::Result<()> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+// copy any attribute the keeper lacks (e.g. finder tags, quarantine or custom
+// user attributes); attributes the keeper already has are left untouched
+fn merge_xattrs(keeper: &Path, taken: &[Taken], notes: &mut Vec<String>) -> io::Result<()> {
+    let mut keeper_names = xattr::list(keeper)?;
+
+    for (name, value) in taken.iter().flat_map(|taken| &taken.xattrs) {
+        if keeper_names.contains(name) {
+            continue;
+        }
+        xattr::set(keeper, name, value)?;
+        notes.push(format!("copied extended attribute '{}'", name));
+        keeper_names.push(name.clone());
+    }
+
+    Ok(())
+}
+
+// copy any NTFS alternate data stream the keeper lacks
+fn merge_streams(keeper: &Path, taken: &[Taken], notes: &mut Vec<String>) -> io::Result<()> {
+    let mut keeper_streams = streams::list(keeper)?;
+
+    for (stream, contents) in taken.iter().flat_map(|taken| &taken.streams) {
+        if keeper_streams.contains(stream) {
+            continue;
+        }
+        fs::write(streams::stream_path(keeper, stream), contents)?;
+        notes.push(format!("copied alternate data stream '{}'", stream));
+        keeper_streams.push(stream.clone());
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn merge_permissions(keeper: &Path, taken: &[Taken], notes: &mut Vec<String>) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let keeper_mode = fs::metadata(keeper)?.permissions().mode() & 0o7777;
+    let mut merged_mode = keeper_mode;
+    for permissions in taken.iter().filter_map(|taken| taken.permissions.as_ref()) {
+        merged_mode |= permissions.mode() & 0o777 & !UNSAFE_MODE_BITS;
+    }
+
+    if merged_mode != keeper_mode {
+        fs::set_permissions(keeper, fs::Permissions::from_mode(merged_mode))?;
+        notes.push(format!("permissions widened from {:o} to {:o}", keeper_mode, merged_mode));
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn merge_permissions(keeper: &Path, taken: &[Taken], notes: &mut Vec<String>) -> io::Result<()> {
+    let mut permissions = fs::metadata(keeper)?.permissions();
+    if !permissions.readonly() {
+        return Ok(());
+    }
+
+    let writable = taken.iter().filter_map(|taken| taken.permissions.as_ref()).any(|copy| !copy.readonly());
+    if writable {
+        permissions.set_readonly(false);
+        fs::set_permissions(keeper, permissions)?;
+        notes.push("cleared read-only flag to match a deleted copy".to_string());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,217 @@
+// filename normalization: strip the suffixes that copy operations add so that
+// "report copy 2.pdf" and "report.pdf" land in the same group
+use crate::config::Config;
+use crate::json;
+use regex::Regex;
+use std::collections::HashMap;
+
+// (name, pattern) - order matters, longer patterns are checked first
+const PATTERNS: [(&str, &str); 6] = [
+    ("copy-number", r" copy \d+$"),                 // "file copy 2"
+    ("copy", r" copy$"),                            // "file copy"
+    ("windows-copy-number", r" - Copy \(\d+\)$"),   // "file - Copy (2)"
+    ("windows-copy", r" - Copy$"),                  // "file - Copy"
+    ("numbered", r" \(\d+\)$"),                     // "file (1)"
+    ("numbered-tight", r"\(\d+\)$"),                // "file(1)"
+];
+
+// spellings of one format: each alias is grouped as its canonical extension,
+// so "photo.jpeg" and "photo.jpg" share a group
+const EXTENSION_ALIASES: [(&str, &[&str]); 4] = [
+    ("jpg", &["jpeg"]),
+    ("tif", &["tiff"]),
+    ("html", &["htm"]),
+    ("yaml", &["yml"]),
+];
+
+#[derive(Debug)]
+struct Rule {
+    glob: String,
+    // indexes into PATTERNS that apply to matching files
+    patterns: Vec<usize>,
+}
+
+#[derive(Debug)]
+pub struct Normalizer {
+    patterns: Vec<Regex>,
+    rules: Vec<Rule>,
+    // lowercase alias -> canonical extension
+    extension_aliases: HashMap<String, String>,
+    // --ignore-extension: "video.mp4" and "video.m4v" normalize alike
+    ignore_extension: bool,
+}
+
+// case-insensitive glob match supporting `*` and `?`
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            n = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+impl Normalizer {
+    pub fn new() -> Normalizer {
+        Normalizer {
+            patterns: PATTERNS.iter().map(|(_, pattern)| Regex::new(pattern).unwrap()).collect(),
+            rules: Vec::new(),
+            extension_aliases: EXTENSION_ALIASES
+                .iter()
+                .flat_map(|(canonical, aliases)| aliases.iter().map(|alias| (alias.to_string(), canonical.to_string())))
+                .collect(),
+            ignore_extension: false,
+        }
+    }
+
+    pub fn ignore_extension(&mut self) {
+        self.ignore_extension = true;
+    }
+
+    // what is in effect, for reports: the copy-suffix patterns by name, the
+    // [normalize] rules limiting them and the extension aliases
+    pub fn to_json(&self) -> String {
+        let patterns: Vec<String> = PATTERNS
+            .iter()
+            .map(|(name, pattern)| format!("{}: {}", json::string(name), json::string(pattern)))
+            .collect();
+        let rules: Vec<String> = self
+            .rules
+            .iter()
+            .map(|rule| {
+                let names: Vec<String> = rule.patterns.iter().map(|index| json::string(PATTERNS[*index].0)).collect();
+                format!("{{\"glob\": {}, \"patterns\": [{}]}}", json::string(&rule.glob), names.join(", "))
+            })
+            .collect();
+        let mut aliases: Vec<(&String, &String)> = self.extension_aliases.iter().collect();
+        aliases.sort();
+        let aliases: Vec<String> = aliases
+            .iter()
+            .map(|(alias, canonical)| format!("{}: {}", json::string(alias), json::string(canonical)))
+            .collect();
+        format!(
+            "{{\"patterns\": {{{}}}, \"rules\": [{}], \"extension_aliases\": {{{}}}, \"ignore_extension\": {}}}",
+            patterns.join(", "),
+            rules.join(", "),
+            aliases.join(", "),
+            self.ignore_extension
+        )
+    }
+
+    // [normalize."GLOB"] sections restrict which patterns apply to matching
+    // files; a bare extension like [normalize.log] means "*.log". the first
+    // matching section wins
+    pub fn from_config(config: &Config) -> Result<Normalizer, String> {
+        let mut normalizer = Normalizer::new();
+
+        for section in config.sections_named("normalize") {
+            let target = match section.path.as_slice() {
+                [_, target] => target,
+                _ => return Err("normalize sections must look like [normalize.\"*.ext\"]".to_string()),
+            };
+            let glob = if target.contains(['*', '?']) { target.clone() } else { format!("*.{}", target) };
+
+            let names = section
+                .get("patterns")
+                .and_then(|value| value.as_string_array())
+                .ok_or(format!("[normalize.\"{}\"] needs a `patterns` list of strings", target))?;
+
+            let mut patterns = Vec::new();
+            for name in names {
+                let index = PATTERNS.iter().position(|(known, _)| *known == name).ok_or(format!(
+                    "unknown normalization pattern '{}' (expected one of: {})",
+                    name,
+                    PATTERNS.iter().map(|(known, _)| *known).collect::<Vec<_>>().join(", ")
+                ))?;
+                patterns.push(index);
+            }
+
+            normalizer.rules.push(Rule { glob, patterns });
+        }
+
+        // [extensions] entries like `jpg = ["jpeg", "jpe"]` add to the built-in aliases
+        for section in config.sections_named("extensions") {
+            for (canonical, value) in &section.entries {
+                let aliases = value
+                    .as_string_array()
+                    .ok_or(format!("[extensions] `{}` needs a list of alias extensions", canonical))?;
+                for alias in aliases {
+                    let alias = alias.trim_start_matches('.').to_lowercase();
+                    normalizer.extension_aliases.insert(alias, canonical.trim_start_matches('.').to_lowercase());
+                }
+            }
+        }
+
+        Ok(normalizer)
+    }
+
+    pub fn normalize(&self, filename: &str) -> String {
+        let normalized = self.strip_copy_suffix(filename);
+        let (stem, extension) = match normalized.rsplit_once('.') {
+            // a dotfile like ".bashrc" is all stem
+            Some((stem, extension)) if !stem.is_empty() => (stem, extension),
+            _ => return normalized,
+        };
+
+        if self.ignore_extension {
+            return stem.to_string();
+        }
+        match self.extension_aliases.get(&extension.to_lowercase()) {
+            // keep an all-caps extension in caps, as cameras write "IMG_1.JPG"
+            Some(canonical) if extension == extension.to_uppercase() => {
+                format!("{}.{}", stem, canonical.to_uppercase())
+            }
+            Some(canonical) => format!("{}.{}", stem, canonical),
+            None => normalized,
+        }
+    }
+
+    // the name without the suffix a copy operation added, extension kept
+    pub fn strip_copy_suffix(&self, filename: &str) -> String {
+        // separate name and extension
+        let (stem, extension) = match filename.rsplit_once('.') {
+            Some((s, e)) => (s, Some(e)),
+            None => (filename, None),
+        };
+
+        let allowed = self
+            .rules
+            .iter()
+            .find(|rule| glob_match(&rule.glob, filename))
+            .map(|rule| rule.patterns.as_slice());
+
+        let mut normalized = stem.to_string();
+
+        for (index, re) in self.patterns.iter().enumerate() {
+            if allowed.is_some_and(|allowed| !allowed.contains(&index)) {
+                continue;
+            }
+            if re.is_match(&normalized) {
+                normalized = re.replace(&normalized, "").to_string();
+                break;
+            }
+        }
+
+        // reconstruct with extension
+        match extension {
+            Some(ext) => format!("{}.{}", normalized, ext),
+            None => normalized,
+        }
+    }
+}
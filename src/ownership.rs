@@ -0,0 +1,159 @@
+// --owner / --group / --writable-only: on a shared machine, only consider
+// files the operator owns or could actually remove, instead of finding out at
+// deletion time. unix only
+use crate::{FileInfo, stats};
+use std::collections::HashMap;
+
+#[derive(Debug, Default)]
+pub struct OwnerFilter {
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    pub writable_only: bool,
+}
+
+impl OwnerFilter {
+    pub fn is_active(&self) -> bool {
+        self.uid.is_some() || self.gid.is_some() || self.writable_only
+    }
+}
+
+#[cfg(unix)]
+mod ffi {
+    use std::os::raw::{c_char, c_int};
+
+    // only the leading fields, which are laid out the same on linux and macos
+    #[repr(C)]
+    pub struct Passwd {
+        pub name: *mut c_char,
+        pub password: *mut c_char,
+        pub uid: u32,
+        pub gid: u32,
+    }
+
+    #[repr(C)]
+    pub struct Group {
+        pub name: *mut c_char,
+        pub password: *mut c_char,
+        pub gid: u32,
+    }
+
+    pub const W_OK: c_int = 2;
+    pub const X_OK: c_int = 1;
+
+    unsafe extern "C" {
+        pub fn getpwnam(name: *const c_char) -> *mut Passwd;
+        pub fn getgrnam(name: *const c_char) -> *mut Group;
+        pub fn geteuid() -> u32;
+        pub fn access(path: *const c_char, mode: c_int) -> c_int;
+    }
+}
+
+// a user name or numeric uid
+#[cfg(unix)]
+pub fn lookup_user(name: &str) -> Option<u32> {
+    if let Ok(uid) = name.parse() {
+        return Some(uid);
+    }
+
+    let name = std::ffi::CString::new(name).ok()?;
+    let entry = unsafe { ffi::getpwnam(name.as_ptr()) };
+    (!entry.is_null()).then(|| unsafe { (*entry).uid })
+}
+
+// a group name or numeric gid
+#[cfg(unix)]
+pub fn lookup_group(name: &str) -> Option<u32> {
+    if let Ok(gid) = name.parse() {
+        return Some(gid);
+    }
+
+    let name = std::ffi::CString::new(name).ok()?;
+    let entry = unsafe { ffi::getgrnam(name.as_ptr()) };
+    (!entry.is_null()).then(|| unsafe { (*entry).gid })
+}
+
+#[cfg(not(unix))]
+pub fn lookup_user(_name: &str) -> Option<u32> {
+    None
+}
+
+#[cfg(not(unix))]
+pub fn lookup_group(_name: &str) -> Option<u32> {
+    None
+}
+
+// whether the running user owns the file; only their own files' metadata is
+// theirs to trust
+#[cfg(unix)]
+pub fn owned_by_me(metadata: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    metadata.uid() == unsafe { ffi::geteuid() }
+}
+
+#[cfg(not(unix))]
+pub fn owned_by_me(_metadata: &std::fs::Metadata) -> bool {
+    true
+}
+
+// removing a file needs write and search permission on its directory, and in
+// a sticky directory (like /tmp) ownership of the file or the directory
+#[cfg(unix)]
+fn removable(path: &std::path::Path, metadata: &std::fs::Metadata) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::MetadataExt;
+
+    const STICKY: u32 = 0o1000;
+
+    let Some(directory) = path.parent() else {
+        return false;
+    };
+    let Ok(directory_metadata) = std::fs::metadata(directory) else {
+        return false;
+    };
+    let Ok(directory) = std::ffi::CString::new(directory.as_os_str().as_bytes()) else {
+        return false;
+    };
+
+    if unsafe { ffi::access(directory.as_ptr(), ffi::W_OK | ffi::X_OK) } != 0 {
+        return false;
+    }
+
+    let euid = unsafe { ffi::geteuid() };
+    directory_metadata.mode() & STICKY == 0 || euid == 0 || metadata.uid() == euid || directory_metadata.uid() == euid
+}
+
+#[cfg(unix)]
+fn allowed(file_info: &FileInfo, filter: &OwnerFilter) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = match std::fs::metadata(&file_info.path) {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            eprintln!("Warning: Could not read owner of '{}': {}", file_info.path.display(), e);
+            return false;
+        }
+    };
+
+    filter.uid.is_none_or(|uid| metadata.uid() == uid)
+        && filter.gid.is_none_or(|gid| metadata.gid() == gid)
+        && (!filter.writable_only || removable(&file_info.path, &metadata))
+}
+
+#[cfg(not(unix))]
+fn allowed(_file_info: &FileInfo, _filter: &OwnerFilter) -> bool {
+    true
+}
+
+// drop files that don't pass the filter; only groups that could still form a
+// set are checked
+pub fn filter_by_owner(hashmap_name: &mut HashMap<String, Vec<FileInfo>>, filter: &OwnerFilter) {
+    for file_infos in hashmap_name.values_mut() {
+        if file_infos.len() < 2 {
+            continue;
+        }
+
+        let before = file_infos.len();
+        file_infos.retain(|file_info| allowed(file_info, filter));
+        stats::filtered("owner/permissions", (before - file_infos.len()) as u64);
+    }
+}
@@ -0,0 +1,80 @@
+// --delete-batch / --delete-interval: on network filesystems and SMR drives a
+// burst of removals can stall every other client, so the action phase pauses
+// after each batch. the directories touched by a batch are fsynced at that
+// point, bounding how much of the run a crash can lose
+use std::collections::HashSet;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+#[derive(Default)]
+struct Batch {
+    removed: usize,
+    directories: HashSet<PathBuf>,
+}
+
+pub struct Pacer {
+    batch_size: Option<usize>,
+    interval: Option<Duration>,
+    batch: Mutex<Batch>,
+}
+
+#[cfg(unix)]
+fn sync_directory(directory: &Path) {
+    if let Err(e) = File::open(directory).and_then(|directory| directory.sync_all()) {
+        eprintln!("Warning: Could not sync '{}': {}", directory.display(), e);
+    }
+}
+
+// windows can't open a directory as a file; its metadata is journaled anyway
+#[cfg(not(unix))]
+fn sync_directory(_directory: &Path) {}
+
+impl Pacer {
+    pub fn new(batch_size: Option<usize>, interval: Option<Duration>) -> Pacer {
+        Pacer {
+            batch_size,
+            interval,
+            batch: Mutex::new(Batch::default()),
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        self.batch_size.is_some() || self.interval.is_some()
+    }
+
+    // called after each file is removed. the lock is held through the pause
+    // on purpose, so every worker waits out the interval together
+    pub fn removed(&self, path: &Path) {
+        if !self.is_active() {
+            return;
+        }
+
+        let mut batch = self.batch.lock().unwrap();
+        batch.removed += 1;
+        if let Some(directory) = path.parent() {
+            batch.directories.insert(directory.to_path_buf());
+        }
+
+        if batch.removed < self.batch_size.unwrap_or(1) {
+            return;
+        }
+        for directory in batch.directories.drain() {
+            sync_directory(&directory);
+        }
+        batch.removed = 0;
+        if let Some(interval) = self.interval {
+            thread::sleep(interval);
+        }
+    }
+
+    // sync whatever the last, partial batch touched
+    pub fn finish(&self) {
+        let mut batch = self.batch.lock().unwrap();
+        for directory in batch.directories.drain() {
+            sync_directory(&directory);
+        }
+    }
+}
@@ -0,0 +1,77 @@
+// like git, long reports on a terminal go through $PAGER so nothing scrolls
+// away before the confirmation prompt. a pager that can't be run, or fails,
+// leaves the report to be printed directly, so it is never lost
+use crate::terminal;
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::process::{Command, Stdio};
+
+// stock windows has no less
+const DEFAULT_PAGER: &str = if cfg!(windows) { "more" } else { "less" };
+const DEFAULT_ROWS: usize = 24;
+
+fn pager_command() -> Option<String> {
+    let pager = env::var("HYDRA_PAGER")
+        .or_else(|_| env::var("PAGER"))
+        .unwrap_or_else(|_| DEFAULT_PAGER.to_string());
+
+    match pager.trim() {
+        "" | "cat" => None,
+        pager => Some(pager.to_string()),
+    }
+}
+
+pub fn shell(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.args(["/C", command]);
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.args(["-c", command]);
+        shell
+    }
+}
+
+fn spawn_pager(command: &str, output: &[u8]) -> io::Result<()> {
+    let mut pager = shell(command);
+    // quit if one screen, keep colors, don't clear the screen on exit
+    if env::var_os("LESS").is_none() {
+        pager.env("LESS", "FRX");
+    }
+
+    let mut child = pager.stdin(Stdio::piped()).spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // the user may quit the pager before reading everything
+        let _ = stdin.write_all(output);
+    }
+    // the shell itself always starts, so a missing pager only shows in the
+    // status (127 from sh, 1 from cmd)
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(io::Error::other(format!("'{}' exited with {}", command, status)));
+    }
+
+    Ok(())
+}
+
+pub fn page(output: &[u8], enabled: bool) {
+    let stdout = io::stdout();
+    let rows = terminal::rows().unwrap_or(DEFAULT_ROWS);
+    let lines = output.iter().filter(|byte| **byte == b'\n').count();
+
+    if enabled
+        && stdout.is_terminal()
+        && lines + 2 > rows
+        && let Some(command) = pager_command()
+    {
+        match spawn_pager(&command, output) {
+            Ok(()) => return,
+            Err(e) => eprintln!("Warning: Could not page the report: {}", e),
+        }
+    }
+
+    let mut stdout = stdout.lock();
+    let _ = stdout.write_all(output);
+    let _ = stdout.flush();
+}
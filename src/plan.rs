@@ -0,0 +1,269 @@
+// `--output FILE` saves the decisions of a run as JSON; `hydra apply FILE`
+// carries out exactly those decisions later without rescanning. every file is
+// still re-checked against its recorded size and mtime before it is removed
+use crate::actions::Action;
+use crate::i18n::tr;
+use crate::json::{self, Json};
+use crate::{DuplicateSet, FileInfo, Options, environment, report};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub const VERSION: u64 = 1;
+
+pub struct Plan {
+    pub directory: String,
+    pub action: Action,
+    pub duplicate_sets: Vec<DuplicateSet>,
+}
+
+fn nanos(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0)
+}
+
+pub fn file_json(file_info: &FileInfo) -> String {
+    let created = file_info
+        .created
+        .map_or("null".to_string(), |created| nanos(created).to_string());
+
+    format!(
+        "{{\"path\": {}, \"size\": {}, \"modified\": {}, \"created\": {}}}",
+        json::string(&file_info.path.to_string_lossy()),
+        file_info.size,
+        nanos(file_info.modified),
+        created
+    )
+}
+
+// one entry of the "sets" array; snapshots share this layout
+pub fn set_json(set: &DuplicateSet) -> String {
+    let duplicates: Vec<String> = set.duplicates.iter().map(file_json).collect();
+    let hash = set.hash.map_or("null".to_string(), |hash| json::string(&format!("{:016x}", hash)));
+    format!(
+        "    {{\"name\": {}, \"size\": {}, \"reclaimable_files\": {}, \"reclaimable_bytes\": {}, \"hash\": {}, \
+         \"keeper\": {}, \"duplicates\": [\n      {}\n    ]}}",
+        json::string(&set.normalized_filename),
+        set.size,
+        set.duplicates.len(),
+        report::reclaimable_bytes(set),
+        hash,
+        file_json(&set.keeper),
+        duplicates.join(",\n      ")
+    )
+}
+
+pub fn to_json(directory: &str, duplicate_sets: &[DuplicateSet], options: &Options) -> String {
+    let mut out = String::new();
+    out.push_str(&format!("{{\n  \"version\": {},\n", VERSION));
+    out.push_str(&format!("  \"directory\": {},\n", json::string(directory)));
+    out.push_str(&format!("  \"action\": {},\n", json::string(options.action.verb())));
+    out.push_str(&format!("  \"environment\": {},\n", environment::to_json(options)));
+    out.push_str("  \"sets\": [");
+
+    for (index, set) in duplicate_sets.iter().enumerate() {
+        out.push_str(if index == 0 { "\n" } else { ",\n" });
+        out.push_str(&set_json(set));
+    }
+    out.push_str("\n  ]\n}\n");
+    out
+}
+
+pub fn write(path: &Path, directory: &str, duplicate_sets: &[DuplicateSet], options: &Options) -> Result<(), String> {
+    let out = to_json(directory, duplicate_sets, options);
+    fs::File::create(path)
+        .and_then(|mut file| file.write_all(out.as_bytes()))
+        .map_err(|e| format!("could not write '{}': {}", path.display(), e))
+}
+
+fn parse_time(value: Option<&Json>) -> Option<SystemTime> {
+    let nanos: u128 = match value? {
+        Json::Number(text) => text.parse().ok()?,
+        _ => return None,
+    };
+    let duration = Duration::new((nanos / 1_000_000_000) as u64, (nanos % 1_000_000_000) as u32);
+    Some(UNIX_EPOCH + duration)
+}
+
+pub fn parse_file(value: &Json) -> Option<FileInfo> {
+    Some(FileInfo {
+        path: PathBuf::from(value.get("path")?.as_str()?),
+        size: value.get("size")?.as_u64()?,
+        modified: parse_time(value.get("modified"))?,
+        created: parse_time(value.get("created")),
+    })
+}
+
+pub fn parse_set(value: &Json) -> Option<DuplicateSet> {
+    let duplicates = value
+        .get("duplicates")?
+        .as_array()?
+        .iter()
+        .map(parse_file)
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(DuplicateSet {
+        normalized_filename: value.get("name")?.as_str()?.to_string(),
+        size: value.get("size")?.as_u64()?,
+        // absent from reports written before sets had IDs
+        hash: value
+            .get("hash")
+            .and_then(Json::as_str)
+            .and_then(|hash| u64::from_str_radix(hash, 16).ok()),
+        keeper: parse_file(value.get("keeper")?)?,
+        duplicates,
+    })
+}
+
+pub fn read(path: &Path) -> Result<Plan, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("could not read '{}': {}", path.display(), e))?;
+    parse(&text, &path.display().to_string())
+}
+
+// `source` names where the text came from, for error messages
+pub fn parse(text: &str, source: &str) -> Result<Plan, String> {
+    let root = json::parse(text).map_err(|e| format!("{}: {}", source, e))?;
+    let invalid = |what: &str| format!("{}: not a hydra report ({})", source, what);
+
+    match root.get("version").and_then(Json::as_u64) {
+        Some(VERSION) => {}
+        Some(version) => return Err(format!("{}: unsupported report version {}", source, version)),
+        None => return Err(invalid("missing version")),
+    }
+    if root.get("complete") == Some(&Json::Bool(false)) {
+        return Err(format!("{}: the results of a run that didn't finish; only a finished run can be applied", source));
+    }
+
+    let directory = root.get("directory").and_then(Json::as_str).ok_or_else(|| invalid("missing directory"))?;
+    let action = root
+        .get("action")
+        .and_then(Json::as_str)
+        .and_then(Action::parse)
+        .ok_or_else(|| invalid("missing or unknown action"))?;
+
+    let sets = root.get("sets").and_then(Json::as_array).ok_or_else(|| invalid("missing sets"))?;
+    let mut duplicate_sets = Vec::new();
+    for (index, set) in sets.iter().enumerate() {
+        duplicate_sets.push(parse_set(set).ok_or_else(|| invalid(&format!("set {} is malformed", index + 1)))?);
+    }
+
+    Ok(Plan {
+        directory: directory.to_string(),
+        action,
+        duplicate_sets,
+    })
+}
+
+// `hydra apply --check REPORT`: re-run every check apply makes before acting
+// and report what it would do, touching nothing. fails when any file has
+// drifted from the plan, so a change can be approved against the report
+fn check(report: &Path) -> Result<(), String> {
+    let plan = read(report)?;
+    let verb = plan.action.verb();
+    println!("Checking {} ({} set(s) from {})", report.display(), plan.duplicate_sets.len(), plan.directory);
+
+    let (mut removable, mut bytes, mut drifted) = (0, 0, 0);
+    for mut set in plan.duplicate_sets {
+        println!("\n--- {} ---", set.normalized_filename);
+        println!("Keep: {}", set.keeper.path.display());
+        if let Err(reason) = crate::actions::verify_keeper(&set.keeper, None) {
+            println!("Would skip the whole set: keeper {}", reason);
+            drifted += set.duplicates.len();
+            continue;
+        }
+
+        let mut keeper_hash = None;
+        let mut planned = Vec::new();
+        for file_info in &mut set.duplicates {
+            match crate::actions::verify_unchanged(file_info, &mut set.keeper) {
+                Ok(hash) => {
+                    keeper_hash = hash.or(keeper_hash);
+                    planned.push(file_info);
+                }
+                Err(reason) => {
+                    println!("Would skip: {} ({})", file_info.path.display(), reason);
+                    drifted += 1;
+                }
+            }
+        }
+
+        if !planned.is_empty()
+            && let Err(reason) = crate::actions::verify_keeper(&set.keeper, keeper_hash)
+        {
+            println!("Would skip the whole set: keeper {}", reason);
+            drifted += planned.len();
+            continue;
+        }
+        for file_info in planned {
+            println!("Would {}: {}", verb, file_info.path.display());
+            removable += 1;
+            bytes += file_info.size;
+        }
+    }
+
+    println!(
+        "\nWould {} {} file(s), freeing {}; {} file(s) no longer match the plan.",
+        verb,
+        removable,
+        crate::format_bytes(bytes),
+        drifted
+    );
+    if drifted > 0 {
+        return Err(format!("{} file(s) changed since the plan was written", drifted));
+    }
+    Ok(())
+}
+
+// `hydra apply REPORT [--yes] [flags]`: any flag of a normal run that affects
+// the action phase (--action, --backup, --jobs, --fix-symlinks, ...) may follow
+// the report; --action overrides the action saved in it
+pub fn run(args: &[String]) -> Result<(), String> {
+    if let Some(index) = args.iter().position(|arg| arg == "--check") {
+        let mut rest = args.to_vec();
+        rest.remove(index);
+        return match rest.as_slice() {
+            [report] => check(Path::new(report)),
+            _ => Err("usage: hydra apply --check REPORT".to_string()),
+        };
+    }
+
+    let (report, flags) = match args.split_first() {
+        Some((report, flags)) if !report.starts_with('-') => (Path::new(report.as_str()), flags),
+        _ => {
+            let usage = "usage: hydra apply REPORT [--yes] [--action delete|trash] [...] | hydra apply --check REPORT";
+            return Err(usage.to_string());
+        }
+    };
+
+    let plan = read(report)?;
+
+    let mut flag_args = vec!["hydra".to_string()];
+    flag_args.extend(flags.iter().map(|flag| flag.to_string()));
+    let mut options = crate::parse_args(&flag_args).and_then(crate::set_up)?;
+    if !flags.iter().any(|flag| flag == "--action") {
+        options.action = plan.action;
+    }
+    // the command isn't saved with the plan; a plan file never runs anything
+    if options.action == Action::Exec && options.exec.is_empty() {
+        return Err("this plan was made with --action exec; pass --action exec:'CMD {path} {keeper}' again".to_string());
+    }
+
+    println!("Applying {} ({} set(s) from {})", report.display(), plan.duplicate_sets.len(), plan.directory);
+
+    let mut output = Vec::new();
+    crate::report::write_report(&mut output, &plan.duplicate_sets, &[], &[], &options).map_err(|e| e.to_string())?;
+    crate::pager::page(&output, options.pager);
+
+    if plan.duplicate_sets.is_empty() {
+        return Ok(());
+    }
+    if options.dry_run {
+        println!("\n{}", tr!("[DRY RUN MODE] No files were deleted."));
+        return Ok(());
+    }
+
+    let directory = plan.directory.clone();
+    crate::apply_sets(&directory, plan.duplicate_sets, &options);
+    crate::hooks::post_run(&directory, &options, 0);
+    Ok(())
+}
@@ -0,0 +1,165 @@
+// rewrite m3u/m3u8 playlist entries that referenced a deleted copy so they
+// point at its keeper
+use crate::{normalize_path, percent_encode, relative_path, scan};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const PLAYLIST_EXTENSIONS: [&str; 2] = ["m3u", "m3u8"];
+
+fn is_playlist(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .is_some_and(|ext| PLAYLIST_EXTENSIONS.contains(&ext.as_str()))
+}
+
+// file:// urls percent-encode anything unusual in the path
+fn percent_decode(value: &str) -> Option<String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = value.get(i + 1..i + 3)?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8(decoded).ok()
+}
+
+// entries are either comments/directives (#...), urls, or paths relative to
+// the playlist's own directory
+fn resolve_entry(playlist_dir: &Path, entry: &str) -> Option<PathBuf> {
+    if entry.is_empty() || entry.starts_with('#') {
+        return None;
+    }
+
+    let entry = match entry.strip_prefix("file://") {
+        Some(path) => percent_decode(path)?,
+        None if entry.contains("://") => return None,
+        None => entry.to_string(),
+    };
+
+    let path = Path::new(&entry);
+    if path.is_absolute() {
+        Some(normalize_path(path))
+    } else {
+        Some(normalize_path(&playlist_dir.join(path)))
+    }
+}
+
+// returns the rewritten playlist and how many entries changed; lines are
+// handled as raw bytes so non-utf8 (latin-1) .m3u files survive untouched
+fn rewrite_playlist(
+    playlist: &Path,
+    contents: &[u8],
+    removed: &HashMap<PathBuf, &PathBuf>,
+) -> (Vec<u8>, usize) {
+    let playlist_dir = playlist.parent().unwrap_or(Path::new(""));
+    let mut rewritten = Vec::with_capacity(contents.len());
+    let mut changed = 0;
+
+    for line in contents.split_inclusive(|byte| *byte == b'\n') {
+        let (body, ending) = match line.strip_suffix(b"\r\n") {
+            Some(body) => (body, &b"\r\n"[..]),
+            None => match line.strip_suffix(b"\n") {
+                Some(body) => (body, &b"\n"[..]),
+                None => (line, &b""[..]),
+            },
+        };
+
+        let keeper = std::str::from_utf8(body)
+            .ok()
+            .and_then(|entry| Some((entry, resolve_entry(playlist_dir, entry.trim())?)))
+            .and_then(|(entry, resolved)| Some((entry, *removed.get(&resolved)?)));
+
+        match keeper {
+            Some((entry, keeper)) => {
+                let entry = entry.trim();
+                let replacement = if entry.starts_with("file://") {
+                    format!("file://{}", percent_encode(&keeper.display().to_string()))
+                } else if Path::new(entry).is_absolute() {
+                    keeper.display().to_string()
+                } else {
+                    relative_path(&normalize_path(playlist_dir), keeper).display().to_string()
+                };
+                rewritten.extend_from_slice(replacement.as_bytes());
+                changed += 1;
+            }
+            None => rewritten.extend_from_slice(body),
+        }
+        rewritten.extend_from_slice(ending);
+    }
+
+    (rewritten, changed)
+}
+
+fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temporary = path.with_file_name(format!(".{}.hydra-tmp", name));
+
+    fs::write(&temporary, contents)?;
+    if let Ok(metadata) = fs::metadata(path) {
+        let _ = fs::set_permissions(&temporary, metadata.permissions());
+    }
+    fs::rename(&temporary, path).inspect_err(|_| {
+        let _ = fs::remove_file(&temporary);
+    })
+}
+
+// `removed` maps each deleted (or, in a dry run, to-be-deleted) path to its
+// keeper; returns the number of playlists updated
+pub fn fix_playlists(
+    directory: &Path,
+    recursive: bool,
+    removed: &HashMap<PathBuf, PathBuf>,
+    dry_run: bool,
+) -> usize {
+    let removed: HashMap<PathBuf, &PathBuf> = removed
+        .iter()
+        .map(|(path, keeper)| (normalize_path(path), keeper))
+        .collect();
+    let mut updated = 0;
+
+    scan::walk_files(directory, recursive, |path, _| {
+        if !is_playlist(&path) {
+            return;
+        }
+
+        let contents = match fs::read(&path) {
+            Ok(contents) => contents,
+            Err(e) => {
+                eprintln!("Error reading playlist '{}': {}", path.display(), e);
+                return;
+            }
+        };
+
+        let (rewritten, changed) = rewrite_playlist(&path, &contents, &removed);
+        if changed == 0 {
+            return;
+        }
+
+        if dry_run {
+            println!("Would update playlist: {} ({} entries)", path.display(), changed);
+            updated += 1;
+            return;
+        }
+
+        match write_atomically(&path, &rewritten) {
+            Ok(_) => {
+                println!("Updated playlist: {} ({} entries)", path.display(), changed);
+                updated += 1;
+            }
+            Err(e) => eprintln!("Error updating playlist '{}': {}", path.display(), e),
+        }
+    });
+
+    updated
+}
@@ -0,0 +1,121 @@
+// --plugin FILE.wasm: matching and keeper rules of the user's own, written in
+// any language that compiles to WebAssembly and run by hydra's interpreter
+// (wasm.rs), so a plugin can't reach anything but its own memory. a plugin
+// exports its memory, `hydra_alloc(len: i32) -> i32` for hydra to copy a path
+// into, and either or both of:
+//   hydra_group(path: i32, len: i32) -> i64: the key a file is grouped by in
+//     place of its normalized name, as (pointer << 32) | length of a UTF-8
+//     string in the plugin's memory, or -1 to leave the file to hydra's rules
+//   hydra_keep_rank(path: i32, len: i32) -> i32: among the copies hydra would
+//     choose a keeper from, the lowest rank is kept
+// paths are passed as UTF-8, and hydra never frees what it allocates, so a
+// plugin may hand out the same buffer every time. a plugin that fails, by
+// trapping or running too long, is set aside for the rest of the run
+use crate::wasm::{Instance, ValType};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+pub struct Plugin {
+    name: String,
+    // None once the plugin has failed
+    instance: Mutex<Option<Instance>>,
+    group: bool,
+    keep_rank: bool,
+}
+
+impl fmt::Debug for Plugin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Plugin({})", self.name)
+    }
+}
+
+impl Plugin {
+    pub fn load(path: &Path) -> Result<Plugin, String> {
+        let name = path.display().to_string();
+        let bytes = fs::read(path).map_err(|e| format!("could not read plugin '{}': {}", name, e))?;
+        let instance = Instance::load(&bytes).map_err(|e| format!("could not load plugin '{}': {}", name, e))?;
+        let exports = |function: &str, params: &[ValType], results: &[ValType]| match instance.signature(function) {
+            None => Ok(false),
+            Some(signature) if signature.params == params && signature.results == results => Ok(true),
+            Some(_) => Err(format!("plugin '{}' exports {} with the wrong signature", name, function)),
+        };
+        if !exports("hydra_alloc", &[ValType::I32], &[ValType::I32])? {
+            return Err(format!("plugin '{}' doesn't export hydra_alloc", name));
+        }
+        let group = exports("hydra_group", &[ValType::I32, ValType::I32], &[ValType::I64])?;
+        let keep_rank = exports("hydra_keep_rank", &[ValType::I32, ValType::I32], &[ValType::I32])?;
+        if !group && !keep_rank {
+            return Err(format!("plugin '{}' exports neither hydra_group nor hydra_keep_rank", name));
+        }
+        Ok(Plugin {
+            name,
+            instance: Mutex::new(Some(instance)),
+            group,
+            keep_rank,
+        })
+    }
+
+    // call `function` on the path and make sense of its result with `read`;
+    // any failure sets the plugin aside
+    fn ask<T>(
+        &self,
+        function: &str,
+        path: &Path,
+        read: impl FnOnce(&Instance, u64) -> Result<T, String>,
+    ) -> Result<Option<T>, String> {
+        let mut slot = self.instance.lock().unwrap();
+        let Some(instance) = slot.as_mut() else {
+            return Ok(None);
+        };
+        match call(instance, function, path).and_then(|result| read(instance, result)) {
+            Ok(value) => Ok(Some(value)),
+            Err(e) => {
+                *slot = None;
+                Err(format!(
+                    "plugin '{}' failed in {}: {}; it is not used for the rest of the run",
+                    self.name, function, e
+                ))
+            }
+        }
+    }
+
+    pub fn group_key(&self, path: &Path) -> Result<Option<String>, String> {
+        if !self.group {
+            return Ok(None);
+        }
+        let key = self.ask("hydra_group", path, |instance, packed| {
+            if (packed as i64) < 0 {
+                return Ok(None);
+            }
+            let (pointer, len) = ((packed >> 32) as usize, packed as u32 as usize);
+            let key = instance
+                .memory()
+                .get(pointer..pointer + len)
+                .ok_or("it returned a key out of bounds")?;
+            Ok(Some(String::from_utf8_lossy(key).into_owned()))
+        })?;
+        Ok(key.flatten())
+    }
+
+    pub fn keep_rank(&self, path: &Path) -> Result<Option<i32>, String> {
+        if !self.keep_rank {
+            return Ok(None);
+        }
+        self.ask("hydra_keep_rank", path, |_, rank| Ok(rank as u32 as i32))
+    }
+}
+
+// copy the path into the plugin's memory and call `function` on it
+fn call(instance: &mut Instance, function: &str, path: &Path) -> Result<u64, String> {
+    let path = path.to_string_lossy();
+    let len = path.len() as u64;
+    let pointer = instance.call("hydra_alloc", &[len])?[0] as u32 as usize;
+    instance
+        .memory_mut()
+        .get_mut(pointer..pointer + path.len())
+        .ok_or("hydra_alloc returned memory out of bounds")?
+        .copy_from_slice(path.as_bytes());
+    Ok(instance.call(function, &[pointer as u64, len])?[0])
+}
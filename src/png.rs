@@ -0,0 +1,155 @@
+// just enough PNG decoding to compare screenshots by eye: non-interlaced
+// images of 8 or 16 bits per channel, reduced to grayscale
+use crate::{hash, inflate};
+use std::path::Path;
+
+const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+// an 8K screenshot has about 33 million pixels; a header claiming far more is
+// damaged or hostile, and the image would not fit in memory anyway
+const MAX_PIXELS: usize = 1 << 25;
+
+pub struct GrayImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+struct Header {
+    width: usize,
+    height: usize,
+    depth: u8,
+    color_type: u8,
+    interlaced: bool,
+}
+
+fn be_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let estimate = left as i16 + up as i16 - up_left as i16;
+    let (a, b, c) = ((estimate - left as i16).abs(), (estimate - up as i16).abs(), (estimate - up_left as i16).abs());
+    if a <= b && a <= c {
+        left
+    } else if b <= c {
+        up
+    } else {
+        up_left
+    }
+}
+
+// undo the per-row filters in place, dropping each row's filter byte
+fn unfilter(data: &[u8], height: usize, stride: usize, pixel_bytes: usize) -> Result<Vec<u8>, String> {
+    let mut out = vec![0u8; height * stride];
+    for row in 0..height {
+        let line = data.get(row * (stride + 1)..(row + 1) * (stride + 1)).ok_or("image data is truncated")?;
+        let (filter, line) = (line[0], &line[1..]);
+        let (previous, current) = out.split_at_mut(row * stride);
+        let up = if row == 0 { None } else { Some(&previous[(row - 1) * stride..]) };
+        let current = &mut current[..stride];
+
+        for index in 0..stride {
+            let left = if index >= pixel_bytes { current[index - pixel_bytes] } else { 0 };
+            let above = up.map_or(0, |up| up[index]);
+            let above_left = match up {
+                Some(up) if index >= pixel_bytes => up[index - pixel_bytes],
+                _ => 0,
+            };
+            let predicted = match filter {
+                0 => 0,
+                1 => left,
+                2 => above,
+                3 => ((left as u16 + above as u16) / 2) as u8,
+                4 => paeth(left, above, above_left),
+                _ => return Err(format!("unknown row filter {}", filter)),
+            };
+            current[index] = line[index].wrapping_add(predicted);
+        }
+    }
+    Ok(out)
+}
+
+pub fn decode_gray(path: &Path) -> Result<GrayImage, String> {
+    let data = hash::read(path).map_err(|e| e.to_string())?;
+    if !data.starts_with(SIGNATURE) {
+        return Err("not a PNG file".to_string());
+    }
+
+    let mut header = None;
+    let mut palette: &[u8] = &[];
+    let mut compressed = Vec::new();
+    let mut offset = SIGNATURE.len();
+    while let Some(chunk) = data.get(offset..offset + 8) {
+        let length = be_u32(chunk) as usize;
+        let body = data.get(offset + 8..offset + 8 + length).ok_or("chunk is truncated")?;
+        match &chunk[4..8] {
+            b"IHDR" if length >= 13 => {
+                header = Some(Header {
+                    width: be_u32(&body[0..4]) as usize,
+                    height: be_u32(&body[4..8]) as usize,
+                    depth: body[8],
+                    color_type: body[9],
+                    interlaced: body[12] != 0,
+                })
+            }
+            b"PLTE" => palette = body,
+            b"IDAT" => compressed.extend_from_slice(body),
+            b"IEND" => break,
+            _ => {}
+        }
+        // length, type, body and crc
+        offset += 12 + length;
+    }
+
+    let header = header.ok_or("missing header")?;
+    let channels = match header.color_type {
+        0 | 3 => 1,
+        2 => 3,
+        4 => 2,
+        6 => 4,
+        other => return Err(format!("unknown color type {}", other)),
+    };
+    if !matches!(header.depth, 8 | 16) || header.interlaced || (header.color_type == 3 && header.depth != 8) {
+        return Err("only non-interlaced 8 and 16-bit images are supported".to_string());
+    }
+
+    let too_large = || format!("{}x{} is too large", header.width, header.height);
+    if header.width.checked_mul(header.height).is_none_or(|pixels| pixels > MAX_PIXELS) {
+        return Err(too_large());
+    }
+
+    // every row is a filter byte and `stride` bytes of samples
+    let sample_bytes = header.depth as usize / 8;
+    let pixel_bytes = channels * sample_bytes;
+    let stride = header.width.checked_mul(pixel_bytes).ok_or_else(too_large)?;
+    let expected = stride.checked_add(1).and_then(|row| row.checked_mul(header.height)).ok_or_else(too_large)?;
+    let filtered = inflate::zlib_decompress(&compressed, expected)?;
+    if filtered.len() != expected {
+        return Err("image data is truncated".to_string());
+    }
+    let raw = unfilter(&filtered, header.height, stride, pixel_bytes)?;
+
+    // the high byte of each sample is plenty for a perceptual hash
+    let sample = |pixel: &[u8], channel: usize| pixel[channel * sample_bytes] as u32;
+    let luma = |r: u32, g: u32, b: u32| ((r * 299 + g * 587 + b * 114) / 1000) as u8;
+    let pixels = raw
+        .chunks_exact(pixel_bytes)
+        .map(|pixel| match header.color_type {
+            0 | 4 => sample(pixel, 0) as u8,
+            3 => {
+                let entry = pixel[0] as usize * 3;
+                match palette.get(entry..entry + 3) {
+                    Some(rgb) => luma(rgb[0] as u32, rgb[1] as u32, rgb[2] as u32),
+                    None => 0,
+                }
+            }
+            _ => luma(sample(pixel, 0), sample(pixel, 1), sample(pixel, 2)),
+        })
+        .collect();
+
+    Ok(GrayImage {
+        width: header.width,
+        height: header.height,
+        pixels,
+    })
+}
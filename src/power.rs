@@ -0,0 +1,258 @@
+// [pause] in the config: a --scheduled run, as `hydra init` sets up, holds off
+// hashing while a laptop is on battery below `on-battery-below` percent, or
+// while someone has used the keyboard or mouse within the last `idle-after`
+// seconds, and picks up where it stopped once the machine is plugged in or
+// left alone. hydra has no daemon; these scheduled runs are its long-running
+// background work. any other run is never paused, since whoever started it is
+// waiting on it
+use crate::config::Config;
+use crate::limits;
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// how often the battery and input are looked at
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Pause {
+    // pause while discharging below this many percent
+    battery_below: Option<u8>,
+    // pause until there has been no input for this long
+    idle_after: Option<Duration>,
+}
+
+static PAUSE: OnceLock<Pause> = OnceLock::new();
+// when the conditions were last checked, and why hashing is paused, if it is
+static STATE: Mutex<Option<(Instant, Option<String>)>> = Mutex::new(None);
+
+impl Pause {
+    // [pause] on-battery-below = 30 / idle-after = 300
+    pub fn from_config(config: &Config) -> Result<Pause, String> {
+        let mut pause = Pause::default();
+        for section in config.sections_named("pause") {
+            if let Some(value) = section.get("on-battery-below") {
+                match value.as_integer() {
+                    Some(percent) if (1..=100).contains(&percent) => pause.battery_below = Some(percent as u8),
+                    _ => return Err("[pause] `on-battery-below` needs a percentage from 1 to 100".to_string()),
+                }
+            }
+            if let Some(value) = section.get("idle-after") {
+                match value.as_integer() {
+                    Some(seconds) if seconds > 0 => pause.idle_after = Some(Duration::from_secs(seconds as u64)),
+                    _ => return Err("[pause] `idle-after` needs a positive number of seconds".to_string()),
+                }
+            }
+        }
+        Ok(pause)
+    }
+
+    fn is_active(&self) -> bool {
+        self.battery_below.is_some() || self.idle_after.is_some()
+    }
+
+    // why hashing should wait right now, if it should
+    fn reason(&self) -> Option<String> {
+        if let Some(threshold) = self.battery_below
+            && let Some(percent) = battery_discharging()
+            && percent < threshold
+        {
+            return Some(format!("on battery at {}%", percent));
+        }
+        if let Some(idle_after) = self.idle_after
+            && let Some(idle) = input_idle()
+            && idle < idle_after
+        {
+            return Some("the computer is in use".to_string());
+        }
+        None
+    }
+}
+
+pub fn set_pause(pause: Pause) {
+    if pause.is_active() {
+        let _ = PAUSE.set(pause);
+    }
+}
+
+// called before each file is hashed; returns once hashing may go on, or
+// --timeout has passed
+pub fn wait() {
+    let Some(pause) = PAUSE.get() else {
+        return;
+    };
+    loop {
+        let mut state = STATE.lock().unwrap();
+        let due = state.as_ref().is_none_or(|(checked, _)| checked.elapsed() >= CHECK_INTERVAL);
+        if due {
+            let reason = pause.reason();
+            let was = state.as_ref().and_then(|(_, reason)| reason.clone());
+            match (&was, &reason) {
+                (None, Some(reason)) => eprintln!("Note: Pausing hashing: {}", reason),
+                (Some(_), None) => eprintln!("Note: Resuming hashing"),
+                _ => {}
+            }
+            *state = Some((Instant::now(), reason));
+        }
+        if state.as_ref().is_none_or(|(_, reason)| reason.is_none()) || limits::timed_out() {
+            return;
+        }
+        drop(state);
+        thread::sleep(CHECK_INTERVAL);
+    }
+}
+
+// the charge in percent while running on battery; None on mains power or
+// without a battery
+#[cfg(target_os = "linux")]
+fn battery_discharging() -> Option<u8> {
+    use std::fs;
+
+    for entry in fs::read_dir("/sys/class/power_supply").ok()?.flatten() {
+        let read = |name: &str| fs::read_to_string(entry.path().join(name)).map(|text| text.trim().to_string());
+        let discharging = read("status").is_ok_and(|status| status == "Discharging");
+        if read("type").is_ok_and(|kind| kind == "Battery") && discharging {
+            return read("capacity").ok()?.parse().ok();
+        }
+    }
+    None
+}
+
+// `pmset -g batt` says "Now drawing from 'Battery Power'" and "... 57%; discharging"
+#[cfg(target_os = "macos")]
+fn battery_discharging() -> Option<u8> {
+    let output = std::process::Command::new("pmset").args(["-g", "batt"]).output().ok()?;
+    let text = String::from_utf8_lossy(&output.stdout);
+    if !text.contains("'Battery Power'") {
+        return None;
+    }
+    let percent = text.split('%').next()?.rsplit(|c: char| !c.is_ascii_digit()).next()?;
+    percent.parse().ok()
+}
+
+#[cfg(windows)]
+fn battery_discharging() -> Option<u8> {
+    #[repr(C)]
+    #[derive(Default)]
+    struct SystemPowerStatus {
+        ac_line_status: u8,
+        _battery_flag: u8,
+        battery_life_percent: u8,
+        _system_status_flag: u8,
+        _battery_life_time: u32,
+        _battery_full_life_time: u32,
+    }
+    const AC_OFFLINE: u8 = 0;
+    const UNKNOWN_PERCENT: u8 = 255;
+
+    unsafe extern "system" {
+        fn GetSystemPowerStatus(status: *mut SystemPowerStatus) -> i32;
+    }
+
+    let mut status = SystemPowerStatus::default();
+    if unsafe { GetSystemPowerStatus(&mut status) } == 0 {
+        return None;
+    }
+    (status.ac_line_status == AC_OFFLINE && status.battery_life_percent != UNKNOWN_PERCENT)
+        .then_some(status.battery_life_percent)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn battery_discharging() -> Option<u8> {
+    None
+}
+
+// how long since the last keyboard or mouse input. on linux, logind's idle
+// hint as desktops report it through their screen savers; reading input
+// devices doesn't touch their access times, so /dev/input can't tell. without
+// a desktop that reports idleness, the latest typing at a terminal stands in
+// for it, as w(1) does
+#[cfg(target_os = "linux")]
+fn input_idle() -> Option<Duration> {
+    logind_idle().or_else(terminal_idle)
+}
+
+// `loginctl show` prints the manager's hints across all sessions; IdleSinceHint
+// is in microseconds since the epoch and stays 0 while no session has ever
+// reported either state
+#[cfg(target_os = "linux")]
+fn logind_idle() -> Option<Duration> {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    let output = std::process::Command::new("loginctl")
+        .args(["show", "--property=IdleHint", "--property=IdleSinceHint"])
+        .stderr(std::process::Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let text = String::from_utf8_lossy(&output.stdout);
+    let value = |name: &str| text.lines().find_map(|line| line.strip_prefix(name)?.strip_prefix('='));
+    let since: u64 = value("IdleSinceHint")?.trim().parse().ok()?;
+    if since == 0 {
+        return None;
+    }
+    if value("IdleHint")?.trim() != "yes" {
+        return Some(Duration::ZERO);
+    }
+    SystemTime::now().duration_since(UNIX_EPOCH + Duration::from_micros(since)).ok()
+}
+
+#[cfg(target_os = "linux")]
+fn terminal_idle() -> Option<Duration> {
+    use std::fs;
+    use std::time::SystemTime;
+
+    let latest = fs::read_dir("/dev/pts")
+        .ok()?
+        .flatten()
+        // opened for every new terminal, typed at or not
+        .filter(|entry| entry.file_name() != "ptmx")
+        .filter_map(|entry| entry.metadata().ok())
+        .filter_map(|metadata| metadata.accessed().ok())
+        .max()?;
+    SystemTime::now().duration_since(latest).ok()
+}
+
+// the HID system's idle time, in nanoseconds, from `ioreg -c IOHIDSystem`
+#[cfg(target_os = "macos")]
+fn input_idle() -> Option<Duration> {
+    let output = std::process::Command::new("ioreg").args(["-c", "IOHIDSystem", "-d", "4"]).output().ok()?;
+    let text = String::from_utf8_lossy(&output.stdout);
+    let line = text.lines().find(|line| line.contains("\"HIDIdleTime\""))?;
+    let nanos = line.rsplit('=').next()?.trim().parse().ok()?;
+    Some(Duration::from_nanos(nanos))
+}
+
+#[cfg(windows)]
+fn input_idle() -> Option<Duration> {
+    #[repr(C)]
+    struct LastInputInfo {
+        size: u32,
+        time: u32,
+    }
+
+    #[link(name = "user32")]
+    unsafe extern "system" {
+        fn GetLastInputInfo(info: *mut LastInputInfo) -> i32;
+    }
+    unsafe extern "system" {
+        fn GetTickCount() -> u32;
+    }
+
+    let mut info = LastInputInfo {
+        size: std::mem::size_of::<LastInputInfo>() as u32,
+        time: 0,
+    };
+    if unsafe { GetLastInputInfo(&mut info) } == 0 {
+        return None;
+    }
+    let now = unsafe { GetTickCount() };
+    Some(Duration::from_millis(now.wrapping_sub(info.time) as u64))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn input_idle() -> Option<Duration> {
+    None
+}
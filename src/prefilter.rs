@@ -0,0 +1,61 @@
+// compact approximate record of which file sizes (or, when names count too,
+// which combinations of name group and size) occur more than once, so the
+// second scan pass only has to keep metadata for plausible duplicates. a flat
+// dump of a million uniquely named camera files then keeps almost nothing.
+// false positives just mean a few extra files are retained; there are no
+// false negatives.
+
+const FILTER_BITS: usize = 1 << 26;
+const HASH_COUNT: u64 = 3;
+
+pub struct SizeFilter {
+    seen: Vec<u64>,
+    repeated: Vec<u64>,
+}
+
+impl SizeFilter {
+    pub fn new() -> SizeFilter {
+        SizeFilter {
+            seen: vec![0; FILTER_BITS / 64],
+            repeated: vec![0; FILTER_BITS / 64],
+        }
+    }
+
+    fn bit_positions(size: u64) -> impl Iterator<Item = usize> {
+        // derive k positions from two mixes of the size (Kirsch-Mitzenmacher)
+        let h1 = mix(size);
+        let h2 = mix(size ^ 0x9e3779b97f4a7c15) | 1;
+        (0..HASH_COUNT).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % FILTER_BITS as u64) as usize)
+    }
+
+    pub fn insert(&mut self, size: u64) {
+        let already_seen = Self::bit_positions(size).all(|bit| test(&self.seen, bit));
+
+        for bit in Self::bit_positions(size) {
+            set(&mut self.seen, bit);
+            if already_seen {
+                set(&mut self.repeated, bit);
+            }
+        }
+    }
+
+    pub fn maybe_repeated(&self, size: u64) -> bool {
+        Self::bit_positions(size).all(|bit| test(&self.repeated, bit))
+    }
+}
+
+// splitmix64 finalizer
+fn mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+fn test(bits: &[u64], bit: usize) -> bool {
+    bits[bit / 64] & (1 << (bit % 64)) != 0
+}
+
+fn set(bits: &mut [u64], bit: usize) {
+    bits[bit / 64] |= 1 << (bit % 64);
+}
@@ -0,0 +1,38 @@
+// --preset: named sets of directories never worth deduplicating. build output
+// and dependency caches are full of identical files that tools expect to find
+// exactly where they are, and walking them is slow
+const PRESETS: &[(&str, &[&str])] = &[(
+    "dev",
+    &[
+        "node_modules",
+        "target",
+        ".venv",
+        "venv",
+        "build",
+        "dist",
+        "__pycache__",
+        ".git",
+        ".hg",
+        ".svn",
+        ".tox",
+        ".mypy_cache",
+        ".pytest_cache",
+        ".gradle",
+        ".next",
+        ".cache",
+    ],
+)];
+
+// every preset, with the directory names it excludes
+pub fn all() -> impl Iterator<Item = (&'static str, &'static [&'static str])> {
+    PRESETS.iter().copied()
+}
+
+pub fn names() -> String {
+    PRESETS.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(", ")
+}
+
+// the directory names excluded by preset `name`
+pub fn directories(name: &str) -> Option<&'static [&'static str]> {
+    PRESETS.iter().find(|(preset, _)| *preset == name).map(|(_, directories)| *directories)
+}
@@ -0,0 +1,77 @@
+// lower the process's cpu and disk priority so a background run stays out of
+// the way; returns false when the platform refused or isn't supported
+
+#[cfg(unix)]
+mod ffi {
+    use std::os::raw::{c_int, c_long, c_uint};
+
+    pub const PRIO_PROCESS: c_int = 0;
+    pub const NICE_LOWEST: c_int = 19;
+
+    unsafe extern "C" {
+        pub fn setpriority(which: c_int, who: c_uint, prio: c_int) -> c_int;
+        #[cfg(target_os = "linux")]
+        pub fn syscall(number: c_long, ...) -> c_long;
+        #[cfg(target_os = "macos")]
+        pub fn setiopolicy_np(iotype: c_int, scope: c_int, policy: c_int) -> c_int;
+    }
+}
+
+#[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn lower_io_priority() -> bool {
+    use std::os::raw::{c_int, c_long};
+
+    const SYS_IOPRIO_SET: c_long = if cfg!(target_arch = "x86_64") { 251 } else { 30 };
+    const IOPRIO_WHO_PROCESS: c_int = 1;
+    const IOPRIO_CLASS_IDLE: c_int = 3;
+    const IOPRIO_CLASS_SHIFT: c_int = 13;
+
+    unsafe { ffi::syscall(SYS_IOPRIO_SET, IOPRIO_WHO_PROCESS, 0, IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT) == 0 }
+}
+
+#[cfg(target_os = "macos")]
+fn lower_io_priority() -> bool {
+    const IOPOL_TYPE_DISK: std::os::raw::c_int = 0;
+    const IOPOL_SCOPE_PROCESS: std::os::raw::c_int = 0;
+    const IOPOL_THROTTLE: std::os::raw::c_int = 3;
+
+    unsafe { ffi::setiopolicy_np(IOPOL_TYPE_DISK, IOPOL_SCOPE_PROCESS, IOPOL_THROTTLE) == 0 }
+}
+
+#[cfg(all(
+    unix,
+    not(any(
+        target_os = "macos",
+        all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64"))
+    ))
+))]
+fn lower_io_priority() -> bool {
+    false
+}
+
+#[cfg(unix)]
+pub fn set_idle_priority() -> bool {
+    let cpu = unsafe { ffi::setpriority(ffi::PRIO_PROCESS, 0, ffi::NICE_LOWEST) == 0 };
+    let io = lower_io_priority();
+    cpu && io
+}
+
+#[cfg(windows)]
+pub fn set_idle_priority() -> bool {
+    use std::ffi::c_void;
+
+    const PROCESS_MODE_BACKGROUND_BEGIN: u32 = 0x0010_0000;
+
+    unsafe extern "system" {
+        fn GetCurrentProcess() -> *mut c_void;
+        fn SetPriorityClass(process: *mut c_void, priority_class: u32) -> i32;
+    }
+
+    // background mode lowers both cpu scheduling and io priority
+    unsafe { SetPriorityClass(GetCurrentProcess(), PROCESS_MODE_BACKGROUND_BEGIN) != 0 }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn set_idle_priority() -> bool {
+    false
+}
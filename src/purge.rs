@@ -0,0 +1,154 @@
+// `hydra purge --older-than 30d`: trashing keeps removals recoverable, but the
+// space only comes back once the trash is emptied. this permanently removes
+// what hydra itself trashed longer ago than the retention window, using the
+// log kept by trash.rs, and leaves everything else in the trash alone
+use crate::{format_bytes, parse_duration, trash};
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+struct Entry {
+    trashed: u64,
+    location: PathBuf,
+}
+
+fn parse_entry(line: &str) -> Option<Entry> {
+    let (trashed, location) = line.split_once('\t')?;
+    Some(Entry {
+        trashed: trashed.parse().ok()?,
+        location: PathBuf::from(location),
+    })
+}
+
+// a freedesktop trash keeps "files/NAME" alongside "info/NAME.trashinfo"
+fn trash_info(location: &Path) -> Option<PathBuf> {
+    let files = location.parent()?;
+    if files.file_name()? != "files" {
+        return None;
+    }
+    let name = location.file_name()?.to_string_lossy();
+    Some(files.parent()?.join("info").join(format!("{}.trashinfo", name)))
+}
+
+// remove logged items trashed more than `older_than` ago, returning how many
+// files and bytes were (or, in a dry run, would be) freed. entries whose item
+// is already gone from the trash are dropped from the log
+pub fn purge(older_than: Duration, dry_run: bool) -> Result<(usize, u64), String> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let cutoff = now.saturating_sub(older_than.as_secs());
+    purge_where(|entry| entry.trashed <= cutoff, dry_run)
+}
+
+// --empty-trash-after: remove exactly the items this run trashed, leaving
+// those of other hydra processes running alongside it alone
+pub fn empty(locations: &[PathBuf]) -> Result<(usize, u64), String> {
+    if locations.is_empty() {
+        return Ok((0, 0));
+    }
+    let locations: HashSet<&Path> = locations.iter().map(PathBuf::as_path).collect();
+    purge_where(|entry| locations.contains(entry.location.as_path()), false)
+}
+
+// remove the logged items `expired` picks. the log is held locked from
+// reading to rewriting, so items other processes trash meanwhile are logged
+// after the rewrite rather than lost by it
+fn purge_where(expired: impl Fn(&Entry) -> bool, dry_run: bool) -> Result<(usize, u64), String> {
+    let Some(log_path) = trash::log_path() else {
+        return Ok((0, 0));
+    };
+    let failed = |e: std::io::Error| format!("could not read '{}': {}", log_path.display(), e);
+    let mut log = match OpenOptions::new().read(true).write(true).open(&log_path) {
+        Ok(log) => log,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok((0, 0)),
+        Err(e) => return Err(failed(e)),
+    };
+    log.lock().map_err(failed)?;
+    let mut text = String::new();
+    log.read_to_string(&mut text).map_err(failed)?;
+
+    let mut remaining = String::new();
+    let (mut files, mut bytes) = (0, 0);
+
+    for line in text.lines().filter(|line| !line.is_empty()) {
+        let Some(entry) = parse_entry(line) else {
+            eprintln!("Warning: Ignoring malformed line in '{}': {}", log_path.display(), line);
+            continue;
+        };
+        let Ok(metadata) = fs::symlink_metadata(&entry.location) else {
+            // restored or emptied from the trash since
+            continue;
+        };
+
+        let expired = expired(&entry);
+        let purged = expired
+            && !dry_run
+            && match fs::remove_file(&entry.location) {
+                Ok(()) => {
+                    if let Some(info) = trash_info(&entry.location) {
+                        let _ = fs::remove_file(info);
+                    }
+                    true
+                }
+                Err(e) => {
+                    eprintln!("Warning: Could not purge '{}': {}", entry.location.display(), e);
+                    false
+                }
+            };
+
+        if !purged {
+            remaining.push_str(line);
+            remaining.push('\n');
+        }
+        let freed = purged || (expired && dry_run);
+        if !freed {
+            continue;
+        }
+
+        let verb = if dry_run { "Would purge" } else { "Purged" };
+        println!("{}: {}", verb, entry.location.display());
+        files += 1;
+        bytes += metadata.len();
+    }
+
+    if !dry_run {
+        let mut rewrite = || {
+            log.set_len(0)?;
+            log.rewind()?;
+            log.write_all(remaining.as_bytes())
+        };
+        rewrite().map_err(|e| format!("could not write '{}': {}", log_path.display(), e))?;
+    }
+    Ok((files, bytes))
+}
+
+// `hydra purge --older-than DURATION [--dry-run]`
+pub fn run(args: &[String]) -> Result<(), String> {
+    let usage = "usage: hydra purge --older-than DURATION [--dry-run] (e.g. 30d)";
+    let mut older_than = None;
+    let mut dry_run = false;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--older-than" => {
+                let value = args.next().ok_or(usage)?;
+                older_than = Some(parse_duration(value).ok_or(format!("invalid --older-than '{}'", value))?);
+            }
+            "--dry-run" => dry_run = true,
+            _ => return Err(usage.to_string()),
+        }
+    }
+    let older_than = older_than.ok_or(usage)?;
+
+    let (files, bytes) = purge(older_than, dry_run)?;
+    if files == 0 {
+        println!("Nothing in the trash is old enough to purge.");
+    } else if dry_run {
+        println!("\n[DRY RUN MODE] Would purge {} file(s), freeing {}.", files, format_bytes(bytes));
+    } else {
+        println!("\nPurged {} file(s), freeing {}.", files, format_bytes(bytes));
+    }
+    Ok(())
+}
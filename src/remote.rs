@@ -0,0 +1,258 @@
+// `hydra remote HOST DIR`: scan on a remote box and decide locally. the client
+// starts `hydra agent DIR` over ssh; the agent scans and sends a snapshot back
+// on stdout, the client reviews it like a normal run and sends the chosen plan
+// down stdin, and the agent carries it out. only metadata crosses the wire.
+// messages are framed as a "TAG LENGTH" line followed by LENGTH bytes; any
+// other line the agent prints is progress and is shown as is
+use crate::{DuplicateSet, FileInfo, hash, plan, report, snapshot};
+use std::env;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Instant;
+
+const SNAPSHOT: &str = "HYDRA-SNAPSHOT";
+const PLAN: &str = "HYDRA-PLAN";
+const CANCEL: &str = "HYDRA-CANCEL";
+
+fn send(writer: &mut impl Write, tag: &str, text: &str) -> io::Result<()> {
+    writeln!(writer, "{} {}", tag, text.len())?;
+    writer.write_all(text.as_bytes())?;
+    writer.flush()
+}
+
+// the next `tag` message, or None on CANCEL or end of input. other lines are
+// printed when `echo` is set
+fn receive(reader: &mut impl BufRead, tag: &str, echo: bool) -> Result<Option<String>, String> {
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).map_err(|e| e.to_string())? == 0 || line.trim_end() == CANCEL {
+            return Ok(None);
+        }
+        let Some(length) = line.strip_prefix(tag).and_then(|rest| rest.trim().parse().ok()) else {
+            if echo {
+                print!("{}", line);
+            }
+            continue;
+        };
+
+        let mut message = vec![0; length];
+        reader.read_exact(&mut message).map_err(|e| format!("incomplete {} message: {}", tag, e))?;
+        return String::from_utf8(message).map(Some).map_err(|e| e.to_string());
+    }
+}
+
+// one word for the remote shell, which ssh hands the command line to
+fn quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+// `hydra agent DIR [flags]`, started by `hydra remote` rather than by hand.
+// scan flags apply here; the review happened on the client, so nothing is asked
+pub fn agent(args: &[String]) -> Result<(), String> {
+    let (directory, flags) = match args {
+        [directory, flags @ ..] if !directory.starts_with('-') => (directory, flags),
+        _ => return Err("usage: hydra agent DIR [flags] (started by `hydra remote`)".to_string()),
+    };
+    env::set_current_dir(directory).map_err(|e| format!("could not read '{}': {}", directory, e))?;
+    let directory = crate::get_current_directory();
+
+    let mut flag_args = vec!["hydra".to_string()];
+    flag_args.extend(flags.iter().map(|flag| flag.to_string()));
+    // nobody answers prompts over here; the review happens on the other side
+    let mut options = crate::parse_args(&flag_args)
+        .map(|mut options| {
+            options.interactive = false;
+            options.edit = false;
+            options.yes = true;
+            options
+        })
+        .and_then(crate::set_up)?;
+
+    let mut started = Instant::now();
+    let findings = crate::find_duplicates(&directory, &options, &mut started)
+        .ok_or(format!("could not read '{}'", directory))?;
+    let snapshot = snapshot::to_json(&directory, &findings.duplicate_sets, &options);
+    send(&mut io::stdout(), SNAPSHOT, &snapshot).map_err(|e| e.to_string())?;
+
+    let Some(text) = receive(&mut io::stdin().lock(), PLAN, false)? else {
+        println!("Nothing was changed on the remote side.");
+        return Ok(());
+    };
+    let plan = plan::parse(&text, "the plan from the client")?;
+    let duplicate_sets = vetted(plan.duplicate_sets, &findings.duplicate_sets, Path::new(&directory))?;
+    if duplicate_sets.is_empty() {
+        println!("Nothing was changed on the remote side.");
+        return Ok(());
+    }
+
+    options.action = plan.action;
+    crate::apply_sets(&directory, duplicate_sets, &options);
+    Ok(())
+}
+
+// the client's plan checked against what this agent scanned: every file must
+// resolve to a place inside DIR and belong to one of the scanned sets, and a
+// planned set may only choose the keeper of a scanned set and which of its
+// files go. the sets are rebuilt from the scan's own records, and each copy
+// is hashed again here and kept if it no longer matches its keeper
+fn vetted(planned: Vec<DuplicateSet>, scanned: &[DuplicateSet], directory: &Path) -> Result<Vec<DuplicateSet>, String> {
+    let resolve = |path: &Path| {
+        fs::canonicalize(path).map_err(|e| format!("the plan names '{}', which can't be read: {}", path.display(), e))
+    };
+    let directory = resolve(directory)?;
+    let mut members: HashMap<PathBuf, (usize, &FileInfo)> = HashMap::new();
+    for (index, set) in scanned.iter().enumerate() {
+        for file_info in std::iter::once(&set.keeper).chain(&set.duplicates) {
+            if let Ok(path) = fs::canonicalize(&file_info.path) {
+                members.insert(path, (index, file_info));
+            }
+        }
+    }
+    let member = |file_info: &FileInfo| {
+        let path = resolve(&file_info.path)?;
+        if !path.starts_with(&directory) {
+            return Err(format!("the plan names '{}', outside {}", file_info.path.display(), directory.display()));
+        }
+        members
+            .get(&path)
+            .copied()
+            .ok_or(format!("the plan names '{}', which this scan found no copies of", file_info.path.display()))
+    };
+
+    let mut duplicate_sets = Vec::new();
+    for set in planned {
+        let (index, keeper) = member(&set.keeper)?;
+        let mut duplicates = Vec::new();
+        for file_info in &set.duplicates {
+            let (other, duplicate) = member(file_info)?;
+            if other != index {
+                return Err(format!(
+                    "the plan puts '{}' with '{}', which this scan found apart",
+                    file_info.path.display(),
+                    set.keeper.path.display()
+                ));
+            }
+            if duplicate.path != keeper.path {
+                duplicates.push(duplicate.clone());
+            }
+        }
+
+        let keeper_hash = match hash::hash_contents(&keeper.path) {
+            Ok(keeper_hash) => keeper_hash,
+            Err(e) => {
+                let name = &set.normalized_filename;
+                eprintln!("Skipped set '{}': could not hash '{}': {}", name, keeper.path.display(), e);
+                continue;
+            }
+        };
+        duplicates.retain(|file_info| match hash::hash_contents(&file_info.path) {
+            Ok(hash) if hash == keeper_hash => true,
+            Ok(_) => {
+                eprintln!("Skipped '{}': not identical to '{}'", file_info.path.display(), keeper.path.display());
+                false
+            }
+            Err(e) => {
+                eprintln!("Skipped '{}': could not hash it: {}", file_info.path.display(), e);
+                false
+            }
+        });
+        if !duplicates.is_empty() {
+            let scanned = &scanned[index];
+            duplicate_sets.push(DuplicateSet {
+                normalized_filename: scanned.normalized_filename.clone(),
+                size: scanned.size,
+                hash: Some(keeper_hash),
+                keeper: keeper.clone(),
+                duplicates,
+            });
+        }
+    }
+    Ok(duplicate_sets)
+}
+
+// `hydra remote HOST DIR [--ssh COMMAND] [--hydra PATH] [flags]`
+pub fn remote(args: &[String]) -> Result<(), String> {
+    let usage = "usage: hydra remote HOST DIR [--ssh COMMAND] [--hydra PATH] [flags]";
+    let (host, directory, rest) = match args {
+        [host, directory, rest @ ..] if !host.starts_with('-') && !directory.starts_with('-') => {
+            (host, directory, rest)
+        }
+        _ => return Err(usage.to_string()),
+    };
+
+    let (mut ssh, mut hydra) = ("ssh".to_string(), "hydra".to_string());
+    let mut flags = Vec::new();
+    let mut rest = rest.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--ssh" => ssh = rest.next().ok_or(usage)?.clone(),
+            "--hydra" => hydra = rest.next().ok_or(usage)?.clone(),
+            _ => flags.push(arg.clone()),
+        }
+    }
+
+    // the same flags shape the scan over there and the review here
+    let mut flag_args = vec!["hydra".to_string()];
+    flag_args.extend(flags.iter().cloned());
+    let options = crate::parse_args(&flag_args).and_then(crate::set_up)?;
+
+    let mut command_line = vec![hydra, "agent".to_string(), directory.clone()];
+    command_line.extend(flags);
+    let command_line: Vec<String> = command_line.iter().map(|arg| quote(arg)).collect();
+
+    let mut ssh_words = ssh.split_whitespace();
+    let program = ssh_words.next().ok_or(usage)?;
+    let mut child = Command::new(program)
+        .args(ssh_words)
+        .arg(host)
+        .arg(command_line.join(" "))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("could not run '{}': {}", ssh, e))?;
+    let mut to_agent = child.stdin.take().unwrap();
+    let mut from_agent = BufReader::new(child.stdout.take().unwrap());
+
+    println!("Scanning {} on {} ...", directory, host);
+    let Some(text) = receive(&mut from_agent, SNAPSHOT, true)? else {
+        let _ = child.wait();
+        return Err(format!("the agent on {} stopped before sending its results", host));
+    };
+    let snapshot = snapshot::parse(&text, &format!("the snapshot from {}", host))?;
+    let mut duplicate_sets = snapshot.duplicate_sets;
+
+    let mut started = Instant::now();
+    let mut proceed = crate::review(&mut duplicate_sets, &options, &mut started);
+    if proceed {
+        let mut output = Vec::new();
+        report::write_report(&mut output, &duplicate_sets, &[], &[], &options).map_err(|e| e.to_string())?;
+        crate::pager::page(&output, options.pager);
+
+        if options.dry_run && !duplicate_sets.is_empty() {
+            println!("\n[DRY RUN MODE] No files were deleted on {}.", host);
+        }
+        proceed = !options.dry_run && !duplicate_sets.is_empty() && crate::confirm(&mut duplicate_sets, &options);
+    }
+
+    let sent = if proceed {
+        send(&mut to_agent, PLAN, &plan::to_json(&snapshot.directory, &duplicate_sets, &options))
+    } else {
+        writeln!(to_agent, "{}", CANCEL)
+    };
+    sent.map_err(|e| format!("lost the connection to {}: {}", host, e))?;
+    drop(to_agent);
+
+    // whatever the agent prints while acting
+    for line in from_agent.lines() {
+        println!("{}", line.map_err(|e| e.to_string())?);
+    }
+    let status = child.wait().map_err(|e| e.to_string())?;
+    if !status.success() {
+        return Err(format!("the agent on {} failed ({})", host, status));
+    }
+    Ok(())
+}
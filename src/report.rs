@@ -0,0 +1,366 @@
+use crate::actions::Action;
+use crate::casefold::CaseCollision;
+use crate::i18n::tr;
+use crate::volumes::Volumes;
+use crate::{DuplicateSet, FileInfo, Options, VolatileFile, acl, format_bytes, integrity, richness, sharing, streams};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GroupBy {
+    Set,
+    Dir,
+    Volume,
+}
+
+impl GroupBy {
+    pub fn parse(value: &str) -> Option<GroupBy> {
+        match value {
+            "set" => Some(GroupBy::Set),
+            "dir" => Some(GroupBy::Dir),
+            "volume" => Some(GroupBy::Volume),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortKey {
+    Size,
+    Count,
+    Name,
+    Path,
+}
+
+impl SortKey {
+    pub fn parse(value: &str) -> Option<SortKey> {
+        match value {
+            "size" => Some(SortKey::Size),
+            "count" => Some(SortKey::Count),
+            "name" => Some(SortKey::Name),
+            "path" => Some(SortKey::Path),
+            _ => None,
+        }
+    }
+}
+
+pub fn reclaimable_bytes(set: &DuplicateSet) -> u64 {
+    set.duplicates.iter().map(|file_info| file_info.size).sum()
+}
+
+// size and count put the most wasteful sets first, name and path sort
+// alphabetically; `reverse` flips either. sets start out ordered by keeper
+// path and copies by path, so runs over the same files list them alike
+// however the scan or the hash maps happened to order them
+pub fn sort_and_limit(duplicate_sets: &mut Vec<DuplicateSet>, options: &Options) {
+    for set in duplicate_sets.iter_mut() {
+        set.duplicates.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+    duplicate_sets.sort_by(|a, b| a.keeper.path.cmp(&b.keeper.path));
+    if let Some(sort) = options.sort {
+        match sort {
+            SortKey::Size => duplicate_sets.sort_by_key(|set| std::cmp::Reverse(reclaimable_bytes(set))),
+            SortKey::Count => duplicate_sets.sort_by_key(|set| std::cmp::Reverse(set.duplicates.len())),
+            SortKey::Name => duplicate_sets.sort_by(|a, b| a.normalized_filename.cmp(&b.normalized_filename)),
+            SortKey::Path => duplicate_sets.sort_by(|a, b| a.keeper.path.cmp(&b.keeper.path)),
+        }
+    }
+    if options.reverse {
+        duplicate_sets.reverse();
+    }
+    if let Some(limit) = options.limit {
+        duplicate_sets.truncate(limit);
+    }
+}
+
+fn delete_verb(options: &Options) -> &'static str {
+    match (options.dry_run, options.action) {
+        (true, Action::Delete) => tr!("Would delete"),
+        (false, Action::Delete) => tr!("Will delete"),
+        (true, Action::Trash) => tr!("Would trash"),
+        (false, Action::Trash) => tr!("Will trash"),
+        (true, Action::Link) => tr!("Would link"),
+        (false, Action::Link) => tr!("Will link"),
+        (true, Action::Exec) => tr!("Would pass on"),
+        (false, Action::Exec) => tr!("Will pass on"),
+    }
+}
+
+// a truncated copy (--truncated) is shorter than the file it's kept against;
+// a copy stripped of metadata (--ignore-image-metadata, --ignore-audio-tags)
+// differs in size too, and a damaged one (--check-integrity) says what is
+// wrong with it
+fn truncation(file_info: &FileInfo, set: &DuplicateSet, options: &Options) -> String {
+    if let Some(damage) = options.check_integrity.then(|| integrity::damage(&file_info.path)).flatten() {
+        format!(" (damaged: {})", damage)
+    } else if file_info.size != set.size
+        && richness::ignores_metadata(options)
+        && richness::same_media(&file_info.path, &set.keeper.path)
+    {
+        format!(" (differs only in metadata, {} bytes)", file_info.size)
+    } else if options.truncated && file_info.size < set.size {
+        format!(" (truncated, {} of {} bytes)", file_info.size, set.size)
+    } else {
+        String::new()
+    }
+}
+
+fn write_sets(out: &mut dyn Write, duplicate_sets: &[DuplicateSet], options: &Options) -> io::Result<()> {
+    for set in duplicate_sets {
+        writeln!(out, "\n{}", tr!("--- Duplicate Set ---"))?;
+        writeln!(out, "{}", tr!("Normalized filename: {}", set.normalized_filename))?;
+        if let Some(id) = set.id() {
+            writeln!(out, "ID: {}", id)?;
+        }
+        writeln!(out, "{}", tr!("Size: {} bytes", set.size))?;
+        let reclaimable = tr!("{} file(s), {} bytes reclaimable", set.duplicates.len(), reclaimable_bytes(set));
+        writeln!(out, "{}", reclaimable)?;
+        writeln!(out, "{}", tr!("Keeping: {}", set.keeper.path.display()))?;
+        if options.explain {
+            writeln!(out, "  {}", tr!("because {}", crate::explain_keeper(set, options)))?;
+        }
+
+        // list files to delete
+        for file_info in &set.duplicates {
+            let note = truncation(file_info, set, options);
+            writeln!(out, "{}: {}{}", delete_verb(options), file_info.path.display(), note)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn count_files(directory: &Path) -> Option<usize> {
+    let entries = fs::read_dir(directory).ok()?;
+    Some(
+        entries
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_file()))
+            .count(),
+    )
+}
+
+// one section per directory holding deletable files, so a folder that is
+// nothing but copies stands out
+fn write_sets_by_directory(out: &mut dyn Write, duplicate_sets: &[DuplicateSet], options: &Options) -> io::Result<()> {
+    let mut by_directory: HashMap<PathBuf, Vec<(&DuplicateSet, Vec<&FileInfo>)>> = HashMap::new();
+
+    for set in duplicate_sets {
+        let mut per_directory: HashMap<PathBuf, Vec<&FileInfo>> = HashMap::new();
+        for file_info in &set.duplicates {
+            let directory = file_info.path.parent().unwrap_or(Path::new("")).to_path_buf();
+            per_directory.entry(directory).or_default().push(file_info);
+        }
+        for (directory, paths) in per_directory {
+            by_directory.entry(directory).or_default().push((set, paths));
+        }
+    }
+
+    let mut directories: Vec<_> = by_directory.into_iter().collect();
+    directories.sort_by(|a, b| a.0.cmp(&b.0));
+
+    for (directory, entries) in directories {
+        let deletable: usize = entries.iter().map(|(_, file_infos)| file_infos.len()).sum();
+        let bytes: u64 = entries.iter().flat_map(|(_, file_infos)| file_infos).map(|file_info| file_info.size).sum();
+
+        writeln!(out, "\n=== {} ===", directory.display())?;
+        let removable = match count_files(&directory) {
+            Some(total) if total == deletable => tr!(" - every file in this directory is a copy").to_string(),
+            Some(total) => tr!(" of {} file(s)", total),
+            None => String::new(),
+        };
+        writeln!(out, "{}{}", tr!("{} file(s) to delete, {}", deletable, format_bytes(bytes)), removable)?;
+
+        for (set, file_infos) in entries {
+            for file_info in file_infos {
+                let note = truncation(file_info, set, options);
+                writeln!(out, "{}: {}{}", delete_verb(options), file_info.path.display(), note)?;
+            }
+            writeln!(out, "{}", tr!("  (keeping {})", set.keeper.path.display()))?;
+        }
+    }
+
+    Ok(())
+}
+
+// deletable files of every set, per volume they would be freed on
+fn by_volume<'a>(
+    duplicate_sets: &'a [DuplicateSet],
+    volumes: &mut Volumes,
+) -> Vec<(PathBuf, Vec<(&'a DuplicateSet, &'a FileInfo)>)> {
+    let mut by_volume: HashMap<PathBuf, Vec<(&DuplicateSet, &FileInfo)>> = HashMap::new();
+    for set in duplicate_sets {
+        for file_info in &set.duplicates {
+            by_volume.entry(volumes.of(&file_info.path)).or_default().push((set, file_info));
+        }
+    }
+
+    let mut by_volume: Vec<_> = by_volume.into_iter().collect();
+    by_volume.sort_by(|a, b| a.0.cmp(&b.0));
+    by_volume
+}
+
+// one section per volume, for scans spanning several disks; a keeper on
+// another volume is named with it, since the space is only freed here
+fn write_sets_by_volume(out: &mut dyn Write, duplicate_sets: &[DuplicateSet], options: &Options) -> io::Result<()> {
+    let mut volumes = Volumes::default();
+
+    for (volume, entries) in by_volume(duplicate_sets, &mut volumes) {
+        let bytes: u64 = entries.iter().map(|(_, file_info)| file_info.size).sum();
+        writeln!(out, "\n{}", tr!("=== Volume {} ===", volume.display()))?;
+        writeln!(out, "{}", tr!("{} file(s) to delete, {} reclaimable", entries.len(), format_bytes(bytes)))?;
+
+        for (set, file_info) in entries {
+            let note = truncation(file_info, set, options);
+            writeln!(out, "{}: {}{}", delete_verb(options), file_info.path.display(), note)?;
+            let keeper_volume = volumes.of(&set.keeper.path);
+            if keeper_volume == volume {
+                writeln!(out, "{}", tr!("  (keeping {})", set.keeper.path.display()))?;
+            } else {
+                writeln!(out, "{}", tr!("  (keeping {}, on {})", set.keeper.path.display(), keeper_volume.display()))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// sets held back by --max-set-size, for a look at what made them so big
+pub fn write_oversized(out: &mut dyn Write, oversized: &[DuplicateSet]) -> io::Result<()> {
+    if oversized.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(out, "\n{}", tr!("--- Oversized Sets (skipped, review by hand) ---"))?;
+    writeln!(out, "{}", tr!("Sets this large usually come from generated files or a normalization rule that"))?;
+    writeln!(out, "{}", tr!("matches too much. Raise --max-set-size (or pass 0) to act on them anyway."))?;
+    for set in oversized {
+        let files = set.duplicates.len() + 1;
+        writeln!(out, "\n{}", tr!("{}: {} files of {} bytes", set.normalized_filename, files, set.size))?;
+        writeln!(out, "{}", tr!("  e.g. {}", set.keeper.path.display()))?;
+    }
+
+    Ok(())
+}
+
+fn write_volatile_files(out: &mut dyn Write, volatile_files: &[VolatileFile]) -> io::Result<()> {
+    if volatile_files.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(out, "\n{}", tr!("--- Volatile Files ---"))?;
+    for volatile_file in volatile_files {
+        writeln!(out, "{}: {}", volatile_file.path.display(), volatile_file.outcome)?;
+    }
+
+    Ok(())
+}
+
+fn write_case_collisions(out: &mut dyn Write, case_collisions: &[CaseCollision]) -> io::Result<()> {
+    if case_collisions.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(out, "\n{}", tr!("--- Case Collisions ---"))?;
+    writeln!(out, "{}", tr!("These names differ only in case on a case-insensitive filesystem, so a path"))?;
+    writeln!(out, "{}", tr!("may open the wrong file. They were left untouched; rename one of each group."))?;
+    for collision in case_collisions {
+        let paths: Vec<String> = collision.paths.iter().map(|path| path.display().to_string()).collect();
+        writeln!(out, "{}", paths.join(" <-> "))?;
+    }
+
+    Ok(())
+}
+
+// --summary: the aggregate numbers only, one per line
+pub fn write_summary(out: &mut dyn Write, duplicate_sets: &[DuplicateSet]) -> io::Result<()> {
+    let files: usize = duplicate_sets.iter().map(|set| set.duplicates.len()).sum();
+    let sharing = sharing::measure(duplicate_sets);
+    let bytes = |bytes: u64| {
+        if bytes < 1024 {
+            format!("{} bytes", bytes)
+        } else {
+            format!("{} bytes ({})", bytes, format_bytes(bytes))
+        }
+    };
+
+    writeln!(out, "Duplicate sets: {}", duplicate_sets.len())?;
+    writeln!(out, "Files to delete: {}", files)?;
+    writeln!(out, "Reclaimable: {}", bytes(sharing.reclaimable))?;
+    if sharing.shared > 0 {
+        writeln!(out, "Already shared: {}", bytes(sharing.shared))?;
+    }
+    Ok(())
+}
+
+// the full human-readable report: sets, volatile files, case collisions,
+// alternate data streams and access controls at risk and the summary
+pub fn write_report(
+    out: &mut dyn Write,
+    duplicate_sets: &[DuplicateSet],
+    volatile_files: &[VolatileFile],
+    case_collisions: &[CaseCollision],
+    options: &Options,
+) -> io::Result<()> {
+    match options.group_by {
+        GroupBy::Set => write_sets(out, duplicate_sets, options)?,
+        GroupBy::Dir => write_sets_by_directory(out, duplicate_sets, options)?,
+        GroupBy::Volume => write_sets_by_volume(out, duplicate_sets, options)?,
+    }
+    write_volatile_files(out, volatile_files)?;
+    write_case_collisions(out, case_collisions)?;
+    streams::write_streams(out, duplicate_sets, options.merge_metadata)?;
+    acl::write_differences(out, duplicate_sets)?;
+
+    if duplicate_sets.is_empty() {
+        writeln!(out, "\n{}", tr!("No duplicates found!"))?;
+        return Ok(());
+    }
+
+    let total_files_to_delete: usize = duplicate_sets.iter().map(|set| set.duplicates.len()).sum();
+
+    writeln!(out, "\n================================")?;
+    writeln!(out, "{}", tr!("Summary: Found {} duplicate set(s)", duplicate_sets.len()))?;
+    let total = match options.action {
+        Action::Delete => tr!("Total files to delete: {}", total_files_to_delete),
+        Action::Trash => tr!("Total files to trash: {}", total_files_to_delete),
+        Action::Link => tr!("Total files to link: {}", total_files_to_delete),
+        Action::Exec => tr!("Total files to pass to the command: {}", total_files_to_delete),
+    };
+    writeln!(out, "{}", total)?;
+    // hard links and reflinks make some of the copies free already
+    let sharing = sharing::measure(duplicate_sets);
+    if sharing.shared > 0 {
+        let (reclaimable, shared) = (format_bytes(sharing.reclaimable), format_bytes(sharing.shared));
+        let line = tr!("Reclaimable: {} ({} more is already shared by hard links or reflinks)", reclaimable, shared);
+        writeln!(out, "{}", line)?;
+    }
+    // the trash keeps the copies on the same filesystem, so their space only
+    // comes back once it is emptied
+    if options.action == Action::Trash {
+        let reclaimable = format_bytes(sharing.reclaimable);
+        let line = if options.empty_trash_after {
+            tr!("Space freed: {}, when this run's items are emptied from the trash at the end", reclaimable)
+        } else {
+            tr!("Space freed now: none; {} once the trash is emptied", reclaimable)
+        };
+        writeln!(out, "{}", line)?;
+    }
+    let per_volume = by_volume(duplicate_sets, &mut Volumes::default());
+    if per_volume.len() > 1 {
+        for (volume, entries) in &per_volume {
+            let bytes: u64 = entries.iter().map(|(_, file_info)| file_info.size).sum();
+            writeln!(out, "{}", tr!("  on {}: {} file(s), {}", volume.display(), entries.len(), format_bytes(bytes)))?;
+        }
+    }
+    if !volatile_files.is_empty() {
+        writeln!(out, "{}", tr!("Volatile files: {}", volatile_files.len()))?;
+    }
+    if !case_collisions.is_empty() {
+        writeln!(out, "{}", tr!("Case collisions (left untouched): {}", case_collisions.len()))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,126 @@
+// the results file: every run keeps what it has found in `last-run.json` in
+// the state directory (or --results FILE), so a run killed partway through
+// doesn't lose its work. groups of files confirmed to be copies are saved as
+// hashing finds them, each time to a temporary file renamed over the last, so
+// the file is always whole: "complete": false and a "found" list while the
+// run is under way, after it was cut short and while its sets await review,
+// and the run's plan, as --output writes it and `hydra apply` reads it, only
+// once the sets are the ones the user settled on
+use crate::json;
+use crate::{DuplicateSet, FileInfo, Options, environment, limits, plan};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// the file is rewritten at most this often while hashing
+const INTERVAL: Duration = Duration::from_secs(1);
+
+struct Run {
+    path: PathBuf,
+    // the fields before "found"
+    header: String,
+    written: Instant,
+}
+
+static RUN: Mutex<Option<Run>> = Mutex::new(None);
+
+// write `text` to a temporary file beside `path` and rename it into place
+fn replace(path: &Path, text: &str) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    fs::write(&temporary, text)?;
+    fs::rename(&temporary, path)
+}
+
+fn group_json(hash: Option<u64>, files: &[&FileInfo]) -> String {
+    let hash = hash.map_or("null".to_string(), |hash| json::string(&format!("{:016x}", hash)));
+    let paths: Vec<String> = files.iter().map(|f| json::string(&f.path.to_string_lossy())).collect();
+    format!(
+        "    {{\"hash\": {}, \"size\": {}, \"files\": [{}]}}",
+        hash,
+        files.first().map_or(0, |f| f.size),
+        paths.join(", ")
+    )
+}
+
+fn partial(header: &str, groups: &[String]) -> String {
+    let found = if groups.is_empty() { "[]".to_string() } else { format!("[\n{}\n  ]", groups.join(",\n")) };
+    format!("{}  \"found\": {}\n}}\n", header, found)
+}
+
+// save `text` to the run's file, dropping the file for the rest of the run if
+// it can't be written
+fn save(run: &mut Option<Run>, text: impl FnOnce(&Run) -> String) {
+    let Some(current) = run else {
+        return;
+    };
+    if let Err(e) = replace(&current.path, &text(current)) {
+        eprintln!("Warning: Could not write the results file '{}': {}", current.path.display(), e);
+        *run = None;
+        return;
+    }
+    current.written = Instant::now();
+}
+
+// start the run's file, empty so far; called as the scan begins
+pub fn start(directory: &str, options: &Options) {
+    let Some(path) = &options.results else {
+        return;
+    };
+    let header = format!(
+        "{{\n  \"version\": {},\n  \"complete\": false,\n  \"directory\": {},\n  \"action\": {},\n  \
+         \"environment\": {},\n",
+        plan::VERSION,
+        json::string(directory),
+        json::string(options.action.verb()),
+        environment::to_json(options)
+    );
+    let mut run = RUN.lock().unwrap();
+    *run = Some(Run {
+        path: path.clone(),
+        header,
+        written: Instant::now(),
+    });
+    save(&mut run, |run| partial(&run.header, &[]));
+}
+
+// the groups confirmed so far, by hash; saved unless the file was written
+// less than INTERVAL ago
+pub fn found<'a>(groups: impl Iterator<Item = (u64, &'a [FileInfo])>) {
+    let mut run = RUN.lock().unwrap();
+    if run.as_ref().is_none_or(|run| run.written.elapsed() < INTERVAL) {
+        return;
+    }
+    let groups: Vec<String> = groups
+        .filter(|(_, files)| files.len() > 1)
+        .map(|(hash, files)| group_json(Some(hash), &files.iter().collect::<Vec<_>>()))
+        .collect();
+    save(&mut run, |run| partial(&run.header, &groups));
+}
+
+// the scan's sets, still marked incomplete: until they are reviewed (or if
+// the run was cut short, for good) they must not be applied
+pub fn finish(duplicate_sets: &[DuplicateSet]) {
+    let mut run = RUN.lock().unwrap();
+    let groups: Vec<String> = duplicate_sets
+        .iter()
+        .map(|set| group_json(set.hash, &std::iter::once(&set.keeper).chain(&set.duplicates).collect::<Vec<_>>()))
+        .collect();
+    save(&mut run, |run| partial(&run.header, &groups));
+    if limits::reached().is_some() {
+        *run = None;
+    }
+}
+
+// replace the file with the run's plan: the sets as review, --edit and the
+// confirmation left them
+pub fn reviewed(directory: &str, duplicate_sets: &[DuplicateSet], options: &Options) {
+    let mut run = RUN.lock().unwrap();
+    save(&mut run, |_| plan::to_json(directory, duplicate_sets, options));
+    *run = None;
+}
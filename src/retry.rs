@@ -0,0 +1,80 @@
+// network filesystems fail now and then in ways that pass: a stale nfs handle,
+// an i/o error from a dropped connection, a timeout. files that fail like this
+// while being listed, hashed or removed are set aside and tried again once the
+// rest of that step is done, a few times with growing pauses, before being
+// counted as failures. anything else (permission denied, not found) fails at
+// once, as before
+use std::io;
+use std::thread;
+use std::time::Duration;
+
+// the pauses before each retry
+pub const DELAYS: [Duration; 3] = [Duration::from_secs(1), Duration::from_secs(2), Duration::from_secs(4)];
+
+#[cfg(unix)]
+fn is_transient_code(code: i32) -> bool {
+    #[cfg(target_os = "linux")]
+    const CODES: [i32; 7] = [
+        4,   // EINTR
+        5,   // EIO
+        11,  // EAGAIN
+        104, // ECONNRESET
+        110, // ETIMEDOUT
+        112, // EHOSTDOWN
+        116, // ESTALE
+    ];
+    #[cfg(not(target_os = "linux"))]
+    const CODES: [i32; 6] = [
+        4,  // EINTR
+        5,  // EIO
+        35, // EAGAIN
+        54, // ECONNRESET
+        60, // ETIMEDOUT
+        70, // ESTALE
+    ];
+    CODES.contains(&code)
+}
+
+#[cfg(windows)]
+fn is_transient_code(code: i32) -> bool {
+    const ERROR_NETNAME_DELETED: i32 = 64;
+    const ERROR_UNEXP_NET_ERR: i32 = 59;
+    const ERROR_SEM_TIMEOUT: i32 = 121;
+    matches!(code, ERROR_NETNAME_DELETED | ERROR_UNEXP_NET_ERR | ERROR_SEM_TIMEOUT)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn is_transient_code(_code: i32) -> bool {
+    false
+}
+
+// whether an error is worth trying again
+pub fn is_transient(e: &io::Error) -> bool {
+    matches!(e.kind(), io::ErrorKind::TimedOut | io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock)
+        || e.raw_os_error().is_some_and(is_transient_code)
+}
+
+// try each failed item again after each of DELAYS, in order, for as long as
+// it keeps failing transiently; returns every item with its final result
+pub fn queue<I, T>(
+    mut failed: Vec<(I, io::Error)>,
+    mut operation: impl FnMut(&I) -> io::Result<T>,
+) -> Vec<(I, io::Result<T>)> {
+    let mut finished = Vec::new();
+    for delay in DELAYS {
+        if failed.is_empty() {
+            break;
+        }
+        thread::sleep(delay);
+        let mut failing = Vec::new();
+        for (item, _) in failed {
+            match operation(&item) {
+                Err(e) if is_transient(&e) => failing.push((item, e)),
+                result => finished.push((item, result)),
+            }
+        }
+        failed = failing;
+    }
+    finished.extend(failed.into_iter().map(|(item, e)| (item, Err(e))));
+    finished
+}
@@ -0,0 +1,338 @@
+// how much embedded metadata a file carries: exif fields and xmp, icc and
+// iptc blocks in jpegs, text and exif chunks in pngs, the tags of tiff-based
+// raws, id3v2 frames in mp3s and vorbis comments in flacs. --prefer-metadata
+// keeps the copy carrying the most. with --ignore-image-metadata, jpegs and
+// pngs whose image data is identical are copies even when one has had its
+// exif stripped, and the copy with the richest metadata is the one kept.
+// --ignore-audio-tags does the same for mp3s and flacs whose audio frames are
+// identical once their tags are set aside, e.g. one retagged by a player
+use crate::{DuplicateSet, FileInfo, Options, bursts, choose_keeper, hash, set_name};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+// jpeg, tiff, mp3 and flac metadata sits near the start of the file
+const HEAD_BYTES: u64 = 256 * 1024;
+
+// png chunks that only describe the image
+const PNG_METADATA: [&[u8; 4]; 6] = [b"tEXt", b"zTXt", b"iTXt", b"eXIf", b"tIME", b"iCCP"];
+
+// the (marker, payload) of each segment before a jpeg's image data, and
+// where the image data starts; None if the head is cut short
+fn jpeg_segments(data: &[u8]) -> (Vec<(u8, &[u8])>, Option<usize>) {
+    let mut segments = Vec::new();
+    let mut offset = 2;
+    while data.get(offset) == Some(&0xff) {
+        let Some(&marker) = data.get(offset + 1) else {
+            break;
+        };
+        // start of scan: the image data follows
+        if marker == 0xda {
+            return (segments, Some(offset));
+        }
+        let Some(length) = data.get(offset + 2..offset + 4) else {
+            break;
+        };
+        let end = offset + 2 + u16::from_be_bytes([length[0], length[1]]) as usize;
+        let Some(payload) = data.get(offset + 4..end) else {
+            break;
+        };
+        segments.push((marker, payload));
+        offset = end;
+    }
+    (segments, None)
+}
+
+// APPn segments and comments hold metadata; the rest is the image
+fn is_jpeg_metadata(marker: u8) -> bool {
+    (0xe0..=0xef).contains(&marker) || marker == 0xfe
+}
+
+fn jpeg_fields(data: &[u8]) -> usize {
+    jpeg_segments(data)
+        .0
+        .iter()
+        .map(|(marker, payload)| match marker {
+            0xe1 if payload.starts_with(b"Exif\x00\x00") => bursts::exif_fields(&payload[6..]),
+            0xe1 if payload.starts_with(b"http://ns.adobe.com/xap/1.0/") => 1,
+            0xe2 if payload.starts_with(b"ICC_PROFILE\x00") => 1,
+            0xed if payload.starts_with(b"Photoshop 3.0\x00") => 1,
+            0xfe => 1,
+            _ => 0,
+        })
+        .sum()
+}
+
+// the (type, data) of each chunk of a png
+fn png_chunks(data: &[u8]) -> Option<Vec<(&[u8], &[u8])>> {
+    let mut chunks = Vec::new();
+    let mut offset = 8;
+    while offset < data.len() {
+        let length = u32::from_be_bytes(data.get(offset..offset + 4)?.try_into().ok()?) as usize;
+        let kind = data.get(offset + 4..offset + 8)?;
+        let end = (offset + 8).checked_add(length)?;
+        chunks.push((kind, data.get(offset + 8..end)?));
+        offset = end + 4;
+    }
+    Some(chunks)
+}
+
+fn png_fields(data: &[u8]) -> usize {
+    png_chunks(data)
+        .unwrap_or_default()
+        .iter()
+        .filter(|(kind, _)| PNG_METADATA.iter().any(|metadata| metadata.as_slice() == *kind))
+        .map(|(kind, data)| if *kind == b"eXIf" { bursts::exif_fields(data) } else { 1 })
+        .sum()
+}
+
+// the frames of an id3v2 tag at the start of the file
+fn id3_fields(data: &[u8]) -> usize {
+    let Some(header) = data.get(..10) else {
+        return 0;
+    };
+    let syncsafe = |bytes: &[u8]| bytes.iter().fold(0, |size, byte| (size << 7) | (*byte & 0x7f) as usize);
+    let version = header[3];
+    let end = (10 + syncsafe(&header[6..10])).min(data.len());
+    let mut offset = 10;
+    if header[5] & 0x40 != 0 {
+        let Some(size) = data.get(10..14) else {
+            return 0;
+        };
+        // id3v2.4 counts the size field in the extended header's size; v2.3 doesn't
+        offset += if version == 4 { syncsafe(size) } else { 4 + u32_be(size) };
+    }
+
+    // id3v2.2 frames have 3-byte ids and sizes, later versions 4-byte ones
+    let (id_len, header_len) = if version == 2 { (3, 6) } else { (4, 10) };
+    let mut frames = 0;
+    while offset + header_len <= end && data[offset] != 0 {
+        let size_bytes = &data[offset + id_len..offset + id_len * 2];
+        let size = match version {
+            2 => size_bytes.iter().fold(0, |size, byte| (size << 8) | *byte as usize),
+            4 => syncsafe(size_bytes),
+            _ => u32_be(size_bytes),
+        };
+        frames += 1;
+        offset += header_len + size;
+    }
+    frames
+}
+
+fn u32_be(bytes: &[u8]) -> usize {
+    bytes.iter().fold(0, |value, byte| (value << 8) | *byte as usize)
+}
+
+// vorbis comments and pictures among a flac's metadata blocks
+fn flac_fields(data: &[u8]) -> usize {
+    let mut fields = 0;
+    let mut offset = 4;
+    while let Some(header) = data.get(offset..offset + 4) {
+        let length = u32_be(&header[1..]);
+        let block = data.get(offset + 4..offset + 4 + length);
+        match (header[0] & 0x7f, block) {
+            (4, Some(block)) => {
+                let vendor = block.get(..4).map_or(0, |bytes| u32::from_le_bytes(bytes.try_into().unwrap()));
+                let count = block.get(4 + vendor as usize..8 + vendor as usize);
+                fields += count.map_or(0, |bytes| u32::from_le_bytes(bytes.try_into().unwrap()) as usize);
+            }
+            (6, _) => fields += 1,
+            _ => {}
+        }
+        if header[0] & 0x80 != 0 {
+            break;
+        }
+        offset += 4 + length;
+    }
+    fields
+}
+
+// the number of metadata fields a file carries, 0 for types that carry none
+// hydra reads; pngs are read whole, as their text chunks can trail the image
+fn fields(path: &Path) -> io::Result<usize> {
+    let mut head = Vec::new();
+    hash::open(path)?.take(HEAD_BYTES).read_to_end(&mut head)?;
+    Ok(if head.starts_with(b"\xff\xd8") {
+        jpeg_fields(&head)
+    } else if head.starts_with(b"\x89PNG\r\n\x1a\n") {
+        png_fields(&hash::read(path)?)
+    } else if head.starts_with(b"II*\x00") || head.starts_with(b"MM\x00*") {
+        bursts::exif_fields(&head)
+    } else if head.starts_with(b"ID3") {
+        id3_fields(&head)
+    } else if head.starts_with(b"fLaC") {
+        flac_fields(&head)
+    } else {
+        0
+    })
+}
+
+// a file's metadata fields, or none when it can't be read
+pub fn fields_or_none(path: &Path) -> usize {
+    fields(path).unwrap_or_else(|e| {
+        eprintln!("Warning: Could not read metadata of '{}': {}", path.display(), e);
+        0
+    })
+}
+
+// where an mp3's audio frames start and end: after any id3v2 tags, and
+// before an id3v1 tag and an apev2 tag at the end
+fn mp3_frames(data: &[u8]) -> Option<(usize, usize)> {
+    let syncsafe = |bytes: &[u8]| bytes.iter().fold(0, |size, byte| (size << 7) | (*byte & 0x7f) as usize);
+    let mut start = 0;
+    while data.get(start..start + 3) == Some(b"ID3") {
+        let header = data.get(start..start + 10)?;
+        let footer = if header[5] & 0x10 != 0 { 10 } else { 0 };
+        start += 10 + syncsafe(&header[6..10]) + footer;
+    }
+    let mut end = data.len();
+    if end >= start + 128 && data[end - 128..].starts_with(b"TAG") {
+        end -= 128;
+    }
+    if end >= start + 32 && data[end - 32..].starts_with(b"APETAGEX") {
+        let footer = &data[end - 32..end];
+        let size = u32::from_le_bytes(footer[12..16].try_into().unwrap()) as usize;
+        let has_header = u32::from_le_bytes(footer[20..24].try_into().unwrap()) & 0x8000_0000 != 0;
+        end = end.checked_sub(size + if has_header { 32 } else { 0 })?;
+    }
+    // the frames start with a frame sync
+    let frames = data.get(start..end)?;
+    (frames.len() >= 2 && frames[0] == 0xff && frames[1] & 0xe0 == 0xe0).then_some((start, end))
+}
+
+// where a flac's audio frames start, after its last metadata block
+fn flac_frames(data: &[u8]) -> Option<usize> {
+    let mut offset = 4;
+    loop {
+        let header = data.get(offset..offset + 4)?;
+        offset += 4 + u32_be(&header[1..]);
+        if header[0] & 0x80 != 0 {
+            return (offset <= data.len()).then_some(offset);
+        }
+    }
+}
+
+// the hash of a jpeg's, png's, mp3's or flac's contents without its
+// metadata, or None for any other file
+fn media_data_hash(path: &Path) -> io::Result<Option<u64>> {
+    let data = hash::read(path)?;
+    let mut image = Vec::with_capacity(data.len());
+    if data.starts_with(b"\xff\xd8") {
+        let (segments, Some(scan)) = jpeg_segments(&data) else {
+            return Ok(None);
+        };
+        for (marker, payload) in segments.iter().filter(|(marker, _)| !is_jpeg_metadata(*marker)) {
+            image.push(*marker);
+            image.extend_from_slice(payload);
+        }
+        image.extend_from_slice(&data[scan..]);
+    } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        let Some(chunks) = png_chunks(&data) else {
+            return Ok(None);
+        };
+        for (kind, data) in chunks {
+            if !PNG_METADATA.iter().any(|metadata| metadata.as_slice() == kind) {
+                image.extend_from_slice(kind);
+                image.extend_from_slice(data);
+            }
+        }
+    } else if data.starts_with(b"fLaC") {
+        let Some(start) = flac_frames(&data) else {
+            return Ok(None);
+        };
+        return Ok(Some(hash::hash_bytes(&data[start..])));
+    } else if let Some((start, end)) = mp3_frames(&data) {
+        return Ok(Some(hash::hash_bytes(&data[start..end])));
+    } else {
+        return Ok(None);
+    }
+    Ok(Some(hash::hash_bytes(&image)))
+}
+
+// whether copies of the file may differ in metadata under the options given
+fn is_candidate(path: &Path, options: &Options) -> bool {
+    let extension = path.extension().map(|extension| extension.to_string_lossy().to_lowercase());
+    match extension.as_deref() {
+        Some("jpg" | "jpeg" | "png") => options.ignore_image_metadata,
+        Some("mp3" | "flac") => options.ignore_audio_tags,
+        _ => false,
+    }
+}
+
+// whether metadata is set aside when matching any kind of file
+pub fn ignores_metadata(options: &Options) -> bool {
+    options.ignore_image_metadata || options.ignore_audio_tags
+}
+
+// whether two files are the same image or audio once their metadata is set
+// aside
+pub fn same_media(a: &Path, b: &Path) -> bool {
+    match (media_data_hash(a), media_data_hash(b)) {
+        (Ok(Some(a)), Ok(Some(b))) => a == b,
+        _ => false,
+    }
+}
+
+// pull images and audio files that differ only in their metadata out of the
+// name groups, returning one set per image or recording with the copy
+// carrying the most metadata as keeper. files of one size are left to the
+// usual matching
+pub fn extract_metadata_copies(
+    hashmap_name: &mut HashMap<String, Vec<FileInfo>>,
+    options: &Options,
+) -> Vec<DuplicateSet> {
+    let mut metadata_sets = Vec::new();
+    let mut taken: HashSet<PathBuf> = HashSet::new();
+
+    for file_infos in hashmap_name.values() {
+        let media: Vec<&FileInfo> = file_infos.iter().filter(|f| is_candidate(&f.path, options)).collect();
+        if media.iter().all(|f| f.size == media[0].size) {
+            continue;
+        }
+        let mut by_data: HashMap<u64, Vec<FileInfo>> = HashMap::new();
+        for file_info in media {
+            match media_data_hash(&file_info.path) {
+                Ok(Some(hash)) => by_data.entry(hash).or_default().push(file_info.clone()),
+                Ok(None) => {}
+                Err(e) => eprintln!("Warning: Could not read '{}': {}", file_info.path.display(), e),
+            }
+        }
+
+        for copies in by_data.into_values() {
+            if copies.iter().all(|f| f.size == copies[0].size) {
+                continue;
+            }
+            let fields: Vec<usize> = copies.iter().map(|f| fields_or_none(&f.path)).collect();
+            let most = fields.iter().copied().max().unwrap_or_default();
+            let richest: Vec<FileInfo> = copies
+                .iter()
+                .zip(&fields)
+                .filter(|(_, fields)| **fields == most)
+                .map(|(file_info, _)| file_info.clone())
+                .collect();
+            let Some(keeper_index) = choose_keeper(&richest, options) else {
+                continue;
+            };
+            let keeper = richest[keeper_index].clone();
+
+            taken.extend(copies.iter().map(|file_info| file_info.path.clone()));
+            let mut duplicates: Vec<FileInfo> = copies.into_iter().filter(|f| f.path != keeper.path).collect();
+            duplicates.sort_by(|a, b| a.path.cmp(&b.path));
+            metadata_sets.push(DuplicateSet {
+                normalized_filename: set_name(&keeper, options),
+                size: keeper.size,
+                hash: None,
+                keeper,
+                duplicates,
+            });
+        }
+    }
+
+    if !taken.is_empty() {
+        for file_infos in hashmap_name.values_mut() {
+            file_infos.retain(|file_info| !taken.contains(&file_info.path));
+        }
+    }
+
+    metadata_sets
+}
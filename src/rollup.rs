@@ -0,0 +1,103 @@
+// --rollup: reclaimable space per subdirectory of the scanned folders, like
+// du, to decide which subtree to clean first before reading individual sets.
+// a copy counts toward the folder --rollup-depth levels below its root that
+// holds it; copies less deep count toward the folder they're in. the same
+// totals, by top-level folder, are shown right before the confirmation prompt
+use crate::i18n::tr;
+use crate::{DuplicateSet, format_bytes, root_of};
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+fn subtree(path: &Path, roots: &[PathBuf], depth: usize) -> PathBuf {
+    let Some(parent) = path.parent() else {
+        return PathBuf::new();
+    };
+    let Some(root) = root_of(path, roots) else {
+        return parent.to_path_buf();
+    };
+    let relative = parent.strip_prefix(root).unwrap_or(parent);
+    let shown: PathBuf = relative.components().take(depth).collect();
+    // with several roots a folder is named in full, as the same name can be under each
+    if roots.len() > 1 { root.join(shown) } else { shown }
+}
+
+fn write_line(out: &mut dyn Write, folder: &Path, files: usize, bytes: u64) -> io::Result<()> {
+    let name = if folder.as_os_str().is_empty() { ".".to_string() } else { folder.display().to_string() };
+    writeln!(out, "{:>11}  {}  ({})", format_bytes(bytes), name, tr!("{} file(s)", files))
+}
+
+pub fn write_rollup(
+    out: &mut dyn Write,
+    duplicate_sets: &[DuplicateSet],
+    roots: &[PathBuf],
+    depth: usize,
+) -> io::Result<()> {
+    let mut totals: HashMap<PathBuf, (usize, u64)> = HashMap::new();
+    for file_info in duplicate_sets.iter().flat_map(|set| &set.duplicates) {
+        let total = totals.entry(subtree(&file_info.path, roots, depth)).or_default();
+        total.0 += 1;
+        total.1 += file_info.size;
+    }
+    if totals.is_empty() {
+        return Ok(());
+    }
+
+    let mut totals: Vec<_> = totals.into_iter().collect();
+    totals.sort_by(|a, b| b.1.1.cmp(&a.1.1).then_with(|| a.0.cmp(&b.0)));
+    writeln!(out, "\n{}", tr!("--- Reclaimable space by folder ---"))?;
+    for (folder, (files, bytes)) in totals {
+        write_line(out, &folder, files, bytes)?;
+    }
+    Ok(())
+}
+
+// the deepest folder holding every copy
+fn common_folder<'a>(mut paths: impl Iterator<Item = &'a Path>) -> PathBuf {
+    let Some(first) = paths.next() else {
+        return PathBuf::new();
+    };
+    let mut common = first.parent().unwrap_or(first).to_path_buf();
+    for path in paths {
+        while !path.starts_with(&common) && common.pop() {}
+    }
+    common
+}
+
+// how many copies, and how many bytes, the run is about to act on, split by
+// the top-level folders below the one holding them all, so the prompt can be
+// answered without scrolling back through the report
+pub fn write_affected(out: &mut dyn Write, duplicate_sets: &[DuplicateSet]) -> io::Result<()> {
+    // more than this and the rest are summed up on one line
+    const SHOWN: usize = 20;
+
+    let copies = || duplicate_sets.iter().flat_map(|set| &set.duplicates);
+    let common = common_folder(copies().map(|file_info| file_info.path.as_path()));
+    let mut totals: HashMap<PathBuf, (usize, u64)> = HashMap::new();
+    for file_info in copies() {
+        let relative = file_info.path.strip_prefix(&common).unwrap_or(&file_info.path);
+        // a copy right in the common folder counts toward it
+        let top: PathBuf = match relative.parent() {
+            Some(parent) => parent.components().take(1).collect(),
+            None => PathBuf::new(),
+        };
+        let total = totals.entry(common.join(top)).or_default();
+        total.0 += 1;
+        total.1 += file_info.size;
+    }
+
+    let (files, bytes) = totals.values().fold((0, 0), |(files, bytes), total| (files + total.0, bytes + total.1));
+    writeln!(out, "\n{}", tr!("Affected: {} file(s), {} in total", files, format_bytes(bytes)))?;
+    let mut totals: Vec<_> = totals.into_iter().collect();
+    totals.sort_by(|a, b| b.1.1.cmp(&a.1.1).then_with(|| a.0.cmp(&b.0)));
+    for (folder, (files, bytes)) in totals.iter().take(SHOWN) {
+        write_line(out, folder, *files, *bytes)?;
+    }
+    if totals.len() > SHOWN {
+        let rest = &totals[SHOWN..];
+        let (files, bytes) = rest.iter().fold((0, 0), |(files, bytes), (_, total)| (files + total.0, bytes + total.1));
+        let line = tr!("and {} more folder(s): {} file(s), {}", rest.len(), files, format_bytes(bytes));
+        writeln!(out, "{:>11}  {}", "", line)?;
+    }
+    Ok(())
+}
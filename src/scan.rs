@@ -0,0 +1,553 @@
+use crate::dircache::{DirCache, Listed};
+use crate::prefilter::SizeFilter;
+use crate::{
+    FileInfo, Options, actions, heartbeat, hidden, libraries, limits, mail, retry, screenshots, snapshots, stats,
+};
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+// what the scan keeps of a file's metadata, whether just read or taken from
+// --dir-cache
+#[derive(Debug, Clone, Copy)]
+pub struct Stat {
+    pub size: u64,
+    pub modified: SystemTime,
+    // birth time isn't available on every platform and filesystem
+    pub created: Option<SystemTime>,
+}
+
+impl Stat {
+    fn of(metadata: &fs::Metadata) -> io::Result<Stat> {
+        Ok(Stat {
+            size: metadata.len(),
+            modified: metadata.modified()?,
+            created: metadata.created().ok(),
+        })
+    }
+}
+
+// an entry the scan's rules decide on, as read from its directory or from
+// --dir-cache
+struct Entry {
+    path: PathBuf,
+    is_dir: bool,
+    hidden: bool,
+}
+
+impl Entry {
+    fn live(entry: &fs::DirEntry) -> Entry {
+        Entry {
+            path: entry.path(),
+            is_dir: entry.file_type().is_ok_and(|file_type| file_type.is_dir()),
+            hidden: hidden::is_hidden(entry),
+        }
+    }
+}
+
+// call `visit` for every regular file in the directory (and, when recursive,
+// its subdirectories); returns false if the directory itself could not be read.
+// symlinks are never treated as files, so a link can't be picked as a keeper
+// or counted as a copy of its own target
+pub fn walk_files(directory: &Path, recursive: bool, visit: impl FnMut(PathBuf, fs::Metadata)) -> bool {
+    walk_files_where(directory, recursive, &|_| true, visit)
+}
+
+// like walk_files, but entries (files or directories) rejected by `include`
+// are skipped entirely
+fn walk_files_where(
+    directory: &Path,
+    recursive: bool,
+    include: &dyn Fn(&fs::DirEntry) -> bool,
+    mut visit: impl FnMut(PathBuf, fs::Metadata),
+) -> bool {
+    // files whose metadata failed transiently, tried again after the walk
+    let mut deferred = Vec::new();
+    let readable = walk_directory(directory, recursive, include, &mut |file| {
+        let path = file.path();
+
+        // skip directories and symlinks, only process files
+        let metadata = match file.metadata() {
+            Ok(m) => m,
+            Err(e) if retry::is_transient(&e) => {
+                deferred.push((path, e));
+                return;
+            }
+            Err(e) => {
+                eprintln!("Error reading metadata for '{}': {}", path.display(), e);
+                return;
+            }
+        };
+
+        // copies an interrupted run left renamed aside are not files of their own
+        if !metadata.is_file() || actions::pending_original(&path).is_some() {
+            return;
+        }
+
+        visit(path, metadata);
+    });
+
+    visit_retried(deferred, &mut visit);
+    readable
+}
+
+// files whose metadata failed transiently during a walk, tried again
+fn visit_retried(deferred: Vec<(PathBuf, io::Error)>, visit: &mut dyn FnMut(PathBuf, fs::Metadata)) {
+    for (path, metadata) in retry::queue(deferred, |path| fs::symlink_metadata(path)) {
+        match metadata {
+            Ok(metadata) if metadata.is_file() => visit(path, metadata),
+            Ok(_) => {}
+            Err(e) => eprintln!(
+                "Error reading metadata for '{}' (tried {} more times): {}",
+                path.display(),
+                retry::DELAYS.len(),
+                e
+            ),
+        }
+    }
+}
+
+pub fn walk_symlinks(directory: &Path, recursive: bool, mut visit: impl FnMut(PathBuf)) -> bool {
+    walk_directory(directory, recursive, &|_| true, &mut |file| {
+        if file.file_type().is_ok_and(|file_type| file_type.is_symlink()) {
+            visit(file.path());
+        }
+    })
+}
+
+fn walk_directory(
+    directory: &Path,
+    recursive: bool,
+    include: &dyn Fn(&fs::DirEntry) -> bool,
+    visit: &mut dyn FnMut(&fs::DirEntry),
+) -> bool {
+    // only the time spent waiting on the directory counts toward a heartbeat,
+    // not the time spent on its entries and subdirectories
+    let listing = heartbeat::begin("listing", directory);
+    let mut entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("Error reading directory '{}': {}", directory.display(), e);
+            return false;
+        }
+    };
+
+    loop {
+        listing.resume();
+        let Some(file) = entries.next() else {
+            break;
+        };
+        listing.pause();
+        let file = match file {
+            Ok(f) => f,
+            Err(e) => {
+                eprintln!("Error reading directory entry: {}", e);
+                continue;
+            }
+        };
+
+        if !include(&file) {
+            continue;
+        }
+
+        // descend into real subdirectories only, never through symlinks
+        if recursive && file.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+            walk_directory(&file.path(), recursive, include, visit);
+            continue;
+        }
+
+        visit(&file);
+    }
+
+    true
+}
+
+// hidden entries (with --skip-hidden), directories excluded by --preset, mail
+// stores (with --profile mail), photo libraries (unless --allow-libraries),
+// backup snapshots (unless --include-snapshots), OS metadata files and
+// generated files (unless --include-generated) never take part in duplicate
+// grouping; returns the rule that excluded `entry`, if any
+fn rejection(entry: &Entry, options: &Options) -> Option<&'static str> {
+    if !entry.is_dir && actions::pending_original(&entry.path).is_some() {
+        return Some("left aside by an interrupted run");
+    }
+    let name = entry.path.file_name().unwrap_or_default().to_string_lossy();
+    if !options.excluded_directories.is_empty() && entry.is_dir && options.excluded_directories.contains(&&*name) {
+        return Some("--preset");
+    }
+    if options.mail && mail::is_mail_store(&entry.path, entry.is_dir) {
+        return Some("mail store");
+    }
+    if !options.allow_libraries && libraries::is_library(&entry.path) {
+        return Some("photo library");
+    }
+    if !options.include_snapshots && entry.is_dir && snapshots::is_snapshot_directory(&name) {
+        return Some("backup snapshot");
+    }
+    if options.skip_hidden && entry.hidden {
+        return Some("hidden");
+    }
+    if !options.include_system_files && hidden::is_system_file(&name) {
+        return Some("system file");
+    }
+    if !options.include_generated && options.generated.matches(&entry.path, entry.is_dir) {
+        return Some("generated files");
+    }
+
+    None
+}
+
+// entries whose metadata failed in a way that may pass
+type Deferred = Vec<(Listed, io::Error)>;
+
+// the entries of `directory`, with the metadata of its files, and the files
+// whose metadata failed transiently. an unchanged directory comes from
+// --dir-cache; any other is read, and cached if nothing failed
+fn listing(directory: &Path, cache: &mut DirCache) -> Option<(Vec<Listed>, Deferred)> {
+    let modified = match fs::metadata(directory).and_then(|metadata| metadata.modified()) {
+        Ok(modified) => modified,
+        Err(e) => {
+            eprintln!("Error reading directory '{}': {}", directory.display(), e);
+            return None;
+        }
+    };
+    if let Some(entries) = cache.get(directory, modified) {
+        stats::add(&stats::DIR_CACHE_HITS, 1);
+        return Some((entries.to_vec(), Vec::new()));
+    }
+    stats::add(&stats::DIR_CACHE_MISSES, 1);
+
+    let reading = heartbeat::begin("listing", directory);
+    let mut entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("Error reading directory '{}': {}", directory.display(), e);
+            return None;
+        }
+    };
+    let (mut listed, mut deferred) = (Vec::new(), Vec::new());
+    let mut complete = true;
+    loop {
+        reading.resume();
+        let Some(entry) = entries.next() else {
+            break;
+        };
+        reading.pause();
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                eprintln!("Error reading directory entry: {}", e);
+                complete = false;
+                continue;
+            }
+        };
+        let mut item = Listed {
+            name: entry.file_name(),
+            hidden: hidden::is_hidden(&entry),
+            stat: None,
+        };
+        // symlinks are never scanned, as in walk_files
+        match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => listed.push(item),
+            Ok(file_type) if !file_type.is_file() => {}
+            _ => match entry.metadata().and_then(|metadata| Stat::of(&metadata)) {
+                Ok(stat) => {
+                    item.stat = Some(stat);
+                    listed.push(item);
+                }
+                Err(e) => {
+                    complete = false;
+                    if retry::is_transient(&e) {
+                        deferred.push((item, e));
+                    } else {
+                        eprintln!("Error reading metadata for '{}': {}", entry.path().display(), e);
+                    }
+                }
+            },
+        }
+    }
+    if complete {
+        cache.insert(directory, modified, &listed);
+    }
+    Some((listed, deferred))
+}
+
+// walk_files_where from --dir-cache listings, handing each file's Stat to `visit`
+fn walk_cached(
+    directory: &Path,
+    recursive: bool,
+    include: &dyn Fn(&Entry) -> bool,
+    cache: &mut DirCache,
+    visit: &mut dyn FnMut(PathBuf, Stat),
+    deferred: &mut Vec<(PathBuf, io::Error)>,
+) -> bool {
+    let Some((entries, failed)) = listing(directory, cache) else {
+        return false;
+    };
+    for (item, e) in failed {
+        let entry = Entry {
+            path: directory.join(&item.name),
+            is_dir: false,
+            hidden: item.hidden,
+        };
+        if include(&entry) {
+            deferred.push((entry.path, e));
+        }
+    }
+    for item in entries {
+        let entry = Entry {
+            path: directory.join(&item.name),
+            is_dir: item.stat.is_none(),
+            hidden: item.hidden,
+        };
+        if !include(&entry) {
+            continue;
+        }
+        match item.stat {
+            Some(stat) => visit(entry.path, stat),
+            None if recursive => {
+                walk_cached(&entry.path, recursive, include, cache, visit, deferred);
+            }
+            None => {}
+        }
+    }
+    true
+}
+
+// walk a root for the scan, through --dir-cache when there is one
+fn walk_root(
+    root: &Path,
+    options: &Options,
+    include: &dyn Fn(&Entry) -> bool,
+    cache: Option<&mut DirCache>,
+    visit: &mut dyn FnMut(PathBuf, Stat),
+) -> bool {
+    // modified time is recorded so changes made during the run can be detected
+    fn with_stat(visit: &mut dyn FnMut(PathBuf, Stat)) -> impl FnMut(PathBuf, fs::Metadata) {
+        move |path, metadata| match Stat::of(&metadata) {
+            Ok(stat) => visit(path, stat),
+            Err(e) => eprintln!("Warning: Could not get modified time for '{}': {}", path.display(), e),
+        }
+    }
+    let Some(cache) = cache else {
+        return walk_files_where(root, options.recursive, &|entry| include(&Entry::live(entry)), with_stat(visit));
+    };
+    let mut deferred = Vec::new();
+    let readable = walk_cached(root, options.recursive, include, cache, visit, &mut deferred);
+    visit_retried(deferred, &mut with_stat(visit));
+    readable
+}
+
+// the name group a file goes into; None if its name can't be read. a --plugin
+// is only asked when `plugin` is set
+fn group_name(path: &Path, options: &Options, plugin: bool) -> Option<String> {
+    let Some(filename) = path.file_name().map(|name| name.to_string_lossy().to_string()) else {
+        eprintln!("Warning: Could not extract filename from path '{}'", path.display());
+        return None;
+    };
+
+    // a --plugin's key replaces the name; it starts with a NUL, which no
+    // file name holds, so it never meets a normalized name by chance
+    let plugin_key = match &options.plugin {
+        Some(plugin_rules) if plugin && options.match_mode.uses_name() => {
+            plugin_rules.group_key(path).unwrap_or_else(|e| {
+                eprintln!("Warning: {}", e);
+                None
+            })
+        }
+        _ => None,
+    };
+
+    // with --match size every file goes into one group, split by size later;
+    // with --screenshots so does every screenshot, whatever its name
+    Some(if let Some(key) = plugin_key {
+        format!("\0plugin:{}", key)
+    } else if options.screenshots && screenshots::is_screenshot(&filename) {
+        screenshots::GROUP.to_string()
+    } else if options.match_mode.uses_name() {
+        options.normalizer.normalize(&filename)
+    } else {
+        String::new()
+    })
+}
+
+// what the two-pass filter records of a file: its size, combined with its name
+// group when files are grouped by name and size both. a --plugin's keys are
+// only known by asking it, which the first pass doesn't, so then the size
+// alone has to do
+fn filter_key(path: &Path, size: u64, options: &Options) -> u64 {
+    if options.plugin.is_some() || !options.match_mode.uses_name() || !options.match_mode.uses_size() {
+        return size;
+    }
+    let mut hasher = DefaultHasher::new();
+    (group_name(path, options, false), size).hash(&mut hasher);
+    hasher.finish()
+}
+
+// first pass of the two-pass mode: remember only which sizes (or names and
+// sizes) occur
+pub fn collect_sizes(roots: &[PathBuf], options: &Options) -> Option<SizeFilter> {
+    let mut size_filter = SizeFilter::new();
+    let include = |entry: &Entry| !limits::stopped() && rejection(entry, options).is_none();
+    let mut cache = options.dir_cache.then(DirCache::load);
+    let mut readable = true;
+    for root in roots {
+        readable &= walk_root(root, options, &include, cache.as_mut(), &mut |path, stat| {
+            size_filter.insert(filter_key(&path, stat.size, options))
+        });
+    }
+    if let Some(cache) = &cache {
+        cache.save();
+    }
+
+    readable.then_some(size_filter)
+}
+
+// files a --scan-only run saved, put into name groups again as this run's
+// options say, which may normalize names differently
+pub fn regroup<'a>(files: impl Iterator<Item = &'a FileInfo>, options: &Options) -> HashMap<String, Vec<FileInfo>> {
+    let mut hashmap_name: HashMap<String, Vec<FileInfo>> = HashMap::new();
+    for file_info in files {
+        if let Some(group) = group_name(&file_info.path, options, true) {
+            hashmap_name.entry(group).or_default().push(file_info.clone());
+        }
+    }
+    hashmap_name
+}
+
+// files a run keeps before it switches to --two-pass by itself; a FileInfo
+// takes about a hundred bytes, so a few hundred MB at most
+const AUTO_TWO_PASS: usize = 2_000_000;
+
+// every root goes into the same name groups, so copies are found across roots.
+// a run without --two-pass that finds more than AUTO_TWO_PASS files starts
+// over as if it had been given, so memory stays bounded however large a
+// directory is; a --scan-only list and modes that need every file keep all
+pub fn scan_roots(
+    roots: &[PathBuf],
+    options: &Options,
+    size_filter: Option<&SizeFilter>,
+) -> Option<HashMap<String, Vec<FileInfo>>> {
+    let bounded = size_filter.is_none() && options.scan_only.is_none() && crate::two_pass_conflict(options).is_none();
+    let (hashmap_name, full) = scan_once(roots, options, size_filter, bounded.then_some(AUTO_TWO_PASS))?;
+    if !full {
+        return Some(hashmap_name);
+    }
+
+    drop(hashmap_name);
+    eprintln!(
+        "Note: More than {} files found; scanning again to keep only those whose name and size repeat, \
+         as --two-pass does",
+        AUTO_TWO_PASS
+    );
+    stats::restart_scan();
+    limits::restart_scan();
+    let size_filter = collect_sizes(roots, options)?;
+    scan_once(roots, options, Some(&size_filter), None).map(|(hashmap_name, _)| hashmap_name)
+}
+
+// the name groups, and whether the scan stopped at `keep_at_most` files
+fn scan_once(
+    roots: &[PathBuf],
+    options: &Options,
+    size_filter: Option<&SizeFilter>,
+    keep_at_most: Option<usize>,
+) -> Option<(HashMap<String, Vec<FileInfo>>, bool)> {
+    let mut hashmap_name: HashMap<String, Vec<FileInfo>> = HashMap::new();
+    let (kept, full) = (Cell::new(0), Cell::new(false));
+
+    let include = |entry: &Entry| {
+        // past --timeout or --max-files, the rest of the tree is left unseen
+        if limits::stopped() || full.get() {
+            return false;
+        }
+        let is_dir = entry.is_dir;
+        match rejection(entry, options) {
+            Some(rule) => {
+                if !is_dir {
+                    stats::add(&stats::FILES, 1);
+                }
+                if rule == "left aside by an interrupted run" {
+                    actions::found_pending(entry.path.clone());
+                }
+                let flag = match rule {
+                    "photo library" => Some("--allow-libraries"),
+                    "backup snapshot" => Some("--include-snapshots"),
+                    "generated files" => Some("--include-generated"),
+                    _ => None,
+                };
+                if let Some(flag) = flag
+                    && is_dir
+                {
+                    eprintln!("Note: Skipping {} '{}'; pass {} to include it", rule, entry.path.display(), flag);
+                }
+                stats::filtered(rule, 1);
+                false
+            }
+            None => {
+                if is_dir && options.recursive {
+                    stats::add(&stats::DIRECTORIES, 1);
+                }
+                true
+            }
+        }
+    };
+    let mut visit = |path: PathBuf, stat: Stat| {
+        if full.get() || !limits::admit_file() {
+            return;
+        }
+        let size = stat.size;
+        stats::add(&stats::FILES, 1);
+
+        // a size (or name and size) seen only once can't be part of a
+        // duplicate set, so nothing of the file is kept
+        if let Some(size_filter) = size_filter
+            && !size_filter.maybe_repeated(filter_key(&path, size, options))
+        {
+            stats::filtered("unique name or size (--two-pass)", 1);
+            return;
+        }
+
+        if keep_at_most.is_some_and(|most| kept.get() >= most) {
+            full.set(true);
+            return;
+        }
+        let Some(normalized_filename) = group_name(&path, options, true) else {
+            return;
+        };
+        kept.set(kept.get() + 1);
+
+        let file_info = FileInfo {
+            path,
+            size,
+            created: stat.created,
+            modified: stat.modified,
+        };
+        // most names in a large flat directory occur once, so a group starts
+        // with room for a single file rather than Vec's usual four
+        hashmap_name
+            .entry(normalized_filename)
+            .or_insert_with(|| Vec::with_capacity(1))
+            .push(file_info);
+    };
+
+    let mut cache = options.dir_cache.then(DirCache::load);
+    let mut readable = true;
+    for root in roots {
+        if walk_root(root, options, &include, cache.as_mut(), &mut visit) {
+            stats::add(&stats::DIRECTORIES, 1);
+        } else {
+            readable = false;
+        }
+    }
+    if let Some(cache) = &cache {
+        cache.save();
+    }
+
+    readable.then_some((hashmap_name, full.get()))
+}
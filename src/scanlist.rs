@@ -0,0 +1,114 @@
+// --scan-only FILE saves the files a scan found, grouped by normalized name,
+// without hashing anything; --hash-only FILE picks such a list up, on another
+// day or another machine that sees the same paths, and goes on from there to
+// the duplicate sets, acting on nothing, so `--output PLAN` can save them for
+// --act-only. each phase can then run on its own schedule: listing a slow
+// share overnight, hashing when the disks are idle, acting once reviewed
+use crate::json::{self, Json};
+use crate::{FileInfo, Options, environment, plan};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const FORMAT: &str = "hydra-scan";
+const VERSION: u64 = 1;
+
+#[derive(Debug)]
+pub struct ScanList {
+    pub created: u64,
+    pub directory: String,
+    pub roots: Vec<PathBuf>,
+    pub files: HashMap<String, Vec<FileInfo>>,
+}
+
+pub fn to_json(
+    directory: &str,
+    roots: &[PathBuf],
+    files: &HashMap<String, Vec<FileInfo>>,
+    options: &Options,
+) -> String {
+    let created = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let roots: Vec<String> = roots.iter().map(|root| json::string(&root.to_string_lossy())).collect();
+    let mut names: Vec<&String> = files.keys().collect();
+    names.sort();
+
+    let mut out = String::new();
+    out.push_str(&format!("{{\n  \"format\": {},\n", json::string(FORMAT)));
+    out.push_str(&format!("  \"version\": {},\n", VERSION));
+    out.push_str(&format!("  \"created\": {},\n", created));
+    out.push_str(&format!("  \"directory\": {},\n", json::string(directory)));
+    out.push_str(&format!("  \"roots\": [{}],\n", roots.join(", ")));
+    out.push_str(&format!("  \"environment\": {},\n", environment::to_json(options)));
+    out.push_str("  \"groups\": [");
+    for (index, name) in names.into_iter().enumerate() {
+        let group: Vec<String> = files[name].iter().map(plan::file_json).collect();
+        out.push_str(if index == 0 { "\n" } else { ",\n" });
+        out.push_str(&format!(
+            "    {{\"name\": {}, \"files\": [\n      {}\n    ]}}",
+            json::string(name),
+            group.join(",\n      ")
+        ));
+    }
+    out.push_str("\n  ]\n}\n");
+    out
+}
+
+pub fn write(
+    path: &Path,
+    directory: &str,
+    roots: &[PathBuf],
+    files: &HashMap<String, Vec<FileInfo>>,
+    options: &Options,
+) -> Result<(), String> {
+    let out = to_json(directory, roots, files, options);
+    fs::write(path, out).map_err(|e| format!("could not write '{}': {}", path.display(), e))
+}
+
+pub fn read(path: &Path) -> Result<ScanList, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("could not read '{}': {}", path.display(), e))?;
+    parse(&text, &path.display().to_string())
+}
+
+// `source` names where the text came from, for error messages
+pub fn parse(text: &str, source: &str) -> Result<ScanList, String> {
+    let root = json::parse(text).map_err(|e| format!("{}: {}", source, e))?;
+    let invalid = |what: &str| format!("{}: not a hydra scan list ({})", source, what);
+
+    if root.get("format").and_then(Json::as_str) != Some(FORMAT) {
+        return Err(invalid("missing or wrong format"));
+    }
+    match root.get("version").and_then(Json::as_u64) {
+        Some(VERSION) => {}
+        Some(version) => return Err(format!("{}: unsupported scan list version {}", source, version)),
+        None => return Err(invalid("missing version")),
+    }
+
+    let created = root.get("created").and_then(Json::as_u64).ok_or_else(|| invalid("missing created"))?;
+    let directory = root.get("directory").and_then(Json::as_str).ok_or_else(|| invalid("missing directory"))?;
+    let roots = root
+        .get("roots")
+        .and_then(Json::as_array)
+        .and_then(|roots| roots.iter().map(|root| root.as_str().map(PathBuf::from)).collect::<Option<Vec<_>>>())
+        .ok_or_else(|| invalid("missing roots"))?;
+
+    let groups = root.get("groups").and_then(Json::as_array).ok_or_else(|| invalid("missing groups"))?;
+    let mut files = HashMap::new();
+    for (index, group) in groups.iter().enumerate() {
+        let malformed = || invalid(&format!("group {} is malformed", index + 1));
+        let name = group.get("name").and_then(Json::as_str).ok_or_else(malformed)?;
+        let file_infos = group
+            .get("files")
+            .and_then(Json::as_array)
+            .and_then(|file_infos| file_infos.iter().map(plan::parse_file).collect::<Option<Vec<_>>>())
+            .ok_or_else(malformed)?;
+        files.insert(name.to_string(), file_infos);
+    }
+
+    Ok(ScanList {
+        created,
+        directory: directory.to_string(),
+        roots,
+        files,
+    })
+}
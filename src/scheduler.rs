@@ -0,0 +1,147 @@
+use crate::hash;
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IoProfile {
+    Auto,
+    Hdd,
+    Ssd,
+}
+
+impl IoProfile {
+    pub fn parse(value: &str) -> Option<IoProfile> {
+        match value {
+            "auto" => Some(IoProfile::Auto),
+            "hdd" => Some(IoProfile::Hdd),
+            "ssd" => Some(IoProfile::Ssd),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(unix)]
+pub fn device_id(path: &Path) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+
+    std::fs::metadata(path).map(|m| m.dev()).unwrap_or(0)
+}
+
+#[cfg(not(unix))]
+pub fn device_id(_path: &Path) -> u64 {
+    0
+}
+
+// linux exposes whether a block device spins under /sys; partitions keep
+// their queue settings on the parent disk
+#[cfg(target_os = "linux")]
+fn is_rotational(device: u64) -> Option<bool> {
+    let major = ((device >> 8) & 0xfff) | ((device >> 32) & !0xfff);
+    let minor = (device & 0xff) | ((device >> 12) & !0xff);
+
+    let candidates = [
+        format!("/sys/dev/block/{}:{}/queue/rotational", major, minor),
+        format!("/sys/dev/block/{}:{}/../queue/rotational", major, minor),
+    ];
+
+    candidates
+        .iter()
+        .find_map(|candidate| std::fs::read_to_string(candidate).ok())
+        .map(|value| value.trim() == "1")
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_rotational(_device: u64) -> Option<bool> {
+    None
+}
+
+// resolve the profile for one device; storage we can't identify (network
+// mounts, other platforms) is read in parallel
+fn resolve_profile(requested: IoProfile, device: u64) -> IoProfile {
+    match requested {
+        IoProfile::Auto => match is_rotational(device) {
+            Some(true) => IoProfile::Hdd,
+            _ => IoProfile::Ssd,
+        },
+        profile => profile,
+    }
+}
+
+// 0 means one worker per cpu
+static WORKERS: AtomicUsize = AtomicUsize::new(0);
+
+// how many files solid-state storage is read in parallel ([hashing] threads in
+// the config)
+pub fn set_workers(workers: usize) {
+    WORKERS.store(workers, Ordering::Relaxed);
+}
+
+pub fn parallel_workers() -> usize {
+    match WORKERS.load(Ordering::Relaxed) {
+        0 => thread::available_parallelism().map(|n| n.get()).unwrap_or(4),
+        workers => workers,
+    }
+}
+
+fn hash_sequential(paths: &[PathBuf]) -> Vec<(PathBuf, io::Result<u64>)> {
+    paths
+        .iter()
+        .map(|path| (path.clone(), hash::hash_file(path)))
+        .collect()
+}
+
+fn hash_parallel(paths: &[PathBuf]) -> Vec<(PathBuf, io::Result<u64>)> {
+    let queue = Mutex::new(paths.iter());
+    let results = Mutex::new(Vec::with_capacity(paths.len()));
+
+    thread::scope(|scope| {
+        for _ in 0..parallel_workers().min(paths.len()) {
+            scope.spawn(|| {
+                loop {
+                    let path = match queue.lock().unwrap().next() {
+                        Some(path) => path.clone(),
+                        None => break,
+                    };
+                    let result = hash::hash_file(&path);
+                    results.lock().unwrap().push((path, result));
+                }
+            });
+        }
+    });
+
+    results.into_inner().unwrap()
+}
+
+// hash a batch of files, reading spinning disks one file at a time and
+// solid-state storage with a worker pool; each device is scheduled independently
+pub fn hash_files(paths: Vec<PathBuf>, requested: IoProfile) -> HashMap<PathBuf, io::Result<u64>> {
+    let mut by_device: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+    for path in paths {
+        by_device.entry(device_id(&path)).or_default().push(path);
+    }
+
+    let mut hashes = HashMap::new();
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = by_device
+            .iter()
+            .map(|(device, device_paths)| {
+                let profile = resolve_profile(requested, *device);
+                scope.spawn(move || match profile {
+                    IoProfile::Hdd => hash_sequential(device_paths),
+                    _ => hash_parallel(device_paths),
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            hashes.extend(handle.join().unwrap());
+        }
+    });
+
+    hashes
+}
@@ -0,0 +1,32 @@
+// --screenshots: screenshot names ("Screenshot 2024-05-01 at 10.00.00.png",
+// "Screenshot (37).png") are unique by design, so copies never share a name
+// group. in this mode every screenshot goes into one group, where the usual
+// content match finds the exact copies, and screenshots that only look alike
+// (the same screen captured twice) are listed for review by a perceptual hash
+// (see similar.rs). those are never removed
+use regex::Regex;
+use std::sync::OnceLock;
+
+// the name group every screenshot is put in; no real file name is empty
+pub const GROUP: &str = "\0screenshots";
+
+fn patterns() -> &'static Regex {
+    static PATTERNS: OnceLock<Regex> = OnceLock::new();
+    PATTERNS.get_or_init(|| {
+        Regex::new(concat!(
+            r"(?i)^(",
+            r"screen ?shot \d{4}-\d{2}-\d{2} at .+", // macos
+            r"|screenshot \(\d+\)",                  // windows 10
+            r"|screenshot \d{4}-\d{2}-\d{2} \d{6}",  // windows 11 snipping tool
+            r"|screenshot from \d{4}-\d{2}-\d{2} .+", // gnome
+            r"|screenshot_\d{8}[-_]\d{6}.*",          // android
+            r"|scrot.*|\d{4}-\d{2}-\d{2}_\d{2}-\d{2}-\d{2}_scrot.*", // scrot
+            r")\.(png|jpe?g)$"
+        ))
+        .unwrap()
+    })
+}
+
+pub fn is_screenshot(filename: &str) -> bool {
+    patterns().is_match(filename)
+}
@@ -0,0 +1,66 @@
+// stable set IDs: a set is identified by its keeper's content hash, so the
+// same duplicates get the same ID in the next run and `--only-set ID` /
+// `--skip-set ID` can pick sets out of an earlier report. an ID may be
+// shortened to any prefix of at least MIN_PREFIX characters, like a git commit
+use crate::DuplicateSet;
+use crate::scheduler::{self, IoProfile};
+
+// shorter prefixes would pick out sets nearly at random
+const MIN_PREFIX: usize = 4;
+
+// the IDs in an --only-set / --skip-set value: comma-separated, blanks
+// dropped, each a hex prefix long enough to mean one set
+pub fn parse(value: &str, flag: &str) -> Result<Vec<String>, String> {
+    let ids: Vec<String> = value.split(',').map(|id| id.trim().to_lowercase()).filter(|id| !id.is_empty()).collect();
+    if ids.is_empty() {
+        return Err(format!("{} requires a set ID", flag));
+    }
+    for id in &ids {
+        if id.len() < MIN_PREFIX || !id.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return Err(format!(
+                "invalid {} '{}' (expected a set ID from the report, at least {} characters of it)",
+                flag, id, MIN_PREFIX
+            ));
+        }
+    }
+    Ok(ids)
+}
+
+// hash the keepers of sets whose hash isn't known yet (every set unless
+// matching by content)
+pub fn assign(duplicate_sets: &mut [DuplicateSet], io_profile: IoProfile) {
+    let paths = duplicate_sets
+        .iter()
+        .filter(|set| set.hash.is_none())
+        .map(|set| set.keeper.path.clone())
+        .collect::<Vec<_>>();
+    if paths.is_empty() {
+        return;
+    }
+
+    let hashes = scheduler::hash_files(paths, io_profile);
+    for set in duplicate_sets.iter_mut().filter(|set| set.hash.is_none()) {
+        match hashes.get(&set.keeper.path) {
+            Some(Ok(hash)) => set.hash = Some(*hash),
+            Some(Err(e)) => {
+                eprintln!("Warning: Could not hash '{}', its set has no ID: {}", set.keeper.path.display(), e)
+            }
+            None => {}
+        }
+    }
+}
+
+fn matches(set: &DuplicateSet, ids: &[String]) -> bool {
+    set.id().is_some_and(|id| ids.iter().any(|prefix| id.starts_with(prefix.as_str())))
+}
+
+// keep the sets named by --only-set (all, if none are) minus those named by --skip-set
+pub fn filter(duplicate_sets: &mut Vec<DuplicateSet>, only: &[String], skip: &[String]) {
+    duplicate_sets.retain(|set| (only.is_empty() || matches(set, only)) && !matches(set, skip));
+
+    for id in only {
+        if !duplicate_sets.iter().any(|set| matches(set, std::slice::from_ref(id))) {
+            eprintln!("Warning: No duplicate set has the ID '{}'", id);
+        }
+    }
+}
@@ -0,0 +1,147 @@
+// how much of the duplication found is already free. a copy that is a hard
+// link of its keeper, or whose data is shared with a reflinked clone or a
+// filesystem snapshot, frees nothing when it is removed, so on snapshot-heavy
+// or previously deduplicated trees the sum of the copies' sizes overstates
+// what a run reclaims. hard links are read from the inode; shared extents
+// are asked of the filesystem on linux (btrfs, xfs and others that report
+// them) and otherwise taken as unshared, so the split is an estimate
+use crate::{DuplicateSet, consolidate};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Sharing {
+    // what removing the copies would free
+    pub reclaimable: u64,
+    // what the copies take up but share with a keeper or something else
+    pub shared: u64,
+}
+
+impl Sharing {
+    fn add_copy(&mut self, path: &Path, size: u64) {
+        let shared = shared_extent_bytes(path).min(size);
+        self.shared += shared;
+        self.reclaimable += size - shared;
+    }
+}
+
+#[cfg(unix)]
+fn links(metadata: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.nlink()
+}
+
+#[cfg(not(unix))]
+fn links(_metadata: &fs::Metadata) -> u64 {
+    1
+}
+
+// the bytes of a file whose extents the filesystem reports as shared
+#[cfg(target_os = "linux")]
+fn shared_extent_bytes(path: &Path) -> u64 {
+    use std::os::fd::AsRawFd;
+    use std::os::raw::{c_int, c_ulong};
+
+    const FS_IOC_FIEMAP: c_ulong = 0xc020_660b;
+    const FIEMAP_FLAG_SYNC: u32 = 0x1;
+    const FIEMAP_EXTENT_LAST: u32 = 0x1;
+    const FIEMAP_EXTENT_SHARED: u32 = 0x2000;
+    const EXTENTS: usize = 64;
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Default)]
+    struct Extent {
+        logical: u64,
+        physical: u64,
+        length: u64,
+        reserved64: [u64; 2],
+        flags: u32,
+        reserved: [u32; 3],
+    }
+
+    #[repr(C)]
+    struct Fiemap {
+        start: u64,
+        length: u64,
+        flags: u32,
+        mapped_extents: u32,
+        extent_count: u32,
+        reserved: u32,
+        extents: [Extent; EXTENTS],
+    }
+
+    unsafe extern "C" {
+        fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;
+    }
+
+    let Ok(file) = fs::File::open(path) else {
+        return 0;
+    };
+    let mut shared = 0;
+    let mut start = 0;
+    loop {
+        let mut map = Fiemap {
+            start,
+            length: u64::MAX - start,
+            flags: FIEMAP_FLAG_SYNC,
+            mapped_extents: 0,
+            extent_count: EXTENTS as u32,
+            reserved: 0,
+            extents: [Extent::default(); EXTENTS],
+        };
+        // filesystems without extent maps refuse the request: nothing shared
+        if unsafe { ioctl(file.as_raw_fd(), FS_IOC_FIEMAP, &mut map as *mut Fiemap) } != 0 {
+            return shared;
+        }
+        let mapped = &map.extents[..(map.mapped_extents as usize).min(EXTENTS)];
+        let Some(last) = mapped.last() else {
+            return shared;
+        };
+        shared += mapped
+            .iter()
+            .filter(|extent| extent.flags & FIEMAP_EXTENT_SHARED != 0)
+            .map(|extent| extent.length)
+            .sum::<u64>();
+        if last.flags & FIEMAP_EXTENT_LAST != 0 {
+            return shared;
+        }
+        start = last.logical + last.length;
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn shared_extent_bytes(_path: &Path) -> u64 {
+    0
+}
+
+// split the copies' sizes into what removing them frees and what is already
+// shared. a hard link of a keeper frees nothing; the links of one file free
+// its size once, and only when every link is among the copies
+pub fn measure(duplicate_sets: &[DuplicateSet]) -> Sharing {
+    let id = |path: &Path| fs::metadata(path).ok().map(|metadata| (consolidate::file_id(&metadata), links(&metadata)));
+    let keepers: HashSet<(u64, u64)> = duplicate_sets.iter().filter_map(|set| id(&set.keeper.path)?.0).collect();
+
+    let mut sharing = Sharing::default();
+    // (links, copies among the duplicates, size, a path) per hard-linked file
+    let mut linked: HashMap<(u64, u64), (u64, u64, u64, &Path)> = HashMap::new();
+    for file_info in duplicate_sets.iter().flat_map(|set| &set.duplicates) {
+        match id(&file_info.path) {
+            Some((Some(file_id), _)) if keepers.contains(&file_id) => sharing.shared += file_info.size,
+            Some((Some(file_id), links)) if links > 1 => {
+                let entry = linked.entry(file_id).or_insert((links, 0, file_info.size, &file_info.path));
+                entry.1 += 1;
+            }
+            _ => sharing.add_copy(&file_info.path, file_info.size),
+        }
+    }
+    for (links, copies, size, path) in linked.into_values() {
+        if copies >= links {
+            sharing.add_copy(path, size);
+            sharing.shared += (copies - 1) * size;
+        } else {
+            sharing.shared += copies * size;
+        }
+    }
+    sharing
+}
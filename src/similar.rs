@@ -0,0 +1,133 @@
+// images that look alike without being the same bytes: resized, recompressed
+// or re-exported copies. each PNG or baseline JPEG is reduced to a 64-bit
+// difference hash, and images whose hashes differ in only a few bits are
+// grouped. these are near-duplicates, listed for review and never removed;
+// used by --screenshots and --similar-images
+use crate::{jpeg, png};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+// bits (of 64) two difference hashes may differ in and still look alike
+const SIMILAR_BITS: u32 = 4;
+
+// the hash is split into SIMILAR_BITS + 1 pieces: two hashes within
+// SIMILAR_BITS of each other agree on at least one, so only images sharing a
+// piece are compared, not every pair
+const PIECES: [(u32, u32); 5] = [(0, 13), (13, 13), (26, 13), (39, 13), (52, 12)];
+
+// a 64-bit difference hash: shrink to 9x8 and record whether each cell is
+// brighter than its right-hand neighbour, which survives recompression,
+// resizing and small changes like a moved cursor. images (or, for a JPEG,
+// 8x8 blocks) too few to fill the cells can't be compared
+fn difference_hash(image: &png::GrayImage) -> Option<u64> {
+    if image.width < 9 || image.height < 8 {
+        return None;
+    }
+
+    let (mut sums, mut counts) = ([0u64; 72], [0u64; 72]);
+    for (index, &pixel) in image.pixels.iter().enumerate() {
+        let (x, y) = (index % image.width, index / image.width);
+        let cell = (y * 8 / image.height) * 9 + x * 9 / image.width;
+        sums[cell] += pixel as u64;
+        counts[cell] += 1;
+    }
+    let average = |cell: usize| sums[cell].checked_div(counts[cell]).unwrap_or(0);
+
+    let mut hash = 0u64;
+    for row in 0..8 {
+        for column in 0..8 {
+            hash = (hash << 1) | (average(row * 9 + column) > average(row * 9 + column + 1)) as u64;
+        }
+    }
+    Some(hash)
+}
+
+// a PNG or JPEG in grayscale, told apart by their first bytes; None for
+// anything else
+fn decode(path: &Path) -> Result<Option<png::GrayImage>, String> {
+    let mut magic = [0u8; 2];
+    File::open(path).and_then(|mut file| file.read_exact(&mut magic)).map_err(|e| e.to_string())?;
+    match &magic {
+        b"\x89P" => png::decode_gray(path).map(Some),
+        b"\xff\xd8" => jpeg::decode_gray(path).map(Some),
+        _ => Ok(None),
+    }
+}
+
+pub fn is_image(path: &Path) -> bool {
+    path.extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .is_some_and(|extension| matches!(extension.as_str(), "png" | "jpg" | "jpeg"))
+}
+
+// groups of images among `paths` that look alike
+pub fn find_similar<'a>(paths: impl Iterator<Item = &'a PathBuf>) -> Vec<Vec<PathBuf>> {
+    let mut hashed: Vec<(u64, &PathBuf)> = Vec::new();
+    for path in paths.filter(|path| is_image(path)) {
+        match decode(path) {
+            Ok(image) => hashed.extend(image.as_ref().and_then(difference_hash).map(|hash| (hash, path))),
+            Err(e) => eprintln!("Warning: Could not compare '{}' visually: {}", path.display(), e),
+        }
+    }
+
+    // union-find over every pair close enough, looking only at pairs that
+    // share a piece
+    let mut parent: Vec<usize> = (0..hashed.len()).collect();
+    fn root(parent: &mut [usize], mut index: usize) -> usize {
+        while parent[index] != index {
+            parent[index] = parent[parent[index]];
+            index = parent[index];
+        }
+        index
+    }
+    for &(shift, bits) in &PIECES {
+        let mut buckets: HashMap<u64, Vec<usize>> = HashMap::new();
+        for (index, (hash, _)) in hashed.iter().enumerate() {
+            buckets.entry((hash >> shift) & ((1 << bits) - 1)).or_default().push(index);
+        }
+        for bucket in buckets.values() {
+            for (position, &a) in bucket.iter().enumerate() {
+                for &b in &bucket[position + 1..] {
+                    if (hashed[a].0 ^ hashed[b].0).count_ones() <= SIMILAR_BITS {
+                        let (root_a, root_b) = (root(&mut parent, a), root(&mut parent, b));
+                        parent[root_a] = root_b;
+                    }
+                }
+            }
+        }
+    }
+
+    let mut groups: Vec<Vec<PathBuf>> = Vec::new();
+    let mut group_of_root = HashMap::new();
+    for (index, (_, path)) in hashed.iter().enumerate() {
+        let group = *group_of_root.entry(root(&mut parent, index)).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[group].push((*path).clone());
+    }
+
+    let mut groups: Vec<Vec<PathBuf>> = groups.into_iter().filter(|group| group.len() > 1).collect();
+    for group in &mut groups {
+        group.sort();
+    }
+    groups.sort();
+    groups
+}
+
+pub fn write_similar(out: &mut dyn Write, title: &str, groups: &[Vec<PathBuf>]) -> io::Result<()> {
+    if groups.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(out, "\n--- {} (look alike, review by hand; never removed) ---", title)?;
+    for group in groups {
+        writeln!(out)?;
+        for path in group {
+            writeln!(out, "  {}", path.display())?;
+        }
+    }
+    Ok(())
+}
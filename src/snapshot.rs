@@ -0,0 +1,143 @@
+// `--snapshot FILE` saves the duplicate sets a scan found, before any decision
+// is made; `hydra import snapshot FILE --output PLAN` reviews them on another
+// machine (a laptop, for a scan run on a NAS) and writes the decisions as a
+// plan for `hydra apply` back where the files are. the snapshot records where
+// and when the scan ran, and is validated field by field when imported
+use crate::json::{self, Json};
+use crate::{environment, plan};
+use crate::{DuplicateSet, Options, report, terminal};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+const FORMAT: &str = "hydra-snapshot";
+const VERSION: u64 = 1;
+
+pub struct Snapshot {
+    pub host: String,
+    pub created: u64,
+    pub directory: String,
+    pub duplicate_sets: Vec<DuplicateSet>,
+}
+
+pub fn to_json(directory: &str, duplicate_sets: &[DuplicateSet], options: &Options) -> String {
+    let created = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+
+    let mut out = String::new();
+    out.push_str(&format!("{{\n  \"format\": {},\n", json::string(FORMAT)));
+    out.push_str(&format!("  \"version\": {},\n", VERSION));
+    out.push_str(&format!("  \"host\": {},\n", json::string(&environment::host_name())));
+    out.push_str(&format!("  \"created\": {},\n", created));
+    out.push_str(&format!("  \"directory\": {},\n", json::string(directory)));
+    out.push_str(&format!("  \"environment\": {},\n", environment::to_json(options)));
+    out.push_str("  \"sets\": [");
+    for (index, set) in duplicate_sets.iter().enumerate() {
+        out.push_str(if index == 0 { "\n" } else { ",\n" });
+        out.push_str(&plan::set_json(set));
+    }
+    out.push_str("\n  ]\n}\n");
+    out
+}
+
+pub fn write(path: &Path, directory: &str, duplicate_sets: &[DuplicateSet], options: &Options) -> Result<(), String> {
+    let out = to_json(directory, duplicate_sets, options);
+    fs::File::create(path)
+        .and_then(|mut file| file.write_all(out.as_bytes()))
+        .map_err(|e| format!("could not write '{}': {}", path.display(), e))
+}
+
+fn read(path: &Path) -> Result<Snapshot, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("could not read '{}': {}", path.display(), e))?;
+    parse(&text, &path.display().to_string())
+}
+
+// `source` names where the text came from, for error messages
+pub fn parse(text: &str, source: &str) -> Result<Snapshot, String> {
+    let root = json::parse(text).map_err(|e| format!("{}: {}", source, e))?;
+    let invalid = |what: &str| format!("{}: not a hydra snapshot ({})", source, what);
+
+    if root.get("format").and_then(Json::as_str) != Some(FORMAT) {
+        return Err(invalid("missing or wrong format"));
+    }
+    match root.get("version").and_then(Json::as_u64) {
+        Some(VERSION) => {}
+        Some(version) => return Err(format!("{}: unsupported snapshot version {}", source, version)),
+        None => return Err(invalid("missing version")),
+    }
+
+    let host = root.get("host").and_then(Json::as_str).ok_or_else(|| invalid("missing host"))?;
+    let created = root.get("created").and_then(Json::as_u64).ok_or_else(|| invalid("missing created"))?;
+    let directory = root.get("directory").and_then(Json::as_str).ok_or_else(|| invalid("missing directory"))?;
+
+    let sets = root.get("sets").and_then(Json::as_array).ok_or_else(|| invalid("missing sets"))?;
+    let mut duplicate_sets = Vec::new();
+    for (index, set) in sets.iter().enumerate() {
+        duplicate_sets.push(plan::parse_set(set).ok_or_else(|| invalid(&format!("set {} is malformed", index + 1)))?);
+    }
+
+    Ok(Snapshot {
+        host: host.to_string(),
+        created,
+        directory: directory.to_string(),
+        duplicate_sets,
+    })
+}
+
+pub fn age(created: u64) -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    match now.saturating_sub(created) {
+        seconds if seconds < 3600 => format!("{} minute(s) ago", seconds / 60),
+        seconds if seconds < 86400 => format!("{} hour(s) ago", seconds / 3600),
+        seconds => format!("{} day(s) ago", seconds / 86400),
+    }
+}
+
+// `hydra import snapshot FILE --output PLAN [flags]`: the files aren't here, so
+// nothing is acted on; the reviewed sets are only written out as a plan. flags
+// that shape the report or the review (--interactive, --edit, --group-by, ...)
+// work as in a normal run
+pub fn import(args: &[String]) -> Result<(), String> {
+    let usage = "usage: hydra import snapshot FILE --output PLAN [--interactive] [--edit] [...]";
+    let (file, flags) = match args {
+        [kind, file, flags @ ..] if kind == "snapshot" && !file.starts_with('-') => (Path::new(file.as_str()), flags),
+        _ => return Err(usage.to_string()),
+    };
+
+    let mut flag_args = vec!["hydra".to_string()];
+    flag_args.extend(flags.iter().map(|flag| flag.to_string()));
+    let options = crate::parse_args(&flag_args)?;
+    let output = options.output.as_ref().ok_or(usage)?;
+    if (options.interactive || options.edit) && !terminal::can_prompt() {
+        return Err("--interactive and --edit need a terminal on stdin".to_string());
+    }
+
+    let Snapshot {
+        host,
+        created,
+        directory,
+        mut duplicate_sets,
+    } = read(file)?;
+    let host = if host.is_empty() { "an unknown host" } else { &host };
+    println!(
+        "Importing {} ({} set(s) from {} on {}, scanned {})",
+        file.display(),
+        duplicate_sets.len(),
+        directory,
+        host,
+        age(created)
+    );
+
+    let mut started = Instant::now();
+    if !crate::review(&mut duplicate_sets, &options, &mut started) {
+        return Ok(());
+    }
+
+    let mut report = Vec::new();
+    report::write_report(&mut report, &duplicate_sets, &[], &[], &options).map_err(|e| e.to_string())?;
+    crate::pager::page(&report, options.pager);
+
+    plan::write(output, &directory, &duplicate_sets, &options)?;
+    println!("\nSaved the plan to {0}; run `hydra apply {0}` on {1} to carry it out.", output.display(), host);
+    Ok(())
+}
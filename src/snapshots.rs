@@ -0,0 +1,28 @@
+// backup snapshot trees hold a full copy of everything they back up, so every
+// file in them is an intentional duplicate. directories laid out the way
+// common snapshot tools lay them out are skipped unless --include-snapshots
+// is passed
+use regex::Regex;
+use std::sync::OnceLock;
+
+fn layouts() -> &'static Regex {
+    static LAYOUTS: OnceLock<Regex> = OnceLock::new();
+    LAYOUTS.get_or_init(|| {
+        Regex::new(concat!(
+            r"^(",
+            r"\.snapshots?|\.zfs|\.ckpt",                              // snapper, netapp, zfs, wafl checkpoints
+            r"|@.*(snapshot|backup).*",                                // btrfs subvolumes like @snapshots
+            r"|@GMT-\d{4}\.\d{2}\.\d{2}-\d{2}\.\d{2}\.\d{2}",           // samba shadow copies
+            r"|Backups\.backupdb|\.MobileBackups",                     // time machine (hfs+)
+            r"|\d{4}-\d{2}-\d{2}-\d{6}\.(backup|previous|inprogress)", // time machine (apfs)
+            r"|timeshift(-btrfs)?",                                    // timeshift
+            r")$"
+        ))
+        .unwrap()
+    })
+}
+
+// whether a directory named `name` holds backup snapshots
+pub fn is_snapshot_directory(name: &str) -> bool {
+    layouts().is_match(name)
+}
@@ -0,0 +1,90 @@
+// --stats: counters gathered during a run, for tuning filters and concurrency
+// on large trees. they are always collected (it's a handful of atomic adds)
+// and only printed on request
+use crate::format_bytes;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+pub static DIRECTORIES: AtomicU64 = AtomicU64::new(0);
+pub static FILES: AtomicU64 = AtomicU64::new(0);
+pub static FILES_HASHED: AtomicU64 = AtomicU64::new(0);
+pub static BYTES_HASHED: AtomicU64 = AtomicU64::new(0);
+pub static CACHE_HITS: AtomicU64 = AtomicU64::new(0);
+pub static CACHE_MISSES: AtomicU64 = AtomicU64::new(0);
+pub static DIR_CACHE_HITS: AtomicU64 = AtomicU64::new(0);
+pub static DIR_CACHE_MISSES: AtomicU64 = AtomicU64::new(0);
+
+// (rule, files removed), in the order rules first fired
+static FILTERED: Mutex<Vec<(&'static str, u64)>> = Mutex::new(Vec::new());
+static PHASES: Mutex<Vec<(&'static str, Duration)>> = Mutex::new(Vec::new());
+
+pub fn add(counter: &AtomicU64, amount: u64) {
+    counter.fetch_add(amount, Ordering::Relaxed);
+}
+
+pub fn filtered(rule: &'static str, count: u64) {
+    if count == 0 {
+        return;
+    }
+
+    let mut filtered = FILTERED.lock().unwrap();
+    match filtered.iter_mut().find(|(name, _)| *name == rule) {
+        Some((_, total)) => *total += count,
+        None => filtered.push((rule, count)),
+    }
+}
+
+// record how long a phase took, starting the clock for the next one
+pub fn phase(name: &'static str, started: &mut Instant) {
+    PHASES.lock().unwrap().push((name, started.elapsed()));
+    *started = Instant::now();
+}
+
+// forget what a scan that is started over counted
+pub fn restart_scan() {
+    DIRECTORIES.store(0, Ordering::Relaxed);
+    FILES.store(0, Ordering::Relaxed);
+    FILTERED.lock().unwrap().clear();
+}
+
+fn get(counter: &AtomicU64) -> u64 {
+    counter.load(Ordering::Relaxed)
+}
+
+pub fn print(total: Duration) {
+    println!("\n--- Statistics ---");
+    println!("Directories walked: {}", get(&DIRECTORIES));
+    println!("Files considered: {}", get(&FILES));
+
+    let filtered = FILTERED.lock().unwrap();
+    if !filtered.is_empty() {
+        println!("Files filtered out:");
+        for (rule, count) in filtered.iter() {
+            println!("  {}: {}", rule, count);
+        }
+    }
+
+    println!("Files hashed: {} ({})", get(&FILES_HASHED), format_bytes(get(&BYTES_HASHED)));
+
+    let (hits, misses) = (get(&CACHE_HITS), get(&CACHE_MISSES));
+    if hits + misses > 0 {
+        println!(
+            "Hash cache: {} hit(s), {} miss(es) ({:.1}% hit rate)",
+            hits,
+            misses,
+            hits as f64 * 100.0 / (hits + misses) as f64
+        );
+    }
+
+    let (hits, misses) = (get(&DIR_CACHE_HITS), get(&DIR_CACHE_MISSES));
+    if hits + misses > 0 {
+        println!("Directory cache: {} unchanged directories taken from the cache, {} read", hits, misses);
+    }
+
+    println!("Time:");
+    for (name, elapsed) in PHASES.lock().unwrap().iter() {
+        println!("  {}: {:.3}s", name, elapsed.as_secs_f64());
+    }
+    println!("  total: {:.3}s", total.as_secs_f64());
+}
@@ -0,0 +1,115 @@
+// NTFS alternate data streams: extra named contents attached to a file
+// (":Zone.Identifier" marks downloads, other tools keep their own metadata
+// there). two files with equal contents can carry different streams, and
+// deleting one loses whatever streams the keeper lacks, so they are reported
+// and, with --merge-metadata, copied onto the keeper first
+use crate::DuplicateSet;
+use std::io::{self, Write};
+use std::path::Path;
+
+// the names of the alternate streams of `path`, without the ":$DATA" type
+#[cfg(windows)]
+pub fn list(path: &Path) -> io::Result<Vec<String>> {
+    use std::ffi::c_void;
+    use std::os::windows::ffi::OsStrExt;
+
+    // WIN32_FIND_STREAM_DATA: a size and a MAX_PATH + 36 character name
+    #[repr(C)]
+    struct FindStreamData {
+        _size: i64,
+        name: [u16; 296],
+    }
+
+    const FIND_STREAM_INFO_STANDARD: i32 = 0;
+    const ERROR_HANDLE_EOF: i32 = 38;
+
+    unsafe extern "system" {
+        fn FindFirstStreamW(name: *const u16, level: i32, data: *mut FindStreamData, flags: u32) -> *mut c_void;
+        fn FindNextStreamW(handle: *mut c_void, data: *mut FindStreamData) -> i32;
+        fn FindClose(handle: *mut c_void) -> i32;
+    }
+
+    let mut wide: Vec<u16> = path.as_os_str().encode_wide().collect();
+    wide.push(0);
+    let mut data = FindStreamData { _size: 0, name: [0; 296] };
+
+    let handle = unsafe { FindFirstStreamW(wide.as_ptr(), FIND_STREAM_INFO_STANDARD, &mut data, 0) };
+    if handle as isize == -1 {
+        let error = io::Error::last_os_error();
+        return match error.raw_os_error() {
+            Some(ERROR_HANDLE_EOF) => Ok(Vec::new()),
+            _ => Err(error),
+        };
+    }
+
+    let mut names = Vec::new();
+    loop {
+        let length = data.name.iter().position(|&c| c == 0).unwrap_or(data.name.len());
+        let name = String::from_utf16_lossy(&data.name[..length]);
+        // "::$DATA" is the file's main contents
+        if let Some(stream) = name.strip_prefix(':').and_then(|name| name.strip_suffix(":$DATA"))
+            && !stream.is_empty()
+        {
+            names.push(stream.to_string());
+        }
+        if unsafe { FindNextStreamW(handle, &mut data) } == 0 {
+            break;
+        }
+    }
+    unsafe { FindClose(handle) };
+
+    Ok(names)
+}
+
+#[cfg(not(windows))]
+pub fn list(_path: &Path) -> io::Result<Vec<String>> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+// "file.txt" and "Zone.Identifier" name "file.txt:Zone.Identifier"
+pub fn stream_path(path: &Path, stream: &str) -> std::path::PathBuf {
+    let mut stream_path = path.as_os_str().to_owned();
+    stream_path.push(":");
+    stream_path.push(stream);
+    stream_path.into()
+}
+
+// streams of `path` that `keeper` doesn't have
+fn missing_from(path: &Path, keeper: &Path) -> Vec<String> {
+    let Ok(streams) = list(path) else {
+        return Vec::new();
+    };
+    let keeper_streams = list(keeper).unwrap_or_default();
+    streams.into_iter().filter(|stream| !keeper_streams.contains(stream)).collect()
+}
+
+pub fn write_streams(out: &mut dyn Write, duplicate_sets: &[DuplicateSet], merge: bool) -> io::Result<()> {
+    // nothing to look for on filesystems without streams
+    if cfg!(not(windows)) {
+        return Ok(());
+    }
+
+    let mut header = false;
+    for set in duplicate_sets {
+        for file_info in &set.duplicates {
+            let missing = missing_from(&file_info.path, &set.keeper.path);
+            if missing.is_empty() {
+                continue;
+            }
+            if !header {
+                writeln!(out, "\n--- Alternate Data Streams ---")?;
+                if merge {
+                    writeln!(out, "These copies carry streams their keeper lacks; they are copied onto the")?;
+                    writeln!(out, "keeper first.")?;
+                } else {
+                    writeln!(out, "These copies carry streams their keeper lacks, which are lost with them.")?;
+                    writeln!(out, "Pass --merge-metadata to copy them onto the keeper first.")?;
+                }
+                header = true;
+            }
+            writeln!(out, "{}: {}", file_info.path.display(), missing.join(", "))?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,107 @@
+// point symlinks that referenced a deleted copy at its keeper instead of
+// leaving them dangling
+use crate::{normalize_path, scan};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Default)]
+pub struct SymlinkReport {
+    pub retargeted: usize,
+    pub dangling: Vec<(PathBuf, PathBuf)>,
+}
+
+fn resolve_target(link: &Path, target: &Path) -> PathBuf {
+    match link.parent() {
+        Some(parent) if target.is_relative() => normalize_path(&parent.join(target)),
+        _ => normalize_path(target),
+    }
+}
+
+// keep relative links relative when the keeper sits next to the link
+fn new_target(link: &Path, old_target: &Path, keeper: &Path) -> PathBuf {
+    if old_target.is_relative()
+        && link.parent() == keeper.parent()
+        && let Some(name) = keeper.file_name()
+    {
+        return PathBuf::from(name);
+    }
+
+    keeper.to_path_buf()
+}
+
+#[cfg(unix)]
+fn create_symlink(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn create_symlink(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::windows::fs::symlink_file(target, link)
+}
+
+// write the new link beside the old one and rename it into place so the link
+// never disappears
+fn replace_symlink(link: &Path, target: &Path) -> io::Result<()> {
+    let name = link.file_name().unwrap_or_default().to_string_lossy();
+    let temporary = link.with_file_name(format!(".{}.hydra-tmp", name));
+
+    create_symlink(target, &temporary)?;
+    fs::rename(&temporary, link).inspect_err(|_| {
+        let _ = fs::remove_file(&temporary);
+    })
+}
+
+// `removed` maps each deleted (or, in a dry run, to-be-deleted) path to its keeper
+pub fn fix_symlinks(
+    directory: &Path,
+    recursive: bool,
+    removed: &HashMap<PathBuf, PathBuf>,
+    dry_run: bool,
+) -> SymlinkReport {
+    let removed: HashMap<PathBuf, &PathBuf> = removed
+        .iter()
+        .map(|(path, keeper)| (normalize_path(path), keeper))
+        .collect();
+    let mut report = SymlinkReport::default();
+
+    scan::walk_symlinks(directory, recursive, |link| {
+        let old_target = match fs::read_link(&link) {
+            Ok(target) => target,
+            Err(e) => {
+                eprintln!("Error reading symlink '{}': {}", link.display(), e);
+                return;
+            }
+        };
+        let resolved = resolve_target(&link, &old_target);
+
+        if let Some(keeper) = removed.get(&resolved) {
+            let target = new_target(&link, &old_target, keeper);
+            if dry_run {
+                println!("Would retarget symlink: {} -> {}", link.display(), target.display());
+                report.retargeted += 1;
+                return;
+            }
+
+            match replace_symlink(&link, &target) {
+                Ok(_) => {
+                    println!("Retargeted symlink: {} -> {}", link.display(), target.display());
+                    report.retargeted += 1;
+                }
+                Err(e) => {
+                    eprintln!("Error retargeting symlink '{}': {}", link.display(), e);
+                    report.dangling.push((link, old_target));
+                }
+            }
+            return;
+        }
+
+        // anything else that points nowhere is reported, whether or not we caused it
+        if !dry_run && fs::metadata(&link).is_err() {
+            report.dangling.push((link, old_target));
+        }
+    });
+
+    report
+}
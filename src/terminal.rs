@@ -0,0 +1,55 @@
+use std::env;
+use std::io::{self, IsTerminal};
+
+// whether anyone can answer a prompt: false in a pipeline or under cron, where
+// read_line would just see end-of-file
+pub fn can_prompt() -> bool {
+    io::stdin().is_terminal()
+}
+
+// number of rows in the controlling terminal, if it can be determined
+pub fn rows() -> Option<usize> {
+    if let Some(lines) = env::var("LINES").ok().and_then(|lines| lines.parse().ok()) {
+        return Some(lines);
+    }
+
+    window_rows()
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn window_rows() -> Option<usize> {
+    use std::os::raw::{c_int, c_ulong, c_ushort};
+
+    #[repr(C)]
+    struct WinSize {
+        rows: c_ushort,
+        columns: c_ushort,
+        x_pixels: c_ushort,
+        y_pixels: c_ushort,
+    }
+
+    #[cfg(target_os = "linux")]
+    const TIOCGWINSZ: c_ulong = 0x5413;
+    #[cfg(target_os = "macos")]
+    const TIOCGWINSZ: c_ulong = 0x40087468;
+    const STDOUT_FILENO: c_int = 1;
+
+    unsafe extern "C" {
+        fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;
+    }
+
+    let mut size = WinSize {
+        rows: 0,
+        columns: 0,
+        x_pixels: 0,
+        y_pixels: 0,
+    };
+    let result = unsafe { ioctl(STDOUT_FILENO, TIOCGWINSZ, &mut size as *mut WinSize) };
+
+    (result == 0 && size.rows > 0).then_some(size.rows as usize)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn window_rows() -> Option<usize> {
+    None
+}
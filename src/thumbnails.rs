@@ -0,0 +1,108 @@
+// inline image thumbnails for terminals with a graphics protocol. the
+// terminal does the decoding and scaling, so we only ever send the file:
+// iTerm2/WezTerm accept any common image format, kitty accepts PNG by path.
+// sixel needs the image decoded on our side and is not supported
+use crate::hash;
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+const THUMBNAIL_COLUMNS: u32 = 24;
+const THUMBNAIL_ROWS: u32 = 8;
+const MAX_INLINE_BYTES: u64 = 10 * 1024 * 1024;
+const IMAGE_EXTENSIONS: [&str; 7] = ["jpg", "jpeg", "png", "gif", "webp", "bmp", "tiff"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Protocol {
+    Kitty,
+    Iterm,
+}
+
+fn detect_protocol() -> Option<Protocol> {
+    if env::var_os("KITTY_WINDOW_ID").is_some() || env::var("TERM").is_ok_and(|term| term == "xterm-kitty") {
+        return Some(Protocol::Kitty);
+    }
+
+    match env::var("TERM_PROGRAM").as_deref() {
+        Ok("iTerm.app") | Ok("WezTerm") => Some(Protocol::Iterm),
+        _ => None,
+    }
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension().map(|ext| ext.to_string_lossy().to_lowercase())
+}
+
+pub fn is_image(path: &Path) -> bool {
+    extension(path).is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.as_str()))
+}
+
+pub fn base64_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    for chunk in bytes.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+
+        encoded.push(ALPHABET[(n >> 18) as usize & 63] as char);
+        encoded.push(ALPHABET[(n >> 12) as usize & 63] as char);
+        encoded.push(if chunk.len() > 1 { ALPHABET[(n >> 6) as usize & 63] as char } else { '=' });
+        encoded.push(if chunk.len() > 2 { ALPHABET[n as usize & 63] as char } else { '=' });
+    }
+
+    encoded
+}
+
+fn kitty_sequence(path: &Path) -> Option<String> {
+    if extension(path).as_deref() != Some("png") {
+        return None;
+    }
+
+    let absolute = fs::canonicalize(path).ok()?;
+    Some(format!(
+        "\x1b_Ga=T,f=100,t=f,c={},r={};{}\x1b\\",
+        THUMBNAIL_COLUMNS,
+        THUMBNAIL_ROWS,
+        base64_encode(absolute.to_string_lossy().as_bytes())
+    ))
+}
+
+fn iterm_sequence(path: &Path) -> Option<String> {
+    if fs::metadata(path).ok()?.len() > MAX_INLINE_BYTES {
+        return None;
+    }
+
+    let contents = hash::read(path).ok()?;
+    Some(format!(
+        "\x1b]1337;File=inline=1;width={};height={};preserveAspectRatio=1:{}\x07",
+        THUMBNAIL_COLUMNS,
+        THUMBNAIL_ROWS,
+        base64_encode(&contents)
+    ))
+}
+
+// draw a thumbnail of `path` if both the file and the terminal allow it;
+// returns whether anything was drawn
+pub fn show(path: &Path) -> bool {
+    if !is_image(path) {
+        return false;
+    }
+
+    let sequence = match detect_protocol() {
+        Some(Protocol::Kitty) => kitty_sequence(path),
+        Some(Protocol::Iterm) => iterm_sequence(path),
+        None => None,
+    };
+
+    match sequence {
+        Some(sequence) => {
+            let mut stdout = io::stdout();
+            let _ = writeln!(stdout, "  {}", sequence);
+            let _ = stdout.flush();
+            true
+        }
+        None => false,
+    }
+}
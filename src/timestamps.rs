@@ -0,0 +1,39 @@
+// which timestamp decides the keeper. birth time isn't available everywhere
+// (older linux kernels and many network or FAT mounts), so the fallback to
+// modification time is explicit rather than silent
+use crate::FileInfo;
+use std::time::SystemTime;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeSource {
+    Created,
+    Modified,
+    OldestOfBoth,
+}
+
+impl TimeSource {
+    pub fn parse(value: &str) -> Option<TimeSource> {
+        match value {
+            "created" => Some(TimeSource::Created),
+            "modified" => Some(TimeSource::Modified),
+            "oldest-of-both" => Some(TimeSource::OldestOfBoth),
+            _ => None,
+        }
+    }
+
+    // `created` falls back to the modification time when there is no birth time
+    pub fn timestamp(self, file_info: &FileInfo) -> SystemTime {
+        match self {
+            TimeSource::Created => file_info.created.unwrap_or(file_info.modified),
+            TimeSource::Modified => file_info.modified,
+            TimeSource::OldestOfBoth => file_info
+                .created
+                .map_or(file_info.modified, |created| created.min(file_info.modified)),
+        }
+    }
+
+    // whether `file_info` had to fall back to its modification time
+    pub fn falls_back(self, file_info: &FileInfo) -> bool {
+        self != TimeSource::Modified && file_info.created.is_none()
+    }
+}
@@ -0,0 +1,219 @@
+// move files to the platform's trash / recycle bin instead of deleting them
+use crate::config;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(all(unix, not(target_os = "macos")))]
+mod freedesktop {
+    use crate::{format_timestamp, percent_encode};
+    use std::env;
+    use std::fs::{self, OpenOptions};
+    use std::io::{self, Write};
+    use std::os::unix::fs::MetadataExt;
+    use std::path::{Path, PathBuf};
+    use std::time::SystemTime;
+
+    const EXDEV: i32 = 18;
+
+    unsafe extern "C" {
+        fn getuid() -> u32;
+    }
+
+    fn home_trash() -> Option<PathBuf> {
+        let data_home = env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))?;
+        Some(data_home.join("Trash"))
+    }
+
+    // the top directory of the mount holding `path`: the highest ancestor on
+    // the same device
+    fn mount_root(path: &Path) -> io::Result<PathBuf> {
+        let device = fs::metadata(path)?.dev();
+        let mut root = path.to_path_buf();
+
+        while let Some(parent) = root.parent() {
+            if fs::metadata(parent)?.dev() != device {
+                break;
+            }
+            root = parent.to_path_buf();
+        }
+
+        Ok(root)
+    }
+
+    fn trash_into(trash: &Path, path: &Path) -> io::Result<PathBuf> {
+        let files = trash.join("files");
+        let info = trash.join("info");
+        fs::create_dir_all(&files)?;
+        fs::create_dir_all(&info)?;
+
+        let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let absolute = fs::canonicalize(path)?;
+
+        // claim a unique name by creating its .trashinfo first, as the spec asks
+        for attempt in 0.. {
+            let candidate = if attempt == 0 { name.clone() } else { format!("{}.{}", name, attempt) };
+            let info_path = info.join(format!("{}.trashinfo", candidate));
+
+            let mut info_file = match OpenOptions::new().write(true).create_new(true).open(&info_path) {
+                Ok(file) => file,
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            };
+            write!(
+                info_file,
+                "[Trash Info]\nPath={}\nDeletionDate={}\n",
+                percent_encode(&absolute.to_string_lossy()),
+                &format_timestamp(SystemTime::now())[..19]
+            )?;
+
+            let destination = files.join(&candidate);
+            return match fs::rename(path, &destination) {
+                Ok(()) => Ok(destination),
+                Err(e) => {
+                    let _ = fs::remove_file(&info_path);
+                    Err(e)
+                }
+            };
+        }
+
+        unreachable!()
+    }
+
+    pub fn move_to_trash(path: &Path) -> io::Result<PathBuf> {
+        let home = home_trash().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no home directory"))?;
+
+        match trash_into(&home, path) {
+            // files on another filesystem go to that filesystem's own trash
+            Err(e) if e.raw_os_error() == Some(EXDEV) => {
+                let uid = unsafe { getuid() };
+                let root = mount_root(path.parent().unwrap_or(Path::new("/")))?;
+                trash_into(&root.join(format!(".Trash-{}", uid)), path)
+            }
+            result => result,
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn move_to_trash_impl(path: &Path) -> io::Result<Option<PathBuf>> {
+    use std::env;
+
+    let trash = env::var_os("HOME")
+        .map(|home| PathBuf::from(home).join(".Trash"))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no home directory"))?;
+
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    let extension = path.extension().map(|ext| format!(".{}", ext.to_string_lossy())).unwrap_or_default();
+
+    // finder appends a counter to clashing names, so do the same
+    let mut destination = trash.join(format!("{}{}", stem, extension));
+    let mut counter = 2;
+    while destination.exists() {
+        destination = trash.join(format!("{} {}{}", stem, counter, extension));
+        counter += 1;
+    }
+
+    fs::rename(path, &destination)?;
+    Ok(Some(destination))
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn move_to_trash_impl(path: &Path) -> io::Result<Option<PathBuf>> {
+    freedesktop::move_to_trash(path).map(Some)
+}
+
+// the recycle bin doesn't say where an item went, so these can't be purged
+#[cfg(windows)]
+fn move_to_trash_impl(path: &Path) -> io::Result<Option<PathBuf>> {
+    use std::ffi::c_void;
+    use std::os::windows::ffi::OsStrExt;
+
+    #[repr(C)]
+    struct ShFileOpStruct {
+        hwnd: *mut c_void,
+        func: u32,
+        from: *const u16,
+        to: *const u16,
+        flags: u16,
+        any_operations_aborted: i32,
+        name_mappings: *mut c_void,
+        progress_title: *const u16,
+    }
+
+    const FO_DELETE: u32 = 3;
+    const FOF_SILENT: u16 = 0x0004;
+    const FOF_NOCONFIRMATION: u16 = 0x0010;
+    const FOF_ALLOWUNDO: u16 = 0x0040;
+    const FOF_NOERRORUI: u16 = 0x0400;
+
+    #[link(name = "shell32")]
+    unsafe extern "system" {
+        fn SHFileOperationW(operation: *mut ShFileOpStruct) -> i32;
+    }
+
+    let absolute = std::fs::canonicalize(path)?;
+    // the api takes a list of paths terminated by an extra nul; canonicalize
+    // returns a \\?\ path, which SHFileOperation does not accept
+    let display = absolute.to_string_lossy();
+    let plain = display.strip_prefix(r"\\?\").unwrap_or(&display);
+    let mut from: Vec<u16> = std::ffi::OsStr::new(plain).encode_wide().collect();
+    from.extend_from_slice(&[0, 0]);
+
+    let mut operation = ShFileOpStruct {
+        hwnd: std::ptr::null_mut(),
+        func: FO_DELETE,
+        from: from.as_ptr(),
+        to: std::ptr::null(),
+        flags: FOF_ALLOWUNDO | FOF_NOCONFIRMATION | FOF_SILENT | FOF_NOERRORUI,
+        any_operations_aborted: 0,
+        name_mappings: std::ptr::null_mut(),
+        progress_title: std::ptr::null(),
+    };
+
+    match unsafe { SHFileOperationW(&mut operation) } {
+        0 if operation.any_operations_aborted == 0 => Ok(None),
+        0 => Err(io::Error::new(io::ErrorKind::Interrupted, "moving to the recycle bin was aborted")),
+        code => Err(io::Error::other(format!("SHFileOperation failed with code {:#x}", code))),
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn move_to_trash_impl(_path: &Path) -> io::Result<Option<PathBuf>> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+// every item hydra trashes is logged with the time it was trashed, so `hydra
+// purge` can later remove exactly those and leave the rest of the trash alone.
+// other hydra processes may be trashing or purging at the same time, so the
+// log is only touched under an exclusive lock on it
+pub fn log_path() -> Option<PathBuf> {
+    config::state_dir().map(|dir| dir.join("trashed.tsv"))
+}
+
+fn record(location: &Path) -> io::Result<()> {
+    let path = log_path().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no home directory"))?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let trashed = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let line = format!("{}\t{}\n", trashed, location.display());
+
+    let mut log = OpenOptions::new().create(true).append(true).open(&path)?;
+    log.lock()?;
+    log.write_all(line.as_bytes())
+}
+
+// returns where the item went in the trash, when the platform says
+pub fn move_to_trash(path: &Path) -> io::Result<Option<PathBuf>> {
+    let location = move_to_trash_impl(path)?;
+    if let Some(location) = &location
+        && let Err(e) = record(location)
+    {
+        eprintln!("Warning: Could not log '{}' for `hydra purge`: {}", location.display(), e);
+    }
+    Ok(location)
+}
@@ -0,0 +1,83 @@
+// --truncated: an interrupted download or copy leaves "file.iso" next to a
+// shorter "file (1).iso" whose bytes are an exact prefix of the complete one.
+// equal-size matching can never pair them, so within each name group every
+// smaller file is compared against the complete copy byte by byte
+use crate::{DuplicateSet, FileInfo, Options, choose_keeper, hash, set_name};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+// whether the first file's contents are the start of the second's
+pub fn is_prefix(partial: &Path, complete: &Path) -> io::Result<bool> {
+    let mut partial = BufReader::new(hash::open(partial)?);
+    let mut complete = BufReader::new(hash::open(complete)?);
+    let mut partial_chunk = vec![0u8; CHUNK_SIZE];
+    let mut complete_chunk = vec![0u8; CHUNK_SIZE];
+
+    loop {
+        let read = partial.read(&mut partial_chunk)?;
+        if read == 0 {
+            return Ok(true);
+        }
+        complete.read_exact(&mut complete_chunk[..read])?;
+        if partial_chunk[..read] != complete_chunk[..read] {
+            return Ok(false);
+        }
+    }
+}
+
+// pull truncated copies out of the name groups, returning one set per group
+// with the complete file as keeper. empty files are never treated as
+// truncated, since every file trivially starts with nothing
+pub fn extract_truncated(hashmap_name: &mut HashMap<String, Vec<FileInfo>>, options: &Options) -> Vec<DuplicateSet> {
+    let mut truncated_sets = Vec::new();
+    let mut truncated_paths: HashSet<PathBuf> = HashSet::new();
+
+    for file_infos in hashmap_name.values() {
+        let Some(largest) = file_infos.iter().map(|file_info| file_info.size).max() else {
+            continue;
+        };
+        let complete: Vec<FileInfo> = file_infos.iter().filter(|f| f.size == largest).cloned().collect();
+        if complete.len() == file_infos.len() {
+            continue;
+        }
+
+        // the same choice the equal-size set would make, so the keeper here is
+        // never removed as a duplicate there
+        let Some(keeper_index) = choose_keeper(&complete, options) else {
+            continue;
+        };
+        let keeper = complete[keeper_index].clone();
+
+        let mut duplicates = Vec::new();
+        for file_info in file_infos.iter().filter(|f| f.size > 0 && f.size < largest) {
+            match is_prefix(&file_info.path, &keeper.path) {
+                Ok(true) => duplicates.push(file_info.clone()),
+                Ok(false) => {}
+                Err(e) => eprintln!("Warning: Could not compare '{}': {}", file_info.path.display(), e),
+            }
+        }
+        if duplicates.is_empty() {
+            continue;
+        }
+
+        truncated_paths.extend(duplicates.iter().map(|file_info| file_info.path.clone()));
+        truncated_sets.push(DuplicateSet {
+            normalized_filename: set_name(&keeper, options),
+            size: keeper.size,
+            hash: None,
+            keeper,
+            duplicates,
+        });
+    }
+
+    if !truncated_paths.is_empty() {
+        for file_infos in hashmap_name.values_mut() {
+            file_infos.retain(|file_info| !truncated_paths.contains(&file_info.path));
+        }
+    }
+
+    truncated_sets
+}
@@ -0,0 +1,44 @@
+// --verify-keepers: for archives, make sure the action phase left every kept
+// file exactly as it was. keepers are hashed before anything is touched and
+// again once the run is done; any difference is reported
+use crate::DuplicateSet;
+use crate::scheduler::{self, IoProfile};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+pub fn hash_keepers(duplicate_sets: &[DuplicateSet], io_profile: IoProfile) -> HashMap<PathBuf, u64> {
+    let paths = duplicate_sets.iter().map(|set| set.keeper.path.clone()).collect();
+    scheduler::hash_files(paths, io_profile)
+        .into_iter()
+        .filter_map(|(path, hash)| match hash {
+            Ok(hash) => Some((path, hash)),
+            Err(e) => {
+                eprintln!("Warning: Could not hash keeper '{}', not verifying it: {}", path.display(), e);
+                None
+            }
+        })
+        .collect()
+}
+
+// re-hash the keepers and print the ones that no longer match
+pub fn verify_keepers(before: &HashMap<PathBuf, u64>, io_profile: IoProfile) {
+    let after = scheduler::hash_files(before.keys().cloned().collect(), io_profile);
+    let mut failures: Vec<(PathBuf, String)> = after
+        .into_iter()
+        .filter_map(|(path, hash)| match hash {
+            Ok(hash) if hash == before[&path] => None,
+            Ok(_) => Some((path, "contents changed".to_string())),
+            Err(e) => Some((path, format!("could not be read: {}", e))),
+        })
+        .collect();
+    failures.sort();
+
+    if failures.is_empty() {
+        println!("Keepers verified: {}", before.len());
+    } else {
+        println!("Keepers that failed verification: {} of {}", failures.len(), before.len());
+        for (path, reason) in &failures {
+            println!("  {}: {}", path.display(), reason);
+        }
+    }
+}
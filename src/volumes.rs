@@ -0,0 +1,73 @@
+// which volume a file lives on, named by its mount point (or drive on
+// windows), so a scan spanning several disks can say how much each one gains
+#[cfg(unix)]
+use std::collections::HashMap;
+#[cfg(not(unix))]
+use std::path::Component;
+use std::path::{Path, PathBuf};
+
+#[derive(Default)]
+pub struct Volumes {
+    #[cfg(unix)]
+    mount_points: HashMap<u64, PathBuf>,
+}
+
+// the highest ancestor still on the same device
+#[cfg(unix)]
+fn mount_point(path: &Path, device: u64) -> PathBuf {
+    let mut mount_point = path.parent().unwrap_or(path);
+    while let Some(parent) = mount_point.parent() {
+        if crate::scheduler::device_id(parent) != device {
+            break;
+        }
+        mount_point = parent;
+    }
+    mount_point.to_path_buf()
+}
+
+impl Volumes {
+    #[cfg(unix)]
+    pub fn of(&mut self, path: &Path) -> PathBuf {
+        let device = crate::scheduler::device_id(path);
+        self.mount_points
+            .entry(device)
+            .or_insert_with(|| mount_point(path, device))
+            .clone()
+    }
+
+    // the drive prefix and root, e.g. C:\
+    #[cfg(not(unix))]
+    pub fn of(&mut self, path: &Path) -> PathBuf {
+        path.components()
+            .take_while(|component| matches!(component, Component::Prefix(_) | Component::RootDir))
+            .collect()
+    }
+}
+
+// the filesystem uuid of `device`, found by matching it against the block
+// devices linux lists under /dev/disk/by-uuid
+#[cfg(target_os = "linux")]
+fn filesystem_uuid(device: u64) -> Option<String> {
+    use std::os::unix::fs::MetadataExt;
+
+    std::fs::read_dir("/dev/disk/by-uuid").ok()?.flatten().find_map(|entry| {
+        let metadata = std::fs::metadata(entry.path()).ok()?;
+        (metadata.rdev() == device).then(|| entry.file_name().to_string_lossy().to_string())
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn filesystem_uuid(_device: u64) -> Option<String> {
+    None
+}
+
+// names the filesystem `path` is on by its uuid (where the platform exposes
+// it) and mount point, so a hash cached on one filesystem isn't trusted when
+// the same path turns up on another, such as a restored backup
+pub fn filesystem_identity(path: &Path, device: u64) -> String {
+    let mount_point = Volumes::default().of(path);
+    match filesystem_uuid(device) {
+        Some(uuid) => format!("{}@{}", uuid, mount_point.display()),
+        None => mount_point.display().to_string(),
+    }
+}
@@ -0,0 +1,1281 @@
+// a small WebAssembly interpreter for --plugin. it runs the 1.0 instruction
+// set, plus the sign-extension, saturating float-to-int and memory.copy/fill
+// instructions compilers emit by default, straight from the bytecode. a module
+// can't import anything, so a plugin sees nothing but its own memory, and
+// every call is bounded in steps, depth and memory. slow next to a real
+// engine, but a plugin is only asked about one path at a time
+use std::collections::HashMap;
+use std::sync::Arc;
+
+const PAGE: usize = 65536;
+// 64 MiB of memory, a table of a million entries, and ten million steps a call
+const MAX_PAGES: usize = 1024;
+const MAX_TABLE: usize = 1 << 20;
+const FUEL: u64 = 10_000_000;
+const MAX_DEPTH: usize = 1000;
+const MAX_LOCALS: usize = 50_000;
+// a null reference, in a table or on the stack
+const NULL: u64 = u64::MAX;
+const UNDERFLOW: &str = "the value stack underflowed";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ValType {
+    I32,
+    I64,
+    F32,
+    F64,
+    FuncRef,
+    ExternRef,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuncType {
+    pub params: Vec<ValType>,
+    pub results: Vec<ValType>,
+}
+
+// bytecode positions to bytecode positions
+type Positions = HashMap<usize, usize>;
+
+struct Function {
+    type_index: usize,
+    // the locals declared after the parameters
+    locals: Vec<ValType>,
+    code: Vec<u8>,
+    // where each block, loop, if and else opcode's matching end is, and each if's else
+    ends: Positions,
+    elses: Positions,
+}
+
+#[derive(Clone, Copy)]
+struct Label {
+    // the stack height below the block's values
+    height: usize,
+    // values a branch to it carries: a block's results, a loop's parameters
+    arity: usize,
+    // where a branch to it continues: after a block's end, at a loop's start
+    target: usize,
+    is_loop: bool,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Result<u8, String> {
+        let byte = *self.data.get(self.position).ok_or("the module is truncated")?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .data
+            .get(self.position..self.position.saturating_add(count))
+            .ok_or("the module is truncated")?;
+        self.position += count;
+        Ok(bytes)
+    }
+
+    // an unsigned LEB128 number
+    fn u32(&mut self) -> Result<u32, String> {
+        let mut value: u64 = 0;
+        for shift in (0..35).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return u32::try_from(value).map_err(|_| "a number is out of range".to_string());
+            }
+        }
+        Err("a number is malformed".to_string())
+    }
+
+    // a signed LEB128 number of up to `bits` bits
+    fn signed(&mut self, bits: u32) -> Result<i64, String> {
+        let mut value: i64 = 0;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte()?;
+            if shift < 64 {
+                value |= ((byte & 0x7f) as i64) << shift;
+            }
+            shift += 7;
+            if byte & 0x80 == 0 {
+                if shift < 64 && byte & 0x40 != 0 {
+                    value |= -1 << shift;
+                }
+                return Ok(value);
+            }
+            if shift >= bits + 7 {
+                return Err("a number is malformed".to_string());
+            }
+        }
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.bytes(len)?.to_vec()).map_err(|_| "a name is not UTF-8".to_string())
+    }
+
+    fn value_type(&mut self) -> Result<ValType, String> {
+        match self.byte()? {
+            0x7f => Ok(ValType::I32),
+            0x7e => Ok(ValType::I64),
+            0x7d => Ok(ValType::F32),
+            0x7c => Ok(ValType::F64),
+            0x70 => Ok(ValType::FuncRef),
+            0x6f => Ok(ValType::ExternRef),
+            byte => Err(format!("unsupported value type 0x{:02x}", byte)),
+        }
+    }
+
+    fn limits(&mut self) -> Result<(u32, Option<u32>), String> {
+        match self.byte()? {
+            0x00 => Ok((self.u32()?, None)),
+            0x01 => Ok((self.u32()?, Some(self.u32()?))),
+            _ => Err("unsupported limits (64-bit or shared memory)".to_string()),
+        }
+    }
+
+    // a constant expression, as globals and segment offsets are given
+    fn constant(&mut self, globals: &[u64]) -> Result<u64, String> {
+        let value = match self.byte()? {
+            0x41 => self.signed(32)? as u32 as u64,
+            0x42 => self.signed(64)? as u64,
+            0x43 => u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()) as u64,
+            0x44 => u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()),
+            0x23 => *globals.get(self.u32()? as usize).ok_or("a constant reads a missing global")?,
+            0xd0 => {
+                self.byte()?;
+                NULL
+            }
+            0xd2 => self.u32()? as u64,
+            byte => return Err(format!("unsupported constant expression 0x{:02x}", byte)),
+        };
+        if self.byte()? != 0x0b {
+            return Err("unsupported constant expression".to_string());
+        }
+        Ok(value)
+    }
+}
+
+fn unsupported(opcode: &str) -> String {
+    format!("it uses an instruction hydra can't run ({})", opcode)
+}
+
+// step over an instruction's immediates, refusing any instruction the
+// interpreter doesn't run
+fn skip_immediates(reader: &mut Reader, opcode: u8) -> Result<(), String> {
+    match opcode {
+        0x00 | 0x01 | 0x05 | 0x0b | 0x0f | 0x1a | 0x1b | 0x45..=0xc4 | 0xd1 => {}
+        0x02..=0x04 => {
+            reader.signed(33)?;
+        }
+        0x0c | 0x0d | 0x10 | 0x20..=0x24 | 0xd2 => {
+            reader.u32()?;
+        }
+        0x0e => {
+            let count = reader.u32()?;
+            for _ in 0..=count {
+                reader.u32()?;
+            }
+        }
+        0x11 | 0x28..=0x3e => {
+            reader.u32()?;
+            reader.u32()?;
+        }
+        0x1c => {
+            let count = reader.u32()? as usize;
+            reader.bytes(count)?;
+        }
+        0x3f | 0x40 | 0xd0 => {
+            reader.byte()?;
+        }
+        0x41 => {
+            reader.signed(32)?;
+        }
+        0x42 => {
+            reader.signed(64)?;
+        }
+        0x43 => {
+            reader.bytes(4)?;
+        }
+        0x44 => {
+            reader.bytes(8)?;
+        }
+        0xfc => match reader.u32()? {
+            0..=7 => {}
+            10 => {
+                reader.bytes(2)?;
+            }
+            11 => {
+                reader.byte()?;
+            }
+            sub => return Err(unsupported(&format!("0xfc {}", sub))),
+        },
+        _ => return Err(unsupported(&format!("0x{:02x}", opcode))),
+    }
+    Ok(())
+}
+
+// pair up each block, loop and if with its end (and else) ahead of running
+// the function, so a branch can jump straight there
+fn match_blocks(code: &[u8]) -> Result<(Positions, Positions), String> {
+    let mut ends = HashMap::new();
+    let mut elses = HashMap::new();
+    let mut open = Vec::new();
+    let mut reader = Reader { data: code, position: 0 };
+    while reader.position < code.len() {
+        let at = reader.position;
+        let opcode = reader.byte()?;
+        match opcode {
+            0x02..=0x04 => open.push(at),
+            0x05 => {
+                let start = *open.last().ok_or("an else is outside any if")?;
+                elses.insert(start, at);
+            }
+            0x0b => match open.pop() {
+                Some(start) => {
+                    ends.insert(start, at);
+                    if let Some(&else_at) = elses.get(&start) {
+                        ends.insert(else_at, at);
+                    }
+                }
+                None if reader.position == code.len() => return Ok((ends, elses)),
+                None => return Err("a function has code after its end".to_string()),
+            },
+            _ => {}
+        }
+        skip_immediates(&mut reader, opcode)?;
+    }
+    Err("a function is missing its end".to_string())
+}
+
+fn pop(stack: &mut Vec<u64>) -> Result<u64, String> {
+    stack.pop().ok_or_else(|| UNDERFLOW.to_string())
+}
+
+// a float's minimum or maximum: NaN if either is, and -0 below +0
+fn min_max(a: f64, b: f64, max: bool) -> f64 {
+    if a.is_nan() || b.is_nan() {
+        f64::NAN
+    } else if a == b {
+        if a.is_sign_negative() == max { b } else { a }
+    } else if max {
+        a.max(b)
+    } else {
+        a.min(b)
+    }
+}
+
+// a float truncated toward zero for an integer in [low, high), trapping outside
+fn truncate(value: f64, low: f64, high: f64) -> Result<f64, String> {
+    let value = value.trunc();
+    if value.is_nan() {
+        Err("invalid conversion to integer".to_string())
+    } else if value < low || value >= high {
+        Err("integer overflow".to_string())
+    } else {
+        Ok(value)
+    }
+}
+
+fn f32_of(value: u64) -> f32 {
+    f32::from_bits(value as u32)
+}
+
+fn f32_bits(value: f32) -> u64 {
+    value.to_bits() as u64
+}
+
+// the numeric instructions, 0x45 to 0xc4
+fn numeric(opcode: u8, stack: &mut Vec<u64>) -> Result<(), String> {
+    const I32_RANGE: (f64, f64) = (-2147483648.0, 2147483648.0);
+    const U32_RANGE: (f64, f64) = (0.0, 4294967296.0);
+    const I64_RANGE: (f64, f64) = (-9223372036854775808.0, 9223372036854775808.0);
+    const U64_RANGE: (f64, f64) = (0.0, 18446744073709551616.0);
+    let divide_by_zero = || "integer divide by zero".to_string();
+
+    let result = match opcode {
+        0x45 => (pop(stack)? as u32 == 0) as u64,
+        0x46..=0x4f => {
+            let b = pop(stack)? as u32;
+            let a = pop(stack)? as u32;
+            let (sa, sb) = (a as i32, b as i32);
+            (match opcode {
+                0x46 => a == b,
+                0x47 => a != b,
+                0x48 => sa < sb,
+                0x49 => a < b,
+                0x4a => sa > sb,
+                0x4b => a > b,
+                0x4c => sa <= sb,
+                0x4d => a <= b,
+                0x4e => sa >= sb,
+                _ => a >= b,
+            }) as u64
+        }
+        0x50 => (pop(stack)? == 0) as u64,
+        0x51..=0x5a => {
+            let b = pop(stack)?;
+            let a = pop(stack)?;
+            let (sa, sb) = (a as i64, b as i64);
+            (match opcode {
+                0x51 => a == b,
+                0x52 => a != b,
+                0x53 => sa < sb,
+                0x54 => a < b,
+                0x55 => sa > sb,
+                0x56 => a > b,
+                0x57 => sa <= sb,
+                0x58 => a <= b,
+                0x59 => sa >= sb,
+                _ => a >= b,
+            }) as u64
+        }
+        0x5b..=0x66 => {
+            let (b, a) = if opcode <= 0x60 {
+                (f32_of(pop(stack)?) as f64, f32_of(pop(stack)?) as f64)
+            } else {
+                (f64::from_bits(pop(stack)?), f64::from_bits(pop(stack)?))
+            };
+            (match (opcode - 0x5b) % 6 {
+                0 => a == b,
+                1 => a != b,
+                2 => a < b,
+                3 => a > b,
+                4 => a <= b,
+                _ => a >= b,
+            }) as u64
+        }
+        0x67..=0x69 => {
+            let a = pop(stack)? as u32;
+            (match opcode {
+                0x67 => a.leading_zeros(),
+                0x68 => a.trailing_zeros(),
+                _ => a.count_ones(),
+            }) as u64
+        }
+        0x6a..=0x78 => {
+            let b = pop(stack)? as u32;
+            let a = pop(stack)? as u32;
+            (match opcode {
+                0x6a => a.wrapping_add(b),
+                0x6b => a.wrapping_sub(b),
+                0x6c => a.wrapping_mul(b),
+                0x6d => {
+                    if b == 0 {
+                        return Err(divide_by_zero());
+                    }
+                    (a as i32).checked_div(b as i32).ok_or("integer overflow")? as u32
+                }
+                0x6e => a.checked_div(b).ok_or_else(divide_by_zero)?,
+                0x6f => {
+                    if b == 0 {
+                        return Err(divide_by_zero());
+                    }
+                    (a as i32).wrapping_rem(b as i32) as u32
+                }
+                0x70 => a.checked_rem(b).ok_or_else(divide_by_zero)?,
+                0x71 => a & b,
+                0x72 => a | b,
+                0x73 => a ^ b,
+                0x74 => a.wrapping_shl(b),
+                0x75 => (a as i32).wrapping_shr(b) as u32,
+                0x76 => a.wrapping_shr(b),
+                0x77 => a.rotate_left(b % 32),
+                _ => a.rotate_right(b % 32),
+            }) as u64
+        }
+        0x79..=0x7b => {
+            let a = pop(stack)?;
+            (match opcode {
+                0x79 => a.leading_zeros(),
+                0x7a => a.trailing_zeros(),
+                _ => a.count_ones(),
+            }) as u64
+        }
+        0x7c..=0x8a => {
+            let b = pop(stack)?;
+            let a = pop(stack)?;
+            match opcode {
+                0x7c => a.wrapping_add(b),
+                0x7d => a.wrapping_sub(b),
+                0x7e => a.wrapping_mul(b),
+                0x7f => {
+                    if b == 0 {
+                        return Err(divide_by_zero());
+                    }
+                    (a as i64).checked_div(b as i64).ok_or("integer overflow")? as u64
+                }
+                0x80 => a.checked_div(b).ok_or_else(divide_by_zero)?,
+                0x81 => {
+                    if b == 0 {
+                        return Err(divide_by_zero());
+                    }
+                    (a as i64).wrapping_rem(b as i64) as u64
+                }
+                0x82 => a.checked_rem(b).ok_or_else(divide_by_zero)?,
+                0x83 => a & b,
+                0x84 => a | b,
+                0x85 => a ^ b,
+                0x86 => a.wrapping_shl(b as u32),
+                0x87 => (a as i64).wrapping_shr(b as u32) as u64,
+                0x88 => a.wrapping_shr(b as u32),
+                0x89 => a.rotate_left((b % 64) as u32),
+                _ => a.rotate_right((b % 64) as u32),
+            }
+        }
+        0x8b..=0x91 => {
+            let a = f32_of(pop(stack)?);
+            f32_bits(match opcode {
+                0x8b => a.abs(),
+                0x8c => -a,
+                0x8d => a.ceil(),
+                0x8e => a.floor(),
+                0x8f => a.trunc(),
+                0x90 => a.round_ties_even(),
+                _ => a.sqrt(),
+            })
+        }
+        0x92..=0x98 => {
+            let b = f32_of(pop(stack)?);
+            let a = f32_of(pop(stack)?);
+            f32_bits(match opcode {
+                0x92 => a + b,
+                0x93 => a - b,
+                0x94 => a * b,
+                0x95 => a / b,
+                0x96 => min_max(a as f64, b as f64, false) as f32,
+                0x97 => min_max(a as f64, b as f64, true) as f32,
+                _ => a.copysign(b),
+            })
+        }
+        0x99..=0x9f => {
+            let a = f64::from_bits(pop(stack)?);
+            (match opcode {
+                0x99 => a.abs(),
+                0x9a => -a,
+                0x9b => a.ceil(),
+                0x9c => a.floor(),
+                0x9d => a.trunc(),
+                0x9e => a.round_ties_even(),
+                _ => a.sqrt(),
+            })
+            .to_bits()
+        }
+        0xa0..=0xa6 => {
+            let b = f64::from_bits(pop(stack)?);
+            let a = f64::from_bits(pop(stack)?);
+            (match opcode {
+                0xa0 => a + b,
+                0xa1 => a - b,
+                0xa2 => a * b,
+                0xa3 => a / b,
+                0xa4 => min_max(a, b, false),
+                0xa5 => min_max(a, b, true),
+                _ => a.copysign(b),
+            })
+            .to_bits()
+        }
+        _ => {
+            let a = pop(stack)?;
+            match opcode {
+                0xa7 => a as u32 as u64,
+                0xa8 => truncate(f32_of(a) as f64, I32_RANGE.0, I32_RANGE.1)? as i32 as u32 as u64,
+                0xa9 => truncate(f32_of(a) as f64, U32_RANGE.0, U32_RANGE.1)? as u32 as u64,
+                0xaa => truncate(f64::from_bits(a), I32_RANGE.0, I32_RANGE.1)? as i32 as u32 as u64,
+                0xab => truncate(f64::from_bits(a), U32_RANGE.0, U32_RANGE.1)? as u32 as u64,
+                0xac => a as u32 as i32 as i64 as u64,
+                0xad => a as u32 as u64,
+                0xae => truncate(f32_of(a) as f64, I64_RANGE.0, I64_RANGE.1)? as i64 as u64,
+                0xaf => truncate(f32_of(a) as f64, U64_RANGE.0, U64_RANGE.1)? as u64,
+                0xb0 => truncate(f64::from_bits(a), I64_RANGE.0, I64_RANGE.1)? as i64 as u64,
+                0xb1 => truncate(f64::from_bits(a), U64_RANGE.0, U64_RANGE.1)? as u64,
+                0xb2 => f32_bits(a as u32 as i32 as f32),
+                0xb3 => f32_bits(a as u32 as f32),
+                0xb4 => f32_bits(a as i64 as f32),
+                0xb5 => f32_bits(a as f32),
+                0xb6 => f32_bits(f64::from_bits(a) as f32),
+                0xb7 => (a as u32 as i32 as f64).to_bits(),
+                0xb8 => (a as u32 as f64).to_bits(),
+                0xb9 => (a as i64 as f64).to_bits(),
+                0xba => (a as f64).to_bits(),
+                0xbb => (f32_of(a) as f64).to_bits(),
+                // reinterpretations: the bits are already what they are
+                0xbc..=0xbf => a,
+                0xc0 => a as u8 as i8 as i32 as u32 as u64,
+                0xc1 => a as u16 as i16 as i32 as u32 as u64,
+                0xc2 => a as u8 as i8 as i64 as u64,
+                0xc3 => a as u16 as i16 as i64 as u64,
+                _ => a as u32 as i32 as i64 as u64,
+            }
+        }
+    };
+    stack.push(result);
+    Ok(())
+}
+
+// the saturating float-to-int conversions, 0xfc 0 to 7; `as` saturates the same way
+fn saturate(sub: u32, value: u64) -> u64 {
+    match sub {
+        0 => f32_of(value) as i32 as u32 as u64,
+        1 => f32_of(value) as u32 as u64,
+        2 => f64::from_bits(value) as i32 as u32 as u64,
+        3 => f64::from_bits(value) as u32 as u64,
+        4 => f32_of(value) as i64 as u64,
+        5 => f32_of(value) as u64,
+        6 => f64::from_bits(value) as i64 as u64,
+        _ => f64::from_bits(value) as u64,
+    }
+}
+
+pub struct Instance {
+    types: Vec<FuncType>,
+    functions: Vec<Arc<Function>>,
+    table: Vec<u64>,
+    memory: Vec<u8>,
+    max_pages: usize,
+    globals: Vec<u64>,
+    // exported functions by name
+    exports: HashMap<String, usize>,
+    fuel: u64,
+}
+
+impl Instance {
+    // parse a module and instantiate it: lay out its table and memory and run
+    // its start function
+    pub fn load(bytes: &[u8]) -> Result<Instance, String> {
+        if bytes.get(0..4) != Some(b"\0asm") {
+            return Err("not a WebAssembly module".to_string());
+        }
+        if bytes.get(4..8) != Some(&[1, 0, 0, 0]) {
+            return Err("unsupported WebAssembly version".to_string());
+        }
+        let mut instance = Instance {
+            types: Vec::new(),
+            functions: Vec::new(),
+            table: Vec::new(),
+            memory: Vec::new(),
+            max_pages: 0,
+            globals: Vec::new(),
+            exports: HashMap::new(),
+            fuel: FUEL,
+        };
+        let mut function_types = Vec::new();
+        let mut start = None;
+        let mut reader = Reader { data: bytes, position: 8 };
+        while reader.position < bytes.len() {
+            let id = reader.byte()?;
+            let size = reader.u32()? as usize;
+            let mut section = Reader {
+                data: reader.bytes(size)?,
+                position: 0,
+            };
+            match id {
+                // custom sections and the data count
+                0 | 12 => continue,
+                1 => {
+                    for _ in 0..section.u32()? {
+                        if section.byte()? != 0x60 {
+                            return Err("a type is not a function type".to_string());
+                        }
+                        let params = (0..section.u32()?).map(|_| section.value_type()).collect::<Result<_, _>>()?;
+                        let results = (0..section.u32()?).map(|_| section.value_type()).collect::<Result<_, _>>()?;
+                        instance.types.push(FuncType { params, results });
+                    }
+                }
+                2 => {
+                    if section.u32()? > 0 {
+                        return Err("it imports from its host; a plugin must be built to import nothing".to_string());
+                    }
+                }
+                3 => {
+                    for _ in 0..section.u32()? {
+                        let type_index = section.u32()? as usize;
+                        if type_index >= instance.types.len() {
+                            return Err("a function has a missing type".to_string());
+                        }
+                        function_types.push(type_index);
+                    }
+                }
+                4 => {
+                    if section.u32()? > 1 {
+                        return Err("it has more than one table".to_string());
+                    }
+                    section.value_type()?;
+                    let (min, _) = section.limits()?;
+                    if min as usize > MAX_TABLE {
+                        return Err(format!("its table has more than {} entries", MAX_TABLE));
+                    }
+                    instance.table = vec![NULL; min as usize];
+                }
+                5 => {
+                    let count = section.u32()?;
+                    if count > 1 {
+                        return Err("it has more than one memory".to_string());
+                    }
+                    if count == 1 {
+                        let (min, max) = section.limits()?;
+                        instance.max_pages = max.map_or(MAX_PAGES, |max| (max as usize).min(MAX_PAGES));
+                        if min as usize > instance.max_pages {
+                            return Err(format!("it needs more than {} MiB of memory", (MAX_PAGES * PAGE) >> 20));
+                        }
+                        instance.memory = vec![0; min as usize * PAGE];
+                    }
+                }
+                6 => {
+                    for _ in 0..section.u32()? {
+                        section.value_type()?;
+                        section.byte()?;
+                        let value = section.constant(&instance.globals)?;
+                        instance.globals.push(value);
+                    }
+                }
+                7 => {
+                    for _ in 0..section.u32()? {
+                        let name = section.name()?;
+                        let kind = section.byte()?;
+                        let index = section.u32()? as usize;
+                        if kind == 0 {
+                            instance.exports.insert(name, index);
+                        }
+                    }
+                }
+                8 => start = Some(section.u32()? as usize),
+                9 => {
+                    for _ in 0..section.u32()? {
+                        // the flags say whether the segment is active, names a
+                        // table, and lists expressions rather than function indices
+                        let flags = section.u32()?;
+                        if flags > 7 {
+                            return Err("an element segment is malformed".to_string());
+                        }
+                        let offset = if flags & 1 == 0 {
+                            if flags & 2 != 0 {
+                                section.u32()?;
+                            }
+                            Some(section.constant(&instance.globals)? as u32 as usize)
+                        } else {
+                            None
+                        };
+                        if flags & 3 != 0 {
+                            section.byte()?;
+                        }
+                        let mut items = Vec::new();
+                        for _ in 0..section.u32()? {
+                            items.push(if flags & 4 != 0 {
+                                section.constant(&instance.globals)?
+                            } else {
+                                section.u32()? as u64
+                            });
+                        }
+                        if let Some(offset) = offset {
+                            instance
+                                .table
+                                .get_mut(offset..offset + items.len())
+                                .ok_or("an element segment is out of the table's bounds")?
+                                .copy_from_slice(&items);
+                        }
+                    }
+                }
+                10 => {
+                    let count = section.u32()? as usize;
+                    if count != function_types.len() {
+                        return Err("the code and function sections disagree".to_string());
+                    }
+                    for &type_index in &function_types {
+                        let size = section.u32()? as usize;
+                        let mut body = Reader {
+                            data: section.bytes(size)?,
+                            position: 0,
+                        };
+                        let mut locals = Vec::new();
+                        for _ in 0..body.u32()? {
+                            let count = body.u32()? as usize;
+                            let value_type = body.value_type()?;
+                            if locals.len() + count > MAX_LOCALS {
+                                return Err("a function has too many locals".to_string());
+                            }
+                            locals.extend(std::iter::repeat_n(value_type, count));
+                        }
+                        let code = body.data[body.position..].to_vec();
+                        let (ends, elses) = match_blocks(&code)?;
+                        instance.functions.push(Arc::new(Function {
+                            type_index,
+                            locals,
+                            code,
+                            ends,
+                            elses,
+                        }));
+                    }
+                }
+                11 => {
+                    for _ in 0..section.u32()? {
+                        let offset = match section.u32()? {
+                            0 => Some(section.constant(&instance.globals)?),
+                            1 => None,
+                            2 => {
+                                section.u32()?;
+                                Some(section.constant(&instance.globals)?)
+                            }
+                            _ => return Err("a data segment is malformed".to_string()),
+                        };
+                        let len = section.u32()? as usize;
+                        let data = section.bytes(len)?;
+                        if let Some(offset) = offset {
+                            let offset = offset as u32 as usize;
+                            instance
+                                .memory
+                                .get_mut(offset..offset + len)
+                                .ok_or("a data segment is out of memory's bounds")?
+                                .copy_from_slice(data);
+                        }
+                    }
+                }
+                _ => return Err(format!("unknown section {}", id)),
+            }
+        }
+        if instance.functions.len() != function_types.len() {
+            return Err("the code section is missing".to_string());
+        }
+        if let Some(start) = start {
+            instance.invoke(start, Vec::new(), 0)?;
+        }
+        Ok(instance)
+    }
+
+    // the signature of an exported function
+    pub fn signature(&self, name: &str) -> Option<&FuncType> {
+        let function = self.functions.get(*self.exports.get(name)?)?;
+        Some(&self.types[function.type_index])
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    pub fn memory_mut(&mut self) -> &mut [u8] {
+        &mut self.memory
+    }
+
+    // call an exported function; i32 and f32 arguments and results are in the
+    // low 32 bits
+    pub fn call(&mut self, name: &str, args: &[u64]) -> Result<Vec<u64>, String> {
+        let index = *self.exports.get(name).ok_or(format!("it doesn't export {}", name))?;
+        self.fuel = FUEL;
+        self.invoke(index, args.to_vec(), 0)
+    }
+
+    fn block_type(&self, reader: &mut Reader) -> Result<(usize, usize), String> {
+        match reader.signed(33)? {
+            -64 => Ok((0, 0)),
+            index if index < 0 => Ok((0, 1)),
+            index => {
+                let block_type = self.types.get(index as usize).ok_or("a block has a missing type")?;
+                Ok((block_type.params.len(), block_type.results.len()))
+            }
+        }
+    }
+
+    // where a memory access of `size` bytes lands, trapping out of bounds
+    fn address(&self, base: u64, offset: u32, size: usize) -> Result<usize, String> {
+        let address = base as u32 as usize + offset as usize;
+        if address + size > self.memory.len() {
+            return Err("out of bounds memory access".to_string());
+        }
+        Ok(address)
+    }
+
+    fn call_function(&mut self, index: usize, stack: &mut Vec<u64>, depth: usize) -> Result<(), String> {
+        let function = self.functions.get(index).ok_or("a call to a missing function")?;
+        let params = self.types[function.type_index].params.len();
+        let args = stack.split_off(stack.len().checked_sub(params).ok_or(UNDERFLOW)?);
+        let results = self.invoke(index, args, depth + 1)?;
+        stack.extend(results);
+        Ok(())
+    }
+
+    fn invoke(&mut self, index: usize, mut locals: Vec<u64>, depth: usize) -> Result<Vec<u64>, String> {
+        if depth > MAX_DEPTH {
+            return Err("call stack exhausted".to_string());
+        }
+        let function = Arc::clone(self.functions.get(index).ok_or("a call to a missing function")?);
+        let results = self.types[function.type_index].results.len();
+        locals.extend(function.locals.iter().map(|value_type| match value_type {
+            ValType::FuncRef | ValType::ExternRef => NULL,
+            _ => 0,
+        }));
+        let code = &function.code;
+        let mut stack: Vec<u64> = Vec::new();
+        // the function body is a block of its own
+        let mut labels = vec![Label {
+            height: 0,
+            arity: results,
+            target: code.len(),
+            is_loop: false,
+        }];
+        let mut reader = Reader { data: code, position: 0 };
+        while reader.position < code.len() {
+            self.fuel = self.fuel.checked_sub(1).ok_or("it ran too long")?;
+            let at = reader.position;
+            let opcode = reader.byte()?;
+            match opcode {
+                0x00 => return Err("unreachable executed".to_string()),
+                0x01 => {}
+                0x02 | 0x03 => {
+                    let (params, block_results) = self.block_type(&mut reader)?;
+                    let height = stack.len().checked_sub(params).ok_or(UNDERFLOW)?;
+                    labels.push(if opcode == 0x03 {
+                        Label {
+                            height,
+                            arity: params,
+                            target: reader.position,
+                            is_loop: true,
+                        }
+                    } else {
+                        Label {
+                            height,
+                            arity: block_results,
+                            target: function.ends[&at] + 1,
+                            is_loop: false,
+                        }
+                    });
+                }
+                0x04 => {
+                    let (params, block_results) = self.block_type(&mut reader)?;
+                    let condition = pop(&mut stack)? as u32;
+                    let end = function.ends[&at];
+                    let label = Label {
+                        height: stack.len().checked_sub(params).ok_or(UNDERFLOW)?,
+                        arity: block_results,
+                        target: end + 1,
+                        is_loop: false,
+                    };
+                    if condition != 0 {
+                        labels.push(label);
+                    } else if let Some(&else_at) = function.elses.get(&at) {
+                        labels.push(label);
+                        reader.position = else_at + 1;
+                    } else {
+                        reader.position = end + 1;
+                    }
+                }
+                // the end of an if's first arm: on to the end
+                0x05 => reader.position = function.ends[&at],
+                0x0b => {
+                    labels.pop();
+                }
+                0x0c..=0x0e => {
+                    let depth = match opcode {
+                        0x0c => reader.u32()?,
+                        0x0d => {
+                            let depth = reader.u32()?;
+                            if pop(&mut stack)? as u32 == 0 {
+                                continue;
+                            }
+                            depth
+                        }
+                        _ => {
+                            let count = reader.u32()?;
+                            let chosen = pop(&mut stack)? as u32;
+                            let mut depth = 0;
+                            for target in 0..=count {
+                                let target_depth = reader.u32()?;
+                                if target == chosen.min(count) {
+                                    depth = target_depth;
+                                }
+                            }
+                            depth
+                        }
+                    };
+                    let index = labels
+                        .len()
+                        .checked_sub(depth as usize + 1)
+                        .ok_or("a branch to a missing label")?;
+                    let label = labels[index];
+                    let values = stack.len().checked_sub(label.arity).ok_or(UNDERFLOW)?;
+                    if values < label.height {
+                        return Err(UNDERFLOW.to_string());
+                    }
+                    stack.drain(label.height..values);
+                    labels.truncate(if label.is_loop { index + 1 } else { index });
+                    reader.position = label.target;
+                }
+                0x0f => break,
+                0x10 => {
+                    let callee = reader.u32()? as usize;
+                    self.call_function(callee, &mut stack, depth)?;
+                }
+                0x11 => {
+                    let type_index = reader.u32()? as usize;
+                    reader.u32()?;
+                    let element = pop(&mut stack)? as u32 as usize;
+                    let callee = *self.table.get(element).ok_or("undefined element")?;
+                    if callee == NULL {
+                        return Err("uninitialized element".to_string());
+                    }
+                    let callee_type = self.functions.get(callee as usize).map(|callee| callee.type_index);
+                    if callee_type.map(|index| &self.types[index]) != self.types.get(type_index) {
+                        return Err("indirect call type mismatch".to_string());
+                    }
+                    self.call_function(callee as usize, &mut stack, depth)?;
+                }
+                0x1a => {
+                    pop(&mut stack)?;
+                }
+                0x1b | 0x1c => {
+                    if opcode == 0x1c {
+                        let count = reader.u32()? as usize;
+                        reader.bytes(count)?;
+                    }
+                    let condition = pop(&mut stack)? as u32;
+                    let b = pop(&mut stack)?;
+                    let a = pop(&mut stack)?;
+                    stack.push(if condition != 0 { a } else { b });
+                }
+                0x20 => {
+                    let local = *locals.get(reader.u32()? as usize).ok_or("a missing local")?;
+                    stack.push(local);
+                }
+                0x21 | 0x22 => {
+                    let local = reader.u32()? as usize;
+                    let value = if opcode == 0x21 {
+                        pop(&mut stack)?
+                    } else {
+                        *stack.last().ok_or(UNDERFLOW)?
+                    };
+                    *locals.get_mut(local).ok_or("a missing local")? = value;
+                }
+                0x23 => {
+                    let global = *self.globals.get(reader.u32()? as usize).ok_or("a missing global")?;
+                    stack.push(global);
+                }
+                0x24 => {
+                    let global = reader.u32()? as usize;
+                    let value = pop(&mut stack)?;
+                    *self.globals.get_mut(global).ok_or("a missing global")? = value;
+                }
+                0x28..=0x35 => {
+                    reader.u32()?;
+                    let offset = reader.u32()?;
+                    let size = match opcode {
+                        0x29 | 0x2b => 8,
+                        0x28 | 0x2a | 0x34 | 0x35 => 4,
+                        0x2e | 0x2f | 0x32 | 0x33 => 2,
+                        _ => 1,
+                    };
+                    let address = self.address(pop(&mut stack)?, offset, size)?;
+                    let mut bytes = [0; 8];
+                    bytes[..size].copy_from_slice(&self.memory[address..address + size]);
+                    let value = u64::from_le_bytes(bytes);
+                    // the signed narrow loads sign-extend; the rest are zero-extended
+                    stack.push(match opcode {
+                        0x2c => value as i8 as i32 as u32 as u64,
+                        0x2e => value as i16 as i32 as u32 as u64,
+                        0x30 => value as i8 as i64 as u64,
+                        0x32 => value as i16 as i64 as u64,
+                        0x34 => value as i32 as i64 as u64,
+                        _ => value,
+                    });
+                }
+                0x36..=0x3e => {
+                    reader.u32()?;
+                    let offset = reader.u32()?;
+                    let size = match opcode {
+                        0x37 | 0x39 => 8,
+                        0x36 | 0x38 | 0x3e => 4,
+                        0x3b | 0x3d => 2,
+                        _ => 1,
+                    };
+                    let value = pop(&mut stack)?;
+                    let address = self.address(pop(&mut stack)?, offset, size)?;
+                    self.memory[address..address + size].copy_from_slice(&value.to_le_bytes()[..size]);
+                }
+                0x3f => {
+                    reader.byte()?;
+                    stack.push((self.memory.len() / PAGE) as u64);
+                }
+                0x40 => {
+                    reader.byte()?;
+                    let pages = self.memory.len() / PAGE;
+                    let grow = pop(&mut stack)? as u32 as usize;
+                    if pages + grow <= self.max_pages {
+                        self.memory.resize((pages + grow) * PAGE, 0);
+                        stack.push(pages as u64);
+                    } else {
+                        stack.push(u32::MAX as u64);
+                    }
+                }
+                0x41 => stack.push(reader.signed(32)? as u32 as u64),
+                0x42 => stack.push(reader.signed(64)? as u64),
+                0x43 => stack.push(u32::from_le_bytes(reader.bytes(4)?.try_into().unwrap()) as u64),
+                0x44 => stack.push(u64::from_le_bytes(reader.bytes(8)?.try_into().unwrap())),
+                0x45..=0xc4 => numeric(opcode, &mut stack)?,
+                0xd0 => {
+                    reader.byte()?;
+                    stack.push(NULL);
+                }
+                0xd1 => {
+                    let reference = pop(&mut stack)?;
+                    stack.push((reference == NULL) as u64);
+                }
+                0xd2 => stack.push(reader.u32()? as u64),
+                0xfc => match reader.u32()? {
+                    sub @ 0..=7 => {
+                        let value = pop(&mut stack)?;
+                        stack.push(saturate(sub, value));
+                    }
+                    10 => {
+                        reader.bytes(2)?;
+                        let count = pop(&mut stack)? as u32 as usize;
+                        let source = self.address(pop(&mut stack)?, 0, count)?;
+                        let destination = self.address(pop(&mut stack)?, 0, count)?;
+                        self.memory.copy_within(source..source + count, destination);
+                    }
+                    _ => {
+                        reader.byte()?;
+                        let count = pop(&mut stack)? as u32 as usize;
+                        let value = pop(&mut stack)? as u8;
+                        let destination = self.address(pop(&mut stack)?, 0, count)?;
+                        self.memory[destination..destination + count].fill(value);
+                    }
+                },
+                _ => return Err(unsupported(&format!("0x{:02x}", opcode))),
+            }
+        }
+        let values = stack.len().checked_sub(results).ok_or(UNDERFLOW)?;
+        Ok(stack.split_off(values))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const I32: u8 = 0x7f;
+    const I64: u8 = 0x7e;
+
+    fn leb(mut value: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                bytes.push(byte);
+                return bytes;
+            }
+            bytes.push(byte | 0x80);
+        }
+    }
+
+    // a section of `items`, preceded by their count
+    fn section(id: u8, items: &[Vec<u8>]) -> Vec<u8> {
+        let mut contents = leb(items.len() as u32);
+        contents.extend(items.concat());
+        let mut bytes = vec![id];
+        bytes.extend(leb(contents.len() as u32));
+        bytes.extend(contents);
+        bytes
+    }
+
+    struct Func<'a> {
+        params: &'a [u8],
+        results: &'a [u8],
+        // (count, type) local declarations
+        locals: &'a [(u32, u8)],
+        // the instructions, up to and including the final end
+        body: &'a [u8],
+    }
+
+    // a module with one type per function, exporting function N as "fN", and
+    // with `pages` pages of memory and a table of `table` entries if given
+    fn module(functions: &[Func], pages: Option<u32>, table: Option<u32>) -> Vec<u8> {
+        let mut bytes = b"\0asm\x01\0\0\0".to_vec();
+        let types: Vec<Vec<u8>> = functions
+            .iter()
+            .map(|f| {
+                let (params, results) = (leb(f.params.len() as u32), leb(f.results.len() as u32));
+                [vec![0x60], params, f.params.to_vec(), results, f.results.to_vec()].concat()
+            })
+            .collect();
+        bytes.extend(section(1, &types));
+        bytes.extend(section(3, &(0..functions.len() as u32).map(leb).collect::<Vec<_>>()));
+        if let Some(min) = table {
+            bytes.extend(section(4, &[[vec![0x70, 0x00], leb(min)].concat()]));
+        }
+        if let Some(pages) = pages {
+            bytes.extend(section(5, &[[vec![0x00], leb(pages)].concat()]));
+        }
+        let exports: Vec<Vec<u8>> = (0..functions.len() as u32)
+            .map(|index| {
+                let name = format!("f{}", index);
+                [leb(name.len() as u32), name.into_bytes(), vec![0x00], leb(index)].concat()
+            })
+            .collect();
+        bytes.extend(section(7, &exports));
+        let code: Vec<Vec<u8>> = functions
+            .iter()
+            .map(|f| {
+                let mut body = leb(f.locals.len() as u32);
+                for (count, value_type) in f.locals {
+                    body.extend(leb(*count));
+                    body.push(*value_type);
+                }
+                body.extend(f.body);
+                [leb(body.len() as u32), body].concat()
+            })
+            .collect();
+        bytes.extend(section(10, &code));
+        bytes
+    }
+
+    fn instance(functions: &[Func]) -> Instance {
+        Instance::load(&module(functions, Some(1), None)).unwrap()
+    }
+
+    #[test]
+    fn arithmetic() {
+        let mut instance = instance(&[
+            // (a + b) * 3
+            Func {
+                params: &[I32, I32],
+                results: &[I32],
+                locals: &[],
+                body: &[0x20, 0, 0x20, 1, 0x6a, 0x41, 3, 0x6c, 0x0b],
+            },
+            // a - b, wrapping
+            Func {
+                params: &[I64, I64],
+                results: &[I64],
+                locals: &[],
+                body: &[0x20, 0, 0x20, 1, 0x7d, 0x0b],
+            },
+        ]);
+        assert_eq!(instance.call("f0", &[2, 5]), Ok(vec![21]));
+        assert_eq!(instance.call("f1", &[2, 5]), Ok(vec![(-3i64) as u64]));
+    }
+
+    #[test]
+    fn control_flow() {
+        let mut instance = instance(&[
+            // n! by a loop that counts n down
+            Func {
+                params: &[I64],
+                results: &[I64],
+                locals: &[(1, I64)],
+                body: &[
+                    0x42, 1, 0x21, 1, // acc = 1
+                    0x02, 0x40, 0x03, 0x40, // block, loop
+                    0x20, 0, 0x50, 0x0d, 1, // leave when n == 0
+                    0x20, 1, 0x20, 0, 0x7e, 0x21, 1, // acc *= n
+                    0x20, 0, 0x42, 1, 0x7d, 0x21, 0, // n -= 1
+                    0x0c, 0, 0x0b, 0x0b, // again
+                    0x20, 1, 0x0b,
+                ],
+            },
+            // |x| by if/else
+            Func {
+                params: &[I32],
+                results: &[I32],
+                locals: &[],
+                body: &[
+                    0x20, 0, 0x41, 0, 0x48, 0x04, I32, // if x < 0
+                    0x41, 0, 0x20, 0, 0x6b, // 0 - x
+                    0x05, 0x20, 0, 0x0b, // else x
+                    0x0b,
+                ],
+            },
+            // fib(n) by recursion
+            Func {
+                params: &[I32],
+                results: &[I32],
+                locals: &[],
+                body: &[
+                    0x20, 0, 0x41, 2, 0x48, 0x04, I32, // if n < 2
+                    0x20, 0, // n
+                    0x05, 0x20, 0, 0x41, 1, 0x6b, 0x10, 2, // else fib(n - 1)
+                    0x20, 0, 0x41, 2, 0x6b, 0x10, 2, 0x6a, 0x0b, // + fib(n - 2)
+                    0x0b,
+                ],
+            },
+        ]);
+        assert_eq!(instance.call("f0", &[10]), Ok(vec![3_628_800]));
+        assert_eq!(instance.call("f1", &[(-7i32) as u32 as u64]), Ok(vec![7]));
+        assert_eq!(instance.call("f1", &[7]), Ok(vec![7]));
+        assert_eq!(instance.call("f2", &[15]), Ok(vec![610]));
+    }
+
+    #[test]
+    fn traps() {
+        let mut instance = instance(&[
+            Func {
+                params: &[I32, I32],
+                results: &[I32],
+                locals: &[],
+                body: &[0x20, 0, 0x20, 1, 0x6d, 0x0b],
+            },
+            Func {
+                params: &[],
+                results: &[],
+                locals: &[],
+                body: &[0x00, 0x0b],
+            },
+            // a load just past the one page of memory
+            Func {
+                params: &[],
+                results: &[I32],
+                locals: &[],
+                body: &[0x41, 0x80, 0x80, 0x04, 0x28, 2, 0, 0x0b],
+            },
+        ]);
+        assert_eq!(instance.call("f0", &[7, 2]), Ok(vec![3]));
+        assert_eq!(instance.call("f0", &[7, 0]), Err("integer divide by zero".to_string()));
+        assert_eq!(instance.call("f1", &[]), Err("unreachable executed".to_string()));
+        assert_eq!(instance.call("f2", &[]), Err("out of bounds memory access".to_string()));
+        assert!(instance.call("f3", &[]).is_err());
+    }
+
+    #[test]
+    fn fuel_and_depth_limits() {
+        let mut instance = instance(&[
+            Func {
+                params: &[],
+                results: &[],
+                locals: &[],
+                body: &[0x03, 0x40, 0x0c, 0, 0x0b, 0x0b],
+            },
+            Func {
+                params: &[],
+                results: &[],
+                locals: &[],
+                body: &[0x10, 1, 0x0b],
+            },
+        ]);
+        assert_eq!(instance.call("f0", &[]), Err("it ran too long".to_string()));
+        // a big stack, as the frames of an unoptimized build are large
+        let depth = std::thread::Builder::new()
+            .stack_size(64 << 20)
+            .spawn(move || instance.call("f1", &[]))
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(depth, Err("call stack exhausted".to_string()));
+    }
+
+    #[test]
+    fn oversized_table_and_memory_are_refused() {
+        let function = [Func {
+            params: &[],
+            results: &[],
+            locals: &[],
+            body: &[0x0b],
+        }];
+        assert!(Instance::load(&module(&function, None, Some(MAX_TABLE as u32))).is_ok());
+        let table = Instance::load(&module(&function, None, Some(u32::MAX))).err();
+        assert_eq!(table, Some(format!("its table has more than {} entries", MAX_TABLE)));
+        assert!(Instance::load(&module(&function, Some(MAX_PAGES as u32 + 1), None)).is_err());
+    }
+}
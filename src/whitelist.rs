@@ -0,0 +1,199 @@
+// sets the user skipped on purpose (intentional copies) are remembered by the
+// content hashes of their members, so later runs stop reporting them. kept in
+// a small tab-separated state file; `hydra whitelist` lists and removes entries
+use crate::config;
+use crate::scheduler::{self, IoProfile};
+use crate::{DuplicateSet, format_timestamp};
+use std::collections::{BTreeSet, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug)]
+struct Entry {
+    size: u64,
+    hashes: BTreeSet<u64>,
+    name: String,
+    added: u64,
+}
+
+fn state_path() -> Option<PathBuf> {
+    config::state_dir().map(|dir| dir.join("whitelist.tsv"))
+}
+
+fn parse_entry(line: &str) -> Option<Entry> {
+    let mut fields = line.splitn(4, '\t');
+    let size = fields.next()?.parse().ok()?;
+    let hashes = fields
+        .next()?
+        .split(',')
+        .map(|hash| u64::from_str_radix(hash, 16).ok())
+        .collect::<Option<BTreeSet<u64>>>()?;
+    let added = fields.next()?.parse().ok()?;
+    let name = fields.next()?.to_string();
+
+    Some(Entry { size, hashes, name, added })
+}
+
+fn load() -> Vec<Entry> {
+    let Some(path) = state_path() else {
+        return Vec::new();
+    };
+    let Ok(text) = fs::read_to_string(&path) else {
+        return Vec::new();
+    };
+
+    text.lines()
+        .filter(|line| !line.is_empty())
+        .filter_map(|line| {
+            let entry = parse_entry(line);
+            if entry.is_none() {
+                eprintln!("Warning: Ignoring malformed line in '{}': {}", path.display(), line);
+            }
+            entry
+        })
+        .collect()
+}
+
+fn save(entries: &[Entry]) -> Result<(), String> {
+    let path = state_path().ok_or("no home directory to keep the whitelist in")?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("could not create '{}': {}", parent.display(), e))?;
+    }
+
+    let mut text = String::new();
+    for entry in entries {
+        let hashes: Vec<String> = entry.hashes.iter().map(|hash| format!("{:016x}", hash)).collect();
+        // tabs and newlines would break the line format
+        let name = entry.name.replace(['\t', '\n'], " ");
+        text.push_str(&format!("{}\t{}\t{}\t{}\n", entry.size, hashes.join(","), entry.added, name));
+    }
+
+    fs::write(&path, text).map_err(|e| format!("could not write '{}': {}", path.display(), e))
+}
+
+// the distinct content hashes of every member of each set, or None for a set
+// that couldn't be fully hashed
+fn hash_sets(duplicate_sets: &[DuplicateSet], io_profile: IoProfile) -> Vec<Option<BTreeSet<u64>>> {
+    let paths: Vec<PathBuf> = duplicate_sets
+        .iter()
+        .flat_map(|set| std::iter::once(&set.keeper).chain(&set.duplicates))
+        .map(|file_info| file_info.path.clone())
+        .collect();
+    let hashes = scheduler::hash_files(paths, io_profile);
+
+    duplicate_sets
+        .iter()
+        .map(|set| {
+            std::iter::once(&set.keeper)
+                .chain(&set.duplicates)
+                .map(|file_info| hashes.get(&file_info.path)?.as_ref().ok().copied())
+                .collect()
+        })
+        .collect()
+}
+
+// drop sets matching a whitelist entry, returning how many were dropped. only
+// sets whose size appears in the whitelist are hashed
+pub fn filter_sets(duplicate_sets: &mut Vec<DuplicateSet>, io_profile: IoProfile) -> usize {
+    let entries = load();
+    if entries.is_empty() {
+        return 0;
+    }
+
+    let sizes: HashSet<u64> = entries.iter().map(|entry| entry.size).collect();
+    let (candidates, mut others): (Vec<DuplicateSet>, Vec<DuplicateSet>) =
+        std::mem::take(duplicate_sets).into_iter().partition(|set| sizes.contains(&set.size));
+
+    let before = candidates.len();
+    let hashes = hash_sets(&candidates, io_profile);
+    let kept: Vec<DuplicateSet> = candidates
+        .into_iter()
+        .zip(hashes)
+        .filter(|(set, hashes)| {
+            hashes.as_ref().is_none_or(|hashes| {
+                !entries.iter().any(|entry| entry.size == set.size && entry.hashes == *hashes)
+            })
+        })
+        .map(|(set, _)| set)
+        .collect();
+    let dropped = before - kept.len();
+
+    others.extend(kept);
+    *duplicate_sets = others;
+    dropped
+}
+
+// remember skipped sets so they aren't reported again
+pub fn remember(skipped: &[DuplicateSet], io_profile: IoProfile) -> Result<usize, String> {
+    if skipped.is_empty() {
+        return Ok(0);
+    }
+
+    let mut entries = load();
+    let added = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let mut count = 0;
+
+    for (set, hashes) in skipped.iter().zip(hash_sets(skipped, io_profile)) {
+        let Some(hashes) = hashes else {
+            eprintln!("Warning: Could not hash every file in '{}', not remembering it", set.normalized_filename);
+            continue;
+        };
+        if entries.iter().any(|entry| entry.size == set.size && entry.hashes == hashes) {
+            continue;
+        }
+        entries.push(Entry {
+            size: set.size,
+            hashes,
+            name: set.normalized_filename.clone(),
+            added,
+        });
+        count += 1;
+    }
+
+    save(&entries)?;
+    Ok(count)
+}
+
+// `hydra whitelist list` / `hydra whitelist remove N...`
+pub fn run(args: &[String]) -> Result<(), String> {
+    let usage = "usage: hydra whitelist list | hydra whitelist remove N...";
+    let mut entries = load();
+
+    match args.split_first() {
+        Some((command, [])) if command == "list" => {
+            if entries.is_empty() {
+                println!("The whitelist is empty.");
+            }
+            for (index, entry) in entries.iter().enumerate() {
+                println!(
+                    "{:>3}. {} ({} bytes, {} distinct content(s)), added {}",
+                    index + 1,
+                    entry.name,
+                    entry.size,
+                    entry.hashes.len(),
+                    &format_timestamp(UNIX_EPOCH + Duration::from_secs(entry.added))[..10]
+                );
+            }
+            Ok(())
+        }
+        Some((command, numbers)) if command == "remove" && !numbers.is_empty() => {
+            let mut indexes = Vec::new();
+            for number in numbers {
+                match number.parse::<usize>() {
+                    Ok(n) if (1..=entries.len()).contains(&n) => indexes.push(n - 1),
+                    _ => return Err(format!("no whitelist entry '{}' (see `hydra whitelist list`)", number)),
+                }
+            }
+            indexes.sort_unstable();
+            indexes.dedup();
+
+            for index in indexes.into_iter().rev() {
+                let entry = entries.remove(index);
+                println!("Removed: {} ({} bytes)", entry.name, entry.size);
+            }
+            save(&entries)
+        }
+        _ => Err(usage.to_string()),
+    }
+}
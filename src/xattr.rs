@@ -0,0 +1,156 @@
+// minimal extended attribute access; platforms without xattr support report
+// io::ErrorKind::Unsupported
+use std::io;
+use std::path::Path;
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+mod ffi {
+    use std::os::raw::{c_char, c_int, c_void};
+
+    #[cfg(target_os = "linux")]
+    unsafe extern "C" {
+        pub fn listxattr(path: *const c_char, list: *mut c_char, size: usize) -> isize;
+        pub fn getxattr(path: *const c_char, name: *const c_char, value: *mut c_void, size: usize) -> isize;
+        pub fn setxattr(
+            path: *const c_char,
+            name: *const c_char,
+            value: *const c_void,
+            size: usize,
+            flags: c_int,
+        ) -> c_int;
+    }
+
+    #[cfg(target_os = "macos")]
+    unsafe extern "C" {
+        pub fn listxattr(path: *const c_char, namebuf: *mut c_char, size: usize, options: c_int) -> isize;
+        pub fn getxattr(
+            path: *const c_char,
+            name: *const c_char,
+            value: *mut c_void,
+            size: usize,
+            position: u32,
+            options: c_int,
+        ) -> isize;
+        pub fn setxattr(
+            path: *const c_char,
+            name: *const c_char,
+            value: *const c_void,
+            size: usize,
+            position: u32,
+            options: c_int,
+        ) -> c_int;
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn c_string(value: &[u8]) -> io::Result<std::ffi::CString> {
+    std::ffi::CString::new(value).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
+
+#[cfg(target_os = "linux")]
+const ENOATTR: i32 = 61; // ENODATA
+#[cfg(target_os = "macos")]
+const ENOATTR: i32 = 93;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+const ERANGE: i32 = 34;
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub fn list(path: &Path) -> io::Result<Vec<String>> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = c_string(path.as_os_str().as_bytes())?;
+    let mut buffer = vec![0u8; 1024];
+
+    loop {
+        let buffer_ptr = buffer.as_mut_ptr() as *mut std::os::raw::c_char;
+        #[cfg(target_os = "linux")]
+        let length = unsafe { ffi::listxattr(c_path.as_ptr(), buffer_ptr, buffer.len()) };
+        #[cfg(target_os = "macos")]
+        let length = unsafe { ffi::listxattr(c_path.as_ptr(), buffer_ptr, buffer.len(), 0) };
+
+        if length >= 0 {
+            buffer.truncate(length as usize);
+            // names come back as a sequence of nul-terminated strings
+            return Ok(buffer
+                .split(|byte| *byte == 0)
+                .filter(|name| !name.is_empty())
+                .map(|name| String::from_utf8_lossy(name).to_string())
+                .collect());
+        }
+
+        let error = io::Error::last_os_error();
+        if error.raw_os_error() == Some(ERANGE) && buffer.len() < 1024 * 1024 {
+            buffer.resize(buffer.len() * 4, 0);
+            continue;
+        }
+        return Err(error);
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub fn get(path: &Path, name: &str) -> io::Result<Option<Vec<u8>>> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = c_string(path.as_os_str().as_bytes())?;
+    let c_name = c_string(name.as_bytes())?;
+    let mut buffer = vec![0u8; 256];
+
+    loop {
+        let buffer_ptr = buffer.as_mut_ptr() as *mut std::os::raw::c_void;
+        #[cfg(target_os = "linux")]
+        let length = unsafe { ffi::getxattr(c_path.as_ptr(), c_name.as_ptr(), buffer_ptr, buffer.len()) };
+        #[cfg(target_os = "macos")]
+        let length = unsafe { ffi::getxattr(c_path.as_ptr(), c_name.as_ptr(), buffer_ptr, buffer.len(), 0, 0) };
+
+        if length >= 0 {
+            buffer.truncate(length as usize);
+            return Ok(Some(buffer));
+        }
+
+        let error = io::Error::last_os_error();
+        if error.raw_os_error() == Some(ENOATTR) {
+            return Ok(None);
+        }
+        // value is bigger than our buffer
+        if error.raw_os_error() == Some(ERANGE) && buffer.len() < 64 * 1024 {
+            buffer.resize(buffer.len() * 4, 0);
+            continue;
+        }
+        return Err(error);
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub fn set(path: &Path, name: &str, value: &[u8]) -> io::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = c_string(path.as_os_str().as_bytes())?;
+    let c_name = c_string(name.as_bytes())?;
+    let value_ptr = value.as_ptr() as *const std::os::raw::c_void;
+
+    #[cfg(target_os = "linux")]
+    let result = unsafe { ffi::setxattr(c_path.as_ptr(), c_name.as_ptr(), value_ptr, value.len(), 0) };
+    #[cfg(target_os = "macos")]
+    let result = unsafe { ffi::setxattr(c_path.as_ptr(), c_name.as_ptr(), value_ptr, value.len(), 0, 0) };
+
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn list(_path: &Path) -> io::Result<Vec<String>> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn get(_path: &Path, _name: &str) -> io::Result<Option<Vec<u8>>> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn set(_path: &Path, _name: &str, _value: &[u8]) -> io::Result<()> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}